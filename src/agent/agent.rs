@@ -0,0 +1,429 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::{Result, anyhow};
+use futures::future::join_all;
+use tokio::time::Instant;
+
+use crate::{
+    agent::{
+        memory::Memory,
+        tool::{Tool, ToolSet},
+        trace::{AgentEvent, AgentStream, StopReason},
+    },
+    model::{
+        conversation::conversation::{Conversation, Turn},
+        function::function::{FunctionCallingMode, FunctionResponse},
+        generation::generation::{GenerateRequest, GenerationConfig, Usage},
+    },
+    models::model_client::ModelClient,
+};
+
+// PLAN_PROMPT:
+// appended to the system prompt of the planning step
+const PLAN_PROMPT: &str = "Before acting, write a short numbered plan of the steps and tools \
+you will use to answer. Do not answer yet and do not call any tool.";
+
+/// Bounds of a single agent run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AgentLimits {
+    /// Model calls allowed, the planning call excluded.
+    pub max_steps: usize,
+    /// Total tokens after which no further model call is made.
+    pub max_tokens: Option<u64>,
+    /// Wall-clock time of the whole run, tool calls included.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for AgentLimits {
+    fn default() -> Self {
+        Self {
+            max_steps: 8,
+            max_tokens: None,
+            timeout: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+/// Outcome of [`Agent::run`].
+#[derive(Clone, Debug)]
+pub struct AgentRun {
+    /// The final answer, or the text of the last model reply when a limit
+    /// stopped the run.
+    pub output: String,
+    pub stop: StopReason,
+    /// Model calls made, the planning call excluded.
+    pub steps: usize,
+    pub usage: Usage,
+    pub trace: Vec<AgentEvent>,
+}
+
+/// A reason-act loop over a [`ModelClient`] and a set of tools.
+///
+/// Each step sends the conversation so far with the tools declared as
+/// functions; the calls the model asks for are run concurrently and their
+/// results (or errors) sent back in the next step, until the model answers
+/// with text or a limit of [`AgentLimits`] is reached. With
+/// [`plan`](Self::plan) the model first writes a plan without calling tools.
+/// The tools of the client ([`ModelClient::set_tools`]) are available too.
+///
+/// The turns of a run are appended to the agent's [`Memory`], so the next
+/// run continues the conversation.
+///
+/// # Example
+/// ```no_run
+/// # use ey_ai::{agent::{agent::Agent, tool::tool_fn}, model::function::function::FunctionDeclaration, models::model_client::ModelClient};
+/// # use serde_json::{Value, json};
+/// # async fn run(client: ModelClient) -> anyhow::Result<()> {
+/// let agent = Agent::new(client)
+///     .system("You are a travel assistant.")
+///     .tool(tool_fn(
+///         FunctionDeclaration::new("get_weather", "Current weather of a city"),
+///         |args: Value| async move { Ok(json!({ "city": args["city"], "celsius": 21 })) },
+///     ))
+///     .max_steps(5);
+///
+/// let run = agent.run("Should I take an umbrella to Paris today?").await?;
+/// println!("{} ({:?} after {} steps)", run.output, run.stop, run.steps);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Agent {
+    client: ModelClient,
+    tools: ToolSet,
+    memory: Memory,
+    system: Option<String>,
+    config: GenerationConfig,
+    tag: Option<String>,
+    limits: AgentLimits,
+    plan: bool,
+}
+
+impl Agent {
+    pub fn new(client: ModelClient) -> Self {
+        Self {
+            client,
+            tools: ToolSet::new(),
+            memory: Memory::new(),
+            system: None,
+            config: GenerationConfig::default(),
+            tag: None,
+            limits: AgentLimits::default(),
+            plan: false,
+        }
+    }
+
+    pub fn system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn tool(mut self, tool: impl Tool + 'static) -> Self {
+        self.tools = self.tools.with(tool);
+        self
+    }
+
+    pub fn tools(mut self, tools: ToolSet) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    pub fn memory(mut self, memory: Memory) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn config(mut self, config: GenerationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Usage tag of the agent's requests.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn limits(mut self, limits: AgentLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.limits.max_steps = max_steps;
+        self
+    }
+
+    pub fn max_tokens(mut self, max_tokens: u64) -> Self {
+        self.limits.max_tokens = Some(max_tokens);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Asks the model for a plan before the first step.
+    pub fn plan(mut self, plan: bool) -> Self {
+        self.plan = plan;
+        self
+    }
+
+    /// Runs the loop on `input` and returns the answer with the trace.
+    ///
+    /// A failed model call fails the run; failed tools don't.
+    pub async fn run(&self, input: impl Into<String>) -> Result<AgentRun> {
+        let mut trace = Vec::new();
+        let mut run = self
+            .drive(input.into(), &mut |event| trace.push(event))
+            .await?;
+        run.trace = trace;
+        Ok(run)
+    }
+
+    /// Runs the loop on `input` in the background and streams its trace as
+    /// it happens, see [`sse`](crate::agent::trace::sse) to serve it.
+    ///
+    /// The stream always ends with [`AgentEvent::Finished`]. Dropping the
+    /// stream stops the run.
+    pub fn run_stream(&self, input: impl Into<String>) -> AgentStream {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let agent = self.clone();
+        let input = input.into();
+        tokio::spawn(async move {
+            let mut emit = |event| {
+                let _ = sender.send(event);
+            };
+            // stops calling the model and the tools once the stream is dropped
+            tokio::select! {
+                _ = agent.drive(input, &mut emit) => {}
+                _ = sender.closed() => {}
+            }
+        });
+        Box::pin(futures::stream::unfold(
+            receiver,
+            |mut receiver| async move { receiver.recv().await.map(|event| (event, receiver)) },
+        ))
+    }
+
+    // drive:
+    // the loop itself, reporting each step to `emit`; the returned run has no trace
+    async fn drive(
+        &self,
+        input: String,
+        emit: &mut (dyn FnMut(AgentEvent) + Send),
+    ) -> Result<AgentRun> {
+        let deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        let tools = &self.tool_set();
+        let mut history = self.memory.turns();
+        let start = history.len();
+        history.push(Turn::user(input));
+        let mut usage = Usage::default();
+        let mut output = String::new();
+        let mut steps = 0;
+
+        let stop = 'run: {
+            if self.plan {
+                let Some(reply) =
+                    within(deadline, self.client.Generate(self.request(&history, true))).await
+                else {
+                    break 'run StopReason::Timeout;
+                };
+                let reply = match reply {
+                    Ok(reply) => reply,
+                    Err(e) => return Err(fail(emit, 0, &usage, e)),
+                };
+                if let Some(reply_usage) = &reply.usage {
+                    usage.add(reply_usage);
+                }
+                emit(AgentEvent::Plan {
+                    text: reply.text.clone(),
+                    usage: reply.usage,
+                });
+                history.push(Turn::model(reply.text));
+            }
+
+            loop {
+                if steps >= self.limits.max_steps {
+                    break 'run StopReason::MaxSteps;
+                }
+                if self
+                    .limits
+                    .max_tokens
+                    .is_some_and(|max| usage.total_tokens >= max)
+                {
+                    break 'run StopReason::MaxTokens;
+                }
+                steps += 1;
+
+                let Some(reply) = within(
+                    deadline,
+                    self.client.Generate(self.request(&history, false)),
+                )
+                .await
+                else {
+                    break 'run StopReason::Timeout;
+                };
+                let reply = match reply {
+                    Ok(reply) => reply,
+                    Err(e) => return Err(fail(emit, steps, &usage, e)),
+                };
+                if let Some(reply_usage) = &reply.usage {
+                    usage.add(reply_usage);
+                }
+                emit(AgentEvent::ModelOutput {
+                    step: steps,
+                    text: reply.text.clone(),
+                    thoughts: reply.thoughts.clone(),
+                    function_calls: reply.function_calls.clone(),
+                    usage: reply.usage,
+                });
+                output = reply.text.clone();
+                if reply.function_calls.is_empty() {
+                    history.push(Turn::model(reply.text));
+                    break 'run StopReason::Answered;
+                }
+
+                let calls = reply.function_calls;
+                history.push(Turn::function_calls(reply.text, calls.clone()));
+                for call in &calls {
+                    emit(AgentEvent::ToolCall {
+                        step: steps,
+                        call: call.clone(),
+                    });
+                }
+                let started = Instant::now();
+                let results = within(
+                    deadline,
+                    join_all(calls.iter().map(|call| async move {
+                        let started = Instant::now();
+                        let result = tools.call(call).await;
+                        (result, started.elapsed())
+                    })),
+                )
+                .await;
+
+                // every call gets a response, so the history stays valid for the next run
+                let timed_out = results.is_none();
+                let results = results.unwrap_or_else(|| {
+                    calls
+                        .iter()
+                        .map(|_| (Err(anyhow!("Timed out")), started.elapsed()))
+                        .collect()
+                });
+                let mut responses = Vec::new();
+                for (call, (result, elapsed)) in calls.iter().zip(results) {
+                    let duration_ms = elapsed.as_millis() as u64;
+                    match result {
+                        Ok(result) => {
+                            emit(AgentEvent::ToolResult {
+                                step: steps,
+                                name: call.name.clone(),
+                                result: result.clone(),
+                                duration_ms,
+                            });
+                            responses.push(FunctionResponse::new(call, result));
+                        }
+                        Err(e) => {
+                            emit(AgentEvent::ToolError {
+                                step: steps,
+                                name: call.name.clone(),
+                                error: e.to_string(),
+                                duration_ms,
+                            });
+                            responses.push(FunctionResponse::error(call, e.to_string()));
+                        }
+                    }
+                }
+                history.push(Turn::function_responses(responses));
+                if timed_out {
+                    break 'run StopReason::Timeout;
+                }
+            }
+        };
+
+        self.memory.extend(history.drain(start..));
+        emit(AgentEvent::Finished {
+            output: output.clone(),
+            stop,
+            steps,
+            usage,
+        });
+        Ok(AgentRun {
+            output,
+            stop,
+            steps,
+            usage,
+            trace: Vec::new(),
+        })
+    }
+
+    // tool_set:
+    // the client's tools with the agent's own, which win on name clashes
+    fn tool_set(&self) -> ToolSet {
+        self.client
+            .tools
+            .lock()
+            .unwrap()
+            .clone()
+            .merge(self.tools.clone())
+    }
+
+    // request:
+    // the conversation so far with the tools declared; the planning request
+    // asks for a plan and forbids calls
+    fn request(&self, history: &[Turn], planning: bool) -> GenerateRequest {
+        let system = match (&self.system, planning) {
+            (Some(system), true) => Some(format!("{}\n\n{}", system, PLAN_PROMPT)),
+            (None, true) => Some(PLAN_PROMPT.to_string()),
+            (system, false) => system.clone(),
+        };
+        let conversation = Conversation {
+            system,
+            turns: history.to_vec(),
+        };
+        let tools = self.tool_set();
+        let mut request = GenerateRequest::new(conversation).config(self.config.clone());
+        for function in tools.declarations() {
+            request = request.function(function);
+        }
+        if planning && !tools.is_empty() {
+            request = request.function_calling(FunctionCallingMode::None);
+        }
+        if let Some(tag) = &self.tag {
+            request = request.tag(tag.clone());
+        }
+        request
+    }
+}
+
+// within:
+// awaits `future` until the deadline, `None` once it passed
+async fn within<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+// fail:
+// reports a failed model call and closes the trace
+fn fail(
+    emit: &mut (dyn FnMut(AgentEvent) + Send),
+    step: usize,
+    usage: &Usage,
+    error: anyhow::Error,
+) -> anyhow::Error {
+    emit(AgentEvent::Error {
+        step,
+        message: error.to_string(),
+    });
+    emit(AgentEvent::Finished {
+        output: String::new(),
+        stop: StopReason::Error,
+        steps: step,
+        usage: *usage,
+    });
+    anyhow!("Agent step {} failed: {}", step, error)
+}
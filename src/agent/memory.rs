@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+use crate::model::conversation::conversation::{Turn, TurnRole};
+
+/// Conversation history an agent carries from one run to the next.
+///
+/// Clones share the history, so one memory can follow a user across
+/// requests. With a window only the latest turns are kept, cut at a user
+/// message so a function call is never separated from its result; the last
+/// exchange is kept whole even when it is longer than the window.
+#[derive(Clone, Default)]
+pub struct Memory {
+    turns: Arc<Mutex<Vec<Turn>>>,
+    window: Option<usize>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps at most `max_turns` turns.
+    pub fn window(max_turns: usize) -> Self {
+        Self {
+            turns: Arc::default(),
+            window: Some(max_turns),
+        }
+    }
+
+    pub fn turns(&self) -> Vec<Turn> {
+        self.turns.lock().unwrap().clone()
+    }
+
+    pub fn extend(&self, turns: impl IntoIterator<Item = Turn>) {
+        let mut stored = self.turns.lock().unwrap();
+        stored.extend(turns);
+        if let Some(window) = self.window
+            && stored.len() > window
+        {
+            let cut = stored.len() - window;
+            let plain_user =
+                |turn: &Turn| turn.role == TurnRole::User && turn.function_responses.is_empty();
+            // start at a user message, not at a function result; when the
+            // last exchange is longer than the window it is kept whole
+            let start = (cut..stored.len())
+                .find(|&i| plain_user(&stored[i]))
+                .or_else(|| (0..cut).rev().find(|&i| plain_user(&stored[i])))
+                .unwrap_or(0);
+            stored.drain(..start);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.turns.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.turns.lock().unwrap().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.turns.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod agent;
+pub mod memory;
+pub mod tool;
+pub mod trace;
@@ -0,0 +1,124 @@
+use std::{future::Future, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::model::function::function::{FunctionCall, FunctionDeclaration};
+
+/// A function an [`Agent`](crate::agent::agent::Agent) lets the model call.
+///
+/// An error returned by `call` is reported to the model, which may retry or
+/// answer without the tool; it does not end the run.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn declaration(&self) -> FunctionDeclaration;
+
+    async fn call(&self, args: Value) -> Result<Value>;
+}
+
+/// A [`Tool`] backed by an async closure, see [`tool_fn`].
+pub struct FnTool<F> {
+    declaration: FunctionDeclaration,
+    handler: F,
+}
+
+/// Builds a tool from its declaration and an async closure.
+///
+/// # Example
+/// ```
+/// # use ey_ai::{agent::tool::tool_fn, model::function::function::FunctionDeclaration};
+/// # use serde_json::{Value, json};
+/// let weather = tool_fn(
+///     FunctionDeclaration::new("get_weather", "Current weather of a city").parameters(json!({
+///         "type": "object",
+///         "properties": { "city": { "type": "string" } },
+///         "required": ["city"]
+///     })),
+///     |args: Value| async move { Ok(json!({ "city": args["city"], "celsius": 21 })) },
+/// );
+/// ```
+pub fn tool_fn<F, Fut>(declaration: FunctionDeclaration, handler: F) -> FnTool<F>
+where
+    F: Fn(Value) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Value>> + Send,
+{
+    FnTool {
+        declaration,
+        handler,
+    }
+}
+
+#[async_trait]
+impl<F, Fut> Tool for FnTool<F>
+where
+    F: Fn(Value) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Value>> + Send,
+{
+    fn declaration(&self) -> FunctionDeclaration {
+        self.declaration.clone()
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        (self.handler)(args).await
+    }
+}
+
+/// The tools of an agent, by name.
+#[derive(Clone, Default)]
+pub struct ToolSet {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `tool`, replacing one with the same name.
+    pub fn with(mut self, tool: impl Tool + 'static) -> Self {
+        self.add(Arc::new(tool));
+        self
+    }
+
+    pub fn add(&mut self, tool: Arc<dyn Tool>) {
+        let name = tool.declaration().name;
+        self.tools.retain(|t| t.declaration().name != name);
+        self.tools.push(tool);
+    }
+
+    /// Adds the tools of `other`, replacing those with the same names.
+    pub fn merge(mut self, other: ToolSet) -> Self {
+        for tool in other.tools {
+            self.add(tool);
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools
+            .iter()
+            .find(|t| t.declaration().name == name)
+            .cloned()
+    }
+
+    pub fn declarations(&self) -> Vec<FunctionDeclaration> {
+        self.tools.iter().map(|t| t.declaration()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Runs the tool `call` asks for.
+    pub async fn call(&self, call: &FunctionCall) -> Result<Value> {
+        let tool = self
+            .get(&call.name)
+            .ok_or_else(|| anyhow!("Unknown tool {}", call.name))?;
+        tool.call(call.args.clone()).await
+    }
+}
@@ -0,0 +1,133 @@
+use std::{convert::Infallible, pin::Pin};
+
+use axum::{
+    extract::ws::{Message as WsMessage, WebSocket},
+    response::{Sse, sse::Event},
+};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::model::{function::function::FunctionCall, generation::generation::Usage};
+
+/// Why an agent run ended.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// The model answered without asking for more tools.
+    Answered,
+    MaxSteps,
+    MaxTokens,
+    Timeout,
+    /// A model call failed, see the preceding [`AgentEvent::Error`].
+    Error,
+}
+
+/// One entry of the trace of an agent run.
+///
+/// Serialized tagged by `type`:
+///
+/// ```json
+/// {"type":"tool_call","step":1,"call":{"name":"get_weather","args":{"city":"Paris"}}}
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AgentEvent {
+    /// The plan written before the first action, when planning is enabled.
+    Plan {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        usage: Option<Usage>,
+    },
+    /// A model reply: the answer or reasoning text and the tools it calls.
+    ModelOutput {
+        step: usize,
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thoughts: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        function_calls: Vec<FunctionCall>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        usage: Option<Usage>,
+    },
+    ToolCall {
+        step: usize,
+        call: FunctionCall,
+    },
+    ToolResult {
+        step: usize,
+        name: String,
+        result: Value,
+        duration_ms: u64,
+    },
+    /// A tool failed; the error is sent to the model as the call's result.
+    ToolError {
+        step: usize,
+        name: String,
+        error: String,
+        duration_ms: u64,
+    },
+    /// The run failed, a `Finished` event with [`StopReason::Error`] follows.
+    Error {
+        step: usize,
+        message: String,
+    },
+    Finished {
+        output: String,
+        stop: StopReason,
+        steps: usize,
+        usage: Usage,
+    },
+}
+
+impl AgentEvent {
+    /// Value of the `type` tag, also used as the SSE event name.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AgentEvent::Plan { .. } => "plan",
+            AgentEvent::ModelOutput { .. } => "model_output",
+            AgentEvent::ToolCall { .. } => "tool_call",
+            AgentEvent::ToolResult { .. } => "tool_result",
+            AgentEvent::ToolError { .. } => "tool_error",
+            AgentEvent::Error { .. } => "error",
+            AgentEvent::Finished { .. } => "finished",
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn to_sse(&self) -> Event {
+        Event::default().event(self.kind()).data(self.to_json())
+    }
+}
+
+/// Trace of a running agent, ending with [`AgentEvent::Finished`].
+pub type AgentStream = Pin<Box<dyn Stream<Item = AgentEvent> + Send>>;
+
+/// Serves an agent trace as Server-Sent Events, one event per step named
+/// after its `type`.
+///
+/// # Example
+/// ```no_run
+/// # use axum::{Json, extract::State, response::IntoResponse};
+/// # use ey_ai::{agent::{agent::Agent, trace::sse}, models::gemini::PromptInput};
+/// async fn run_agent(State(agent): State<Agent>, Json(input): Json<PromptInput>) -> impl IntoResponse {
+///     sse(agent.run_stream(input.prompt))
+/// }
+/// ```
+pub fn sse(events: AgentStream) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(events.map(|event| Ok::<Event, Infallible>(event.to_sse())))
+}
+
+/// Sends an agent trace over a WebSocket as JSON text messages, stopping
+/// early when the client goes away.
+pub async fn forward_to_socket(mut events: AgentStream, socket: &mut WebSocket) {
+    while let Some(event) = events.next().await {
+        if let Err(e) = socket.send(WsMessage::Text(event.to_json().into())).await {
+            eprintln!("Failed to send agent event: {}", e);
+            return;
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Runs an eval dataset against one or more Gemini models and reports pass
+//! rate, latency, tokens and cost.
+//!
+//! ```text
+//! cargo run --features eval --bin ey-eval -- \
+//!     --dataset evals/geography.yaml \
+//!     --target flash=gemini-2.5-flash,temperature=0 \
+//!     --target pro=gemini-2.5-pro \
+//!     --judge-model gemini-2.5-pro \
+//!     --out runs/today.json --baseline runs/yesterday.json
+//! ```
+//!
+//! The API key is read from `GEMINI_API_KEY` (a `.env` file is honoured);
+//! `GEMINI_BASE_URL` points the run at another endpoint such as the
+//! `gemini-standin` binary.
+
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use ey_ai::{
+    eval::{
+        dataset::Dataset,
+        report::EvalReport,
+        runner::{EvalTarget, Evaluator},
+    },
+    model::generation::generation::GenerationConfig,
+    model_llm::Models,
+    models::{gemini::GeminiProvider, model_client::ModelClient},
+};
+
+const USAGE: &str = "Usage: ey-eval --dataset <PATH> --target <SPEC> [OPTIONS]
+
+Options:
+  --dataset <PATH>         Dataset file (.yaml, .yml, .json or .jsonl)
+  --target <SPEC>          Model to evaluate, repeatable. SPEC is
+                           [name=]model[,temperature=T][,max-tokens=N][,seed=S]
+  --judge-model <MODEL>    Model grading `judge` assertions
+  --out <PATH>             Write the JSON report to PATH
+  --markdown <PATH>        Write the Markdown report to PATH
+  --baseline <PATH>        Compare with the JSON report of a previous run
+  --concurrency <N>        Cases in flight per target [default: 4]
+  --fail-under <RATE>      Exit with an error when a target passes less than
+                           RATE (0 to 1) of the cases
+  --fail-on-regression     Exit with an error when a case passing in the
+                           baseline fails now
+  -h, --help               Print this help";
+
+#[derive(Default)]
+struct Args {
+    dataset: Option<String>,
+    targets: Vec<(String, Models, GenerationConfig)>,
+    judge: Option<Models>,
+    out: Option<String>,
+    markdown: Option<String>,
+    baseline: Option<String>,
+    concurrency: Option<usize>,
+    fail_under: Option<f64>,
+    fail_on_regression: bool,
+}
+
+fn model(name: &str) -> Result<Models> {
+    Models::from_name(name).ok_or_else(|| anyhow!("Unknown model {}", name))
+}
+
+// parse_target:
+// `[name=]model[,key=value...]` into a target name, model and config
+fn parse_target(spec: &str) -> Result<(String, Models, GenerationConfig)> {
+    let mut parts = spec.split(',');
+    let head = parts.next().unwrap_or_default();
+    let (name, model_name) = head.split_once('=').unwrap_or((head, head));
+    let mut config = GenerationConfig::default();
+    for part in parts {
+        let (key, value) = part
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid target option {}", part))?;
+        let invalid = || anyhow!("Invalid value {} for {}", value, key);
+        match key {
+            "temperature" => config.temperature = Some(value.parse().map_err(|_| invalid())?),
+            "max-tokens" => config.max_output_tokens = Some(value.parse().map_err(|_| invalid())?),
+            "seed" => config.seed = Some(value.parse().map_err(|_| invalid())?),
+            _ => return Err(anyhow!("Unknown target option {}", key)),
+        }
+    }
+    Ok((name.to_string(), model(model_name)?, config))
+}
+
+// parse_args:
+// run options from the command line, `None` when help was asked for
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Args>> {
+    let mut parsed = Args::default();
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--fail-on-regression" => {
+                parsed.fail_on_regression = true;
+                continue;
+            }
+            _ => {}
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--dataset" => parsed.dataset = Some(value),
+            "--target" => parsed.targets.push(parse_target(&value)?),
+            "--judge-model" => parsed.judge = Some(model(&value)?),
+            "--out" => parsed.out = Some(value),
+            "--markdown" => parsed.markdown = Some(value),
+            "--baseline" => parsed.baseline = Some(value),
+            "--concurrency" => {
+                parsed.concurrency = Some(
+                    value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid count {} for --concurrency", value))?,
+                )
+            }
+            "--fail-under" => {
+                parsed.fail_under = Some(
+                    value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid rate {} for --fail-under", value))?,
+                )
+            }
+            _ => return Err(anyhow!("Unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if parsed.dataset.is_none() || parsed.targets.is_empty() {
+        return Err(anyhow!("--dataset and --target are required\n\n{}", USAGE));
+    }
+    Ok(Some(parsed))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Some(args) = parse_args(std::env::args().skip(1))? else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    dotenvy::dotenv().ok();
+    let api_key =
+        std::env::var("GEMINI_API_KEY").map_err(|_| anyhow!("GEMINI_API_KEY is not set"))?;
+    let provider = Arc::new(GeminiProvider::new());
+    let client = |model: Models| ModelClient::new(provider.clone()).init(api_key.clone(), model);
+
+    let dataset = Dataset::load(args.dataset.as_deref().unwrap_or_default())?;
+    let mut evaluator = Evaluator::new();
+    for (name, model, config) in args.targets {
+        evaluator = evaluator.target(EvalTarget::new(name, client(model)).config(config));
+    }
+    if let Some(judge) = args.judge {
+        evaluator = evaluator.judge(client(judge));
+    }
+    if let Some(concurrency) = args.concurrency {
+        evaluator = evaluator.concurrency(concurrency);
+    }
+
+    let report = evaluator.run(&dataset).await?;
+    println!("{}", report.to_markdown());
+    if let Some(path) = &args.out {
+        report.save(path)?;
+    }
+    if let Some(path) = &args.markdown {
+        std::fs::write(path, report.to_markdown())?;
+    }
+
+    let mut failures = Vec::new();
+    if let Some(path) = &args.baseline {
+        let diff = report.diff(&EvalReport::load(path)?);
+        println!("{}", diff.to_markdown());
+        if args.fail_on_regression && diff.has_regressions() {
+            failures.push("cases regressed since the baseline".to_string());
+        }
+    }
+    if let Some(rate) = args.fail_under {
+        failures.extend(
+            report
+                .targets
+                .iter()
+                .filter(|t| t.summary.pass_rate < rate)
+                .map(|t| {
+                    format!(
+                        "{} passed {:.1}% of the cases",
+                        t.name,
+                        t.summary.pass_rate * 100.0
+                    )
+                }),
+        );
+    }
+    if !failures.is_empty() {
+        return Err(anyhow!("Eval failed: {}", failures.join("; ")));
+    }
+    Ok(())
+}
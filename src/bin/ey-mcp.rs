@@ -0,0 +1,132 @@
+//! Serves the crate's Gemini client as an MCP server, so other agents can
+//! delegate generation and embeddings to it with our keys, budgets and usage
+//! ledger applied.
+//!
+//! ```text
+//! cargo run --bin ey-mcp -- --model gemini-2.5-flash --templates prompts
+//! cargo run --bin ey-mcp -- --http 127.0.0.1:8931
+//! ```
+//!
+//! Keys are read from `GEMINI_API_KEY`, or `GEMINI_API_KEYS` (comma separated)
+//! for a key pool; a `.env` file is honoured. Over HTTP, clients must send the
+//! token of `EY_MCP_TOKEN` as a bearer token when it is set; without it only
+//! loopback addresses are served.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use ey_ai::{
+    mcp::server::McpServer,
+    model_llm::Models,
+    models::{gemini::GeminiProvider, model_client::ModelClient},
+    prompt::registry::TemplateRegistry,
+};
+
+const USAGE: &str = "Usage: ey-mcp [OPTIONS]
+
+Options:
+  --http <ADDR>              Serve streamable HTTP on ADDR instead of stdio
+  --model <MODEL>            Model of the generate tools [default: gemini-2.5-flash]
+  --embedding-model <MODEL>  Model of the embed tool [default: gemini-embedding-001]
+  --templates <DIR>          Serve the prompt templates of DIR, reloaded on change
+  --tag <TAG>                Usage tag of the requests [default: mcp]
+  --tenant <TENANT>          Budget tenant of the requests
+  -h, --help                 Print this help";
+
+struct Args {
+    http: Option<String>,
+    model: Models,
+    embedding_model: String,
+    templates: Option<String>,
+    tag: String,
+    tenant: Option<String>,
+}
+
+// parse_args:
+// server options from the command line, `None` when help was asked for
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<Args>> {
+    let mut parsed = Args {
+        http: None,
+        model: Models::Gemini25Flash,
+        embedding_model: "gemini-embedding-001".to_string(),
+        templates: None,
+        tag: "mcp".to_string(),
+        tenant: None,
+    };
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--http" => parsed.http = Some(value),
+            "--model" => {
+                parsed.model =
+                    Models::from_name(&value).ok_or_else(|| anyhow!("Unknown model {}", value))?
+            }
+            "--embedding-model" => parsed.embedding_model = value,
+            "--templates" => parsed.templates = Some(value),
+            "--tag" => parsed.tag = value,
+            "--tenant" => parsed.tenant = Some(value),
+            _ => return Err(anyhow!("Unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    Ok(Some(parsed))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Some(args) = parse_args(std::env::args().skip(1))? else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+    dotenvy::dotenv().ok();
+    let keys: Vec<String> = std::env::var("GEMINI_API_KEYS")
+        .or_else(|_| std::env::var("GEMINI_API_KEY"))
+        .map_err(|_| anyhow!("GEMINI_API_KEY is not set"))?
+        .split(',')
+        .map(|key| key.trim().to_string())
+        .filter(|key| !key.is_empty())
+        .collect();
+    let first = keys.first().cloned().unwrap_or_default();
+
+    let mut client = ModelClient::new(Arc::new(GeminiProvider::new())).init(first, args.model);
+    if keys.len() > 1 {
+        for key in &keys {
+            client = client.add_key(key.clone(), 1);
+        }
+    }
+    if let Some(dir) = &args.templates {
+        let templates = Arc::new(TemplateRegistry::load(dir.as_str())?);
+        templates.watch(Duration::from_secs(2));
+        client = client.set_templates(Some(templates));
+    }
+
+    let mut server = McpServer::new(client)
+        .embedding_model(args.embedding_model)
+        .tag(args.tag);
+    if let Some(tenant) = args.tenant {
+        server = server.tenant(tenant);
+    }
+    if let Ok(token) = std::env::var("EY_MCP_TOKEN") {
+        server = server.bearer_token(token);
+    }
+
+    match args.http {
+        Some(addr) => {
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            // stdout is free when serving HTTP
+            println!(
+                "MCP server listening on http://{}/mcp",
+                listener.local_addr()?
+            );
+            server.serve_http(listener).await?;
+        }
+        None => server.serve_stdio().await?,
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Local stand-in for the Gemini REST API, for CI and demos without network
+//! or keys.
+//!
+//! ```text
+//! cargo run --features testing --bin gemini-standin -- --addr 127.0.0.1:8787 --fault 429 --fail-every 5
+//! ```
+//!
+//! Applications then reach it with `GEMINI_BASE_URL=http://127.0.0.1:8787`,
+//! picked up by `GeminiProvider::new()`.
+
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use ey_ai::testing::standin::{Fault, StandInConfig, router};
+
+const USAGE: &str = "Usage: gemini-standin [OPTIONS]
+
+Options:
+  --addr <ADDR>            Address to listen on [default: 127.0.0.1:8787]
+  --reply <TEXT>           Fixed reply text, the prompt is echoed otherwise
+  --latency-ms <MS>        Delay before every generation reply [default: 0]
+  --chunk-delay-ms <MS>    Delay between streamed chunks [default: 0]
+  --fault <KIND>           Injected failure: 429, 500, safety or malformed
+  --fail-every <N>         Inject the fault every N generation requests [default: 1]
+  -h, --help               Print this help
+
+A prompt containing [standin:429], [standin:500], [standin:safety] or
+[standin:malformed] always fails that way.";
+
+// parse_args:
+// listen address and stand-in configuration from the command line
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Option<(String, StandInConfig)>> {
+    let mut addr = "127.0.0.1:8787".to_string();
+    let mut config = StandInConfig::default();
+    let mut fault: Option<Fault> = None;
+    let mut every = 1;
+
+    let mut args = args;
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("Missing value for {}", arg))?;
+        let millis = |value: &str| -> Result<Duration> {
+            Ok(Duration::from_millis(value.parse().map_err(|_| {
+                anyhow!("Invalid milliseconds {} for {}", value, arg)
+            })?))
+        };
+        match arg.as_str() {
+            "--addr" => addr = value,
+            "--reply" => config = config.reply(value),
+            "--latency-ms" => config = config.latency(millis(&value)?),
+            "--chunk-delay-ms" => config = config.chunk_delay(millis(&value)?),
+            "--fault" => fault = Some(value.parse()?),
+            "--fail-every" => {
+                every = value
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid count {} for --fail-every", value))?
+            }
+            _ => return Err(anyhow!("Unknown option {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if let Some(fault) = fault {
+        config = config.fail(fault, every);
+    }
+    Ok(Some((addr, config)))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let Some((addr, config)) = parse_args(std::env::args().skip(1))? else {
+        println!("{}", USAGE);
+        return Ok(());
+    };
+
+    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    println!(
+        "Gemini stand-in listening on http://{}",
+        listener.local_addr()?
+    );
+    axum::serve(listener, router(config)).await?;
+    Ok(())
+}
@@ -0,0 +1,14 @@
+//! Tiny MCP server speaking over stdio, for testing MCP clients.
+//!
+//! ```text
+//! cargo run --features testing --bin mcp-fixture
+//! ```
+//!
+//! See [`ey_ai::testing::mcp::handle`] for what it serves.
+
+use anyhow::Result;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    ey_ai::testing::mcp::serve_stdio().await
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::model::generation::generation::Generation;
+
+/// A cached reply with its expiry, in seconds since the Unix epoch.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CacheEntry {
+    pub generation: Generation,
+    pub stored_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl CacheEntry {
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+}
+
+// unix_now:
+// current time in seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Storage of a [`ResponseCache`](super::response::ResponseCache).
+///
+/// Backends only store entries and enforce their own size limit; expiry is
+/// checked by the cache on read.
+pub trait CacheBackend: Send + Sync {
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+
+    /// Stores `entry`, returning how many entries were evicted to make room.
+    fn put(&self, key: &str, entry: CacheEntry) -> usize;
+
+    fn remove(&self, key: &str);
+
+    fn clear(&self);
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+struct LruState {
+    entries: HashMap<String, (CacheEntry, u64)>,
+    // last use tick -> key, oldest first
+    order: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+/// In-memory backend evicting the least recently used entry beyond `max_entries`.
+pub struct MemoryCache {
+    max_entries: usize,
+    state: Mutex<LruState>,
+}
+
+impl MemoryCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            state: Mutex::new(LruState {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        let (entry, used) = state.entries.get_mut(key)?;
+        let previous = std::mem::replace(used, tick);
+        let entry = entry.clone();
+        state.order.remove(&previous);
+        state.order.insert(tick, key.to_string());
+        Some(entry)
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let tick = state.tick;
+        if let Some((_, previous)) = state.entries.insert(key.to_string(), (entry, tick)) {
+            state.order.remove(&previous);
+        }
+        state.order.insert(tick, key.to_string());
+
+        let mut evicted = 0;
+        while state.entries.len() > self.max_entries {
+            let Some((_, oldest)) = state.order.pop_first() else {
+                break;
+            };
+            state.entries.remove(&oldest);
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, used)) = state.entries.remove(key) {
+            state.order.remove(&used);
+        }
+    }
+
+    fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+}
+
+/// On-disk backend storing one JSON file per entry in `dir`.
+///
+/// Entries survive restarts, which suits test suites and eval jobs. Once the
+/// files exceed `max_bytes` the least recently written ones are deleted;
+/// other files in `dir` are never counted or deleted.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    // serializes writes and evictions of this process
+    lock: Mutex<()>,
+}
+
+impl DiskCache {
+    /// Opens (and creates when missing) the cache directory.
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create cache directory {}", dir.display()))?;
+        Ok(Self {
+            dir,
+            max_bytes,
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    // files:
+    // cache files with their size and modification time, oldest first; other
+    // files sharing the directory are left alone
+    fn files(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<_> = dir
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| is_entry_file(&entry.path()))
+            .filter_map(|entry| {
+                let meta = entry.metadata().ok()?;
+                Some((entry.path(), meta.len(), meta.modified().ok()?))
+            })
+            .collect();
+        files.sort_by_key(|(_, _, modified)| *modified);
+        files
+    }
+}
+
+// is_entry_file:
+// whether `path` is named like a cache entry, a response cache key (64 hex
+// digits) with the `json` extension
+fn is_entry_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+        && path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.len() == 64 && stem.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+impl CacheBackend for DiskCache {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let content = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn put(&self, key: &str, entry: CacheEntry) -> usize {
+        let _guard = self.lock.lock().unwrap();
+        let Ok(content) = serde_json::to_vec(&entry) else {
+            return 0;
+        };
+        // write then rename so readers never see a partial file
+        let tmp = self.dir.join(format!("{}.tmp", key));
+        if fs::write(&tmp, content).is_err() || fs::rename(&tmp, self.path(key)).is_err() {
+            let _ = fs::remove_file(&tmp);
+            return 0;
+        }
+
+        let files = self.files();
+        let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+        let mut evicted = 0;
+        for (path, size, _) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= size;
+                evicted += 1;
+            }
+        }
+        evicted
+    }
+
+    fn remove(&self, key: &str) {
+        let _ = fs::remove_file(self.path(key));
+    }
+
+    fn clear(&self) {
+        let _guard = self.lock.lock().unwrap();
+        for (path, _, _) in self.files() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.files().len()
+    }
+}
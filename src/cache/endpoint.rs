@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State};
+
+use crate::{
+    cache::{response::CacheSnapshot, semantic::SemanticSnapshot},
+    models::model_client::ModelClient,
+};
+
+/// Reports hits, misses and size of the client's response cache, `null`
+/// when no cache is configured.
+///
+/// # Example Request
+///
+/// ```http
+/// GET /cache
+/// ```
+pub async fn CacheStatsResponse(
+    State(client): State<Arc<ModelClient>>,
+) -> Json<Option<CacheSnapshot>> {
+    let cache = client.cache.lock().unwrap().clone();
+    Json(cache.map(|cache| cache.snapshot()))
+}
+
+/// Reports hits, misses and size of the client's semantic cache, `null`
+/// when no semantic cache is configured.
+///
+/// # Example Request
+///
+/// ```http
+/// GET /cache/semantic
+/// ```
+pub async fn SemanticCacheStatsResponse(
+    State(client): State<Arc<ModelClient>>,
+) -> Json<Option<SemanticSnapshot>> {
+    let cache = client.semantic_cache.lock().unwrap().clone();
+    Json(cache.map(|cache| cache.snapshot()))
+}
@@ -0,0 +1,4 @@
+pub mod backend;
+pub mod endpoint;
+pub mod response;
+pub mod semantic;
@@ -0,0 +1,198 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    cache::backend::{CacheBackend, CacheEntry, unix_now},
+    model::generation::generation::{GenerateRequest, Generation},
+};
+
+/// How a single request uses the response cache of `ModelClient`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheControl {
+    /// Answer from the cache when possible, store the reply otherwise.
+    #[default]
+    Default,
+    /// Neither read nor write the cache.
+    Bypass,
+    /// Skip the cached reply but store the fresh one in its place.
+    Refresh,
+}
+
+impl CacheControl {
+    pub fn reads(&self) -> bool {
+        *self == CacheControl::Default
+    }
+
+    pub fn writes(&self) -> bool {
+        *self != CacheControl::Bypass
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    /// Requests sent with [`CacheControl::Bypass`] or [`CacheControl::Refresh`].
+    pub bypassed: u64,
+    pub stores: u64,
+    /// Entries dropped because of the size limit or their TTL.
+    pub evictions: u64,
+    /// Entries currently stored.
+    pub entries: u64,
+    /// Share of cache reads answered from the cache.
+    pub hit_rate: f64,
+}
+
+#[derive(Default)]
+struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+    stores: AtomicU64,
+    evictions: AtomicU64,
+}
+
+/// Cache of generated replies, keyed on the model, the conversation and the
+/// generation config of a request.
+///
+/// Tags, tenants and priorities don't take part in the key, so the same
+/// prompt is answered once for every caller.
+///
+/// # Usage
+/// ```rust,no_run
+/// # use std::time::Duration;
+/// # use ey_ai::{cache::{backend::DiskCache, response::ResponseCache}, model_llm::ModelLLM, utils::select_model::selector};
+/// let cache = ResponseCache::new(DiskCache::new(".ey-cache", 64 << 20).unwrap())
+///     .ttl(Duration::from_secs(24 * 3600));
+/// let client = selector(ModelLLM::Gemini).set_cache(Some(cache));
+/// ```
+pub struct ResponseCache {
+    backend: Box<dyn CacheBackend>,
+    ttl: Option<Duration>,
+    stats: CacheStats,
+}
+
+impl ResponseCache {
+    pub fn new(backend: impl CacheBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            ttl: None,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Lifetime of stored replies; without TTL they stay until evicted.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// SHA-256 of the canonical JSON of model, conversation, generation config,
+    /// cached content name, safety settings, tools and functions.
+    pub fn key(model: &str, request: &GenerateRequest) -> String {
+        let canonical = json!([
+            model,
+            request.conversation,
+            request.config,
+            request.cached_content,
+            request.safety_settings,
+            request.tools,
+            request.functions,
+            request.function_calling
+        ])
+        .to_string();
+        let digest = Sha256::digest(canonical.as_bytes());
+        digest
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, byte| {
+                let _ = write!(hex, "{:02x}", byte);
+                hex
+            })
+    }
+
+    /// Looks up the reply to `request`, honouring its [`CacheControl`].
+    pub fn get(&self, model: &str, request: &GenerateRequest) -> Option<Generation> {
+        if !request.cache.reads() {
+            self.stats.bypassed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
+        let key = Self::key(model, request);
+        let entry = match self.backend.get(&key) {
+            Some(entry) if entry.is_expired(unix_now()) => {
+                self.backend.remove(&key);
+                self.stats.evictions.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+            entry => entry,
+        };
+
+        let counter = match entry {
+            Some(_) => &self.stats.hits,
+            None => &self.stats.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry.map(|entry| entry.generation)
+    }
+
+    /// Stores the reply to `request` unless the request bypasses the cache.
+    pub fn put(&self, model: &str, request: &GenerateRequest, generation: &Generation) {
+        if !request.cache.writes() {
+            return;
+        }
+
+        let now = unix_now();
+        let entry = CacheEntry {
+            generation: generation.clone(),
+            stored_at: now,
+            expires_at: self.ttl.map(|ttl| now + ttl.as_secs()),
+        };
+        let evicted = self.backend.put(&Self::key(model, request), entry);
+        self.stats.stores.fetch_add(1, Ordering::Relaxed);
+        self.stats
+            .evictions
+            .fetch_add(evicted as u64, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.backend.clear();
+    }
+
+    pub fn snapshot(&self) -> CacheSnapshot {
+        let hits = self.stats.hits.load(Ordering::Relaxed);
+        let misses = self.stats.misses.load(Ordering::Relaxed);
+        CacheSnapshot {
+            hits,
+            misses,
+            bypassed: self.stats.bypassed.load(Ordering::Relaxed),
+            stores: self.stats.stores.load(Ordering::Relaxed),
+            evictions: self.stats.evictions.load(Ordering::Relaxed),
+            entries: self.backend.len() as u64,
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        for counter in [
+            &self.stats.hits,
+            &self.stats.misses,
+            &self.stats.bypassed,
+            &self.stats.stores,
+            &self.stats.evictions,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    cache::backend::unix_now,
+    embedding::{embedder::Embedder, index::VectorIndex},
+    model::generation::generation::{GenerateRequest, Generation},
+};
+
+struct SemanticEntry {
+    prompt: String,
+    generation: Generation,
+    expires_at: Option<u64>,
+}
+
+/// A stored answer returned for a similar prompt.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SemanticMatch {
+    pub generation: Generation,
+    /// Cosine similarity between the incoming and the stored prompt.
+    pub similarity: f32,
+    /// The stored prompt the answer was generated for.
+    pub prompt: String,
+}
+
+/// Outcome of [`SemanticCache::lookup`].
+pub enum SemanticLookup {
+    Hit(Box<SemanticMatch>),
+    /// No close enough prompt; the embedding is kept to store the answer.
+    Miss(Vec<f32>),
+    /// The request bypasses the cache or the prompt could not be embedded.
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SemanticSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+    pub bypassed: u64,
+    /// Lookups skipped because the embedder failed.
+    pub errors: u64,
+    pub stores: u64,
+    /// Entries stored across all scopes.
+    pub entries: u64,
+    pub hit_rate: f64,
+}
+
+#[derive(Default)]
+struct SemanticStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    bypassed: AtomicU64,
+    errors: AtomicU64,
+    stores: AtomicU64,
+}
+
+/// Cache answering prompts that mean the same as a previous one.
+///
+/// The turns of a request are embedded and compared with the prompts stored
+/// for the same model, system prompt and safety settings; the stored answer is returned when
+/// the cosine similarity reaches the threshold (0.9 by default).
+///
+/// # Usage
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use ey_ai::{cache::semantic::SemanticCache, embedding::embedder::ClientEmbedder, model_llm::ModelLLM, utils::select_model::selector};
+/// let client = selector(ModelLLM::Gemini);
+/// let embedder = Arc::new(ClientEmbedder::new(client.clone(), "gemini-embedding-001").tag("semantic-cache"));
+/// let client = client.set_semantic_cache(Some(SemanticCache::new(embedder).threshold(0.93)));
+/// ```
+pub struct SemanticCache {
+    embedder: Arc<dyn Embedder>,
+    threshold: f32,
+    ttl: Option<Duration>,
+    max_entries: usize,
+    scopes: Mutex<HashMap<String, VectorIndex<SemanticEntry>>>,
+    stats: SemanticStats,
+}
+
+impl SemanticCache {
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        Self {
+            embedder,
+            threshold: 0.9,
+            ttl: None,
+            max_entries: 10_000,
+            scopes: Mutex::new(HashMap::new()),
+            stats: SemanticStats::default(),
+        }
+    }
+
+    /// Minimum cosine similarity for a stored answer to be returned.
+    pub fn threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Entries kept per scope, the oldest are dropped first.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    // scope:
+    // model, generation config, system prompt (or cached prefix), safety
+    // settings, tools, functions and embedder a prompt is compared within
+    fn scope(&self, model: &str, request: &GenerateRequest) -> String {
+        json!([
+            self.embedder.name(),
+            model,
+            request.config,
+            request.conversation.system,
+            request.cached_content,
+            request.safety_settings,
+            request.tools,
+            request.functions,
+            request.function_calling
+        ])
+        .to_string()
+    }
+
+    // prompt:
+    // text embedded for a request, its turns without the system prompt
+    fn prompt(request: &GenerateRequest) -> String {
+        let turns: Vec<&str> = request
+            .conversation
+            .turns
+            .iter()
+            .map(|turn| turn.text.as_str())
+            .collect();
+        turns.join("\n")
+    }
+
+    /// Embeds the prompt of `request` and looks for a close enough stored one,
+    /// honouring the request's [`CacheControl`](crate::cache::response::CacheControl).
+    ///
+    /// An embedder failure never fails the request, the lookup is skipped.
+    pub async fn lookup(&self, model: &str, request: &GenerateRequest) -> SemanticLookup {
+        if !request.cache.writes() {
+            self.stats.bypassed.fetch_add(1, Ordering::Relaxed);
+            return SemanticLookup::Skipped;
+        }
+        // function results aren't part of the embedded prompt, so two steps
+        // of a tool loop would look alike
+        if request
+            .conversation
+            .turns
+            .iter()
+            .any(|turn| !turn.function_responses.is_empty())
+        {
+            return SemanticLookup::Skipped;
+        }
+        let embedding = match self.embedder.embed_one(&Self::prompt(request)).await {
+            Ok(embedding) => embedding,
+            Err(_) => {
+                self.stats.errors.fetch_add(1, Ordering::Relaxed);
+                return SemanticLookup::Skipped;
+            }
+        };
+        if !request.cache.reads() {
+            self.stats.bypassed.fetch_add(1, Ordering::Relaxed);
+            return SemanticLookup::Miss(embedding);
+        }
+
+        let now = unix_now();
+        let mut scopes = self.scopes.lock().unwrap();
+        let found = scopes
+            .get_mut(&self.scope(model, request))
+            .and_then(|index| {
+                index.retain(|entry| entry.value.expires_at.is_none_or(|at| at > now));
+                let (similarity, entry) = index.search(&embedding, 1).into_iter().next()?;
+                (similarity >= self.threshold).then(|| SemanticMatch {
+                    generation: entry.value.generation.clone(),
+                    similarity,
+                    prompt: entry.value.prompt.clone(),
+                })
+            });
+
+        match found {
+            Some(found) => {
+                self.stats.hits.fetch_add(1, Ordering::Relaxed);
+                SemanticLookup::Hit(Box::new(found))
+            }
+            None => {
+                self.stats.misses.fetch_add(1, Ordering::Relaxed);
+                SemanticLookup::Miss(embedding)
+            }
+        }
+    }
+
+    /// Stores the answer to `request` under the embedding returned by a missed
+    /// lookup, replacing the answer stored for the same prompt.
+    pub fn store(
+        &self,
+        model: &str,
+        request: &GenerateRequest,
+        embedding: Vec<f32>,
+        generation: &Generation,
+    ) {
+        let entry = SemanticEntry {
+            prompt: Self::prompt(request),
+            generation: generation.clone(),
+            expires_at: self.ttl.map(|ttl| unix_now() + ttl.as_secs()),
+        };
+        let mut scopes = self.scopes.lock().unwrap();
+        let index = scopes.entry(self.scope(model, request)).or_default();
+        index.retain(|stored| stored.value.prompt != entry.prompt);
+        index.insert(embedding, entry);
+        index.truncate_oldest(self.max_entries);
+        self.stats.stores.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        self.scopes.lock().unwrap().clear();
+    }
+
+    pub fn snapshot(&self) -> SemanticSnapshot {
+        let hits = self.stats.hits.load(Ordering::Relaxed);
+        let misses = self.stats.misses.load(Ordering::Relaxed);
+        let entries: usize = self.scopes.lock().unwrap().values().map(|i| i.len()).sum();
+        SemanticSnapshot {
+            hits,
+            misses,
+            bypassed: self.stats.bypassed.load(Ordering::Relaxed),
+            errors: self.stats.errors.load(Ordering::Relaxed),
+            stores: self.stats.stores.load(Ordering::Relaxed),
+            entries: entries as u64,
+            hit_rate: if hits + misses == 0 {
+                0.0
+            } else {
+                hits as f64 / (hits + misses) as f64
+            },
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde_json::{Value, json};
+
+use crate::{
+    embedding::index::normalize,
+    models::{
+        gemini::{default_base_url, read_json, send_error},
+        model_client::ModelClient,
+    },
+};
+
+/// A model turning texts into vectors, used by the semantic cache and by
+/// retrieval.
+///
+/// Vectors of one embedder all have the same number of dimensions.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Short identifier of the embedder, part of the scope of stored vectors.
+    fn name(&self) -> &str;
+
+    /// Embeds every text of `texts`, in order.
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    async fn embed_one(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&[text.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("Embedder returned no vector"))
+    }
+}
+
+/// Deterministic local embedder hashing words and character trigrams into a
+/// fixed number of dimensions.
+///
+/// No network and no model: the same text always gets the same vector and
+/// texts sharing words or word fragments end up close. Meant for tests and
+/// offline use, paraphrases with different wording score lower than with a
+/// real embedding model.
+pub struct HashEmbedder {
+    dimensions: usize,
+}
+
+impl HashEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        Self {
+            dimensions: dimensions.max(1),
+        }
+    }
+
+    pub fn embed_text(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0; self.dimensions];
+        let lower = text.to_lowercase();
+        for word in lower.split(|c: char| !c.is_alphanumeric()) {
+            if word.is_empty() {
+                continue;
+            }
+            self.add(&mut vector, word.as_bytes(), 1.0);
+
+            let padded: Vec<char> = format!(" {} ", word).chars().collect();
+            for trigram in padded.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                self.add(&mut vector, trigram.as_bytes(), 0.5);
+            }
+        }
+        normalize(&mut vector);
+        vector
+    }
+
+    // add:
+    // adds `weight` to the bucket of `feature`, the hash also picks the sign
+    fn add(&self, vector: &mut [f32], feature: &[u8], weight: f32) {
+        let hash = fnv1a(feature);
+        let bucket = (hash % self.dimensions as u64) as usize;
+        let sign = if (hash >> 63) == 0 { 1.0 } else { -1.0 };
+        vector[bucket] += sign * weight;
+    }
+}
+
+impl Default for HashEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+// fnv1a:
+// 64-bit FNV-1a, stable across platforms and releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[async_trait]
+impl Embedder for HashEmbedder {
+    fn name(&self) -> &str {
+        "hash"
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+/// Embedder calling an embedding model through a [`ModelClient`], see
+/// [`ModelClient::Embed`].
+///
+/// The requests go through the client's key pool, are checked against the
+/// tenant budget and recorded in its usage ledger like generations.
+#[derive(Clone)]
+pub struct ClientEmbedder {
+    client: ModelClient,
+    model: String,
+    tag: Option<String>,
+    tenant: Option<String>,
+}
+
+impl ClientEmbedder {
+    /// `model` is an embedding model such as `"gemini-embedding-001"`.
+    pub fn new(client: ModelClient, model: impl Into<String>) -> Self {
+        Self {
+            client,
+            model: model.into(),
+            tag: None,
+            tenant: None,
+        }
+    }
+
+    /// Usage tag of the embedding requests.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Tenant whose budget pays for the embedding requests.
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+}
+
+#[async_trait]
+impl Embedder for ClientEmbedder {
+    fn name(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.client
+            .Embed(
+                &self.model,
+                texts,
+                self.tag.as_deref(),
+                self.tenant.as_deref(),
+            )
+            .await
+    }
+}
+
+/// Embedder backed by the Gemini `batchEmbedContents` endpoint.
+pub struct GeminiEmbedder {
+    api_key: String,
+    model: String,
+    base_url: String,
+}
+
+impl GeminiEmbedder {
+    /// `model` is an embedding model such as `"gemini-embedding-001"`.
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            base_url: default_base_url(),
+        }
+    }
+
+    /// Sends requests to `base_url` instead of the Gemini API (or the
+    /// `GEMINI_BASE_URL` environment variable).
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl Embedder for GeminiEmbedder {
+    fn name(&self) -> &str {
+        &self.model
+    }
+
+    /// # API Details
+    /// **Endpoint:**
+    /// ```text
+    /// POST https://generativelanguage.googleapis.com/v1beta/models/{model}:batchEmbedContents?key={api_key}
+    /// ```
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let url = format!(
+            "{}/v1beta/models/{}:batchEmbedContents?key={}",
+            self.base_url, self.model, self.api_key
+        );
+
+        let requests: Vec<Value> = texts
+            .iter()
+            .map(|text| {
+                json!({
+                    "model": format!("models/{}", self.model),
+                    "content": { "parts": [{ "text": text }] },
+                })
+            })
+            .collect();
+
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&json!({ "requests": requests }))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+
+        let json = read_json(res).await?;
+        let embeddings = json["embeddings"]
+            .as_array()
+            .ok_or_else(|| anyhow!("No embeddings from Gemini"))?;
+        embeddings
+            .iter()
+            .map(|embedding| {
+                embedding["values"]
+                    .as_array()
+                    .map(|values| {
+                        values
+                            .iter()
+                            .filter_map(|v| v.as_f64().map(|v| v as f32))
+                            .collect()
+                    })
+                    .ok_or_else(|| anyhow!("Malformed embedding from Gemini"))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,117 @@
+/// Scales `vector` to unit length, leaving a zero vector untouched.
+pub fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// Cosine similarity of two vectors, 0 when either is zero or the lengths differ.
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|v| v * v).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+pub struct IndexEntry<T> {
+    pub id: u64,
+    /// Unit length copy of the inserted vector.
+    pub vector: Vec<f32>,
+    pub value: T,
+}
+
+/// In-process vector index with exact (brute force) cosine search.
+///
+/// Vectors are normalized on insert so a search is a dot product per entry,
+/// fine for the few thousand entries of a cache or a small knowledge base.
+pub struct VectorIndex<T> {
+    entries: Vec<IndexEntry<T>>,
+    next_id: u64,
+}
+
+impl<T> Default for VectorIndex<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> VectorIndex<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Adds `value` under `vector` and returns its id; ids grow with insertion order.
+    pub fn insert(&mut self, mut vector: Vec<f32>, value: T) -> u64 {
+        normalize(&mut vector);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(IndexEntry { id, vector, value });
+        id
+    }
+
+    /// The `k` entries most similar to `query`, best first, with their similarity.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(f32, &IndexEntry<T>)> {
+        let mut query = query.to_vec();
+        normalize(&mut query);
+
+        let mut scored: Vec<(f32, &IndexEntry<T>)> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.vector.len() == query.len())
+            .map(|entry| {
+                let score = entry.vector.iter().zip(&query).map(|(a, b)| a * b).sum();
+                (score, entry)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(k);
+        scored
+    }
+
+    pub fn get(&self, id: u64) -> Option<&IndexEntry<T>> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn remove(&mut self, id: u64) -> Option<T> {
+        let position = self.entries.iter().position(|entry| entry.id == id)?;
+        Some(self.entries.remove(position).value)
+    }
+
+    /// Drops the oldest entries until at most `max` are left, returning how many were dropped.
+    pub fn truncate_oldest(&mut self, max: usize) -> usize {
+        let excess = self.entries.len().saturating_sub(max);
+        self.entries.drain(..excess);
+        excess
+    }
+
+    pub fn retain(&mut self, mut keep: impl FnMut(&IndexEntry<T>) -> bool) {
+        self.entries.retain(|entry| keep(entry));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IndexEntry<T>> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
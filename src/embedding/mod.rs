@@ -0,0 +1,2 @@
+pub mod embedder;
+pub mod index;
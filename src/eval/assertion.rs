@@ -0,0 +1,183 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    cache::response::CacheControl,
+    eval::{dataset::EvalCase, report::AssertionResult, schema},
+    model::generation::generation::GenerateRequest,
+    models::model_client::ModelClient,
+};
+
+/// A Rust check registered on the evaluator under a name, referenced by
+/// [`Assertion::Predicate`]. Returning an error fails the assertion with
+/// the error as reason.
+pub type Predicate = Arc<dyn Fn(&EvalCase, &str) -> Result<()> + Send + Sync>;
+
+/// A check applied to the answer of a case.
+///
+/// In datasets an assertion is an object tagged by `type`:
+///
+/// ```yaml
+/// assert:
+///   - type: exact
+///     value: "4"
+///   - type: contains
+///     value: paris
+///     ignore_case: true
+///   - type: regex
+///     pattern: "^\\d+$"
+///   - type: json_schema
+///     schema: { type: object, required: [name] }
+///   - type: predicate
+///     name: short_answer
+///   - type: judge
+///     rubric: The answer is polite and names a capital city.
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Assertion {
+    /// The trimmed answer equals `value`.
+    Exact {
+        value: String,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    Contains {
+        value: String,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    /// The answer matches the regular expression.
+    Regex { pattern: String },
+    /// The answer, stripped of a Markdown code fence, is JSON valid against
+    /// `schema` (see [`schema::validate`] for the supported keywords).
+    JsonSchema { schema: Value },
+    /// A named Rust predicate registered with
+    /// [`Evaluator::predicate`](crate::eval::runner::Evaluator::predicate).
+    Predicate { name: String },
+    /// A judge model grades the answer against `rubric`.
+    Judge { rubric: String },
+}
+
+impl Assertion {
+    /// Short description used in reports.
+    pub fn label(&self) -> String {
+        match self {
+            Assertion::Exact { value, .. } => format!("exact: {}", value),
+            Assertion::Contains { value, .. } => format!("contains: {}", value),
+            Assertion::Regex { pattern } => format!("regex: {}", pattern),
+            Assertion::JsonSchema { .. } => "json_schema".to_string(),
+            Assertion::Predicate { name } => format!("predicate: {}", name),
+            Assertion::Judge { rubric } => format!("judge: {}", rubric),
+        }
+    }
+
+    /// Applies the assertion to `output`, the answer to `case`.
+    ///
+    /// Misconfigurations (an invalid regex, an unknown predicate, a judge
+    /// assertion without a judge model) fail the assertion rather than the run.
+    pub async fn check(
+        &self,
+        case: &EvalCase,
+        output: &str,
+        predicates: &HashMap<String, Predicate>,
+        judge: Option<&ModelClient>,
+    ) -> AssertionResult {
+        let outcome = match self {
+            Assertion::Exact { value, ignore_case } => {
+                let output = output.trim();
+                let equal = if *ignore_case {
+                    output.to_lowercase() == value.trim().to_lowercase()
+                } else {
+                    output == value.trim()
+                };
+                expect(equal, || format!("expected {:?}, got {:?}", value, output))
+            }
+            Assertion::Contains { value, ignore_case } => {
+                let found = if *ignore_case {
+                    output.to_lowercase().contains(&value.to_lowercase())
+                } else {
+                    output.contains(value.as_str())
+                };
+                expect(found, || format!("{:?} not found", value))
+            }
+            Assertion::Regex { pattern } => regex::Regex::new(pattern)
+                .map_err(|e| anyhow!("Invalid regex: {}", e))
+                .and_then(|re| expect(re.is_match(output), || "no match".to_string())),
+            Assertion::JsonSchema { schema } => serde_json::from_str::<Value>(strip_fence(output))
+                .map_err(|e| anyhow!("Invalid JSON: {}", e))
+                .and_then(|value| {
+                    let errors = schema::validate(schema, &value);
+                    expect(errors.is_empty(), || errors.join("; "))
+                }),
+            Assertion::Predicate { name } => match predicates.get(name) {
+                Some(predicate) => predicate(case, output),
+                None => Err(anyhow!("Unknown predicate {}", name)),
+            },
+            Assertion::Judge { rubric } => match judge {
+                Some(judge) => grade(judge, rubric, case, output).await,
+                None => Err(anyhow!("No judge model configured")),
+            },
+        };
+
+        AssertionResult {
+            assertion: self.label(),
+            passed: outcome.is_ok(),
+            reason: outcome.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+fn expect(ok: bool, reason: impl FnOnce() -> String) -> Result<()> {
+    if ok { Ok(()) } else { Err(anyhow!(reason())) }
+}
+
+/// Removes a surrounding Markdown code fence (```` ```json ... ``` ````)
+/// models often wrap JSON answers in.
+pub fn strip_fence(text: &str) -> &str {
+    let text = text.trim();
+    let Some(inner) = text.strip_prefix("```") else {
+        return text;
+    };
+    let inner = inner.strip_suffix("```").unwrap_or(inner);
+    // drop the language tag of the opening fence
+    match inner.split_once('\n') {
+        Some((tag, body)) if !tag.trim().contains(' ') => body.trim(),
+        _ => inner.trim(),
+    }
+}
+
+// JUDGE_PROMPT:
+// instructions for the judge model, the rubric, question and answer follow
+const JUDGE_PROMPT: &str = "You grade answers of an AI assistant. Decide whether the answer \
+satisfies the rubric. Reply with JSON only, in the form {\"pass\": true|false, \"reason\": \"...\"}.";
+
+#[derive(Deserialize)]
+struct Verdict {
+    pass: bool,
+    #[serde(default)]
+    reason: String,
+}
+
+// grade:
+// asks the judge model whether `output` satisfies `rubric`
+async fn grade(judge: &ModelClient, rubric: &str, case: &EvalCase, output: &str) -> Result<()> {
+    let prompt = format!(
+        "{}\n\nRubric:\n{}\n\nQuestion:\n{}\n\nAnswer:\n{}",
+        JUDGE_PROMPT, rubric, case.prompt, output
+    );
+    let request = GenerateRequest::from_prompt(prompt)
+        .temperature(0.0)
+        .tag("eval-judge")
+        .cache(CacheControl::Bypass);
+    let reply = judge
+        .Generate(request)
+        .await
+        .map_err(|e| anyhow!("Judge failed: {}", e))?;
+    let verdict: Verdict = serde_json::from_str(strip_fence(&reply.text))
+        .map_err(|e| anyhow!("Unreadable judge verdict {:?}: {}", reply.text, e))?;
+    expect(verdict.pass, || verdict.reason)
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    eval::assertion::Assertion,
+    model::{conversation::conversation::Conversation, generation::generation::GenerateRequest},
+};
+
+/// One prompt of a dataset and the checks its answer must pass.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EvalCase {
+    pub id: String,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default, rename = "assert", skip_serializing_if = "Vec::is_empty")]
+    pub assertions: Vec<Assertion>,
+}
+
+impl EvalCase {
+    pub fn new(id: impl Into<String>, prompt: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            prompt: prompt.into(),
+            system: None,
+            assertions: Vec::new(),
+        }
+    }
+
+    pub fn system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn assert(mut self, assertion: Assertion) -> Self {
+        self.assertions.push(assertion);
+        self
+    }
+
+    pub fn request(&self) -> GenerateRequest {
+        let mut conversation = Conversation::from_prompt(self.prompt.clone());
+        if let Some(system) = &self.system {
+            conversation = conversation.with_system(system.clone());
+        }
+        GenerateRequest::new(conversation)
+    }
+}
+
+/// A named list of [`EvalCase`]s.
+///
+/// Loaded from YAML (`cases:` list, with an optional `name:`) or from JSONL,
+/// one case per line:
+///
+/// ```yaml
+/// name: geography
+/// cases:
+///   - id: capital-fr
+///     prompt: What is the capital of France? One word.
+///     assert:
+///       - type: contains
+///         value: Paris
+///       - type: regex
+///         pattern: "^\\w+\\.?$"
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Dataset {
+    #[serde(default)]
+    pub name: String,
+    pub cases: Vec<EvalCase>,
+}
+
+impl Dataset {
+    pub fn new(name: impl Into<String>, cases: Vec<EvalCase>) -> Self {
+        Self {
+            name: name.into(),
+            cases,
+        }
+    }
+
+    /// Reads a `.yaml` / `.yml`, `.json` or `.jsonl` dataset; the file name
+    /// is the default dataset name.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read dataset {}: {}", path.display(), e))?;
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let mut dataset = match extension {
+            "yaml" | "yml" => Self::from_yaml(&content)?,
+            "json" => serde_json::from_str(&content)
+                .map_err(|e| anyhow!("Failed to parse dataset: {}", e))?,
+            "jsonl" => Self::from_jsonl(&content)?,
+            _ => return Err(anyhow!("Unsupported dataset format {}", path.display())),
+        };
+        if dataset.name.is_empty() {
+            dataset.name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+        }
+        dataset.check()?;
+        Ok(dataset)
+    }
+
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        serde_norway::from_str(content).map_err(|e| anyhow!("Failed to parse dataset: {}", e))
+    }
+
+    pub fn from_jsonl(content: &str) -> Result<Self> {
+        let cases = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| anyhow!("Failed to parse dataset line {}: {}", i + 1, e))
+            })
+            .collect::<Result<Vec<EvalCase>>>()?;
+        Ok(Self::new("", cases))
+    }
+
+    // check:
+    // case ids identify results across runs, they must be unique
+    fn check(&self) -> Result<()> {
+        let mut ids = std::collections::HashSet::new();
+        match self.cases.iter().find(|case| !ids.insert(case.id.as_str())) {
+            Some(case) => Err(anyhow!("Duplicate case id {}", case.id)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod assertion;
+pub mod dataset;
+pub mod report;
+pub mod runner;
+pub mod schema;
@@ -0,0 +1,289 @@
+use std::{collections::HashMap, fmt::Write, path::Path};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::model::generation::generation::Usage;
+
+/// Outcome of one assertion on one answer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AssertionResult {
+    pub assertion: String,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Outcome of one case against one target. A case passes when the request
+/// succeeded and every assertion passed; a case without assertions fails.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CaseResult {
+    pub id: String,
+    pub passed: bool,
+    pub output: String,
+    /// The request failed, no assertion was run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    pub cost_usd: f64,
+    pub assertions: Vec<AssertionResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct EvalSummary {
+    pub cases: usize,
+    pub passed: usize,
+    /// Cases whose request failed.
+    pub errors: usize,
+    pub pass_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p95_latency_ms: u64,
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    pub total_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl EvalSummary {
+    pub fn from_cases(cases: &[CaseResult]) -> Self {
+        if cases.is_empty() {
+            return Self::default();
+        }
+        let passed = cases.iter().filter(|c| c.passed).count();
+        let mut latencies: Vec<u64> = cases.iter().map(|c| c.latency_ms).collect();
+        latencies.sort_unstable();
+        // nearest rank
+        let p95 = latencies[(latencies.len() * 95).div_ceil(100).max(1) - 1];
+        let mut usage = Usage::default();
+        for case in cases.iter().filter_map(|c| c.usage.as_ref()) {
+            usage.add(case);
+        }
+
+        Self {
+            cases: cases.len(),
+            passed,
+            errors: cases.iter().filter(|c| c.error.is_some()).count(),
+            pass_rate: passed as f64 / cases.len() as f64,
+            avg_latency_ms: latencies.iter().sum::<u64>() as f64 / cases.len() as f64,
+            p95_latency_ms: p95,
+            prompt_tokens: usage.prompt_tokens,
+            output_tokens: usage.candidates_tokens + usage.thoughts_tokens,
+            total_tokens: usage.total_tokens,
+            cost_usd: cases.iter().map(|c| c.cost_usd).sum(),
+        }
+    }
+}
+
+/// Results of a dataset against one target configuration.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TargetReport {
+    pub name: String,
+    pub model: String,
+    pub summary: EvalSummary,
+    pub cases: Vec<CaseResult>,
+}
+
+/// Report of an evaluation run, saved as JSON to compare later runs against.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EvalReport {
+    pub dataset: String,
+    /// RFC 3339 start time of the run.
+    pub started_at: String,
+    pub targets: Vec<TargetReport>,
+}
+
+impl EvalReport {
+    pub fn target(&self, name: &str) -> Option<&TargetReport> {
+        self.targets.iter().find(|t| t.name == name)
+    }
+
+    /// True when every case of every target passed.
+    pub fn passed(&self) -> bool {
+        self.targets
+            .iter()
+            .all(|t| t.summary.passed == t.summary.cases)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| anyhow!("Failed to serialize report: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| anyhow!("Failed to parse report: {}", e))
+    }
+
+    /// Writes the report, as Markdown when `path` ends in `.md` and as JSON
+    /// otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = match path.extension().and_then(|e| e.to_str()) {
+            Some("md") => self.to_markdown(),
+            _ => self.to_json()?,
+        };
+        std::fs::write(path, content)
+            .map_err(|e| anyhow!("Failed to write report {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read report {}: {}", path.display(), e))?;
+        Self::from_json(&content)
+    }
+
+    /// Summary table of the targets followed by the failed cases.
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Eval: {}\n\n_{}_\n\n", self.dataset, self.started_at);
+        out.push_str(
+            "| Target | Model | Pass rate | Passed | Errors | Avg latency | p95 latency | Tokens | Cost (USD) |\n",
+        );
+        out.push_str("|---|---|---|---|---|---|---|---|---|\n");
+        for target in &self.targets {
+            let s = &target.summary;
+            let _ = writeln!(
+                out,
+                "| {} | {} | {:.1}% | {}/{} | {} | {:.0} ms | {} ms | {} | {:.4} |",
+                cell(&target.name),
+                cell(&target.model),
+                s.pass_rate * 100.0,
+                s.passed,
+                s.cases,
+                s.errors,
+                s.avg_latency_ms,
+                s.p95_latency_ms,
+                s.total_tokens,
+                s.cost_usd
+            );
+        }
+
+        for target in &self.targets {
+            let failed: Vec<&CaseResult> = target.cases.iter().filter(|c| !c.passed).collect();
+            if failed.is_empty() {
+                continue;
+            }
+            let _ = write!(out, "\n## Failures: {}\n\n", target.name);
+            out.push_str("| Case | Check | Reason |\n|---|---|---|\n");
+            for case in failed {
+                if let Some(error) = &case.error {
+                    let _ = writeln!(out, "| {} | request | {} |", cell(&case.id), cell(error));
+                }
+                for assertion in case.assertions.iter().filter(|a| !a.passed) {
+                    let _ = writeln!(
+                        out,
+                        "| {} | {} | {} |",
+                        cell(&case.id),
+                        cell(&assertion.assertion),
+                        cell(assertion.reason.as_deref().unwrap_or(""))
+                    );
+                }
+            }
+        }
+        out
+    }
+
+    /// Compares this run with `baseline`, target by target and case by case.
+    pub fn diff(&self, baseline: &EvalReport) -> EvalDiff {
+        let mut targets = Vec::new();
+        let mut unmatched = Vec::new();
+        for target in &self.targets {
+            let Some(before) = baseline.target(&target.name) else {
+                unmatched.push(target.name.clone());
+                continue;
+            };
+            let before_cases: HashMap<&str, bool> = before
+                .cases
+                .iter()
+                .map(|c| (c.id.as_str(), c.passed))
+                .collect();
+            let mut regressions = Vec::new();
+            let mut fixes = Vec::new();
+            for case in &target.cases {
+                match before_cases.get(case.id.as_str()) {
+                    Some(true) if !case.passed => regressions.push(case.id.clone()),
+                    Some(false) if case.passed => fixes.push(case.id.clone()),
+                    _ => {}
+                }
+            }
+
+            let (now, then) = (&target.summary, &before.summary);
+            targets.push(TargetDiff {
+                name: target.name.clone(),
+                pass_rate_delta: now.pass_rate - then.pass_rate,
+                avg_latency_delta_ms: now.avg_latency_ms - then.avg_latency_ms,
+                total_tokens_delta: now.total_tokens as i64 - then.total_tokens as i64,
+                cost_delta_usd: now.cost_usd - then.cost_usd,
+                regressions,
+                fixes,
+            });
+        }
+        unmatched.extend(
+            baseline
+                .targets
+                .iter()
+                .filter(|t| self.target(&t.name).is_none())
+                .map(|t| t.name.clone()),
+        );
+
+        EvalDiff { targets, unmatched }
+    }
+}
+
+/// Change of one target between two runs; deltas are current minus baseline.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TargetDiff {
+    pub name: String,
+    pub pass_rate_delta: f64,
+    pub avg_latency_delta_ms: f64,
+    pub total_tokens_delta: i64,
+    pub cost_delta_usd: f64,
+    /// Cases that passed in the baseline and fail now.
+    pub regressions: Vec<String>,
+    /// Cases that failed in the baseline and pass now.
+    pub fixes: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct EvalDiff {
+    pub targets: Vec<TargetDiff>,
+    /// Targets present in only one of the two runs.
+    pub unmatched: Vec<String>,
+}
+
+impl EvalDiff {
+    pub fn has_regressions(&self) -> bool {
+        self.targets.iter().any(|t| !t.regressions.is_empty())
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("## Changes since baseline\n\n");
+        out.push_str(
+            "| Target | Pass rate | Avg latency | Tokens | Cost (USD) | Regressions | Fixes |\n",
+        );
+        out.push_str("|---|---|---|---|---|---|---|\n");
+        for target in &self.targets {
+            let _ = writeln!(
+                out,
+                "| {} | {:+.1} pts | {:+.0} ms | {:+} | {:+.4} | {} | {} |",
+                cell(&target.name),
+                target.pass_rate_delta * 100.0,
+                target.avg_latency_delta_ms,
+                target.total_tokens_delta,
+                target.cost_delta_usd,
+                cell(&target.regressions.join(", ")),
+                cell(&target.fixes.join(", "))
+            );
+        }
+        if !self.unmatched.is_empty() {
+            let _ = write!(out, "\nNot in both runs: {}\n", self.unmatched.join(", "));
+        }
+        out
+    }
+}
+
+// cell:
+// escapes text for a single line Markdown table cell
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
@@ -0,0 +1,214 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use anyhow::{Result, anyhow};
+use chrono::Utc;
+use futures::{StreamExt, stream};
+
+use crate::{
+    cache::response::CacheControl,
+    eval::{
+        assertion::Predicate,
+        dataset::{Dataset, EvalCase},
+        report::{AssertionResult, CaseResult, EvalReport, EvalSummary, TargetReport},
+    },
+    model::generation::generation::GenerationConfig,
+    models::model_client::ModelClient,
+};
+
+/// A model configuration the dataset is run against.
+#[derive(Clone)]
+pub struct EvalTarget {
+    pub name: String,
+    pub client: ModelClient,
+    pub config: GenerationConfig,
+}
+
+impl EvalTarget {
+    pub fn new(name: impl Into<String>, client: ModelClient) -> Self {
+        Self {
+            name: name.into(),
+            client,
+            config: GenerationConfig::default(),
+        }
+    }
+
+    pub fn config(mut self, config: GenerationConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+/// Runs datasets against targets and grades the answers.
+///
+/// Requests bypass the response caches so every run measures the model, and
+/// are tagged `eval` in the usage ledger (`eval-judge` for the judge model).
+///
+/// # Example
+/// ```no_run
+/// # use ey_ai::eval::{dataset::Dataset, runner::{EvalTarget, Evaluator}};
+/// # use ey_ai::models::model_client::ModelClient;
+/// # async fn run(flash: ModelClient, pro: ModelClient) -> anyhow::Result<()> {
+/// let report = Evaluator::new()
+///     .target(EvalTarget::new("flash", flash))
+///     .target(EvalTarget::new("pro", pro.clone()))
+///     .judge(pro)
+///     .predicate("short", |_, output| {
+///         anyhow::ensure!(output.len() < 200, "answer too long");
+///         Ok(())
+///     })
+///     .run(&Dataset::load("evals/geography.yaml")?)
+///     .await?;
+/// report.save("report.md")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Evaluator {
+    targets: Vec<EvalTarget>,
+    judge: Option<ModelClient>,
+    predicates: HashMap<String, Predicate>,
+    concurrency: usize,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Self {
+            targets: Vec::new(),
+            judge: None,
+            predicates: HashMap::new(),
+            concurrency: 4,
+        }
+    }
+
+    pub fn target(mut self, target: EvalTarget) -> Self {
+        self.targets.push(target);
+        self
+    }
+
+    /// Model grading [`Assertion::Judge`](crate::eval::assertion::Assertion::Judge) checks.
+    pub fn judge(mut self, client: ModelClient) -> Self {
+        self.judge = Some(client);
+        self
+    }
+
+    pub fn predicate(
+        mut self,
+        name: impl Into<String>,
+        predicate: impl Fn(&EvalCase, &str) -> Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.predicates.insert(name.into(), Arc::new(predicate));
+        self
+    }
+
+    /// Number of cases of a target in flight at once (4 by default).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Runs every case of `dataset` against every target, one target after
+    /// the other. Failed requests are reported as failed cases.
+    pub async fn run(&self, dataset: &Dataset) -> Result<EvalReport> {
+        if self.targets.is_empty() {
+            return Err(anyhow!("No eval target configured"));
+        }
+        let started_at = Utc::now().to_rfc3339();
+
+        let mut targets = Vec::new();
+        for target in &self.targets {
+            let cases: Vec<CaseResult> = stream::iter(&dataset.cases)
+                .map(|case| self.run_case(target, case))
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+            targets.push(TargetReport {
+                name: target.name.clone(),
+                model: target.client.model.lock().unwrap().clone(),
+                summary: EvalSummary::from_cases(&cases),
+                cases,
+            });
+        }
+
+        Ok(EvalReport {
+            dataset: dataset.name.clone(),
+            started_at,
+            targets,
+        })
+    }
+
+    // run_case:
+    // generates the answer of one case and applies its assertions
+    async fn run_case(&self, target: &EvalTarget, case: &EvalCase) -> CaseResult {
+        let request = case
+            .request()
+            .config(target.config.clone())
+            .tag("eval")
+            .cache(CacheControl::Bypass);
+        let started = Instant::now();
+        let reply = target.client.Generate(request).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let generation = match reply {
+            Ok(generation) => generation,
+            Err(e) => {
+                return CaseResult {
+                    id: case.id.clone(),
+                    passed: false,
+                    output: String::new(),
+                    error: Some(e.to_string()),
+                    latency_ms,
+                    usage: None,
+                    cost_usd: 0.0,
+                    assertions: Vec::new(),
+                };
+            }
+        };
+
+        let model = match &generation.served_by {
+            Some(served) => served.model.clone(),
+            None => target.client.model.lock().unwrap().clone(),
+        };
+        let cost_usd = generation
+            .usage
+            .as_ref()
+            .map(|usage| target.client.usage.estimate_cost(&model, usage))
+            .unwrap_or(0.0);
+
+        let mut assertions = Vec::new();
+        for assertion in &case.assertions {
+            let result = assertion
+                .check(
+                    case,
+                    &generation.text,
+                    &self.predicates,
+                    self.judge.as_ref(),
+                )
+                .await;
+            assertions.push(result);
+        }
+        if assertions.is_empty() {
+            assertions.push(AssertionResult {
+                assertion: "assert".into(),
+                passed: false,
+                reason: Some("the case has no assertions".into()),
+            });
+        }
+
+        CaseResult {
+            id: case.id.clone(),
+            passed: assertions.iter().all(|a| a.passed),
+            output: generation.text,
+            error: None,
+            latency_ms,
+            usage: generation.usage,
+            cost_usd,
+            assertions,
+        }
+    }
+}
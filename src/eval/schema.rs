@@ -0,0 +1,241 @@
+use serde_json::Value;
+
+// keywords `check` enforces
+const SUPPORTED: [&str; 13] = [
+    "type",
+    "enum",
+    "const",
+    "properties",
+    "required",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minLength",
+    "maxLength",
+    "minimum",
+    "maximum",
+];
+
+// keywords without effect on validation
+const ANNOTATIONS: [&str; 7] = [
+    "$schema",
+    "title",
+    "description",
+    "default",
+    "examples",
+    "$id",
+    "$comment",
+];
+
+/// Validates `value` against a JSON schema and returns every violation found,
+/// empty when the value is valid.
+///
+/// Only the subset of JSON Schema that structured model output needs is
+/// understood: `type` (a name or a list of names), `enum`, `const`,
+/// `properties`, `required`, `additionalProperties: false`, `items`,
+/// `minItems` / `maxItems`, `minLength` / `maxLength` and `minimum` /
+/// `maximum`. Annotations (`title`, `description`, `default`, ...) are
+/// allowed; any other keyword (`pattern`, `oneOf`, `$ref`, ...) is reported
+/// as a violation rather than silently accepted.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    unsupported(schema, "schema", &mut errors);
+    if errors.is_empty() {
+        check(schema, value, "$", &mut errors);
+    }
+    errors
+}
+
+// unsupported:
+// reports the keywords `check` would not enforce, anywhere in the schema
+fn unsupported(schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    for (keyword, value) in schema {
+        match keyword.as_str() {
+            "properties" => {
+                for (name, property) in value.as_object().into_iter().flatten() {
+                    unsupported(property, &format!("{}.properties.{}", path, name), errors);
+                }
+            }
+            "items" | "additionalProperties" => {
+                unsupported(value, &format!("{}.{}", path, keyword), errors)
+            }
+            keyword if SUPPORTED.contains(&keyword) || ANNOTATIONS.contains(&keyword) => {}
+            keyword => errors.push(format!("{}: unsupported keyword {}", path, keyword)),
+        }
+    }
+}
+
+// check:
+// validates one value at `path` and pushes violations to `errors`
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` / `{}` accept anything, `false` nothing
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{}: not allowed", path));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                path,
+                names.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array)
+        && !allowed.contains(value)
+    {
+        errors.push(format!(
+            "{}: {} is not one of {}",
+            path,
+            value,
+            Value::from(allowed.clone())
+        ));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        errors.push(format!("{}: expected {}", path, expected));
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for name in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !object.contains_key(name) {
+                    errors.push(format!("{}: missing property {}", path, name));
+                }
+            }
+            for (name, field) in object {
+                let field_path = format!("{}.{}", path, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => check(field_schema, field, &field_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{}: unexpected property", field_path))
+                        }
+                        Some(extra) => check(extra, field, &field_path, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            bound(
+                schema,
+                "minItems",
+                "maxItems",
+                items.len(),
+                "items",
+                path,
+                errors,
+            );
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        Value::String(text) => {
+            let length = text.chars().count();
+            bound(
+                schema,
+                "minLength",
+                "maxLength",
+                length,
+                "characters",
+                path,
+                errors,
+            );
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+                && number < minimum
+            {
+                errors.push(format!(
+                    "{}: {} is below the minimum {}",
+                    path, number, minimum
+                ));
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64)
+                && number > maximum
+            {
+                errors.push(format!(
+                    "{}: {} is above the maximum {}",
+                    path, number, maximum
+                ));
+            }
+        }
+        _ => {}
+    }
+}
+
+// bound:
+// checks a length against the `min` / `max` keywords of the schema
+fn bound(
+    schema: &serde_json::Map<String, Value>,
+    min: &str,
+    max: &str,
+    length: usize,
+    unit: &str,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(min) = schema.get(min).and_then(Value::as_u64)
+        && (length as u64) < min
+    {
+        errors.push(format!(
+            "{}: expected at least {} {}, got {}",
+            path, min, unit, length
+        ));
+    }
+    if let Some(max) = schema.get(max).and_then(Value::as_u64)
+        && (length as u64) > max
+    {
+        errors.push(format!(
+            "{}: expected at most {} {}, got {}",
+            path, max, unit, length
+        ));
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "integer" => value.as_i64().is_some() || value.as_u64().is_some(),
+        "number" => value.is_number(),
+        other => type_name(value) == other,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
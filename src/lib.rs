@@ -1,8 +1,20 @@
 #![allow(non_snake_case)]
+#![allow(clippy::module_inception)]
 
+pub mod agent;
+pub mod cache;
+pub mod embedding;
+#[cfg(feature = "eval")]
+pub mod eval;
+pub mod mcp;
 pub mod model;
 pub mod model_llm;
 pub mod models;
+pub mod prompt;
+pub mod rag;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod traits;
+pub mod usage;
 pub mod utils;
 pub mod websocket;
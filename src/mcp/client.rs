@@ -0,0 +1,204 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tokio::process::Command;
+
+use crate::{
+    agent::tool::ToolSet,
+    mcp::{
+        protocol::{
+            Implementation, McpPrompt, McpResource, McpToolInfo, PROTOCOL_VERSION, PromptMessage,
+            ResourceContents, ServerInfo, ToolOutput,
+        },
+        tool::McpTool,
+        transport::{HttpTransport, McpTransport, StdioTransport},
+    },
+    model::conversation::conversation::{Conversation, Turn},
+};
+
+/// Pages a list call follows before giving up, against servers repeating cursors.
+const MAX_PAGES: usize = 100;
+
+/// A session with an MCP server, over any [`McpTransport`].
+///
+/// The session is initialized on connect; its tools can be handed to a
+/// [`ModelClient`](crate::models::model_client::ModelClient) or an
+/// [`Agent`](crate::agent::agent::Agent) with [`tool_set`](Self::tool_set).
+///
+/// # Example
+/// ```no_run
+/// # use ey_ai::mcp::client::McpClient;
+/// # async fn run() -> anyhow::Result<()> {
+/// let github = McpClient::http("https://mcp.example.com/mcp").await?;
+/// for tool in github.list_tools().await? {
+///     println!("{}: {}", tool.name, tool.description.unwrap_or_default());
+/// }
+/// let output = github.call_tool("search_issues", serde_json::json!({"query": "crash"})).await?;
+/// println!("{}", output.text());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct McpClient {
+    transport: Arc<dyn McpTransport>,
+    server: ServerInfo,
+}
+
+impl McpClient {
+    /// Initializes a session over `transport`.
+    pub async fn connect(transport: impl McpTransport + 'static) -> Result<Self> {
+        let transport: Arc<dyn McpTransport> = Arc::new(transport);
+        let client = Implementation {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        let result = transport
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": client,
+                }),
+            )
+            .await?;
+        let server: ServerInfo = serde_json::from_value(result)
+            .map_err(|e| anyhow!("Invalid MCP initialize result: {}", e))?;
+        transport.set_protocol_version(&server.protocol_version);
+        transport
+            .notify("notifications/initialized", Value::Null)
+            .await?;
+
+        Ok(Self { transport, server })
+    }
+
+    /// Spawns `program` with `args` and talks to it over stdio.
+    pub async fn stdio(program: &str, args: &[&str]) -> Result<Self> {
+        let mut command = Command::new(program);
+        command.args(args);
+        Self::connect(StdioTransport::spawn(command)?).await
+    }
+
+    /// Connects to a streamable HTTP endpoint.
+    pub async fn http(url: &str) -> Result<Self> {
+        Self::connect(HttpTransport::new(url)).await
+    }
+
+    pub fn server_info(&self) -> &ServerInfo {
+        &self.server
+    }
+
+    pub async fn ping(&self) -> Result<()> {
+        self.transport.request("ping", Value::Null).await?;
+        Ok(())
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>> {
+        self.list("tools/list", "tools").await
+    }
+
+    /// Runs tool `name`. A tool that ran and failed is an `Ok` output with
+    /// `is_error` set, see [`ToolOutput::into_result`].
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<ToolOutput> {
+        let result = self
+            .transport
+            .request(
+                "tools/call",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        serde_json::from_value(result).map_err(|e| anyhow!("Invalid MCP tool result: {}", e))
+    }
+
+    pub async fn list_resources(&self) -> Result<Vec<McpResource>> {
+        self.list("resources/list", "resources").await
+    }
+
+    pub async fn read_resource(&self, uri: &str) -> Result<Vec<ResourceContents>> {
+        let result = self
+            .transport
+            .request("resources/read", json!({ "uri": uri }))
+            .await?;
+        serde_json::from_value(result["contents"].clone())
+            .map_err(|e| anyhow!("Invalid MCP resource: {}", e))
+    }
+
+    pub async fn list_prompts(&self) -> Result<Vec<McpPrompt>> {
+        self.list("prompts/list", "prompts").await
+    }
+
+    /// Renders prompt `name` with `arguments` as a conversation; assistant
+    /// messages become model turns and non text content is left out.
+    pub async fn get_prompt(
+        &self,
+        name: &str,
+        arguments: HashMap<String, String>,
+    ) -> Result<Conversation> {
+        let result = self
+            .transport
+            .request(
+                "prompts/get",
+                json!({ "name": name, "arguments": arguments }),
+            )
+            .await?;
+        let messages: Vec<PromptMessage> = serde_json::from_value(result["messages"].clone())
+            .map_err(|e| anyhow!("Invalid MCP prompt: {}", e))?;
+
+        let mut conversation = Conversation::new();
+        for message in messages {
+            let Some(text) = message.content.as_text() else {
+                continue;
+            };
+            conversation.push(match message.role.as_str() {
+                "assistant" => Turn::model(text),
+                _ => Turn::user(text),
+            });
+        }
+        Ok(conversation)
+    }
+
+    /// The server's tools, ready for a `ModelClient` or an `Agent`.
+    pub async fn tool_set(&self) -> Result<ToolSet> {
+        self.tool_set_prefixed("").await
+    }
+
+    /// The server's tools with `prefix` prepended to their names, to tell
+    /// apart the tools of several servers.
+    pub async fn tool_set_prefixed(&self, prefix: &str) -> Result<ToolSet> {
+        let mut tools = ToolSet::new();
+        for info in self.list_tools().await? {
+            tools.add(Arc::new(McpTool::new(self.clone(), info, prefix)));
+        }
+        Ok(tools)
+    }
+
+    /// Ends the session; a stdio server is stopped.
+    pub async fn close(&self) -> Result<()> {
+        self.transport.close().await
+    }
+
+    // list:
+    // every item of a paginated list call, under `field` of each page
+    async fn list<T: DeserializeOwned>(&self, method: &str, field: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        for _ in 0..MAX_PAGES {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let mut page = self.transport.request(method, params).await?;
+            let page_items: Vec<T> = serde_json::from_value(page[field].take())
+                .map_err(|e| anyhow!("Invalid MCP {} result: {}", method, e))?;
+            items.extend(page_items);
+
+            cursor = page["nextCursor"].as_str().map(str::to_string);
+            if cursor.is_none() {
+                return Ok(items);
+            }
+        }
+        Err(anyhow!("MCP {} returned too many pages", method))
+    }
+}
@@ -0,0 +1,5 @@
+pub mod client;
+pub mod protocol;
+pub mod server;
+pub mod tool;
+pub mod transport;
@@ -0,0 +1,256 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// MCP revision spoken by the client and the server.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Earlier revisions the server still accepts from clients.
+pub const SUPPORTED_PROTOCOL_VERSIONS: [&str; 3] = [PROTOCOL_VERSION, "2025-03-26", "2024-11-05"];
+
+// JSON-RPC error codes
+pub(crate) const PARSE_ERROR: i64 = -32700;
+pub(crate) const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+
+/// Name and version of an MCP client or server.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Implementation {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+}
+
+/// What a server answered to `initialize`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerInfo {
+    pub protocol_version: String,
+    #[serde(default)]
+    pub capabilities: Value,
+    #[serde(default)]
+    pub server_info: Implementation,
+    /// Hints on how to use the server, meant for the model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+}
+
+impl ServerInfo {
+    /// Whether the server declared `capability` (`tools`, `resources`, `prompts`...).
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.get(capability).is_some()
+    }
+}
+
+/// A tool offered by an MCP server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON schema of the arguments.
+    #[serde(default = "empty_object_schema")]
+    pub input_schema: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>,
+}
+
+fn empty_object_schema() -> Value {
+    json!({ "type": "object" })
+}
+
+/// A resource offered by an MCP server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+}
+
+/// The contents of a resource, as text or base64 encoded `blob`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceContents {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+}
+
+/// A prompt template offered by an MCP server.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct McpPrompt {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<PromptArgument>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PromptArgument {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// A piece of a tool result or prompt message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Content {
+    Text {
+        text: String,
+    },
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    Audio {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    ResourceLink {
+        uri: String,
+        #[serde(default)]
+        name: String,
+    },
+    Resource {
+        resource: ResourceContents,
+    },
+    /// Content types this client does not know.
+    #[serde(other)]
+    Unknown,
+}
+
+impl Content {
+    pub fn text(text: impl Into<String>) -> Self {
+        Content::Text { text: text.into() }
+    }
+
+    /// The text of text content and of embedded text resources.
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            Content::Text { text } => Some(text),
+            Content::Resource { resource } => resource.text.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Result of `tools/call`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolOutput {
+    #[serde(default)]
+    pub content: Vec<Content>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
+    /// The tool ran and failed; `content` describes the failure.
+    #[serde(default)]
+    pub is_error: bool,
+}
+
+impl ToolOutput {
+    /// Text parts of the output, one per line.
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(Content::as_text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The output as the result of a function call: the structured content
+    /// when there is one, the text otherwise. Tool failures are errors.
+    pub fn into_result(self) -> Result<Value> {
+        if self.is_error {
+            return Err(anyhow!("{}", self.text()));
+        }
+        Ok(match self.structured_content {
+            Some(structured) => structured,
+            None => json!(self.text()),
+        })
+    }
+}
+
+/// A message of a prompt returned by `prompts/get`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PromptMessage {
+    /// `user` or `assistant`.
+    pub role: String,
+    pub content: Content,
+}
+
+// request:
+// a JSON-RPC request, or a notification without `id`
+pub(crate) fn request(id: Option<u64>, method: &str, params: Value) -> Value {
+    let mut message = json!({ "jsonrpc": "2.0", "method": method });
+    if !params.is_null() {
+        message["params"] = params;
+    }
+    if let Some(id) = id {
+        message["id"] = json!(id);
+    }
+    message
+}
+
+// response_result:
+// the `result` of a JSON-RPC response, its `error` as an error
+pub(crate) fn response_result(message: Value) -> Result<Value> {
+    if let Some(error) = message.get("error") {
+        return Err(anyhow!(
+            "MCP error {}: {}",
+            error["code"],
+            error["message"].as_str().unwrap_or("unknown error")
+        ));
+    }
+    message
+        .get("result")
+        .cloned()
+        .ok_or_else(|| anyhow!("Invalid MCP response: {}", message))
+}
+
+// success:
+// a JSON-RPC response carrying `result`
+pub(crate) fn success(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+// failure:
+// a JSON-RPC error response
+pub(crate) fn failure(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message }
+    })
+}
+
+// answer_server_request:
+// reply to a request sent by the server; only pings are served
+pub(crate) fn answer_server_request(message: &Value) -> Value {
+    let id = message["id"].clone();
+    match message["method"].as_str() {
+        Some("ping") => success(id, json!({})),
+        _ => failure(id, METHOD_NOT_FOUND, "Method not found"),
+    }
+}
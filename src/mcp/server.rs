@@ -0,0 +1,618 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+use crate::{
+    mcp::protocol::{
+        Content, INVALID_PARAMS, INVALID_REQUEST, Implementation, METHOD_NOT_FOUND, PARSE_ERROR,
+        PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS, ToolOutput, failure, success,
+    },
+    model::{
+        conversation::conversation::{Conversation, TurnRole},
+        generation::generation::{GenerateRequest, GenerationConfig},
+    },
+    models::model_client::ModelClient,
+    prompt::template::VarType,
+};
+
+/// Serves a [`ModelClient`] to MCP clients, over stdio or streamable HTTP.
+///
+/// Tools:
+/// - `generate`: a reply to `prompt` (with optional `system`, `temperature`
+///   and `max_output_tokens`) from the client's model;
+/// - `generate_template`: a reply to prompt template `id` rendered with
+///   `vars`, when the client has templates;
+/// - `embed`: vectors of `texts`, when an embedding model is set.
+///
+/// The client's prompt templates are also served as MCP prompts. Requests go
+/// through the client, so its key pool, rate limits, budgets, caches and usage
+/// ledger apply; [`tag`](Self::tag) and [`tenant`](Self::tenant) attribute
+/// them.
+///
+/// Over HTTP, requests from browser pages are refused unless their `Origin`
+/// is a loopback address or was allowed with
+/// [`allowed_origin`](Self::allowed_origin), and idle sessions are forgotten
+/// (see [`session_timeout`](Self::session_timeout)).
+///
+/// # Example
+/// ```no_run
+/// # use ey_ai::{mcp::server::McpServer, models::model_client::ModelClient};
+/// # async fn run(client: ModelClient) -> anyhow::Result<()> {
+/// let server = McpServer::new(client).tag("mcp").bearer_token("s3cret");
+/// let listener = tokio::net::TcpListener::bind("0.0.0.0:8931").await?;
+/// server.serve_http(listener).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct McpServer {
+    client: ModelClient,
+    embedding_model: Option<String>,
+    tag: Option<String>,
+    tenant: Option<String>,
+    token: Option<String>,
+    origins: Vec<String>,
+    session_timeout: Duration,
+    // open sessions and when they were last used
+    sessions: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// Time after which an HTTP session nobody used is forgotten by default; its
+/// client gets `404` and opens a new one.
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+impl McpServer {
+    pub fn new(client: ModelClient) -> Self {
+        Self {
+            client,
+            embedding_model: None,
+            tag: None,
+            tenant: None,
+            token: None,
+            origins: Vec::new(),
+            session_timeout: SESSION_IDLE_TIMEOUT,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Serves the `embed` tool with the embedding `model` of the client's
+    /// provider, e.g. `"gemini-embedding-001"`.
+    pub fn embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    /// Usage tag of the generation requests.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// Budget tenant of the generation requests.
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Token HTTP clients must send as `Authorization: Bearer <token>`.
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Time after which an unused HTTP session is forgotten, by default
+    /// [`SESSION_IDLE_TIMEOUT`].
+    pub fn session_timeout(mut self, timeout: Duration) -> Self {
+        self.session_timeout = timeout;
+        self
+    }
+
+    /// Accepts HTTP requests sent from pages of `origin` (e.g.
+    /// `"https://tools.example.com"`), besides loopback origins.
+    pub fn allowed_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins
+            .push(origin.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Answers one JSON-RPC message, `None` for notifications and responses.
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let Some(method) = message["method"].as_str() else {
+            // a response, the server sends no requests
+            return None;
+        };
+        let params = &message["params"];
+        let result = match method {
+            "initialize" => Ok(self.initialize(params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.tools() })),
+            "tools/call" => self.call_tool(params).await,
+            "prompts/list" => Ok(json!({ "prompts": self.prompts() })),
+            "prompts/get" => self.get_prompt(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => success(id, result),
+            Err((code, message)) => failure(id, code, &message),
+        })
+    }
+
+    /// Serves MCP over stdin and stdout until stdin is closed. Requests are
+    /// handled concurrently; nothing else may write to stdout.
+    pub async fn serve_stdio(&self) -> Result<()> {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<Value>();
+        let writer = tokio::spawn(async move {
+            let mut stdout = tokio::io::stdout();
+            while let Some(message) = receiver.recv().await {
+                let line = format!("{}\n", message);
+                if stdout.write_all(line.as_bytes()).await.is_err() || stdout.flush().await.is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    let _ = sender.send(failure(Value::Null, PARSE_ERROR, &e.to_string()));
+                    continue;
+                }
+            };
+            let server = self.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                if let Some(response) = server.handle(message).await {
+                    let _ = sender.send(response);
+                }
+            });
+        }
+
+        drop(sender);
+        let _ = writer.await;
+        Ok(())
+    }
+
+    /// Serves [`router`](Self::router) on `listener`. Without a bearer token
+    /// only loopback addresses may be listened on.
+    pub async fn serve_http(&self, listener: TcpListener) -> Result<()> {
+        let addr = listener.local_addr()?;
+        if self.token.is_none() && !addr.ip().is_loopback() {
+            return Err(anyhow!(
+                "Refusing to serve MCP on {} without a bearer token",
+                addr
+            ));
+        }
+        axum::serve(listener, self.router()).await?;
+        Ok(())
+    }
+
+    /// Streamable HTTP endpoint at `/mcp`. Responses are plain JSON, the
+    /// server opens no event streams.
+    ///
+    /// The router does not know the address it is served on: prefer
+    /// [`serve_http`](Self::serve_http), or set a bearer token before exposing
+    /// it beyond the local machine.
+    pub fn router(&self) -> Router {
+        Router::new()
+            .route(
+                "/mcp",
+                post(post_message)
+                    .get(|| async { StatusCode::METHOD_NOT_ALLOWED })
+                    .delete(end_session),
+            )
+            .with_state(self.clone())
+    }
+
+    // initialize:
+    // answers with the client's protocol version when supported, ours otherwise
+    fn initialize(&self, params: &Value) -> Value {
+        let version = params["protocolVersion"]
+            .as_str()
+            .filter(|v| SUPPORTED_PROTOCOL_VERSIONS.contains(v))
+            .unwrap_or(PROTOCOL_VERSION);
+        let server = Implementation {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        };
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": {}, "prompts": {} },
+            "serverInfo": server,
+            "instructions": format!(
+                "Delegates generation to {}.",
+                self.client.model.lock().unwrap()
+            ),
+        })
+    }
+
+    // authorized:
+    // whether the request carries the bearer token, when one is required
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .is_some_and(|v| constant_time_eq(v.as_bytes(), token.as_bytes()))
+    }
+
+    // origin_allowed:
+    // whether a browser may send the request: no `Origin` (not a browser), a
+    // loopback origin or one allowed explicitly
+    fn origin_allowed(&self, headers: &HeaderMap) -> bool {
+        let Some(origin) = headers.get("origin") else {
+            return true;
+        };
+        let Ok(origin) = origin.to_str() else {
+            return false;
+        };
+        let origin = origin.trim_end_matches('/');
+        if self.origins.iter().any(|allowed| allowed == origin) {
+            return true;
+        }
+        let host = origin
+            .strip_prefix("http://")
+            .or_else(|| origin.strip_prefix("https://"))
+            .unwrap_or_default();
+        let host = match host.strip_prefix('[') {
+            Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+            None => host.split(':').next().unwrap_or_default(),
+        };
+        host == "localhost"
+            || host
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+    }
+
+    // open_session:
+    // registers a new session, forgetting the ones idle for too long
+    fn open_session(&self) -> String {
+        let session = uuid::Uuid::new_v4().to_string();
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, used| now.duration_since(*used) < self.session_timeout);
+        sessions.insert(session.clone(), now);
+        session
+    }
+
+    // touch_session:
+    // whether `session` is open, marking it as used
+    fn touch_session(&self, session: &str) -> bool {
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(session) {
+            Some(used) if now.duration_since(*used) < self.session_timeout => {
+                *used = now;
+                true
+            }
+            Some(_) => {
+                sessions.remove(session);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn tools(&self) -> Vec<Value> {
+        let mut tools = vec![json!({
+            "name": "generate",
+            "description": "Generates a reply to a prompt",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "prompt": { "type": "string" },
+                    "system": { "type": "string", "description": "System instruction" },
+                    "temperature": { "type": "number" },
+                    "max_output_tokens": { "type": "integer" }
+                },
+                "required": ["prompt"]
+            },
+            "outputSchema": generation_schema()
+        })];
+        if self.client.templates.lock().unwrap().is_some() {
+            tools.push(json!({
+                "name": "generate_template",
+                "description": "Generates a reply to a prompt template, see prompts/list",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string", "description": "name or name@version" },
+                        "vars": { "type": "object" }
+                    },
+                    "required": ["id"]
+                },
+                "outputSchema": generation_schema()
+            }));
+        }
+        if self.embedding_model.is_some() {
+            tools.push(json!({
+                "name": "embed",
+                "description": "Embeds texts into vectors",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "texts": { "type": "array", "items": { "type": "string" } }
+                    },
+                    "required": ["texts"]
+                },
+                "outputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "embeddings": {
+                            "type": "array",
+                            "items": { "type": "array", "items": { "type": "number" } }
+                        }
+                    },
+                    "required": ["embeddings"]
+                }
+            }));
+        }
+        tools
+    }
+
+    // call_tool:
+    // runs a tool; failures of the model or of the arguments are tool errors
+    // the caller's model can see, unknown tools are protocol errors
+    async fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params["name"].as_str().unwrap_or_default();
+        if !self.tools().iter().any(|t| t["name"] == name) {
+            return Err((INVALID_PARAMS, format!("Unknown tool: {}", name)));
+        }
+        let args = &params["arguments"];
+        let output = match name {
+            "generate" => self.generate(args).await,
+            "generate_template" => self.generate_template(args).await,
+            _ => self.embed(args).await,
+        };
+
+        let output = output.unwrap_or_else(|e| ToolOutput {
+            content: vec![Content::text(e.to_string())],
+            structured_content: None,
+            is_error: true,
+        });
+        Ok(serde_json::to_value(output).unwrap_or_default())
+    }
+
+    async fn generate(&self, args: &Value) -> Result<ToolOutput> {
+        let prompt = args["prompt"]
+            .as_str()
+            .ok_or_else(|| anyhow!("prompt is required"))?;
+        let mut conversation = Conversation::from_prompt(prompt);
+        if let Some(system) = args["system"].as_str() {
+            conversation = conversation.with_system(system);
+        }
+        let config = GenerationConfig {
+            temperature: args["temperature"].as_f64().map(|t| t as f32),
+            max_output_tokens: args["max_output_tokens"].as_u64().map(|n| n as u32),
+            ..Default::default()
+        };
+        self.reply(GenerateRequest::new(conversation).config(config))
+            .await
+    }
+
+    async fn generate_template(&self, args: &Value) -> Result<ToolOutput> {
+        let id = args["id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("id is required"))?;
+        let templates = self
+            .client
+            .templates
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("No prompt templates configured"))?;
+        self.reply(templates.request(id, &args["vars"])?).await
+    }
+
+    // reply:
+    // generates with the server's tag and tenant, the text with its usage
+    async fn reply(&self, mut request: GenerateRequest) -> Result<ToolOutput> {
+        if let Some(tag) = &self.tag {
+            request = request.tag(tag.clone());
+        }
+        if let Some(tenant) = &self.tenant {
+            request = request.tenant(tenant.clone());
+        }
+        let reply = self.client.Generate(request).await?;
+        Ok(ToolOutput {
+            structured_content: Some(json!({
+                "text": reply.text,
+                "usage": reply.usage,
+                "model_version": reply.model_version,
+            })),
+            content: vec![Content::text(reply.text)],
+            is_error: false,
+        })
+    }
+
+    async fn embed(&self, args: &Value) -> Result<ToolOutput> {
+        let model = self
+            .embedding_model
+            .as_deref()
+            .ok_or_else(|| anyhow!("No embedding model configured"))?;
+        let texts: Vec<String> = serde_json::from_value(args["texts"].clone())
+            .map_err(|_| anyhow!("texts must be an array of strings"))?;
+        let embeddings = self
+            .client
+            .Embed(model, &texts, self.tag.as_deref(), self.tenant.as_deref())
+            .await?;
+        let structured = json!({ "embeddings": embeddings });
+        Ok(ToolOutput {
+            content: vec![Content::text(structured.to_string())],
+            structured_content: Some(structured),
+            is_error: false,
+        })
+    }
+
+    // prompts:
+    // the latest version of every template, with its variables as arguments
+    fn prompts(&self) -> Vec<Value> {
+        let Some(templates) = self.client.templates.lock().unwrap().clone() else {
+            return Vec::new();
+        };
+        templates
+            .names()
+            .into_iter()
+            .filter_map(|name| {
+                let template = templates.get(&name).ok()?;
+                let arguments: Vec<Value> = template
+                    .variables
+                    .iter()
+                    .map(|v| {
+                        json!({
+                            "name": v.name,
+                            "description": serde_json::to_value(v.var_type).unwrap_or_default(),
+                            "required": v.required && v.default.is_none(),
+                        })
+                    })
+                    .collect();
+                let versions = templates.versions(&name);
+                let mut prompt = json!({ "name": name, "arguments": arguments });
+                if !versions.is_empty() {
+                    prompt["description"] = json!(format!("Versions: {}", versions.join(", ")));
+                }
+                Some(prompt)
+            })
+            .collect()
+    }
+
+    // get_prompt:
+    // renders a template; the system instruction leads as a user message
+    fn get_prompt(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let invalid = |e: anyhow::Error| (INVALID_PARAMS, e.to_string());
+        let templates = self
+            .client
+            .templates
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| invalid(anyhow!("No prompt templates configured")))?;
+        let name = params["name"].as_str().unwrap_or_default();
+        let template = templates.get(name).map_err(invalid)?;
+        let arguments: HashMap<String, String> =
+            serde_json::from_value(params["arguments"].clone()).unwrap_or_default();
+
+        // MCP arguments are strings, typed variables are read as JSON
+        let mut vars = serde_json::Map::new();
+        for (key, value) in arguments {
+            let declared = template.variables.iter().find(|v| v.name == key);
+            let typed = match declared.map(|v| v.var_type) {
+                Some(VarType::String | VarType::Any) | None => None,
+                Some(_) => serde_json::from_str(&value).ok(),
+            };
+            vars.insert(key, typed.unwrap_or(Value::String(value)));
+        }
+        let conversation = templates
+            .render(name, &Value::Object(vars))
+            .map_err(invalid)?;
+
+        let mut messages = Vec::new();
+        if let Some(system) = conversation.system {
+            messages.push(json!({ "role": "user", "content": Content::text(system) }));
+        }
+        for turn in conversation.turns {
+            let role = match turn.role {
+                TurnRole::User => "user",
+                TurnRole::Model => "assistant",
+            };
+            messages.push(json!({ "role": role, "content": Content::text(turn.text) }));
+        }
+        Ok(json!({ "description": template.id(), "messages": messages }))
+    }
+}
+
+// constant_time_eq:
+// compares secrets without stopping at the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn generation_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "text": { "type": "string" },
+            "usage": { "type": ["object", "null"] },
+            "model_version": { "type": ["string", "null"] }
+        },
+        "required": ["text"]
+    })
+}
+
+async fn post_message(
+    State(server): State<McpServer>,
+    headers: HeaderMap,
+    Json(message): Json<Value>,
+) -> Response {
+    if !server.origin_allowed(&headers) {
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    if !server.authorized(&headers) {
+        return (StatusCode::UNAUTHORIZED, "Invalid or missing bearer token").into_response();
+    }
+    if !message.is_object() {
+        let error = failure(Value::Null, INVALID_REQUEST, "Batches are not supported");
+        return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+    }
+
+    let initialize = message["method"] == "initialize";
+    if !initialize {
+        let session = headers.get("mcp-session-id").and_then(|v| v.to_str().ok());
+        match session {
+            None => return (StatusCode::BAD_REQUEST, "Missing Mcp-Session-Id").into_response(),
+            Some(session) if !server.touch_session(session) => {
+                return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+            }
+            Some(_) => {}
+        }
+    }
+
+    let Some(response) = server.handle(message).await else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    if initialize && response.get("result").is_some() {
+        let session = server.open_session();
+        return ([("mcp-session-id", session)], Json(response)).into_response();
+    }
+    Json(response).into_response()
+}
+
+async fn end_session(State(server): State<McpServer>, headers: HeaderMap) -> StatusCode {
+    if !server.origin_allowed(&headers) {
+        return StatusCode::FORBIDDEN;
+    }
+    if !server.authorized(&headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    let session = headers.get("mcp-session-id").and_then(|v| v.to_str().ok());
+    match session.map(|s| server.sessions.lock().unwrap().remove(s)) {
+        Some(Some(_)) => StatusCode::OK,
+        _ => StatusCode::NOT_FOUND,
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::{
+    agent::tool::Tool,
+    mcp::{client::McpClient, protocol::McpToolInfo},
+    model::function::function::FunctionDeclaration,
+};
+
+/// Longest function name Gemini accepts.
+const MAX_NAME_LEN: usize = 64;
+
+/// A tool of an MCP server, callable by the model as a function.
+///
+/// The declared name is the tool name (with its prefix) made valid for
+/// function names; calls go to the server under the original name.
+pub struct McpTool {
+    client: McpClient,
+    info: McpToolInfo,
+    name: String,
+}
+
+impl McpTool {
+    pub fn new(client: McpClient, info: McpToolInfo, prefix: &str) -> Self {
+        let name = function_name(&format!("{}{}", prefix, info.name));
+        Self { client, info, name }
+    }
+
+    pub fn info(&self) -> &McpToolInfo {
+        &self.info
+    }
+}
+
+// function_name:
+// replaces characters not allowed in function names and cuts to the maximum length
+fn function_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LEN)
+        .collect()
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn declaration(&self) -> FunctionDeclaration {
+        let description = self
+            .info
+            .description
+            .clone()
+            .or_else(|| self.info.title.clone())
+            .unwrap_or_default();
+        FunctionDeclaration::new(&self.name, description)
+            .json_schema(self.info.input_schema.clone())
+    }
+
+    async fn call(&self, args: Value) -> Result<Value> {
+        let args = if args.is_null() {
+            Value::Object(Default::default())
+        } else {
+            args
+        };
+        self.client
+            .call_tool(&self.info.name, args)
+            .await?
+            .into_result()
+    }
+}
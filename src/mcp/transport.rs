@@ -0,0 +1,397 @@
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, Command},
+    sync::oneshot,
+};
+
+use crate::mcp::protocol::{answer_server_request, request, response_result};
+
+/// Time a request waits for its response by default.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A connection to an MCP server carrying JSON-RPC messages.
+#[async_trait]
+pub trait McpTransport: Send + Sync {
+    /// Sends a request and returns the `result` of its response.
+    async fn request(&self, method: &str, params: Value) -> Result<Value>;
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()>;
+
+    /// Called once the protocol version is negotiated.
+    fn set_protocol_version(&self, _version: &str) {}
+
+    async fn close(&self) -> Result<()>;
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value>>>>>;
+
+/// MCP over the standard input and output of a child process, one JSON
+/// message per line. The server's stderr is passed through.
+pub struct StdioTransport {
+    stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+    child: tokio::sync::Mutex<Child>,
+    pending: Pending,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl StdioTransport {
+    /// Spawns `command` and starts reading its messages.
+    pub fn spawn(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start MCP server: {}", e))?;
+        let stdin = child.stdin.take().ok_or_else(|| anyhow!("No stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("No stdout"))?;
+
+        let stdin = Arc::new(tokio::sync::Mutex::new(Some(stdin)));
+        let pending: Pending = Arc::default();
+        tokio::spawn(read_messages(stdout, stdin.clone(), pending.clone()));
+
+        Ok(Self {
+            stdin,
+            child: tokio::sync::Mutex::new(child),
+            pending,
+            next_id: AtomicU64::new(1),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        })
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn send(&self, message: &Value) -> Result<()> {
+        write_line(&self.stdin, message).await
+    }
+}
+
+// write_line:
+// writes one message followed by a newline
+async fn write_line(stdin: &tokio::sync::Mutex<Option<ChildStdin>>, message: &Value) -> Result<()> {
+    let mut stdin = stdin.lock().await;
+    let stdin = stdin
+        .as_mut()
+        .ok_or_else(|| anyhow!("MCP connection is closed"))?;
+    let mut line = message.to_string();
+    line.push('\n');
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| anyhow!("Failed to write to MCP server: {}", e))?;
+    stdin
+        .flush()
+        .await
+        .map_err(|e| anyhow!("Failed to write to MCP server: {}", e))
+}
+
+// read_messages:
+// routes responses to their waiting requests and answers server requests,
+// until the server closes its output
+async fn read_messages(
+    stdout: tokio::process::ChildStdout,
+    stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+    pending: Pending,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Invalid message from MCP server: {}", e);
+                continue;
+            }
+        };
+        if message.get("method").is_some() {
+            // server request; notifications need no answer
+            if message.get("id").is_some() {
+                let _ = write_line(&stdin, &answer_server_request(&message)).await;
+            }
+            continue;
+        }
+        let waiter = message["id"]
+            .as_u64()
+            .and_then(|id| pending.lock().unwrap().remove(&id));
+        if let Some(waiter) = waiter {
+            let _ = waiter.send(response_result(message));
+        }
+    }
+    for (_, waiter) in pending.lock().unwrap().drain() {
+        let _ = waiter.send(Err(anyhow!("MCP server closed the connection")));
+    }
+}
+
+#[async_trait]
+impl McpTransport for StdioTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+        if let Err(e) = self.send(&request(Some(id), method, params)).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(anyhow!("MCP server closed the connection")),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(anyhow!("MCP request {} timed out", method))
+            }
+        }
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        self.send(&request(None, method, params)).await
+    }
+
+    /// Closes the server's input and waits briefly for it to exit before
+    /// killing it.
+    async fn close(&self) -> Result<()> {
+        self.stdin.lock().await.take();
+        let mut child = self.child.lock().await;
+        if tokio::time::timeout(Duration::from_secs(2), child.wait())
+            .await
+            .is_err()
+        {
+            child.kill().await?;
+        }
+        Ok(())
+    }
+}
+
+/// MCP over streamable HTTP: every message is POSTed to one endpoint, which
+/// answers with JSON or with an event stream carrying the response. Requests
+/// the server sends on that stream are answered like over stdio.
+pub struct HttpTransport {
+    http: reqwest::Client,
+    url: String,
+    headers: Vec<(String, String)>,
+    session: Mutex<Option<String>>,
+    protocol_version: Mutex<Option<String>>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl HttpTransport {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url: url.into(),
+            headers: Vec::new(),
+            session: Mutex::new(None),
+            protocol_version: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Adds a header to every request, e.g. `Authorization`.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // post:
+    // sends one message with the session and protocol headers
+    async fn post(&self, message: &Value) -> Result<reqwest::Response> {
+        let mut builder = self
+            .http
+            .post(&self.url)
+            .timeout(self.timeout)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(session) = self.session.lock().unwrap().clone() {
+            builder = builder.header("Mcp-Session-Id", session);
+        }
+        if let Some(version) = self.protocol_version.lock().unwrap().clone() {
+            builder = builder.header("MCP-Protocol-Version", version);
+        }
+
+        let res = builder
+            .send()
+            .await
+            .map_err(|e| anyhow!("Failed to reach MCP server: {}", e))?;
+        if let Some(session) = res
+            .headers()
+            .get("mcp-session-id")
+            .and_then(|v| v.to_str().ok())
+        {
+            *self.session.lock().unwrap() = Some(session.to_string());
+        }
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(anyhow!("MCP server returned {}: {}", status, body));
+        }
+        Ok(res)
+    }
+}
+
+impl HttpTransport {
+    // on_event:
+    // the result of request `id` when the event answers it; requests of the
+    // server on the stream are answered with a POST of their own
+    async fn on_event(&self, data: &str, id: u64) -> Option<Result<Value>> {
+        let message: Value = serde_json::from_str(data).ok()?;
+        if message.get("method").is_some() {
+            // notifications need no answer
+            if message.get("id").is_some() {
+                let _ = self.post(&answer_server_request(&message)).await;
+            }
+            return None;
+        }
+        find_response(message, id).map(response_result)
+    }
+}
+
+// data of the server-sent events of a response, read chunk by chunk
+#[derive(Default)]
+struct SseEvents {
+    // bytes of the line being received
+    line: Vec<u8>,
+    // `data` lines of the event being received
+    data: Vec<String>,
+}
+
+impl SseEvents {
+    // push:
+    // data of the events a chunk completes
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for byte in chunk {
+            if *byte != b'\n' {
+                self.line.push(*byte);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&self.line).into_owned();
+            self.line.clear();
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(self.data.join("\n"));
+                    self.data.clear();
+                }
+            } else if let Some(data) = line.strip_prefix("data:") {
+                self.data
+                    .push(data.strip_prefix(' ').unwrap_or(data).to_string());
+            }
+        }
+        events
+    }
+
+    // finish:
+    // data of an event the stream ended without closing
+    fn finish(mut self) -> Option<String> {
+        let mut events = self.push(b"\n\n");
+        events.pop()
+    }
+}
+
+// find_response:
+// the response to request `id` in a JSON body (a message or a batch)
+fn find_response(body: Value, id: u64) -> Option<Value> {
+    match body {
+        Value::Array(messages) => messages.into_iter().find(|m| m["id"] == id),
+        message if message["id"] == id => Some(message),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl McpTransport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let res = self.post(&request(Some(id), method, params)).await?;
+        let is_stream = res
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/event-stream"));
+
+        if !is_stream {
+            let body: Value = res
+                .json()
+                .await
+                .map_err(|e| anyhow!("Invalid MCP response: {}", e))?;
+            let response =
+                find_response(body, id).ok_or_else(|| anyhow!("No response to {}", method))?;
+            return response_result(response);
+        }
+
+        // read events until the one answering this request
+        let mut body = res.bytes_stream();
+        let mut events = SseEvents::default();
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk.map_err(|e| anyhow!("Failed to read MCP response: {}", e))?;
+            for data in events.push(&chunk) {
+                if let Some(result) = self.on_event(&data, id).await {
+                    return result;
+                }
+            }
+        }
+        if let Some(data) = events.finish()
+            && let Some(result) = self.on_event(&data, id).await
+        {
+            return result;
+        }
+        Err(anyhow!("MCP stream ended without a response to {}", method))
+    }
+
+    async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        self.post(&request(None, method, params)).await?;
+        Ok(())
+    }
+
+    fn set_protocol_version(&self, version: &str) {
+        *self.protocol_version.lock().unwrap() = Some(version.to_string());
+    }
+
+    /// Ends the session on the server, when it opened one.
+    async fn close(&self) -> Result<()> {
+        let Some(session) = self.session.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let mut builder = self
+            .http
+            .delete(&self.url)
+            .header("Mcp-Session-Id", session);
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        // servers may not allow clients to end sessions
+        let _ = builder.send().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{
+    model::generation::generation::{GenerateRequest, Generation},
+    usage::budget::Reservation,
+};
+
+/// One prompt of a batch job, identified by a caller chosen id the result is
+/// reported under.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchRequest {
+    pub id: String,
+    pub request: GenerateRequest,
+}
+
+impl BatchRequest {
+    pub fn new(id: impl Into<String>, request: GenerateRequest) -> Self {
+        Self {
+            id: id.into(),
+            request,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BatchState {
+    #[serde(rename = "BATCH_STATE_PENDING")]
+    Pending,
+    #[serde(rename = "BATCH_STATE_RUNNING")]
+    Running,
+    #[serde(rename = "BATCH_STATE_SUCCEEDED")]
+    Succeeded,
+    #[serde(rename = "BATCH_STATE_FAILED")]
+    Failed,
+    #[serde(rename = "BATCH_STATE_CANCELLED")]
+    Cancelled,
+    #[serde(rename = "BATCH_STATE_EXPIRED")]
+    Expired,
+    #[default]
+    #[serde(rename = "BATCH_STATE_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+impl BatchState {
+    /// Whether the job is over and won't change state anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            BatchState::Succeeded
+                | BatchState::Failed
+                | BatchState::Cancelled
+                | BatchState::Expired
+        )
+    }
+}
+
+/// Request counts of a batch job.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchStats {
+    #[serde(default, deserialize_with = "count")]
+    pub request_count: u64,
+    #[serde(default, deserialize_with = "count")]
+    pub successful_request_count: u64,
+    #[serde(default, deserialize_with = "count")]
+    pub failed_request_count: u64,
+    #[serde(default, deserialize_with = "count")]
+    pub pending_request_count: u64,
+}
+
+// count:
+// int64 counters are sent as JSON strings by the Gemini API
+fn count<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = Value::deserialize(deserializer)?;
+    Ok(match value {
+        Value::String(s) => s.parse().unwrap_or(0),
+        value => value.as_u64().unwrap_or(0),
+    })
+}
+
+/// A batch job (Gemini `batches`), run asynchronously at a discounted price.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchJob {
+    /// Resource name, e.g. `"batches/abc123"`.
+    pub name: String,
+    #[serde(default)]
+    pub display_name: String,
+    /// Model the job runs on, e.g. `"models/gemini-2.5-flash"`.
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub state: BatchState,
+    #[serde(default, rename = "batchStats")]
+    pub stats: BatchStats,
+    /// RFC 3339 timestamps as returned by the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
+    /// File holding the results of a job submitted as a file, once succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub responses_file: Option<String>,
+    /// Why the job failed, when it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Outcome of one [`BatchRequest`], under its id.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BatchResult {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<Generation>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    pub fn is_ok(&self) -> bool {
+        self.generation.is_some()
+    }
+}
+
+/// What a `ModelClient` keeps of a batch it submitted, to record the usage of
+/// its results once.
+#[derive(Clone, Debug, Default)]
+pub struct SubmittedBatch {
+    pub model: String,
+    pub api_key: String,
+    /// Tag and tenant of every request, by id.
+    pub origins: HashMap<String, (Option<String>, String)>,
+    /// Budget estimates held until the results are read or the job ends
+    /// without them.
+    pub reservations: Vec<Reservation>,
+}
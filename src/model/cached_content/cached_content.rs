@@ -0,0 +1,57 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A prefix stored by the provider (Gemini `cachedContents`) and referenced
+/// by name from generation requests, so it is billed at the cached token rate.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContent {
+    /// Resource name, e.g. `"cachedContents/abc123"`.
+    pub name: String,
+    /// Model the content was cached for, e.g. `"models/gemini-2.5-flash"`.
+    #[serde(default)]
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// RFC 3339 timestamps as returned by the provider.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expire_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_metadata: Option<CachedContentUsage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedContentUsage {
+    /// Tokens held by the cached content.
+    #[serde(default)]
+    pub total_token_count: u64,
+}
+
+impl CachedContent {
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        let expire_time = self.expire_time.as_deref()?;
+        DateTime::parse_from_rfc3339(expire_time)
+            .ok()
+            .map(|at| at.with_timezone(&Utc))
+    }
+
+    /// Whether the content is still usable for at least `margin`; unknown
+    /// expiry counts as valid.
+    pub fn is_valid_for(&self, margin: Duration) -> bool {
+        let margin = chrono::Duration::from_std(margin).unwrap_or_default();
+        self.expires_at().is_none_or(|at| at > Utc::now() + margin)
+    }
+}
+
+// ttl_string:
+// duration in the `"<seconds>s"` format of the Gemini API
+pub fn ttl_string(ttl: Duration) -> String {
+    format!("{}s", ttl.as_secs().max(1))
+}
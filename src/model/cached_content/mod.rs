@@ -0,0 +1 @@
+pub mod cached_content;
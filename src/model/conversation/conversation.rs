@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::model::function::function::{FunctionCall, FunctionResponse};
+
+/*
+Conversation:
+ example:
+    {
+    "system":"You are a helpful assistant",
+    "turns":[
+        { "role":"user", "text":"Hi!" },
+        { "role":"model", "text":"Hello, how can I help?" },
+        { "role":"user", "text":"Explain Rust ownership" }
+        ]
+    }
+*/
+
+/// Author of a single conversation turn.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TurnRole {
+    User,
+    Model,
+}
+
+/// A single message exchanged between the user and the model.
+///
+/// Besides text, a model turn may request function calls and the user turn
+/// after it carries their results.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Turn {
+    pub role: TurnRole,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub function_calls: Vec<FunctionCall>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub function_responses: Vec<FunctionResponse>,
+}
+
+impl Turn {
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: TurnRole::User,
+            text: text.into(),
+            function_calls: Vec::new(),
+            function_responses: Vec::new(),
+        }
+    }
+
+    pub fn model(text: impl Into<String>) -> Self {
+        Self {
+            role: TurnRole::Model,
+            text: text.into(),
+            function_calls: Vec::new(),
+            function_responses: Vec::new(),
+        }
+    }
+
+    /// A model turn asking for `calls`, with the text that came along.
+    pub fn function_calls(text: impl Into<String>, calls: Vec<FunctionCall>) -> Self {
+        Self {
+            function_calls: calls,
+            ..Self::model(text)
+        }
+    }
+
+    /// A user turn answering the calls of the previous model turn.
+    pub fn function_responses(responses: Vec<FunctionResponse>) -> Self {
+        Self {
+            function_responses: responses,
+            ..Self::user("")
+        }
+    }
+
+    // estimate_tokens:
+    // text plus the JSON of function calls and responses
+    fn estimate_tokens(&self) -> u64 {
+        let calls = self
+            .function_calls
+            .iter()
+            .map(|c| estimate_text_tokens(&c.name) + estimate_text_tokens(&c.args.to_string()));
+        let responses = self
+            .function_responses
+            .iter()
+            .map(|r| estimate_text_tokens(&r.name) + estimate_text_tokens(&r.response.to_string()));
+        estimate_text_tokens(&self.text) + calls.chain(responses).sum::<u64>()
+    }
+}
+
+/// A multi-turn conversation with an optional system prompt.
+///
+/// This is the unit sent to a [`ModelProvider`](crate::traits::ModelProvider)
+/// when the history of a chat session matters, as opposed to the single
+/// `prompt: String` accepted by `generate_text`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Conversation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    #[serde(default)]
+    pub turns: Vec<Turn>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a conversation holding a single user turn.
+    pub fn from_prompt(prompt: impl Into<String>) -> Self {
+        Self {
+            system: None,
+            turns: vec![Turn::user(prompt)],
+        }
+    }
+
+    pub fn with_system(mut self, system: impl Into<String>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    pub fn push(&mut self, turn: Turn) {
+        self.turns.push(turn);
+    }
+
+    /// Flattens the conversation into a plain prompt for providers that only
+    /// understand single-turn text input.
+    pub fn to_prompt(&self) -> String {
+        let mut out = String::new();
+        if let Some(system) = &self.system {
+            out.push_str(&format!("System: {}\n\n", system));
+        }
+        for turn in &self.turns {
+            let who = match turn.role {
+                TurnRole::User => "User",
+                TurnRole::Model => "Model",
+            };
+            let function_turn =
+                !turn.function_calls.is_empty() || !turn.function_responses.is_empty();
+            if !turn.text.is_empty() || !function_turn {
+                out.push_str(&format!("{}: {}\n", who, turn.text));
+            }
+            for call in &turn.function_calls {
+                out.push_str(&format!("{} calls {}({})\n", who, call.name, call.args));
+            }
+            for response in &turn.function_responses {
+                out.push_str(&format!(
+                    "{} result: {}\n",
+                    response.name, response.response
+                ));
+            }
+        }
+        out
+    }
+
+    /// Rough token estimate (about four characters per token).
+    ///
+    /// Used when a provider has no token counting endpoint, and to pick a
+    /// truncation point before confirming with the provider.
+    pub fn estimate_tokens(&self) -> u64 {
+        let system = self
+            .system
+            .as_deref()
+            .map(estimate_text_tokens)
+            .unwrap_or(0);
+        system + self.turns.iter().map(Turn::estimate_tokens).sum::<u64>()
+    }
+}
+
+/// Rough token estimate for a piece of text (about four characters per token).
+pub fn estimate_text_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
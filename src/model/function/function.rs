@@ -0,0 +1,178 @@
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// A function the model may ask to call, declared on a request.
+///
+/// `parameters` is the JSON schema (OpenAPI subset) of the arguments;
+/// schemas using the rest of JSON Schema go in `parameters_json_schema`
+/// instead.
+///
+/// ```json
+/// {
+///   "name": "get_weather",
+///   "description": "Current weather of a city",
+///   "parameters": {
+///     "type": "object",
+///     "properties": { "city": { "type": "string" } },
+///     "required": ["city"]
+///   }
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FunctionDeclaration {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<Value>,
+    #[serde(
+        default,
+        rename = "parametersJsonSchema",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub parameters_json_schema: Option<Value>,
+}
+
+impl FunctionDeclaration {
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            parameters: None,
+            parameters_json_schema: None,
+        }
+    }
+
+    pub fn parameters(mut self, schema: Value) -> Self {
+        self.parameters = Some(schema);
+        self
+    }
+
+    /// Declares the arguments with a full JSON schema, as MCP tools do.
+    pub fn json_schema(mut self, schema: Value) -> Self {
+        self.parameters_json_schema = Some(schema);
+        self
+    }
+}
+
+/// Whether the model may, must or must not call the declared functions.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FunctionCallingMode {
+    /// The model decides between answering and calling functions.
+    #[default]
+    Auto,
+    /// The model must call at least one function.
+    Any,
+    /// The model must answer with text.
+    None,
+}
+
+/// A call of a declared function, requested by the model.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FunctionCall {
+    /// Identifier to answer with, when the provider assigns one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    #[serde(default)]
+    pub args: Value,
+    /// Opaque signature of the model's reasoning, sent back with the call in
+    /// the next turn so thinking models keep their context.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thought_signature: Option<String>,
+}
+
+impl Hash for FunctionCall {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.name.hash(state);
+        self.args.to_string().hash(state);
+        self.thought_signature.hash(state);
+    }
+}
+
+impl FunctionCall {
+    pub fn new(name: impl Into<String>, args: Value) -> Self {
+        Self {
+            id: None,
+            name: name.into(),
+            args,
+            thought_signature: None,
+        }
+    }
+
+    /// Reads a Gemini `functionCall` part.
+    pub fn from_part(part: &Value) -> Option<Self> {
+        let call = part.get("functionCall")?;
+        Some(Self {
+            id: call["id"].as_str().map(str::to_string),
+            name: call["name"].as_str()?.to_string(),
+            args: call.get("args").cloned().unwrap_or_else(|| json!({})),
+            thought_signature: part["thoughtSignature"].as_str().map(str::to_string),
+        })
+    }
+
+    /// The call as a Gemini content part.
+    pub fn to_part(&self) -> Value {
+        let mut call = json!({ "name": self.name, "args": self.args });
+        if let Some(id) = &self.id {
+            call["id"] = json!(id);
+        }
+        let mut part = json!({ "functionCall": call });
+        if let Some(signature) = &self.thought_signature {
+            part["thoughtSignature"] = json!(signature);
+        }
+        part
+    }
+}
+
+/// The result of a [`FunctionCall`], sent back to the model.
+///
+/// `response` is always a JSON object: other results are wrapped as
+/// `{"result": ...}` and failures reported as `{"error": "..."}`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FunctionResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    pub response: Value,
+}
+
+impl Hash for FunctionResponse {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.name.hash(state);
+        self.response.to_string().hash(state);
+    }
+}
+
+impl FunctionResponse {
+    /// Answers `call` with `result`.
+    pub fn new(call: &FunctionCall, result: Value) -> Self {
+        let response = match result {
+            Value::Object(_) => result,
+            other => json!({ "result": other }),
+        };
+        Self {
+            id: call.id.clone(),
+            name: call.name.clone(),
+            response,
+        }
+    }
+
+    /// Reports to the model that `call` failed.
+    pub fn error(call: &FunctionCall, message: impl Into<String>) -> Self {
+        Self::new(call, json!({ "error": message.into() }))
+    }
+
+    /// The response as a Gemini content part.
+    pub fn to_part(&self) -> Value {
+        let mut response = json!({ "name": self.name, "response": self.response });
+        if let Some(id) = &self.id {
+            response["id"] = json!(id);
+        }
+        json!({ "functionResponse": response })
+    }
+}
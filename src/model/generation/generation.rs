@@ -0,0 +1,339 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cache::response::CacheControl,
+    model::{
+        conversation::conversation::Conversation,
+        function::function::{FunctionCall, FunctionCallingMode, FunctionDeclaration},
+        grounding::grounding::{BuiltinTool, CodePart, GroundingMetadata},
+        safety::safety::{HarmBlockThreshold, HarmCategory, SafetyRating, SafetySetting},
+    },
+    utils::rate_limit::Priority,
+};
+
+/// Token usage reported by the provider for a single request.
+///
+/// Mirrors Gemini's `usageMetadata`; providers that don't report a field
+/// leave it at zero.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub candidates_tokens: u64,
+    pub cached_tokens: u64,
+    pub thoughts_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.candidates_tokens += other.candidates_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.thoughts_tokens += other.thoughts_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
+/// Sampling parameters sent along with a request.
+///
+/// Unset fields are left to the provider defaults. Serialized in Gemini's
+/// `generationConfig` layout.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Reasoning settings of thinking models (Gemini 2.5 Pro / Flash, Gemini 3).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_config: Option<ThinkingConfig>,
+}
+
+/// How much a thinking model reasons before answering.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ThinkingConfig {
+    /// Tokens the model may spend thinking: `0` turns thinking off where the
+    /// model allows it, `-1` lets the model decide (Gemini 2.5).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_budget: Option<i32>,
+    /// Return summaries of the thoughts, as [`Generation::thoughts`] and
+    /// [`StreamEvent::Thought`].
+    #[serde(default)]
+    pub include_thoughts: bool,
+    /// Reasoning depth of Gemini 3 models, used instead of a budget.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_level: Option<ThinkingLevel>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThinkingLevel {
+    Low,
+    High,
+}
+
+impl ThinkingConfig {
+    pub fn budget(budget: i32) -> Self {
+        Self {
+            thinking_budget: Some(budget),
+            ..Default::default()
+        }
+    }
+
+    pub fn level(level: ThinkingLevel) -> Self {
+        Self {
+            thinking_level: Some(level),
+            ..Default::default()
+        }
+    }
+
+    pub fn include_thoughts(mut self) -> Self {
+        self.include_thoughts = true;
+        self
+    }
+}
+
+impl GenerationConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether the same request always yields the same reply (temperature 0).
+    pub fn is_deterministic(&self) -> bool {
+        self.temperature == Some(0.0)
+    }
+}
+
+/// A generation request handled by `ModelClient`.
+///
+/// `conversation`, `config`, `cached_content`, `safety_settings`, `tools`,
+/// `functions` and `function_calling` are what gets sent to the provider, the
+/// remaining fields only steer how the client processes the request.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct GenerateRequest {
+    pub conversation: Conversation,
+    #[serde(default, skip_serializing_if = "GenerationConfig::is_empty")]
+    pub config: GenerationConfig,
+    /// Name of a provider side cached prefix (e.g. `"cachedContents/abc123"`)
+    /// the conversation continues.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached_content: Option<String>,
+    /// Safety thresholds of this request, overriding the client's per category.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_settings: Vec<SafetySetting>,
+    /// Provider side tools (search, url context, code execution) the model may use.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<BuiltinTool>,
+    /// Functions the model may ask the caller to run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub functions: Vec<FunctionDeclaration>,
+    /// Constrains the use of `functions`, the provider default when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub function_calling: Option<FunctionCallingMode>,
+    /// Caller supplied label used to group usage (e.g. a team or a feature).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Tenant whose budget the request is charged to, `"default"` when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// Queue priority when the client is rate limited.
+    #[serde(default)]
+    pub priority: Priority,
+    /// Use of the client's response cache, when one is configured.
+    #[serde(default)]
+    pub cache: CacheControl,
+}
+
+impl GenerateRequest {
+    pub fn new(conversation: Conversation) -> Self {
+        Self {
+            conversation,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_prompt(prompt: impl Into<String>) -> Self {
+        Self::new(Conversation::from_prompt(prompt))
+    }
+
+    pub fn config(mut self, config: GenerationConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.config.temperature = Some(temperature);
+        self
+    }
+
+    pub fn thinking(mut self, thinking: ThinkingConfig) -> Self {
+        self.config.thinking_config = Some(thinking);
+        self
+    }
+
+    pub fn cached_content(mut self, name: impl Into<String>) -> Self {
+        self.cached_content = Some(name.into());
+        self
+    }
+
+    pub fn safety_settings(mut self, settings: Vec<SafetySetting>) -> Self {
+        self.safety_settings = settings;
+        self
+    }
+
+    /// Sets the threshold of one category, replacing an earlier one.
+    pub fn safety(mut self, category: HarmCategory, threshold: HarmBlockThreshold) -> Self {
+        self.safety_settings.retain(|s| s.category != category);
+        self.safety_settings
+            .push(SafetySetting::new(category, threshold));
+        self
+    }
+
+    /// Enables a built-in tool, once.
+    pub fn tool(mut self, tool: BuiltinTool) -> Self {
+        if !self.tools.contains(&tool) {
+            self.tools.push(tool);
+        }
+        self
+    }
+
+    /// Declares a function, replacing one with the same name.
+    pub fn function(mut self, function: FunctionDeclaration) -> Self {
+        self.functions.retain(|f| f.name != function.name);
+        self.functions.push(function);
+        self
+    }
+
+    pub fn function_calling(mut self, mode: FunctionCallingMode) -> Self {
+        self.function_calling = Some(mode);
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn cache(mut self, cache: CacheControl) -> Self {
+        self.cache = cache;
+        self
+    }
+}
+
+/// Provider and model that actually answered a request, which may differ from
+/// the configured ones when a fallback chain is used.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ServedBy {
+    pub provider: String,
+    pub model: String,
+    /// Key the answer was billed to, when it is not the key of the request.
+    /// Never serialized nor printed.
+    #[serde(skip)]
+    pub api_key: Option<String>,
+}
+
+impl fmt::Debug for ServedBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServedBy")
+            .field("provider", &self.provider)
+            .field("model", &self.model)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+/// The result of a non-streaming generation.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Generation {
+    /// The answer, without thought parts.
+    pub text: String,
+    /// Thought summaries, when requested with [`ThinkingConfig::include_thoughts`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thoughts: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub served_by: Option<ServedBy>,
+    /// Safety ratings of the answer, when the provider reports them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub safety_ratings: Vec<SafetyRating>,
+    /// Sources of a grounded answer, see [`GroundingMetadata::citations`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grounding: Option<GroundingMetadata>,
+    /// Code written and run by [`BuiltinTool::CodeExecution`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub code: Vec<CodePart>,
+    /// Functions the model asks to run before it answers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub function_calls: Vec<FunctionCall>,
+}
+
+impl Generation {
+    pub fn from_text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// An item of a streaming generation.
+///
+/// A stream yields any number of `Text` (and, for thinking models asked to
+/// include them, `Thought`) chunks followed by exactly one `Done` carrying the
+/// usage of the whole request, when known.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    Text {
+        text: String,
+    },
+    /// Part of the thought summary, sent before the answer it leads to.
+    Thought {
+        text: String,
+    },
+    /// Code written or run by the model, between the text chunks around it.
+    Code {
+        part: CodePart,
+    },
+    /// A function the model asks to run, see [`Generation::function_calls`].
+    FunctionCall {
+        call: FunctionCall,
+    },
+    /// Sources of the answer, sent once before `Done` when it was grounded.
+    Grounding {
+        grounding: GroundingMetadata,
+    },
+    Done {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        usage: Option<Usage>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        model_version: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        served_by: Option<ServedBy>,
+    },
+}
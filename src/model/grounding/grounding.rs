@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// A tool run by the provider itself while answering.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum BuiltinTool {
+    /// Grounds the answer in Google Search results.
+    GoogleSearch,
+    /// Reads the URLs mentioned in the prompt.
+    UrlContext,
+    /// Writes and runs Python to compute the answer.
+    CodeExecution,
+}
+
+impl BuiltinTool {
+    // to_json:
+    // entry of the Gemini `tools` array enabling the tool
+    pub fn to_json(&self) -> Value {
+        match self {
+            BuiltinTool::GoogleSearch => json!({ "googleSearch": {} }),
+            BuiltinTool::UrlContext => json!({ "urlContext": {} }),
+            BuiltinTool::CodeExecution => json!({ "codeExecution": {} }),
+        }
+    }
+}
+
+/// Sources an answer was grounded in and which parts of the text they back.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingMetadata {
+    /// Queries the model sent to Google Search.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub web_search_queries: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_chunks: Vec<GroundingChunk>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub grounding_supports: Vec<GroundingSupport>,
+    /// Results of the URLs read with [`BuiltinTool::UrlContext`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub url_metadata: Vec<UrlMetadata>,
+}
+
+/// A retrieved source.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GroundingChunk {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub web: Option<WebSource>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WebSource {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// A span of the answer and the chunks backing it.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroundingSupport {
+    pub segment: Segment,
+    /// Indices into [`GroundingMetadata::grounding_chunks`].
+    #[serde(default)]
+    pub grounding_chunk_indices: Vec<usize>,
+    /// Confidence of each chunk, in the order of the indices.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub confidence_scores: Vec<f32>,
+}
+
+/// Byte range of the answer text.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Segment {
+    #[serde(default)]
+    pub start_index: usize,
+    #[serde(default)]
+    pub end_index: usize,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UrlMetadata {
+    pub retrieved_url: String,
+    /// e.g. `"URL_RETRIEVAL_STATUS_SUCCESS"`.
+    #[serde(default)]
+    pub url_retrieval_status: String,
+}
+
+/// A citation: a source and the answer text it supports.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Citation {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub text: String,
+    pub start_index: usize,
+    pub end_index: usize,
+}
+
+impl GroundingMetadata {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Web sources, in the order the supports refer to them.
+    pub fn sources(&self) -> Vec<&WebSource> {
+        self.grounding_chunks
+            .iter()
+            .filter_map(|chunk| chunk.web.as_ref())
+            .collect()
+    }
+
+    /// One citation per supported span and web source.
+    pub fn citations(&self) -> Vec<Citation> {
+        self.grounding_supports
+            .iter()
+            .flat_map(|support| {
+                support.grounding_chunk_indices.iter().filter_map(|index| {
+                    let web = self.grounding_chunks.get(*index)?.web.as_ref()?;
+                    Some(Citation {
+                        uri: web.uri.clone(),
+                        title: web.title.clone(),
+                        text: support.segment.text.clone(),
+                        start_index: support.segment.start_index,
+                        end_index: support.segment.end_index,
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// `text` with `[n]` markers after every supported span, `n` being the
+    /// 1-based index of the source.
+    ///
+    /// Spans are byte offsets into the answer; spans not on a char boundary are
+    /// left unmarked.
+    pub fn cite(&self, text: &str) -> String {
+        let mut marks: Vec<(usize, String)> = self
+            .grounding_supports
+            .iter()
+            .filter(|support| !support.grounding_chunk_indices.is_empty())
+            .map(|support| {
+                let refs: String = support
+                    .grounding_chunk_indices
+                    .iter()
+                    .map(|index| format!("[{}]", index + 1))
+                    .collect();
+                (support.segment.end_index, refs)
+            })
+            .filter(|(end, _)| text.is_char_boundary(*end))
+            .collect();
+        marks.sort_by_key(|(end, _)| *end);
+
+        let mut cited = String::with_capacity(text.len());
+        let mut start = 0;
+        for (end, refs) in marks {
+            cited.push_str(&text[start..end]);
+            cited.push_str(&refs);
+            start = end;
+        }
+        cited.push_str(&text[start..]);
+        cited
+    }
+}
+
+/// Code written or run by [`BuiltinTool::CodeExecution`], in answer order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CodePart {
+    /// Code the model wrote (`executableCode`).
+    Executable { language: String, code: String },
+    /// What running it gave (`codeExecutionResult`).
+    Result {
+        /// e.g. `"OUTCOME_OK"`, `"OUTCOME_FAILED"`, `"OUTCOME_DEADLINE_EXCEEDED"`.
+        outcome: String,
+        #[serde(default)]
+        output: String,
+    },
+}
+
+impl CodePart {
+    // from_part:
+    // typed form of an `executableCode` or `codeExecutionResult` Gemini part
+    pub fn from_part(part: &Value) -> Option<Self> {
+        let field = |value: &Value, name: &str| value[name].as_str().unwrap_or("").to_string();
+        if let Some(code) = part.get("executableCode") {
+            return Some(CodePart::Executable {
+                language: field(code, "language"),
+                code: field(code, "code"),
+            });
+        }
+        let result = part.get("codeExecutionResult")?;
+        Some(CodePart::Result {
+            outcome: field(result, "outcome"),
+            output: field(result, "output"),
+        })
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::model::generation::generation::Usage;
+
 /*
 Message:
  return example:
@@ -26,6 +28,8 @@ pub struct Message {
     pub choice: Choice,
     pub timestamp: String,
     pub loading: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
 }
 
 #[derive(Serialize, Deserialize, Default)]
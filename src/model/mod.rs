@@ -1 +1,8 @@
-pub mod message;
\ No newline at end of file
+pub mod batch;
+pub mod cached_content;
+pub mod conversation;
+pub mod function;
+pub mod generation;
+pub mod grounding;
+pub mod message;
+pub mod safety;
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Harm category a safety threshold or rating applies to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HarmCategory {
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
+    #[serde(rename = "HARM_CATEGORY_CIVIC_INTEGRITY")]
+    CivicIntegrity,
+    #[serde(rename = "HARM_CATEGORY_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+impl HarmCategory {
+    /// Categories Gemini accepts thresholds for.
+    pub const ALL: [HarmCategory; 5] = [
+        HarmCategory::Harassment,
+        HarmCategory::HateSpeech,
+        HarmCategory::SexuallyExplicit,
+        HarmCategory::DangerousContent,
+        HarmCategory::CivicIntegrity,
+    ];
+}
+
+impl fmt::Display for HarmCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            HarmCategory::Harassment => "harassment",
+            HarmCategory::HateSpeech => "hate speech",
+            HarmCategory::SexuallyExplicit => "sexually explicit",
+            HarmCategory::DangerousContent => "dangerous content",
+            HarmCategory::CivicIntegrity => "civic integrity",
+            HarmCategory::Unspecified => "unspecified",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Probability above which content of a category is blocked.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmBlockThreshold {
+    BlockLowAndAbove,
+    BlockMediumAndAbove,
+    BlockOnlyHigh,
+    BlockNone,
+    /// Turns the filter of the category off entirely.
+    Off,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SafetySetting {
+    pub category: HarmCategory,
+    pub threshold: HarmBlockThreshold,
+}
+
+impl SafetySetting {
+    pub fn new(category: HarmCategory, threshold: HarmBlockThreshold) -> Self {
+        Self {
+            category,
+            threshold,
+        }
+    }
+
+    /// The same threshold for every category.
+    pub fn all(threshold: HarmBlockThreshold) -> Vec<Self> {
+        HarmCategory::ALL
+            .iter()
+            .map(|category| Self::new(*category, threshold))
+            .collect()
+    }
+}
+
+// merge_settings:
+// `overrides` replace the defaults of the same category
+pub fn merge_settings(
+    defaults: &[SafetySetting],
+    overrides: &[SafetySetting],
+) -> Vec<SafetySetting> {
+    let mut merged: Vec<SafetySetting> = defaults
+        .iter()
+        .filter(|setting| !overrides.iter().any(|o| o.category == setting.category))
+        .copied()
+        .collect();
+    merged.extend_from_slice(overrides);
+    merged
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmProbability {
+    Negligible,
+    Low,
+    Medium,
+    High,
+    #[serde(rename = "HARM_PROBABILITY_UNSPECIFIED", other)]
+    Unspecified,
+}
+
+/// How likely a prompt or an answer is to be harmful in one category.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SafetyRating {
+    pub category: HarmCategory,
+    pub probability: HarmProbability,
+    /// Whether this rating caused the block.
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+impl SafetyRating {
+    /// Whether the rating caused a block or is at least medium.
+    pub fn is_flagged(&self) -> bool {
+        self.blocked
+            || matches!(
+                self.probability,
+                HarmProbability::Medium | HarmProbability::High
+            )
+    }
+}
+
+impl fmt::Display for SafetyRating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let probability = format!("{:?}", self.probability).to_lowercase();
+        write!(f, "{} ({})", self.category, probability)
+    }
+}
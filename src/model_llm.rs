@@ -13,3 +13,44 @@ pub enum Models {
     Gemini25FlashLite,
     Gemini3ProPreview,
 }
+
+impl Models {
+    pub const ALL: [Models; 4] = [
+        Models::Gemini25Flash,
+        Models::Gemini25Pro,
+        Models::Gemini25FlashLite,
+        Models::Gemini3ProPreview,
+    ];
+
+    // name:
+    // model identifier as expected by the provider API
+    pub fn name(&self) -> &'static str {
+        match self {
+            Models::Gemini25Flash => "gemini-2.5-flash",
+            Models::Gemini25Pro => "gemini-2.5-pro",
+            Models::Gemini25FlashLite => "gemini-2.5-flash-lite",
+            Models::Gemini3ProPreview => "gemini-3-pro-preview",
+        }
+    }
+
+    // input_token_limit:
+    // maximum prompt size accepted by the model
+    pub fn input_token_limit(&self) -> u64 {
+        match self {
+            Models::Gemini25Flash
+            | Models::Gemini25Pro
+            | Models::Gemini25FlashLite
+            | Models::Gemini3ProPreview => 1_048_576,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gemini-2.5-flash" => Some(Models::Gemini25Flash),
+            "gemini-2.5-pro" => Some(Models::Gemini25Pro),
+            "gemini-2.5-flash-lite" => Some(Models::Gemini25FlashLite),
+            "gemini-3-pro-preview" => Some(Models::Gemini3ProPreview),
+            _ => None,
+        }
+    }
+}
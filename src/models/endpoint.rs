@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State};
+
+use crate::{
+    models::{hedge::HedgeSnapshot, model_client::ModelClient},
+    utils::key_pool::KeyStats,
+};
+
+/// Reports health and usage of the keys in the client's key pool, under their
+/// [`key_id`](crate::usage::ledger::key_id).
+///
+/// # Example Request
+///
+/// ```http
+/// GET /keys
+/// ```
+pub async fn KeysResponse(State(client): State<Arc<ModelClient>>) -> Json<Vec<KeyStats>> {
+    Json(client.keys.stats())
+}
+
+/// Reports how often hedging fired and which side won.
+///
+/// # Example Request
+///
+/// ```http
+/// GET /hedging
+/// ```
+pub async fn HedgeStatsResponse(State(client): State<Arc<ModelClient>>) -> Json<HedgeSnapshot> {
+    Json(client.hedge_stats.snapshot())
+}
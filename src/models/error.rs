@@ -0,0 +1,108 @@
+use std::fmt;
+
+use crate::model::safety::safety::SafetyRating;
+
+/// Typed failure of a provider call, carried inside `anyhow::Error`.
+///
+/// Providers return it for failures callers may want to react to (retry,
+/// fail over, show a message); use [`ProviderError::classify`] to inspect any
+/// error coming out of a provider.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProviderError {
+    /// The provider answered with a non-2xx HTTP status.
+    Status { status: u16, message: String },
+    /// The request did not complete in time.
+    Timeout,
+    /// The prompt or the answer was blocked by the provider's safety filters.
+    /// `ratings` are those of the blocked prompt or answer, when reported.
+    SafetyBlock {
+        reason: String,
+        ratings: Vec<SafetyRating>,
+    },
+    /// The request could not be sent or the response could not be read.
+    Transport(String),
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProviderError::Status { status, message } => {
+                write!(f, "Provider returned {}: {}", status, message)
+            }
+            ProviderError::Timeout => write!(f, "Provider request timed out"),
+            ProviderError::SafetyBlock { reason, ratings } => {
+                write!(f, "Blocked by safety filters: {}", reason)?;
+                let flagged: Vec<String> = ratings
+                    .iter()
+                    .filter(|r| r.is_flagged())
+                    .map(|r| r.to_string())
+                    .collect();
+                if !flagged.is_empty() {
+                    write!(f, " [{}]", flagged.join(", "))?;
+                }
+                Ok(())
+            }
+            ProviderError::Transport(message) => write!(f, "Failed to send request: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+impl ProviderError {
+    /// Converts a `reqwest` error, leaving out the query of its URL where
+    /// Gemini requests carry the API key.
+    pub fn from_reqwest(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ProviderError::Timeout
+        } else if let Some(status) = error.status() {
+            ProviderError::Status {
+                status: status.as_u16(),
+                message: without_query(error),
+            }
+        } else {
+            ProviderError::Transport(without_query(error))
+        }
+    }
+
+    /// Extracts a `ProviderError` from an error returned by a provider, also
+    /// recognising raw `reqwest` errors.
+    pub fn classify(error: &anyhow::Error) -> Option<ProviderError> {
+        if let Some(provider) = error.downcast_ref::<ProviderError>() {
+            return Some(provider.clone());
+        }
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+            .map(ProviderError::from_reqwest)
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ProviderError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn is_rate_limit(&self) -> bool {
+        self.status() == Some(429)
+    }
+
+    pub fn is_server_error(&self) -> bool {
+        self.status().is_some_and(|s| s >= 500)
+    }
+}
+
+// without_query:
+// message of `error` with the query string of its URL removed
+fn without_query(error: &reqwest::Error) -> String {
+    let message = error.to_string();
+    match error.url() {
+        Some(url) if url.query().is_some() => {
+            let mut bare = url.clone();
+            bare.set_query(None);
+            message.replace(url.as_str(), bare.as_str())
+        }
+        _ => message,
+    }
+}
@@ -0,0 +1,431 @@
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use futures::{Stream, StreamExt, future::BoxFuture};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    model::{
+        batch::batch::{BatchJob, BatchRequest, BatchResult},
+        cached_content::cached_content::CachedContent,
+        conversation::conversation::Conversation,
+        generation::generation::{GenerateRequest, Generation, ServedBy, StreamEvent},
+    },
+    model_llm::Models,
+    models::error::ProviderError,
+    traits::{EventStream, ModelProvider},
+};
+
+/// Failures that make a [`FallbackProvider`] move on to the next target.
+/// Any other error is returned to the caller right away.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FailoverConditions {
+    /// HTTP 429, quota or rate limit exhausted.
+    pub rate_limit: bool,
+    /// HTTP 5xx or a connection failure.
+    pub server_error: bool,
+    /// No answer within the target's timeout.
+    pub timeout: bool,
+    /// Prompt or answer blocked by the target's safety filters.
+    pub safety_block: bool,
+}
+
+impl Default for FailoverConditions {
+    fn default() -> Self {
+        Self {
+            rate_limit: true,
+            server_error: true,
+            timeout: true,
+            safety_block: false,
+        }
+    }
+}
+
+impl FailoverConditions {
+    pub fn matches(&self, error: &anyhow::Error) -> bool {
+        match ProviderError::classify(error) {
+            Some(ProviderError::Timeout) => self.timeout,
+            Some(ProviderError::SafetyBlock { .. }) => self.safety_block,
+            Some(ProviderError::Transport(_)) => self.server_error,
+            Some(e) if e.is_rate_limit() => self.rate_limit,
+            Some(e) if e.is_server_error() => self.server_error,
+            _ => false,
+        }
+    }
+}
+
+/// One entry of a fallback chain.
+///
+/// `model` and `api_key` default to the ones configured on the `ModelClient`.
+#[derive(Clone)]
+pub struct FallbackTarget {
+    pub provider: Arc<dyn ModelProvider>,
+    pub model: Option<String>,
+    pub api_key: Option<String>,
+    pub timeout: Option<Duration>,
+}
+
+impl FallbackTarget {
+    pub fn new(provider: Arc<dyn ModelProvider>) -> Self {
+        Self {
+            provider,
+            model: None,
+            api_key: None,
+            timeout: None,
+        }
+    }
+
+    pub fn model(mut self, model: Models) -> Self {
+        self.model = Some(model.name().to_string());
+        self
+    }
+
+    pub fn model_name(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn resolve(&self, api_key: &str, model: &str) -> (String, String) {
+        (
+            self.api_key.clone().unwrap_or_else(|| api_key.to_string()),
+            self.model.clone().unwrap_or_else(|| model.to_string()),
+        )
+    }
+
+    fn served_by(&self, model: &str) -> ServedBy {
+        ServedBy {
+            provider: self.provider.name().to_string(),
+            model: model.to_string(),
+            api_key: self.api_key.clone(),
+        }
+    }
+}
+
+/// Composite provider trying an ordered list of targets until one succeeds.
+///
+/// A target is skipped when its call fails with one of the configured
+/// [`FailoverConditions`]; the target that finally answered is reported in
+/// [`Generation::served_by`] and in the closing [`StreamEvent::Done`].
+///
+/// Context caching, batch jobs and embeddings are not failed over: they go to
+/// the first target, with its key (and model, embeddings keep theirs). A request naming a cached content only
+/// succeeds on targets sharing the project and model of the first one.
+///
+/// # Usage
+/// ```rust,no_run
+/// # use std::{sync::Arc, time::Duration};
+/// # use ey_ai::{model_llm::Models, models::{fallback::{FallbackProvider, FallbackTarget}, gemini::GeminiProvider, model_client::ModelClient}};
+/// let gemini = Arc::new(GeminiProvider::new());
+/// let chain = FallbackProvider::chain(vec![
+///     FallbackTarget::new(gemini.clone())
+///         .model(Models::Gemini25Pro)
+///         .timeout(Duration::from_secs(30)),
+///     FallbackTarget::new(gemini).model(Models::Gemini25Flash),
+/// ]);
+/// let client = ModelClient::new(Arc::new(chain));
+/// ```
+pub struct FallbackProvider {
+    targets: Vec<FallbackTarget>,
+    conditions: FailoverConditions,
+}
+
+impl FallbackProvider {
+    pub fn chain(targets: Vec<FallbackTarget>) -> Self {
+        Self {
+            targets,
+            conditions: FailoverConditions::default(),
+        }
+    }
+
+    pub fn conditions(mut self, conditions: FailoverConditions) -> Self {
+        self.conditions = conditions;
+        self
+    }
+
+    pub fn targets(&self) -> &[FallbackTarget] {
+        &self.targets
+    }
+
+    // run:
+    // calls every target in order until one succeeds or fails with a non failover error
+    async fn run<T: Send>(
+        &self,
+        api_key: &str,
+        model: &str,
+        call: impl for<'a> Fn(&'a dyn ModelProvider, &'a str, &'a str) -> BoxFuture<'a, Result<T>>
+        + Send
+        + Sync,
+    ) -> Result<(T, ServedBy)> {
+        let mut last_error = None;
+
+        for target in &self.targets {
+            let (key, target_model) = target.resolve(api_key, model);
+            let attempt = call(target.provider.as_ref(), &key, &target_model);
+            let result = match target.timeout {
+                Some(timeout) => tokio::time::timeout(timeout, attempt)
+                    .await
+                    .unwrap_or_else(|_| Err(ProviderError::Timeout.into())),
+                None => attempt.await,
+            };
+
+            match result {
+                Ok(value) => return Ok((value, target.served_by(&target_model))),
+                Err(e) if self.conditions.matches(&e) => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("Fallback chain has no targets")))
+    }
+
+    // first:
+    // target cached contents and batch jobs go to, with its key
+    fn first(&self, api_key: &str) -> Result<(&dyn ModelProvider, String)> {
+        let target = self
+            .targets
+            .first()
+            .ok_or_else(|| anyhow!("Fallback chain has no targets"))?;
+        let key = target.api_key.as_deref().unwrap_or(api_key).to_string();
+        Ok((target.provider.as_ref(), key))
+    }
+
+    // first_model:
+    // model of the first target, `model` unless the target names one
+    fn first_model<'a>(&'a self, model: &'a str) -> &'a str {
+        self.targets
+            .first()
+            .and_then(|t| t.model.as_deref())
+            .unwrap_or(model)
+    }
+}
+
+#[async_trait]
+impl ModelProvider for FallbackProvider {
+    fn new() -> Self {
+        Self::chain(Vec::new())
+    }
+
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    async fn generate_text(&self, api_key: &str, model: &str, prompt: String) -> Result<String> {
+        let request = GenerateRequest::from_prompt(prompt);
+        Ok(self.generate(api_key, model, &request).await?.text)
+    }
+
+    /// Tries the targets in order with their blocking implementation.
+    /// Target timeouts are not applied on this path.
+    fn generate_without_async(
+        &self,
+        api_key: String,
+        model: String,
+        prompt: String,
+    ) -> Result<Value> {
+        let mut last_error = None;
+
+        for target in &self.targets {
+            let (key, target_model) = target.resolve(&api_key, &model);
+            match target
+                .provider
+                .generate_without_async(key, target_model.clone(), prompt.clone())
+            {
+                Ok(mut message) => {
+                    message["served_by"] = json!(target.served_by(&target_model));
+                    return Ok(message);
+                }
+                Err(e) if self.conditions.matches(&e) => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("Fallback chain has no targets")))
+    }
+
+    async fn generate_stream(
+        &self,
+        api_key: String,
+        model: String,
+        prompt: String,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<String, anyhow::Error>> + Send>>> {
+        let request = GenerateRequest::from_prompt(prompt);
+        let events = self.generate_events(&api_key, &model, &request).await?;
+
+        let text = events.filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text { text }) => Some(Ok(text)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        });
+        Ok(Box::pin(text))
+    }
+
+    /// Counts tokens with the first target that answers, failing over like
+    /// generation; a later target counts with its own tokenizer.
+    async fn count_tokens(
+        &self,
+        api_key: &str,
+        model: &str,
+        conversation: &Conversation,
+    ) -> Result<u64> {
+        let conversation = conversation.clone();
+        let (count, _) = self
+            .run(api_key, model, move |provider, key, model| {
+                let conversation = conversation.clone();
+                Box::pin(async move { provider.count_tokens(key, model, &conversation).await })
+            })
+            .await?;
+        Ok(count)
+    }
+
+    async fn generate(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<Generation> {
+        let request = request.clone();
+        let (mut generation, served_by) = self
+            .run(api_key, model, move |provider, key, model| {
+                let request = request.clone();
+                Box::pin(async move { provider.generate(key, model, &request).await })
+            })
+            .await?;
+
+        generation.served_by = Some(served_by);
+        Ok(generation)
+    }
+
+    /// Fails over while opening the stream and on an error as first event;
+    /// once a target has produced output the stream stays on that target.
+    async fn generate_events(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<EventStream> {
+        let request = request.clone();
+        let ((first, rest), served_by) = self
+            .run(api_key, model, move |provider, key, model| {
+                let request = request.clone();
+                Box::pin(async move {
+                    let mut stream = provider.generate_events(key, model, &request).await?;
+                    match stream.next().await {
+                        Some(Err(e)) => Err(e),
+                        first => Ok((first, stream)),
+                    }
+                })
+            })
+            .await?;
+
+        let events = futures::stream::iter(first).chain(rest).map(move |event| {
+            event.map(|event| match event {
+                StreamEvent::Done {
+                    usage,
+                    model_version,
+                    ..
+                } => StreamEvent::Done {
+                    usage,
+                    model_version,
+                    served_by: Some(served_by.clone()),
+                },
+                other => other,
+            })
+        });
+        Ok(Box::pin(events))
+    }
+
+    async fn create_cached_content(
+        &self,
+        api_key: &str,
+        model: &str,
+        prefix: &Conversation,
+        ttl: Duration,
+    ) -> Result<CachedContent> {
+        let (provider, key) = self.first(api_key)?;
+        provider
+            .create_cached_content(&key, self.first_model(model), prefix, ttl)
+            .await
+    }
+
+    async fn list_cached_contents(&self, api_key: &str) -> Result<Vec<CachedContent>> {
+        let (provider, key) = self.first(api_key)?;
+        provider.list_cached_contents(&key).await
+    }
+
+    async fn get_cached_content(&self, api_key: &str, name: &str) -> Result<CachedContent> {
+        let (provider, key) = self.first(api_key)?;
+        provider.get_cached_content(&key, name).await
+    }
+
+    async fn update_cached_content_ttl(
+        &self,
+        api_key: &str,
+        name: &str,
+        ttl: Duration,
+    ) -> Result<CachedContent> {
+        let (provider, key) = self.first(api_key)?;
+        provider.update_cached_content_ttl(&key, name, ttl).await
+    }
+
+    async fn delete_cached_content(&self, api_key: &str, name: &str) -> Result<()> {
+        let (provider, key) = self.first(api_key)?;
+        provider.delete_cached_content(&key, name).await
+    }
+
+    async fn create_batch(
+        &self,
+        api_key: &str,
+        model: &str,
+        display_name: &str,
+        requests: &[BatchRequest],
+    ) -> Result<BatchJob> {
+        let (provider, key) = self.first(api_key)?;
+        provider
+            .create_batch(&key, self.first_model(model), display_name, requests)
+            .await
+    }
+
+    async fn get_batch(&self, api_key: &str, name: &str) -> Result<BatchJob> {
+        let (provider, key) = self.first(api_key)?;
+        provider.get_batch(&key, name).await
+    }
+
+    async fn list_batches(&self, api_key: &str) -> Result<Vec<BatchJob>> {
+        let (provider, key) = self.first(api_key)?;
+        provider.list_batches(&key).await
+    }
+
+    async fn cancel_batch(&self, api_key: &str, name: &str) -> Result<()> {
+        let (provider, key) = self.first(api_key)?;
+        provider.cancel_batch(&key, name).await
+    }
+
+    async fn delete_batch(&self, api_key: &str, name: &str) -> Result<()> {
+        let (provider, key) = self.first(api_key)?;
+        provider.delete_batch(&key, name).await
+    }
+
+    async fn batch_results(&self, api_key: &str, name: &str) -> Result<Vec<BatchResult>> {
+        let (provider, key) = self.first(api_key)?;
+        provider.batch_results(&key, name).await
+    }
+
+    async fn embed(&self, api_key: &str, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let (provider, key) = self.first(api_key)?;
+        provider.embed(&key, model, texts).await
+    }
+}
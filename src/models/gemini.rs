@@ -1,8 +1,19 @@
-use std::pin::Pin;
+use std::{collections::VecDeque, pin::Pin, time::Duration};
 
 use crate::{
-    model::message::message::{Choice, Message, Role},
-    traits::ModelProvider,
+    embedding::embedder::{Embedder, GeminiEmbedder},
+    model::{
+        batch::batch::{BatchJob, BatchRequest, BatchResult, BatchState},
+        cached_content::cached_content::{CachedContent, ttl_string},
+        conversation::conversation::{Conversation, TurnRole},
+        function::function::{FunctionCall, FunctionResponse},
+        generation::generation::{GenerateRequest, Generation, StreamEvent, Usage},
+        grounding::grounding::{CodePart, GroundingMetadata, UrlMetadata},
+        message::message::{Choice, Message, Role},
+        safety::safety::SafetyRating,
+    },
+    models::error::ProviderError,
+    traits::{EventStream, ModelProvider},
 };
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
@@ -15,6 +26,9 @@ use futures::StreamExt;
 use std::result::Result::Ok;
 use uuid::Uuid;
 
+// requests larger than this are uploaded as a JSONL file instead of sent inline
+const INLINE_BATCH_LIMIT: usize = 20 * 1024 * 1024;
+
 /// Input structure for receiving prompts from API requests.
 ///
 /// # Fields
@@ -42,16 +56,31 @@ pub struct PromptInput {
 /// This provider should not be used directly. Instead, use it through `ModelClient`:
 ///
 /// ```rust,no_run
-///
+/// # use std::sync::Arc;
+/// # use ey_ai::{model_llm::Models, models::{gemini::GeminiProvider, model_client::ModelClient}};
 /// let provider = Arc::new(GeminiProvider::new());
 /// let client = ModelClient::new(provider);
-/// client.init("YOUR_API_KEY".to_string(), "gemini-pro".to_string());
+/// client.init("YOUR_API_KEY".to_string(), Models::Gemini25Flash);
 /// ```
 ///
 /// # See Also
 /// * [`ModelClient`] - The recommended wrapper for using this provider
 /// * [`ModelProvider`] - The trait this struct implements
-pub struct GeminiProvider;
+pub struct GeminiProvider {
+    base_url: String,
+}
+
+/// Root of the Gemini API used unless another base URL is given.
+pub const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+// default_base_url:
+// `GEMINI_BASE_URL` from the environment, or the Gemini API
+pub(crate) fn default_base_url() -> String {
+    match std::env::var("GEMINI_BASE_URL") {
+        Ok(base_url) if !base_url.is_empty() => base_url,
+        _ => GEMINI_BASE_URL.to_string(),
+    }
+}
 
 impl GeminiProvider {
     /// Creates a new instance of `GeminiProvider`.
@@ -61,15 +90,542 @@ impl GeminiProvider {
     ///
     /// No need to using this vanilla function
     /// consider to using selector(), see: select_model.rs
+    ///
+    /// Requests go to `GEMINI_BASE_URL` when that environment variable is
+    /// set (e.g. a local `gemini-standin`), to the Gemini API otherwise.
     pub fn new() -> Self {
-        Self
+        Self::with_base_url(default_base_url())
+    }
+
+    /// Sends every request to `base_url` instead of the Gemini API, e.g. a
+    /// proxy or the cassette server of the `testing` feature.
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+}
+
+impl Default for GeminiProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// conversation_body:
+// converts a conversation into the `contents` / `systemInstruction` fields of a Gemini request
+fn conversation_body(conversation: &Conversation) -> Value {
+    let contents: Vec<Value> = conversation
+        .turns
+        .iter()
+        .map(|turn| {
+            let role = match turn.role {
+                TurnRole::User => "user",
+                TurnRole::Model => "model",
+            };
+            let mut parts = Vec::new();
+            // function call and result turns may have no text
+            let function_turn =
+                !turn.function_calls.is_empty() || !turn.function_responses.is_empty();
+            if !turn.text.is_empty() || !function_turn {
+                parts.push(json!({ "text": turn.text }));
+            }
+            parts.extend(turn.function_calls.iter().map(FunctionCall::to_part));
+            parts.extend(
+                turn.function_responses
+                    .iter()
+                    .map(FunctionResponse::to_part),
+            );
+            json!({ "role": role, "parts": parts })
+        })
+        .collect();
+
+    let mut body = json!({ "contents": contents });
+    if let Some(system) = &conversation.system {
+        body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+    }
+    body
+}
+
+// request_body:
+// full Gemini request for a `GenerateRequest`, adding `generationConfig`,
+// `cachedContent`, `safetySettings`, `tools` and `toolConfig` when set
+fn request_body(request: &GenerateRequest) -> Value {
+    let mut body = conversation_body(&request.conversation);
+    if !request.config.is_empty() {
+        body["generationConfig"] = json!(request.config);
+    }
+    if let Some(name) = &request.cached_content {
+        body["cachedContent"] = json!(name);
+    }
+    if !request.safety_settings.is_empty() {
+        body["safetySettings"] = json!(request.safety_settings);
+    }
+    let mut tools: Vec<Value> = request.tools.iter().map(|tool| tool.to_json()).collect();
+    if !request.functions.is_empty() {
+        tools.push(json!({ "functionDeclarations": request.functions }));
+    }
+    if !tools.is_empty() {
+        body["tools"] = json!(tools);
+    }
+    if let Some(mode) = request.function_calling {
+        body["toolConfig"] = json!({ "functionCallingConfig": { "mode": mode } });
+    }
+    body
+}
+
+// cached_content_url:
+// `cachedContents` collection, or one cached content when `name` is given
+fn cached_content_url(base_url: &str, api_key: &str, name: Option<&str>) -> String {
+    format!(
+        "{}/v1beta/{}?key={}",
+        base_url,
+        name.unwrap_or("cachedContents"),
+        api_key
+    )
+}
+
+// batch_url:
+// `batches` collection, or one batch job (`batches/...`) when `name` is given
+fn batch_url(base_url: &str, api_key: &str, name: Option<&str>) -> String {
+    format!(
+        "{}/v1beta/{}?key={}",
+        base_url,
+        name.unwrap_or("batches"),
+        api_key
+    )
+}
+
+// parse_batch:
+// reads a batch job from a long running operation (`metadata` / `response`)
+// or from a bare batch
+fn parse_batch(json: &Value) -> Result<BatchJob> {
+    let batch = json.get("metadata").unwrap_or(json);
+    let mut job: BatchJob = serde_json::from_value(batch.clone())
+        .map_err(|e| anyhow!("Failed to parse batch: {}", e))?;
+    if job.name.is_empty() {
+        job.name = json["name"].as_str().unwrap_or_default().to_string();
+    }
+    job.responses_file = batch_output(json)["responsesFile"]
+        .as_str()
+        .map(str::to_string);
+    job.error = json["error"]["message"].as_str().map(str::to_string);
+    Ok(job)
+}
+
+// batch_output:
+// output of a finished batch, in the operation `response` or the batch `output`
+fn batch_output(json: &Value) -> &Value {
+    match json.get("response") {
+        Some(response) => response,
+        None => &json.get("metadata").unwrap_or(json)["output"],
+    }
+}
+
+// batch_result:
+// result of one batch entry, `{ "response": ... }` or `{ "error": ... }`
+fn batch_result(id: String, entry: &Value) -> BatchResult {
+    let outcome = match entry.get("error") {
+        Some(error) => Err(error["message"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string())),
+        None => parse_generation(&entry["response"]).map_err(|e| e.to_string()),
+    };
+    match outcome {
+        Ok(generation) => BatchResult {
+            id,
+            generation: Some(generation),
+            error: None,
+        },
+        Err(error) => BatchResult {
+            id,
+            generation: None,
+            error: Some(error),
+        },
+    }
+}
+
+// upload_file:
+// uploads `data` with the resumable protocol of the Files API and returns the
+// file name (`files/...`)
+async fn upload_file(
+    base_url: &str,
+    api_key: &str,
+    display_name: &str,
+    mime_type: &str,
+    data: String,
+) -> Result<String> {
+    let client = reqwest::Client::new();
+    let res = client
+        .post(format!("{}/upload/v1beta/files?key={}", base_url, api_key))
+        .header("X-Goog-Upload-Protocol", "resumable")
+        .header("X-Goog-Upload-Command", "start")
+        .header("X-Goog-Upload-Header-Content-Length", data.len())
+        .header("X-Goog-Upload-Header-Content-Type", mime_type)
+        .json(&json!({ "file": { "displayName": display_name } }))
+        .send()
+        .await
+        .map_err(|e| send_error(&e))?;
+
+    let status = res.status();
+    let upload_url = res
+        .headers()
+        .get("x-goog-upload-url")
+        .and_then(|url| url.to_str().ok())
+        .map(str::to_string);
+    let body = res.text().await.map_err(|e| send_error(&e))?;
+    if !status.is_success() {
+        check_status(status, &body)?;
+    }
+    let upload_url = upload_url.ok_or_else(|| anyhow!("No upload URL from Gemini"))?;
+
+    let res = client
+        .post(upload_url)
+        .header("X-Goog-Upload-Offset", 0)
+        .header("X-Goog-Upload-Command", "upload, finalize")
+        .body(data)
+        .send()
+        .await
+        .map_err(|e| send_error(&e))?;
+    let json = read_json(res).await?;
+    json["file"]["name"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("No file name from Gemini"))
+}
+
+// download_file:
+// content of a file produced by the API, such as batch results
+async fn download_file(base_url: &str, api_key: &str, name: &str) -> Result<String> {
+    let res = reqwest::Client::new()
+        .get(format!(
+            "{}/download/v1beta/{}:download?alt=media&key={}",
+            base_url, name, api_key
+        ))
+        .send()
+        .await
+        .map_err(|e| send_error(&e))?;
+    let status = res.status();
+    let body = res.text().await.map_err(|e| send_error(&e))?;
+    if !status.is_success() {
+        check_status(status, &body)?;
+    }
+    Ok(body)
+}
+
+fn parse_cached_content(json: Value) -> Result<CachedContent> {
+    serde_json::from_value(json).map_err(|e| anyhow!("Failed to parse cached content: {}", e))
+}
+
+// candidate_text:
+// concatenates the answer parts of the first candidate, thought parts excluded
+fn candidate_text(json: &Value) -> Option<String> {
+    candidate_parts(json, false)
+}
+
+// candidate_thoughts:
+// concatenates the thought summary parts of the first candidate
+fn candidate_thoughts(json: &Value) -> Option<String> {
+    candidate_parts(json, true).filter(|text| !text.is_empty())
+}
+
+fn candidate_parts(json: &Value, thought: bool) -> Option<String> {
+    let parts = json["candidates"].get(0)?["content"]["parts"].as_array()?;
+    let text: String = parts
+        .iter()
+        .filter(|p| p["thought"].as_bool().unwrap_or(false) == thought)
+        .filter_map(|p| p["text"].as_str())
+        .collect();
+    Some(text)
+}
+
+// candidate_code:
+// `executableCode` and `codeExecutionResult` parts of the first candidate
+fn candidate_code(json: &Value) -> Vec<CodePart> {
+    json["candidates"][0]["content"]["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(CodePart::from_part)
+        .collect()
+}
+
+// candidate_function_calls:
+// `functionCall` parts of the first candidate
+fn candidate_function_calls(json: &Value) -> Vec<FunctionCall> {
+    json["candidates"][0]["content"]["parts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(FunctionCall::from_part)
+        .collect()
+}
+
+// chunk_events:
+// events of a streamed chunk, in part order; adjacent text parts of the same
+// kind are joined
+fn chunk_events(json: &Value) -> Vec<StreamEvent> {
+    let mut events: Vec<StreamEvent> = Vec::new();
+    let parts = json["candidates"][0]["content"]["parts"].as_array();
+    for part in parts.into_iter().flatten() {
+        if let Some(code) = CodePart::from_part(part) {
+            events.push(StreamEvent::Code { part: code });
+            continue;
+        }
+        if let Some(call) = FunctionCall::from_part(part) {
+            events.push(StreamEvent::FunctionCall { call });
+            continue;
+        }
+        let Some(text) = part["text"].as_str().filter(|text| !text.is_empty()) else {
+            continue;
+        };
+        let thought = part["thought"].as_bool().unwrap_or(false);
+        match events.last_mut() {
+            Some(StreamEvent::Thought { text: last }) if thought => last.push_str(text),
+            Some(StreamEvent::Text { text: last }) if !thought => last.push_str(text),
+            _ if thought => events.push(StreamEvent::Thought {
+                text: text.to_string(),
+            }),
+            _ => events.push(StreamEvent::Text {
+                text: text.to_string(),
+            }),
+        }
+    }
+    events
+}
+
+// parse_grounding:
+// reads the `groundingMetadata` and `urlContextMetadata` of the first candidate
+fn parse_grounding(json: &Value) -> Option<GroundingMetadata> {
+    let candidate = &json["candidates"][0];
+    let mut grounding: GroundingMetadata = candidate
+        .get("groundingMetadata")
+        .and_then(|meta| serde_json::from_value(meta.clone()).ok())
+        .unwrap_or_default();
+    grounding.url_metadata = candidate["urlContextMetadata"]["urlMetadata"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|meta| serde_json::from_value::<UrlMetadata>(meta.clone()).ok())
+        .collect();
+    (!grounding.is_empty()).then_some(grounding)
+}
+
+// parse_generation:
+// reads a `generateContent` response, also used for batch results
+fn parse_generation(json: &Value) -> Result<Generation> {
+    let code = candidate_code(json);
+    let function_calls = candidate_function_calls(json);
+    // an answer made only of code execution parts or function calls has no text
+    let text = candidate_text(json)
+        .filter(|text| !text.is_empty() || !code.is_empty() || !function_calls.is_empty())
+        .ok_or_else(|| no_reply(json))?;
+
+    Ok(Generation {
+        text,
+        thoughts: candidate_thoughts(json),
+        usage: parse_usage(json),
+        model_version: json["modelVersion"].as_str().map(str::to_string),
+        served_by: None,
+        safety_ratings: safety_ratings(&json["candidates"][0]["safetyRatings"]),
+        grounding: parse_grounding(json),
+        code,
+        function_calls,
+    })
+}
+
+// parse_usage:
+// reads `usageMetadata` of a Gemini response
+fn parse_usage(json: &Value) -> Option<Usage> {
+    let meta = json.get("usageMetadata")?;
+    let field = |name: &str| meta[name].as_u64().unwrap_or(0);
+    Some(Usage {
+        prompt_tokens: field("promptTokenCount"),
+        candidates_tokens: field("candidatesTokenCount"),
+        cached_tokens: field("cachedContentTokenCount"),
+        thoughts_tokens: field("thoughtsTokenCount"),
+        total_tokens: field("totalTokenCount"),
+    })
+}
+
+// sse_events:
+// turns a `streamGenerateContent?alt=sse` body into stream events,
+// buffering bytes until a full `data:` line is available
+fn sse_events(res: reqwest::Response) -> EventStream {
+    struct State {
+        body: Pin<Box<dyn Stream<Item = reqwest::Result<Vec<u8>>> + Send>>,
+        buffer: Vec<u8>,
+        usage: Option<Usage>,
+        model_version: Option<String>,
+        grounding: Option<GroundingMetadata>,
+        // events of a chunk not yielded yet
+        pending: VecDeque<StreamEvent>,
+        finished: bool,
+    }
+
+    let state = State {
+        body: Box::pin(res.bytes_stream().map(|b| b.map(|b| b.to_vec()))),
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        usage: None,
+        model_version: None,
+        grounding: None,
+        finished: false,
+    };
+
+    let events = futures::stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((Ok(event), state));
+            }
+            if state.finished {
+                return None;
+            }
+
+            if let Some(pos) = state.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = state.buffer.drain(..=pos).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    continue;
+                };
+                let chunk: Value = match serde_json::from_str(data.trim()) {
+                    Ok(chunk) => chunk,
+                    Err(e) => return Some((Err(anyhow!("Failed to parse chunk: {}", e)), state)),
+                };
+                if let Some(usage) = parse_usage(&chunk) {
+                    state.usage = Some(usage);
+                }
+                if let Some(version) = chunk["modelVersion"].as_str() {
+                    state.model_version = Some(version.to_string());
+                }
+                if let Some(block) = safety_block(&chunk) {
+                    state.finished = true;
+                    return Some((Err(block.into()), state));
+                }
+                if let Some(grounding) = parse_grounding(&chunk) {
+                    state.grounding = Some(grounding);
+                }
+                state.pending.extend(chunk_events(&chunk));
+                continue;
+            }
+
+            match state.body.next().await {
+                Some(Ok(bytes)) => state.buffer.extend_from_slice(&bytes),
+                Some(Err(e)) => return Some((Err(send_error(&e)), state)),
+                None => {
+                    // flush a trailing event without newline
+                    if !state.buffer.iter().all(u8::is_ascii_whitespace) {
+                        state.buffer.push(b'\n');
+                        continue;
+                    }
+                    state.finished = true;
+                    if let Some(grounding) = state.grounding.take() {
+                        state
+                            .pending
+                            .push_back(StreamEvent::Grounding { grounding });
+                    }
+                    state.pending.push_back(StreamEvent::Done {
+                        usage: state.usage,
+                        model_version: state.model_version.clone(),
+                        served_by: None,
+                    });
+                    continue;
+                }
+            }
+        }
+    });
+
+    Box::pin(events)
+}
+
+// read_json:
+// parses a Gemini response body, turning non-2xx replies into a `ProviderError`
+pub(crate) async fn read_json(res: reqwest::Response) -> Result<Value> {
+    let status = res.status();
+    let body = res.text().await.map_err(|e| send_error(&e))?;
+    check_status(status, &body)
+}
+
+// read_json_blocking:
+// blocking counterpart of `read_json`
+fn read_json_blocking(res: reqwest::blocking::Response) -> Result<Value> {
+    let status = res.status();
+    let body = res.text().map_err(|e| send_error(&e))?;
+    check_status(status, &body)
+}
+
+fn check_status(status: reqwest::StatusCode, body: &str) -> Result<Value> {
+    if !status.is_success() {
+        let message = serde_json::from_str::<Value>(body)
+            .ok()
+            .and_then(|json| json["error"]["message"].as_str().map(str::to_string))
+            .unwrap_or_else(|| body.to_string());
+        return Err(ProviderError::Status {
+            status: status.as_u16(),
+            message,
+        }
+        .into());
+    }
+    serde_json::from_str(body).map_err(|e| anyhow!("Failed to parse response: {}", e))
+}
+
+pub(crate) fn send_error(error: &reqwest::Error) -> anyhow::Error {
+    ProviderError::from_reqwest(error).into()
+}
+
+// safety_ratings:
+// parses a `safetyRatings` array, unknown entries are skipped
+fn safety_ratings(json: &Value) -> Vec<SafetyRating> {
+    json.as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|rating| serde_json::from_value(rating.clone()).ok())
+        .collect()
+}
+
+// safety_block:
+// the block of a blocked prompt (`promptFeedback`) or answer (`finishReason`), with its ratings
+fn safety_block(json: &Value) -> Option<ProviderError> {
+    if let Some(reason) = json["promptFeedback"]["blockReason"].as_str() {
+        return Some(ProviderError::SafetyBlock {
+            reason: reason.to_string(),
+            ratings: safety_ratings(&json["promptFeedback"]["safetyRatings"]),
+        });
+    }
+    let candidate = &json["candidates"][0];
+    match candidate["finishReason"].as_str() {
+        Some(reason @ ("SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII")) => {
+            Some(ProviderError::SafetyBlock {
+                reason: reason.to_string(),
+                ratings: safety_ratings(&candidate["safetyRatings"]),
+            })
+        }
+        _ => None,
+    }
+}
+
+// no_reply:
+// error for a response without text, telling safety blocks apart
+fn no_reply(json: &Value) -> anyhow::Error {
+    match safety_block(json) {
+        Some(block) => block.into(),
+        None => anyhow!("No response from Gemini"),
     }
 }
 
 #[async_trait]
 impl ModelProvider for GeminiProvider {
     fn new() -> Self {
-        Self
+        GeminiProvider::new()
+    }
+
+    fn name(&self) -> &str {
+        "gemini"
     }
 
     /// Generates text using the Gemini API (asynchronous implementation).
@@ -115,22 +671,21 @@ impl ModelProvider for GeminiProvider {
     ///
     /// # Recommended Usage Pattern
     /// ```rust,no_run
+    /// # use std::env;
+    /// # use ey_ai::{model_llm::{ModelLLM, Models}, utils::select_model::selector};
     /// # async fn example() {
-    /// dotenv.ok();
+    /// dotenvy::dotenv().ok();
     /// // Recommended: Use ModelClient wrapper
     /// let gemini = selector(ModelLLM::Gemini);
-    ///  gemini.init(
-    ///    env::var("GEMINI_API_KEY").unwrap(),
-    ///    "gemini-2.5-flash".into(),
-    ///  );
+    /// gemini.init(env::var("GEMINI_API_KEY").unwrap(), Models::Gemini25Flash);
     ///
-    //  let _ = println!("{:?}", gemini.generate_sync("Hello!".to_string()));
+    /// let _ = println!("{:?}", gemini.GenerateContent("Hello!".to_string()).await);
     /// # }
     /// ```
     async fn generate_text(&self, api_key: &str, model: &str, prompt: String) -> Result<String> {
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            model, api_key
+            "{}/v1beta/models/{}:generateContent?key={}",
+            self.base_url, model, api_key
         );
 
         let body = json!({
@@ -148,18 +703,15 @@ impl ModelProvider for GeminiProvider {
             .header("Content-Type", "application/json")
             .send()
             .await
-            .map_err(|e| anyhow!("Failed to send request: {}", e))?;
+            .map_err(|e| send_error(&e))?;
 
-        let json: Value = res
-            .json()
-            .await
-            .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
+        let json = read_json(res).await?;
 
         let reply = json["candidates"]
             .get(0)
             .and_then(|c| c["content"]["parts"].get(0))
             .and_then(|p| p["text"].as_str())
-            .ok_or_else(|| anyhow!("No response from Gemini"))?
+            .ok_or_else(|| no_reply(&json))?
             .to_string();
 
         Ok(reply)
@@ -281,8 +833,8 @@ impl ModelProvider for GeminiProvider {
         let req = reqwest::blocking::Client::new();
 
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
-            model, api_key
+            "{}/v1beta/models/{}:generateContent?key={}",
+            self.base_url, model, api_key
         );
 
         let body = json!({
@@ -297,8 +849,12 @@ impl ModelProvider for GeminiProvider {
             .post(&url)
             .json(&body)
             .header("Content-Type", "application/json")
-            .send()?
-            .json::<Value>()?;
+            .send()
+            .map_err(|e| send_error(&e))?;
+        let res = read_json_blocking(res)?;
+        if let Some(block) = safety_block(&res) {
+            return Err(block.into());
+        }
         let reply = res["candidates"]
             .get(0)
             .and_then(|c| c["content"]["parts"].get(0))
@@ -321,17 +877,15 @@ impl ModelProvider for GeminiProvider {
             },
             timestamp: Utc::now().to_string(),
             loading: true,
+            usage: parse_usage(&res),
         };
         Ok(json!(message))
     }
 
     /// Generates text in a streaming fashion using the Gemini API.
     ///
-    /// # Warning: Not Yet Implemented
-    /// This function is a placeholder and does not have a functional implementation yet.
-    ///
     /// # Purpose
-    /// This function is designed to connect to the `streamGenerateContent` endpoint of the Gemini API.
+    /// This function connects to the `streamGenerateContent` endpoint of the Gemini API.
     /// Unlike `generateContent`, which waits for the entire response to complete, `streamGenerateContent`
     /// sends data in chunks in real-time as the model generates text. This allows for incremental
     /// data processing, which is ideal for applications that display responses directly to the user.
@@ -339,48 +893,410 @@ impl ModelProvider for GeminiProvider {
     /// # Detail API
     /// **Endpoint:**
     /// ```text
-    /// POST https://generativelanguage.googleapis.com/v1beta/models/{model}:streamGenerateContent?key={api_key}
+    /// POST https://generativelanguage.googleapis.com/v1beta/models/{model}:streamGenerateContent?alt=sse&key={api_key}
     /// ```
     /// **Response Handling:**
-    /// Each `chunk` from the stream will be a JSON object containing a part of the generated text.
-    /// The text needs to be extracted from `response.candidates[0].content.parts[0].text`.
+    /// Each server-sent event carries a JSON object containing a part of the generated text.
+    /// The text is extracted from `response.candidates[0].content.parts[*].text`.
     ///
-    /// # Arguments (Planned)
+    /// # Arguments
     /// * `api_key` - The API key for authenticating with the Google Gemini API.
     /// * `model` - The name of the Gemini model to be used (e.g., "gemini-2.5-flash").
     /// * `prompt` - The text prompt to be sent to the model.
     ///
-    /// # Returns (Planned)
-    /// Will return a stream, for example, `Box<dyn Stream<Item = Result<String>> + Send>`,
-    /// where each item is a chunk of text generated by the model.
+    /// # Returns
+    /// A stream where each item is a chunk of text generated by the model.
+    /// Use [`generate_events`](ModelProvider::generate_events) to also receive usage.
     async fn generate_stream(
         &self,
         api_key: String,
         model: String,
         prompt: String,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<String, anyhow::Error>> + Send>>> {
-        let client = reqwest::Client::new();
+        let request = GenerateRequest::from_prompt(prompt);
+        let events = self.generate_events(&api_key, &model, &request).await?;
+
+        let text = events.filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text { text }) => Some(Ok(text)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        });
+
+        Ok(Box::pin(text))
+    }
+
+    /// Generates a streaming reply to a [`GenerateRequest`].
+    ///
+    /// Requests `alt=sse` so every event holds one complete JSON chunk. Text of
+    /// each chunk is yielded as [`StreamEvent::Text`], thought summaries as
+    /// [`StreamEvent::Thought`]; the `usageMetadata` of the last chunk is
+    /// reported by the final [`StreamEvent::Done`].
+    async fn generate_events(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<EventStream> {
+        let url = format!(
+            "{}/v1beta/models/{}:streamGenerateContent?alt=sse&key={}",
+            self.base_url, model, api_key
+        );
+
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&request_body(request))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+
+        if !res.status().is_success() {
+            read_json(res).await?;
+            return Err(anyhow!("Gemini stream request failed"));
+        }
 
+        Ok(sse_events(res))
+    }
+
+    /// Counts tokens of a conversation using the Gemini `countTokens` endpoint.
+    ///
+    /// # API Details
+    /// **Endpoint:**
+    /// ```text
+    /// POST https://generativelanguage.googleapis.com/v1beta/models/{model}:countTokens?key={api_key}
+    /// ```
+    ///
+    /// The conversation is wrapped in a `generateContentRequest` so the system
+    /// instruction is counted as well.
+    async fn count_tokens(
+        &self,
+        api_key: &str,
+        model: &str,
+        conversation: &Conversation,
+    ) -> Result<u64> {
         let url = format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{}:streamGenerateContent?key={}",
-            model, api_key
+            "{}/v1beta/models/{}:countTokens?key={}",
+            self.base_url, model, api_key
+        );
+
+        let mut request = conversation_body(conversation);
+        request["model"] = json!(format!("models/{}", model));
+        let body = json!({ "generateContentRequest": request });
+
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+
+        let json = read_json(res).await?;
+        json["totalTokens"]
+            .as_u64()
+            .ok_or_else(|| anyhow!("No token count from Gemini"))
+    }
+
+    /// Generates a reply to a [`GenerateRequest`], including token usage.
+    ///
+    /// Turns are sent as `contents` with `user` / `model` roles and the
+    /// system prompt as `systemInstruction`. Unlike [`generate_text`], a
+    /// non-2xx status is reported with the error message returned by Gemini,
+    /// and `usageMetadata` / `modelVersion` are kept in the returned [`Generation`].
+    async fn generate(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<Generation> {
+        let url = format!(
+            "{}/v1beta/models/{}:generateContent?key={}",
+            self.base_url, model, api_key
         );
 
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&request_body(request))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+
+        parse_generation(&read_json(res).await?)
+    }
+
+    /// Creates a `cachedContents` resource holding `prefix` (its turns and
+    /// system prompt) for `ttl`.
+    ///
+    /// # API Details
+    /// **Endpoint:**
+    /// ```text
+    /// POST https://generativelanguage.googleapis.com/v1beta/cachedContents?key={api_key}
+    /// ```
+    ///
+    /// Gemini only caches prefixes above a minimum size (a few thousand
+    /// tokens depending on the model) and answers 400 below it.
+    async fn create_cached_content(
+        &self,
+        api_key: &str,
+        model: &str,
+        prefix: &Conversation,
+        ttl: Duration,
+    ) -> Result<CachedContent> {
+        let mut body = conversation_body(prefix);
+        body["model"] = json!(format!("models/{}", model));
+        body["ttl"] = json!(ttl_string(ttl));
+
+        let res = reqwest::Client::new()
+            .post(cached_content_url(&self.base_url, api_key, None))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        parse_cached_content(read_json(res).await?)
+    }
+
+    /// Lists the cached contents of the key's project, following pagination.
+    async fn list_cached_contents(&self, api_key: &str) -> Result<Vec<CachedContent>> {
+        let client = reqwest::Client::new();
+        let mut contents = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let mut req = client.get(cached_content_url(&self.base_url, api_key, None));
+            if let Some(token) = &page_token {
+                req = req.query(&[("pageToken", token)]);
+            }
+            let res = req.send().await.map_err(|e| send_error(&e))?;
+            let json = read_json(res).await?;
+
+            for content in json["cachedContents"].as_array().into_iter().flatten() {
+                contents.push(parse_cached_content(content.clone())?);
+            }
+            match json["nextPageToken"].as_str() {
+                Some(token) if !token.is_empty() => page_token = Some(token.to_string()),
+                _ => return Ok(contents),
+            }
+        }
+    }
+
+    async fn get_cached_content(&self, api_key: &str, name: &str) -> Result<CachedContent> {
+        let res = reqwest::Client::new()
+            .get(cached_content_url(&self.base_url, api_key, Some(name)))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        parse_cached_content(read_json(res).await?)
+    }
+
+    /// # API Details
+    /// **Endpoint:**
+    /// ```text
+    /// PATCH https://generativelanguage.googleapis.com/v1beta/{name}?updateMask=ttl&key={api_key}
+    /// ```
+    async fn update_cached_content_ttl(
+        &self,
+        api_key: &str,
+        name: &str,
+        ttl: Duration,
+    ) -> Result<CachedContent> {
+        let res = reqwest::Client::new()
+            .patch(cached_content_url(&self.base_url, api_key, Some(name)))
+            .query(&[("updateMask", "ttl")])
+            .json(&json!({ "ttl": ttl_string(ttl) }))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        parse_cached_content(read_json(res).await?)
+    }
+
+    async fn delete_cached_content(&self, api_key: &str, name: &str) -> Result<()> {
+        let res = reqwest::Client::new()
+            .delete(cached_content_url(&self.base_url, api_key, Some(name)))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        read_json(res).await?;
+        Ok(())
+    }
+
+    /// Submits `requests` to Gemini batch mode, at half the price of
+    /// interactive requests.
+    ///
+    /// # API Details
+    /// **Endpoint:**
+    /// ```text
+    /// POST https://generativelanguage.googleapis.com/v1beta/models/{model}:batchGenerateContent?key={api_key}
+    /// ```
+    ///
+    /// Up to 20 MB the requests are sent inline, larger jobs are uploaded
+    /// first as a JSONL file through the Files API. Either way every request
+    /// carries its id as key, so results are matched back to it.
+    async fn create_batch(
+        &self,
+        api_key: &str,
+        model: &str,
+        display_name: &str,
+        requests: &[BatchRequest],
+    ) -> Result<BatchJob> {
+        let lines: Vec<String> = requests
+            .iter()
+            .map(|r| json!({ "key": r.id, "request": request_body(&r.request) }).to_string())
+            .collect();
+        let size: usize = lines.iter().map(String::len).sum();
+
+        let input = if size <= INLINE_BATCH_LIMIT {
+            let inline: Vec<Value> = requests
+                .iter()
+                .map(|r| {
+                    json!({
+                        "request": request_body(&r.request),
+                        "metadata": { "key": r.id },
+                    })
+                })
+                .collect();
+            json!({ "requests": { "requests": inline } })
+        } else {
+            let file = upload_file(
+                &self.base_url,
+                api_key,
+                display_name,
+                "application/jsonl",
+                lines.join("\n"),
+            )
+            .await?;
+            json!({ "fileName": file })
+        };
+
+        let url = format!(
+            "{}/v1beta/models/{}:batchGenerateContent?key={}",
+            self.base_url, model, api_key
+        );
         let body = json!({
-            "contents": [{
-                "parts": [{ "text": prompt }]
-            }]
+            "batch": {
+                "displayName": display_name,
+                "inputConfig": input,
+            }
         });
+        let res = reqwest::Client::new()
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        parse_batch(&read_json(res).await?)
+    }
 
-        let res = client.post(url).json(&body).send().await?;
+    async fn get_batch(&self, api_key: &str, name: &str) -> Result<BatchJob> {
+        let res = reqwest::Client::new()
+            .get(batch_url(&self.base_url, api_key, Some(name)))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        parse_batch(&read_json(res).await?)
+    }
 
-        let raw_stream = res.bytes_stream();
+    /// Lists the batch jobs of the key's project, following pagination.
+    async fn list_batches(&self, api_key: &str) -> Result<Vec<BatchJob>> {
+        let client = reqwest::Client::new();
+        let mut jobs = Vec::new();
+        let mut page_token: Option<String> = None;
 
-        let mapped = raw_stream.map(|chunk| match chunk {
-            Ok(bytes) => Ok(String::from_utf8_lossy(&bytes).to_string()),
-            Err(e) => Err(anyhow!(e)),
-        });
+        loop {
+            let mut req = client.get(batch_url(&self.base_url, api_key, None));
+            if let Some(token) = &page_token {
+                req = req.query(&[("pageToken", token)]);
+            }
+            let res = req.send().await.map_err(|e| send_error(&e))?;
+            let json = read_json(res).await?;
+
+            let batches = json
+                .get("operations")
+                .or_else(|| json.get("batches"))
+                .and_then(Value::as_array);
+            for batch in batches.into_iter().flatten() {
+                jobs.push(parse_batch(batch)?);
+            }
+            match json["nextPageToken"].as_str() {
+                Some(token) if !token.is_empty() => page_token = Some(token.to_string()),
+                _ => return Ok(jobs),
+            }
+        }
+    }
 
-        Ok(Box::pin(mapped))
+    async fn cancel_batch(&self, api_key: &str, name: &str) -> Result<()> {
+        let url = format!("{}/v1beta/{}:cancel?key={}", self.base_url, name, api_key);
+        let res = reqwest::Client::new()
+            .post(&url)
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        read_json(res).await?;
+        Ok(())
+    }
+
+    async fn delete_batch(&self, api_key: &str, name: &str) -> Result<()> {
+        let res = reqwest::Client::new()
+            .delete(batch_url(&self.base_url, api_key, Some(name)))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        read_json(res).await?;
+        Ok(())
+    }
+
+    /// Reads the inline responses of a succeeded job, or downloads its
+    /// results file. Entries without key are numbered in submission order.
+    async fn batch_results(&self, api_key: &str, name: &str) -> Result<Vec<BatchResult>> {
+        let res = reqwest::Client::new()
+            .get(batch_url(&self.base_url, api_key, Some(name)))
+            .send()
+            .await
+            .map_err(|e| send_error(&e))?;
+        let json = read_json(res).await?;
+        let job = parse_batch(&json)?;
+        if job.state != BatchState::Succeeded {
+            return Err(anyhow!(
+                "Batch {} has no results, its state is {:?}",
+                name,
+                job.state
+            ));
+        }
+
+        let key = |entry: &Value, index: usize| {
+            entry["key"]
+                .as_str()
+                .or_else(|| entry["metadata"]["key"].as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| index.to_string())
+        };
+
+        if let Some(file) = &job.responses_file {
+            let content = download_file(&self.base_url, api_key, file).await?;
+            return content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(index, line)| {
+                    let entry: Value = serde_json::from_str(line)
+                        .map_err(|e| anyhow!("Failed to parse batch result: {}", e))?;
+                    Ok(batch_result(key(&entry, index), &entry))
+                })
+                .collect();
+        }
+
+        let inline = batch_output(&json)["inlinedResponses"]["inlinedResponses"].as_array();
+        Ok(inline
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, entry)| batch_result(key(entry, index), entry))
+            .collect())
+    }
+
+    async fn embed(&self, api_key: &str, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        GeminiEmbedder::new(api_key, model)
+            .base_url(&self.base_url)
+            .embed(texts)
+            .await
     }
 }
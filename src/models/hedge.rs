@@ -0,0 +1,261 @@
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::generation::generation::{GenerateRequest, Generation, ServedBy, StreamEvent},
+    traits::{EventStream, ModelProvider},
+};
+
+/// Second request fired by `ModelClient` when the first one is slow.
+///
+/// `model` and `api_key` default to the ones of the client, so hedging onto
+/// the same provider only needs a delay.
+#[derive(Clone)]
+pub struct HedgeConfig {
+    pub provider: Arc<dyn ModelProvider>,
+    pub model: Option<String>,
+    pub api_key: Option<String>,
+    /// Time without response (or first stream event) before the hedge is fired.
+    pub delay: Duration,
+}
+
+impl HedgeConfig {
+    pub fn new(provider: Arc<dyn ModelProvider>, delay: Duration) -> Self {
+        Self {
+            provider,
+            model: None,
+            api_key: None,
+            delay,
+        }
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+}
+
+/// Counters of the hedging mode.
+#[derive(Default)]
+pub struct HedgeStats {
+    requests: AtomicU64,
+    hedged: AtomicU64,
+    primary_wins: AtomicU64,
+    hedge_wins: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct HedgeSnapshot {
+    /// Requests sent while hedging was enabled.
+    pub requests: u64,
+    /// Requests whose hedge was fired.
+    pub hedged: u64,
+    /// Hedged requests answered by the first request.
+    pub primary_wins: u64,
+    /// Hedged requests answered by the hedge.
+    pub hedge_wins: u64,
+    /// Share of requests whose hedge was fired.
+    pub hedge_rate: f64,
+}
+
+impl HedgeStats {
+    pub fn snapshot(&self) -> HedgeSnapshot {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let hedged = self.hedged.load(Ordering::Relaxed);
+        HedgeSnapshot {
+            requests,
+            hedged,
+            primary_wins: self.primary_wins.load(Ordering::Relaxed),
+            hedge_wins: self.hedge_wins.load(Ordering::Relaxed),
+            hedge_rate: if requests == 0 {
+                0.0
+            } else {
+                hedged as f64 / requests as f64
+            },
+        }
+    }
+
+    pub fn reset(&self) {
+        for counter in [
+            &self.requests,
+            &self.hedged,
+            &self.primary_wins,
+            &self.hedge_wins,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// race:
+// awaits `primary`, fires `hedge` after `delay` and returns the first success;
+// the slower future is dropped, which cancels its request.
+// When both fail the error of the primary is returned.
+async fn race<T, P, H, F>(
+    stats: &HedgeStats,
+    delay: Duration,
+    primary: P,
+    hedge: H,
+) -> Result<(T, bool)>
+where
+    P: Future<Output = Result<T>>,
+    H: FnOnce() -> F,
+    F: Future<Output = Result<T>>,
+{
+    stats.requests.fetch_add(1, Ordering::Relaxed);
+    tokio::pin!(primary);
+
+    tokio::select! {
+        result = &mut primary => return result.map(|value| (value, false)),
+        _ = tokio::time::sleep(delay) => {}
+    }
+
+    stats.hedged.fetch_add(1, Ordering::Relaxed);
+    let hedge = hedge();
+    tokio::pin!(hedge);
+
+    let (first, primary_first) = tokio::select! {
+        result = &mut primary => (result, true),
+        result = &mut hedge => (result, false),
+    };
+
+    let (result, from_primary) = match first {
+        Ok(value) => (Ok(value), primary_first),
+        // one side failed, the other one still has a chance
+        Err(e) if primary_first => match hedge.await {
+            Ok(value) => (Ok(value), false),
+            Err(_) => (Err(e), true),
+        },
+        Err(_) => (primary.await, true),
+    };
+
+    if result.is_ok() {
+        let counter = if from_primary {
+            &stats.primary_wins
+        } else {
+            &stats.hedge_wins
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    result.map(|value| (value, !from_primary))
+}
+
+/// Races `generate` on the client's provider against the configured hedge.
+///
+/// A reply of the hedge reports it in [`Generation::served_by`], with the key
+/// of the hedge when it has its own.
+pub async fn hedged_generate(
+    stats: &HedgeStats,
+    hedge: &HedgeConfig,
+    provider: &dyn ModelProvider,
+    api_key: &str,
+    model: &str,
+    request: &GenerateRequest,
+) -> Result<Generation> {
+    let hedge_key = hedge.api_key.as_deref().unwrap_or(api_key);
+    let hedge_model = hedge.model.as_deref().unwrap_or(model);
+
+    let (mut generation, hedged) = race(
+        stats,
+        hedge.delay,
+        provider.generate(api_key, model, request),
+        || hedge.provider.generate(hedge_key, hedge_model, request),
+    )
+    .await?;
+
+    if hedged {
+        let served_by = generation.served_by.get_or_insert_with(|| ServedBy {
+            provider: hedge.provider.name().to_string(),
+            model: hedge_model.to_string(),
+            api_key: None,
+        });
+        if served_by.api_key.is_none() {
+            served_by.api_key = hedge.api_key.clone();
+        }
+    }
+    Ok(generation)
+}
+
+/// Races the first event of `generate_events` on the client's provider
+/// against the configured hedge, then continues with the winning stream,
+/// along with whether the hedge won.
+///
+/// A stream of the hedge reports it in the `served_by` of its closing
+/// [`StreamEvent::Done`], with the key of the hedge when it has its own.
+pub async fn hedged_events(
+    stats: &HedgeStats,
+    hedge: &HedgeConfig,
+    provider: &dyn ModelProvider,
+    api_key: &str,
+    model: &str,
+    request: &GenerateRequest,
+) -> Result<(EventStream, bool)> {
+    let hedge_key = hedge.api_key.as_deref().unwrap_or(api_key);
+    let hedge_model = hedge.model.as_deref().unwrap_or(model);
+
+    let ((first, rest), hedged) = race(
+        stats,
+        hedge.delay,
+        first_event(provider, api_key, model, request),
+        || first_event(hedge.provider.as_ref(), hedge_key, hedge_model, request),
+    )
+    .await?;
+
+    let provider = hedge.provider.name().to_string();
+    let (hedge_model, hedge_key) = (hedge_model.to_string(), hedge.api_key.clone());
+    let events = futures::stream::iter(first)
+        .chain(rest)
+        .map(move |event| match event {
+            Ok(StreamEvent::Done {
+                usage,
+                model_version,
+                served_by,
+            }) if hedged => {
+                let mut served_by = served_by.unwrap_or_else(|| ServedBy {
+                    provider: provider.clone(),
+                    model: hedge_model.clone(),
+                    api_key: None,
+                });
+                if served_by.api_key.is_none() {
+                    served_by.api_key = hedge_key.clone();
+                }
+                Ok(StreamEvent::Done {
+                    usage,
+                    model_version,
+                    served_by: Some(served_by),
+                })
+            }
+            other => other,
+        });
+    Ok((Box::pin(events), hedged))
+}
+
+// first_event:
+// opens a stream and waits for its first event, an error as first event fails the attempt
+async fn first_event(
+    provider: &dyn ModelProvider,
+    api_key: &str,
+    model: &str,
+    request: &GenerateRequest,
+) -> Result<(Option<Result<StreamEvent>>, EventStream)> {
+    let mut stream = provider.generate_events(api_key, model, request).await?;
+    match stream.next().await {
+        Some(Err(e)) => Err(e),
+        first => Ok((first, stream)),
+    }
+}
@@ -1,2 +1,6 @@
+pub mod endpoint;
+pub mod error;
+pub mod fallback;
 pub mod gemini;
+pub mod hedge;
 pub mod model_client;
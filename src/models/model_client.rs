@@ -1,13 +1,101 @@
-use crate::{model_llm::Models, traits::ModelProvider};
-use anyhow::Result;
+use crate::{
+    agent::tool::ToolSet,
+    cache::{
+        response::ResponseCache,
+        semantic::{SemanticCache, SemanticLookup},
+    },
+    model::{
+        batch::batch::{BatchJob, BatchRequest, BatchResult, BatchState, SubmittedBatch},
+        cached_content::cached_content::CachedContent,
+        conversation::conversation::{Conversation, Turn},
+        function::function::{FunctionCall, FunctionResponse},
+        generation::generation::{GenerateRequest, Generation, ServedBy, StreamEvent, Usage},
+        grounding::grounding::{BuiltinTool, CodePart, GroundingMetadata},
+        safety::safety::{SafetySetting, merge_settings},
+    },
+    model_llm::Models,
+    models::{
+        error::ProviderError,
+        hedge::{HedgeConfig, HedgeStats, hedged_events, hedged_generate},
+    },
+    prompt::registry::TemplateRegistry,
+    rag::{
+        context::{RagAnswer, question, with_context},
+        document::Filter,
+        retriever::Retriever,
+    },
+    traits::{EventStream, ModelProvider},
+    usage::{
+        budget::{BudgetManager, DEFAULT_TENANT, Reservation},
+        ledger::UsageLedger,
+    },
+    utils::{
+        coalesce::{Coalescer, fingerprint},
+        key_pool::{KeyPool, KeySelection},
+        rate_limit::{Permit, Priority, RateLimiterSet, RateLimits},
+        truncate::{TRUNCATION_TAG, TruncationPolicy, may_exceed, truncate},
+    },
+};
+use anyhow::{Result, anyhow};
+use futures::StreamExt;
 use serde_json::Value;
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Model calls [`GenerateWithTools`](ModelClient::GenerateWithTools) answers
+/// with tool results before giving up.
+pub const MAX_TOOL_ROUNDS: usize = 8;
+
+/// Time a prefix the provider refused to cache is sent inline before
+/// [`GenerateWithPrefix`](ModelClient::GenerateWithPrefix) asks again.
+pub const CONTEXT_CACHE_RETRY: Duration = Duration::from_secs(600);
+
+/// Provider side cache of a prefix sent with
+/// [`GenerateWithPrefix`](ModelClient::GenerateWithPrefix).
+#[derive(Clone, Debug)]
+pub enum ContextCache {
+    Cached(CachedContent),
+    /// The provider refused to cache the prefix, it is sent inline until then.
+    Refused {
+        until: Instant,
+    },
+}
+
+/// Entry of [`ModelClient::context_caches`], locked while its cache is
+/// created so a prefix is only cached once.
+pub type ContextCacheSlot = Arc<tokio::sync::Mutex<Option<ContextCache>>>;
 
 #[derive(Clone)]
 pub struct ModelClient {
     pub key: Arc<Mutex<String>>,
     pub model: Arc<Mutex<String>>,
     pub provider: Arc<dyn ModelProvider>,
+    pub truncation: Arc<Mutex<TruncationPolicy>>,
+    pub token_limit: Arc<Mutex<Option<u64>>>,
+    pub usage: Arc<UsageLedger>,
+    pub budgets: Arc<BudgetManager>,
+    pub limiters: Arc<RateLimiterSet>,
+    pub keys: Arc<KeyPool>,
+    pub hedging: Arc<Mutex<Option<HedgeConfig>>>,
+    pub hedge_stats: Arc<HedgeStats>,
+    pub coalescer: Arc<Coalescer>,
+    pub cache: Arc<Mutex<Option<Arc<ResponseCache>>>>,
+    pub semantic_cache: Arc<Mutex<Option<Arc<SemanticCache>>>>,
+    /// Provider side caches created by [`GenerateWithPrefix`](Self::GenerateWithPrefix),
+    /// by model and prefix.
+    pub context_caches: Arc<Mutex<HashMap<String, ContextCacheSlot>>>,
+    pub context_cache_ttl: Arc<Mutex<Duration>>,
+    pub safety_settings: Arc<Mutex<Vec<SafetySetting>>>,
+    pub builtin_tools: Arc<Mutex<Vec<BuiltinTool>>>,
+    /// Batch jobs submitted by this client whose results were not read yet.
+    pub batches: Arc<Mutex<HashMap<String, SubmittedBatch>>>,
+    pub templates: Arc<Mutex<Option<Arc<TemplateRegistry>>>>,
+    /// Functions declared and run by [`GenerateWithTools`](Self::GenerateWithTools) and agents.
+    pub tools: Arc<Mutex<ToolSet>>,
 }
 
 impl ModelClient {
@@ -16,32 +104,1357 @@ impl ModelClient {
             key: Arc::new(Mutex::new(String::new())),
             model: Arc::new(Mutex::new(String::new())),
             provider,
+            truncation: Arc::new(Mutex::new(TruncationPolicy::default())),
+            token_limit: Arc::new(Mutex::new(None)),
+            usage: Arc::new(UsageLedger::default()),
+            budgets: Arc::new(BudgetManager::new()),
+            limiters: Arc::new(RateLimiterSet::default()),
+            keys: Arc::new(KeyPool::default()),
+            hedging: Arc::new(Mutex::new(None)),
+            hedge_stats: Arc::new(HedgeStats::default()),
+            coalescer: Arc::new(Coalescer::default()),
+            cache: Arc::new(Mutex::new(None)),
+            semantic_cache: Arc::new(Mutex::new(None)),
+            context_caches: Arc::new(Mutex::new(HashMap::new())),
+            context_cache_ttl: Arc::new(Mutex::new(Duration::from_secs(3600))),
+            safety_settings: Arc::new(Mutex::new(Vec::new())),
+            builtin_tools: Arc::new(Mutex::new(Vec::new())),
+            batches: Arc::new(Mutex::new(HashMap::new())),
+            templates: Arc::new(Mutex::new(None)),
+            tools: Arc::new(Mutex::new(ToolSet::new())),
         }
     }
 
     pub fn init(&self, api_key: String, model_name: Models) -> Self {
         *self.key.lock().unwrap() = api_key;
+        *self.model.lock().unwrap() = model_name.name().to_string();
+        self.clone()
+    }
 
-        let match_model = match model_name {
-            Models::Gemini25Flash => "gemini-2.5-flash".to_string(),
-            Models::Gemini25Pro => "gemini-2.5-pro".to_string(),
-            Models::Gemini25FlashLite => "gemini-2.5-flash-lite".to_string(),
-            Models::Gemini3ProPreview => "gemini-3-pro-preview".to_string(),
-        };
+    // set_truncation:
+    // policy applied when a conversation exceeds the model input window
+    pub fn set_truncation(&self, policy: TruncationPolicy) -> Self {
+        *self.truncation.lock().unwrap() = policy;
+        self.clone()
+    }
 
-        *self.model.lock().unwrap() = match_model;
+    // set_token_limit:
+    // overrides the input window of the configured model, `None` restores the default
+    pub fn set_token_limit(&self, limit: Option<u64>) -> Self {
+        *self.token_limit.lock().unwrap() = limit;
         self.clone()
     }
 
+    // add_key:
+    // adds a key to the pool; once the pool has keys it is used instead of the init() key
+    pub fn add_key(&self, api_key: impl Into<String>, weight: u32) -> Self {
+        self.keys.add(api_key, weight);
+        self.clone()
+    }
+
+    // set_key_selection:
+    // strategy used to pick a key of the pool
+    pub fn set_key_selection(&self, selection: KeySelection) -> Self {
+        self.keys.set_selection(selection);
+        self.clone()
+    }
+
+    // set_hedging:
+    // races a second request when the first one is slower than the hedge delay,
+    // `None` turns hedging off
+    pub fn set_hedging(&self, hedge: Option<HedgeConfig>) -> Self {
+        *self.hedging.lock().unwrap() = hedge;
+        self.clone()
+    }
+
+    // set_coalescing:
+    // merges concurrent identical requests with temperature 0 into one upstream call
+    pub fn set_coalescing(&self, enabled: bool) -> Self {
+        self.coalescer.set_enabled(enabled);
+        self.clone()
+    }
+
+    // set_cache:
+    // response cache consulted before going upstream, `None` turns caching off
+    pub fn set_cache(&self, cache: Option<ResponseCache>) -> Self {
+        *self.cache.lock().unwrap() = cache.map(Arc::new);
+        self.clone()
+    }
+
+    // set_semantic_cache:
+    // answers prompts similar to a previous one, consulted after the response cache
+    pub fn set_semantic_cache(&self, cache: Option<SemanticCache>) -> Self {
+        *self.semantic_cache.lock().unwrap() = cache.map(Arc::new);
+        self.clone()
+    }
+
+    // set_context_cache_ttl:
+    // lifetime of the caches created by GenerateWithPrefix (one hour by default)
+    pub fn set_context_cache_ttl(&self, ttl: Duration) -> Self {
+        *self.context_cache_ttl.lock().unwrap() = ttl;
+        self.clone()
+    }
+
+    // set_safety_settings:
+    // default safety thresholds, a request's own settings win per category
+    pub fn set_safety_settings(&self, settings: Vec<SafetySetting>) -> Self {
+        *self.safety_settings.lock().unwrap() = settings;
+        self.clone()
+    }
+
+    // set_builtin_tools:
+    // provider side tools (Google Search, url context, code execution) enabled
+    // on every request, in addition to the request's own
+    pub fn set_builtin_tools(&self, tools: Vec<BuiltinTool>) -> Self {
+        *self.builtin_tools.lock().unwrap() = tools;
+        self.clone()
+    }
+
+    // set_templates:
+    // prompt templates rendered by GenerateTemplate
+    pub fn set_templates(&self, templates: Option<Arc<TemplateRegistry>>) -> Self {
+        *self.templates.lock().unwrap() = templates;
+        self.clone()
+    }
+
+    // set_tools:
+    // functions (e.g. the tools of MCP servers) declared by GenerateWithTools
+    // and agents, the request's own declarations win on name clashes; plain
+    // Generate calls never declare them
+    pub fn set_tools(&self, tools: ToolSet) -> Self {
+        *self.tools.lock().unwrap() = tools;
+        self.clone()
+    }
+
+    // set_rate_limits:
+    // local RPM / TPM / in-flight limits for `model`, or for every model when `None`
+    pub fn set_rate_limits(&self, model: Option<&str>, limits: Option<RateLimits>) -> Self {
+        self.limiters.set(model, limits);
+        self.clone()
+    }
+
+    // input_token_limit:
+    // explicit override first, then the known limit of the configured model
+    pub fn input_token_limit(&self) -> Option<u64> {
+        let limit = *self.token_limit.lock().unwrap();
+        limit.or_else(|| {
+            Models::from_name(&self.model.lock().unwrap()).map(|m| m.input_token_limit())
+        })
+    }
+
     pub async fn GenerateContent(&self, prompt: String) -> Result<String> {
-        let key = self.key.lock().unwrap().clone();
-        let model = self.model.lock().unwrap().clone();
-        self.provider.generate_text(&key, &model, prompt).await
+        let generation = self.Generate(GenerateRequest::from_prompt(prompt)).await?;
+        Ok(generation.text)
     }
 
+    /// Blocking counterpart of [`GenerateContent`](Self::GenerateContent),
+    /// returning the raw message of the provider.
+    ///
+    /// The blocking path sends the bare prompt, so client defaults such as
+    /// safety settings cannot be applied: a client with safety settings
+    /// configured is rejected rather than generating with the provider's
+    /// thresholds.
     pub fn GenerateSyncContent(&self, prompt: String) -> Result<Value> {
-        let key = self.key.lock().unwrap().clone().to_string();
+        if !self.safety_settings.lock().unwrap().is_empty() {
+            return Err(anyhow!(
+                "GenerateSyncContent cannot apply the client safety settings, use GenerateContent"
+            ));
+        }
         let model = self.model.lock().unwrap().clone().to_string();
-        self.provider.generate_without_async(key, model, prompt)
+        let conversation = Conversation::from_prompt(prompt.clone());
+        let admission = self.admit(DEFAULT_TENANT, &model, &conversation)?;
+
+        let mut permit = self.limiters.get(&model).map(|limiter| {
+            limiter.acquire_blocking(Priority::default(), conversation.estimate_tokens())
+        });
+        let (message, key) = self.with_key_blocking(|key| {
+            self.provider
+                .generate_without_async(key, model.clone(), prompt.clone())
+        })?;
+
+        let usage: Option<Usage> = serde_json::from_value(message["usage"].clone()).ok();
+        settle_permit(permit.as_mut(), usage.as_ref());
+        self.settle(&key, &model, None, admission, usage.as_ref(), &conversation);
+        Ok(message)
+    }
+
+    /// Counts the tokens `conversation` uses with the configured model.
+    pub async fn CountTokens(&self, conversation: &Conversation) -> Result<u64> {
+        let model = &self.model.lock().unwrap().clone();
+        let (count, _) = self
+            .with_key(
+                |key| async move { self.provider.count_tokens(&key, model, conversation).await },
+            )
+            .await?;
+        Ok(count)
+    }
+
+    /// Caches `prefix` on the provider for `ttl` with the configured model.
+    ///
+    /// Cached contents belong to the project of the API key; with a key pool
+    /// all keys should come from the same project.
+    pub async fn CreateCachedContent(
+        &self,
+        prefix: &Conversation,
+        ttl: Duration,
+    ) -> Result<CachedContent> {
+        let model = &self.model.lock().unwrap().clone();
+        let (content, _) = self
+            .with_key(|key| async move {
+                self.provider
+                    .create_cached_content(&key, model, prefix, ttl)
+                    .await
+            })
+            .await?;
+        Ok(content)
+    }
+
+    pub async fn ListCachedContents(&self) -> Result<Vec<CachedContent>> {
+        let (contents, _) = self
+            .with_key(|key| async move { self.provider.list_cached_contents(&key).await })
+            .await?;
+        Ok(contents)
+    }
+
+    pub async fn UpdateCachedContentTtl(&self, name: &str, ttl: Duration) -> Result<CachedContent> {
+        let (content, _) = self
+            .with_key(|key| async move {
+                self.provider
+                    .update_cached_content_ttl(&key, name, ttl)
+                    .await
+            })
+            .await?;
+
+        for slot in self.context_cache_slots() {
+            if let Some(ContextCache::Cached(cached)) = &mut *slot.lock().await
+                && cached.name == content.name
+            {
+                *cached = content.clone();
+            }
+        }
+        Ok(content)
+    }
+
+    pub async fn DeleteCachedContent(&self, name: &str) -> Result<()> {
+        self.with_key(|key| async move { self.provider.delete_cached_content(&key, name).await })
+            .await?;
+        for slot in self.context_cache_slots() {
+            let mut slot = slot.lock().await;
+            if matches!(&*slot, Some(ContextCache::Cached(cached)) if cached.name == name) {
+                *slot = None;
+            }
+        }
+        Ok(())
+    }
+
+    // context_cache_slots:
+    // every slot of `context_caches`, cloned out of the map lock
+    fn context_cache_slots(&self) -> Vec<ContextCacheSlot> {
+        self.context_caches
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Submits `requests` as a Gemini batch job on the configured model.
+    ///
+    /// Batch jobs run asynchronously (usually within hours, at most 24h) at
+    /// half the price of interactive requests; poll them with
+    /// [`WaitBatch`](Self::WaitBatch) and read the results with
+    /// [`BatchResults`](Self::BatchResults), under the ids of `requests`.
+    ///
+    /// Client defaults (safety settings, built-in tools) are applied and the
+    /// tenant budgets are checked with the estimated prompt size of the whole
+    /// job. Truncation is not applied. Batches belong to the project of the
+    /// key, with a key pool all keys should come from the same project.
+    ///
+    /// # Usage
+    /// ```rust,no_run
+    /// # use std::time::Duration;
+    /// # use ey_ai::{model::{batch::batch::BatchRequest, generation::generation::GenerateRequest}, model_llm::{ModelLLM, Models}, utils::select_model::selector};
+    /// # async fn run() -> anyhow::Result<()> {
+    /// let client = selector(ModelLLM::Gemini).init("YOUR_API_KEY".to_string(), Models::Gemini25Flash);
+    /// let requests = vec![
+    ///     BatchRequest::new("q1", GenerateRequest::from_prompt("Summarize ticket 1")),
+    ///     BatchRequest::new("q2", GenerateRequest::from_prompt("Summarize ticket 2")),
+    /// ];
+    /// let job = client.SubmitBatch("nightly-summaries", requests).await?;
+    /// client.WaitBatch(&job.name, Duration::from_secs(60), Duration::from_secs(24 * 3600)).await?;
+    /// for result in client.BatchResults(&job.name).await? {
+    ///     println!("{}: {:?}", result.id, result.generation.map(|g| g.text));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn SubmitBatch(
+        &self,
+        display_name: &str,
+        requests: Vec<BatchRequest>,
+    ) -> Result<BatchJob> {
+        let model = &self.model.lock().unwrap().clone();
+        let mut ids = HashSet::new();
+        if let Some(duplicate) = requests.iter().find(|r| !ids.insert(r.id.as_str())) {
+            return Err(anyhow!("Duplicate batch request id {}", duplicate.id));
+        }
+
+        let requests: Vec<BatchRequest> = requests
+            .into_iter()
+            .map(|r| BatchRequest::new(r.id, self.with_defaults(r.request)))
+            .collect();
+
+        let mut tokens: HashMap<&str, u64> = HashMap::new();
+        for r in &requests {
+            let tenant = r.request.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+            *tokens.entry(tenant).or_default() += r.request.conversation.estimate_tokens();
+        }
+        let pricing = self.usage.pricing();
+        let mut admissions = Vec::new();
+        for (tenant, tokens) in tokens {
+            let prompt = Usage {
+                prompt_tokens: tokens,
+                ..Default::default()
+            };
+            let reservation =
+                self.budgets
+                    .check(tenant, tokens, pricing.batch_cost(model, &prompt))?;
+            admissions.push(Admission {
+                budgets: self.budgets.clone(),
+                reservation: Some(reservation),
+            });
+        }
+
+        let requests = &requests;
+        let (job, key) = self
+            .with_key(|key| async move {
+                self.provider
+                    .create_batch(&key, model, display_name, requests)
+                    .await
+            })
+            .await?;
+
+        let origins = requests
+            .iter()
+            .map(|r| {
+                let tenant = r.request.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+                (r.id.clone(), (r.request.tag.clone(), tenant.to_string()))
+            })
+            .collect();
+        self.batches.lock().unwrap().insert(
+            job.name.clone(),
+            SubmittedBatch {
+                model: model.clone(),
+                api_key: key,
+                origins,
+                reservations: admissions
+                    .iter_mut()
+                    .filter_map(|a| a.reservation.take())
+                    .collect(),
+            },
+        );
+        Ok(job)
+    }
+
+    /// The job `name`; a job submitted by this client that failed, was
+    /// cancelled or expired gives back its budget reservations.
+    pub async fn GetBatch(&self, name: &str) -> Result<BatchJob> {
+        let job = self
+            .with_batch_key(name, |key| async move {
+                self.provider.get_batch(&key, name).await
+            })
+            .await?;
+        if matches!(job.state, BatchState::Failed | BatchState::Cancelled | BatchState::Expired) {
+            self.release_batch(name);
+        }
+        Ok(job)
+    }
+
+    pub async fn ListBatches(&self) -> Result<Vec<BatchJob>> {
+        let (jobs, _) = self
+            .with_key(|key| async move { self.provider.list_batches(&key).await })
+            .await?;
+        Ok(jobs)
+    }
+
+    /// Polls the job every `poll_interval` until it succeeded, failed, was
+    /// cancelled or expired, giving up with an error after `timeout`; the job
+    /// itself keeps running.
+    pub async fn WaitBatch(
+        &self,
+        name: &str,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<BatchJob> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let job = self.GetBatch(name).await?;
+            if job.state.is_terminal() {
+                return Ok(job);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return Err(anyhow!(
+                    "Batch {} is still {:?} after {:?}",
+                    name,
+                    job.state,
+                    timeout
+                ));
+            }
+            tokio::time::sleep(poll_interval.min(left)).await;
+        }
+    }
+
+    pub async fn CancelBatch(&self, name: &str) -> Result<()> {
+        self.with_batch_key(name, |key| async move {
+            self.provider.cancel_batch(&key, name).await
+        })
+        .await?;
+        self.release_batch(name);
+        Ok(())
+    }
+
+    pub async fn DeleteBatch(&self, name: &str) -> Result<()> {
+        self.with_batch_key(name, |key| async move {
+            self.provider.delete_batch(&key, name).await
+        })
+        .await?;
+        self.release_batch(name);
+        self.batches.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    // with_batch_key:
+    // runs `call` with the key a job of this client was submitted with, the
+    // job belongs to the project of that key; other jobs go through with_key
+    async fn with_batch_key<T, F, Fut>(&self, name: &str, call: F) -> Result<T>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let submitted = self
+            .batches
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|b| b.api_key.clone());
+        match submitted {
+            Some(key) => call(key).await,
+            None => self.with_key(call).await.map(|(value, _)| value),
+        }
+    }
+
+    // release_batch:
+    // gives back the budget reservations of a job submitted by this client
+    fn release_batch(&self, name: &str) {
+        let reservations = match self.batches.lock().unwrap().get_mut(name) {
+            Some(submitted) => std::mem::take(&mut submitted.reservations),
+            None => return,
+        };
+        for reservation in reservations {
+            self.budgets.release(reservation);
+        }
+    }
+
+    /// Results of a succeeded batch job, one per request.
+    ///
+    /// The first time the results of a job submitted by this client are read
+    /// their usage is recorded, at the batch price, under the tag and tenant
+    /// of each request.
+    pub async fn BatchResults(&self, name: &str) -> Result<Vec<BatchResult>> {
+        let results = self
+            .with_batch_key(name, |key| async move {
+                self.provider.batch_results(&key, name).await
+            })
+            .await?;
+
+        let submitted = self.batches.lock().unwrap().remove(name);
+        if let Some(submitted) = submitted {
+            for reservation in submitted.reservations {
+                self.budgets.release(reservation);
+            }
+            for result in &results {
+                let (tag, tenant) = submitted
+                    .origins
+                    .get(&result.id)
+                    .cloned()
+                    .unwrap_or((None, DEFAULT_TENANT.to_string()));
+                let usage = result.generation.as_ref().and_then(|g| g.usage.as_ref());
+                let cost = self.usage.record_batch(
+                    &submitted.api_key,
+                    &submitted.model,
+                    tag.as_deref(),
+                    usage,
+                );
+                self.budgets
+                    .record(&tenant, usage.map(|u| u.total_tokens).unwrap_or(0), cost);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Generates a reply to `request` continuing a large shared `prefix`
+    /// (documents, long system prompt).
+    ///
+    /// The prefix is cached on the provider the first time it is seen and the
+    /// cache is reused, and recreated once expired, for the following calls;
+    /// only `request` is sent each time. Prefixes the provider refuses to cache
+    /// (too small, or no context caching) are sent inline instead, and offered
+    /// again after [`CONTEXT_CACHE_RETRY`].
+    ///
+    /// The system prompt belongs in `prefix`: with a cached prefix the system
+    /// prompt of `request` is ignored by Gemini.
+    pub async fn GenerateWithPrefix(
+        &self,
+        prefix: &Conversation,
+        request: GenerateRequest,
+    ) -> Result<Generation> {
+        let cached = self.context_cache(prefix, false).await?;
+        let Some(name) = cached else {
+            return self.Generate(with_prefix(prefix, request)).await;
+        };
+
+        match self.Generate(request.clone().cached_content(name)).await {
+            // the cache may have been deleted or expired on the provider side
+            Err(e) if matches!(classify_status(&e), Some(403 | 404)) => {
+                match self.context_cache(prefix, true).await? {
+                    Some(name) => self.Generate(request.cached_content(name)).await,
+                    None => self.Generate(with_prefix(prefix, request)).await,
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Generates a reply to a multi-turn conversation.
+    ///
+    /// See [`Generate`](Self::Generate) for truncation and usage accounting.
+    pub async fn GenerateConversation(&self, conversation: Conversation) -> Result<String> {
+        let generation = self.Generate(GenerateRequest::new(conversation)).await?;
+        Ok(generation.text)
+    }
+
+    /// Generates a reply to `request`, returning the text along with its usage.
+    ///
+    /// When the configured model has a known input limit, the conversation is
+    /// first truncated according to the client's [`TruncationPolicy`]. The
+    /// request is then checked against the tenant's budget and rejected with a
+    /// [`BudgetError`](crate::usage::budget::BudgetError) before any upstream
+    /// call if it is over a hard limit. When rate limits are configured the
+    /// request waits in the limiter queue for its turn. With a key pool, a
+    /// request failing with 429 / 401 / 403 is retried with the next key. The
+    /// reported usage is recorded in the client's [`UsageLedger`] under the
+    /// request's tag.
+    ///
+    /// With hedging enabled (see [`set_hedging`](Self::set_hedging)) a second
+    /// request is raced against the first one once the hedge delay passes.
+    ///
+    /// With coalescing enabled (see [`set_coalescing`](Self::set_coalescing))
+    /// a temperature 0 request identical to one already in flight waits for
+    /// that call instead of going upstream; only the upstream call is charged
+    /// to the usage ledger and rate limits.
+    ///
+    /// With a response cache (see [`set_cache`](Self::set_cache)) a cached
+    /// reply is returned without any upstream call, budget or rate limit; a
+    /// semantic cache (see [`set_semantic_cache`](Self::set_semantic_cache))
+    /// is consulted next and also answers prompts close to a previous one.
+    pub async fn Generate(&self, request: GenerateRequest) -> Result<Generation> {
+        let model = self.model.lock().unwrap().clone();
+        let request = self.with_defaults(request);
+        let (hit, fill) = self.lookup_caches(&model, &request).await;
+        if let Some(generation) = hit {
+            return Ok(generation);
+        }
+
+        let request = self.prepare(&model, request).await?;
+        let tenant = request.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+        let admission = self.admit(tenant, &model, &request.conversation)?;
+
+        // a request joining one in flight drops its admission unused
+        let generation = if self.coalescer.accepts(&request) {
+            let key = fingerprint(self.provider.name(), &model, &request);
+            let client = self.clone();
+            let model = model.clone();
+            self.coalescer
+                .generate(key, async move {
+                    client.dispatch(model, request, admission).await
+                })
+                .await?
+        } else {
+            self.dispatch(model.clone(), request, admission).await?
+        };
+
+        if let Some(fill) = fill {
+            fill.store(&generation);
+        }
+        Ok(generation)
+    }
+
+    // dispatch:
+    // sends an admitted request upstream and settles its usage; a failed
+    // request releases its admission
+    async fn dispatch(
+        &self,
+        model: String,
+        request: GenerateRequest,
+        admission: Admission,
+    ) -> Result<Generation> {
+        let mut permit = self.throttle(&model, &request).await;
+        let hedge = self.hedging.lock().unwrap().clone();
+        let (generation, key) = self
+            .with_key_answered(|key| {
+                let (model, request, hedge) = (&model, &request, &hedge);
+                async move {
+                    let generation = match hedge {
+                        Some(hedge) => {
+                            hedged_generate(
+                                &self.hedge_stats,
+                                hedge,
+                                self.provider.as_ref(),
+                                &key,
+                                model,
+                                request,
+                            )
+                            .await?
+                        }
+                        None => self.provider.generate(&key, model, request).await?,
+                    };
+                    let answered = generation
+                        .served_by
+                        .as_ref()
+                        .is_none_or(|s| s.api_key.is_none());
+                    Ok((generation, answered))
+                }
+            })
+            .await?;
+        settle_permit(permit.as_mut(), generation.usage.as_ref());
+        // a fallback chain may have answered with another model and key
+        let served_by = generation.served_by.as_ref();
+        let served_model = served_by.map(|s| s.model.as_str());
+        let served_key = served_by.and_then(|s| s.api_key.as_deref());
+        self.settle(
+            served_key.unwrap_or(&key),
+            served_model.unwrap_or(&model),
+            request.tag.as_deref(),
+            admission,
+            generation.usage.as_ref(),
+            &request.conversation,
+        );
+        Ok(generation)
+    }
+
+    /// Streams a reply to `request`.
+    ///
+    /// Budgets and rate limits apply as in [`Generate`](Self::Generate); the
+    /// rate limiter slot is held until the stream is dropped. The stream
+    /// ends with a [`StreamEvent::Done`] carrying the usage of the request,
+    /// which is recorded in the usage ledger once it is reached; a stream
+    /// failing or dropped before is charged its estimated prompt size. Coalesced
+    /// streams are fanned out to every subscriber. A cached reply is replayed
+    /// as a single text chunk.
+    pub async fn GenerateStream(&self, request: GenerateRequest) -> Result<EventStream> {
+        let model = self.model.lock().unwrap().clone();
+        let request = self.with_defaults(request);
+        let (hit, fill) = self.lookup_caches(&model, &request).await;
+        if let Some(generation) = hit {
+            return Ok(replay(generation));
+        }
+
+        let request = self.prepare(&model, request).await?;
+        let tenant = request.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+        let admission = self.admit(tenant, &model, &request.conversation)?;
+
+        let stream = if self.coalescer.accepts(&request) {
+            let key = fingerprint(self.provider.name(), &model, &request);
+            let client = self.clone();
+            let model = model.clone();
+            self.coalescer
+                .events(key, async move {
+                    client.dispatch_events(model, request, admission).await
+                })
+                .await?
+        } else {
+            self.dispatch_events(model.clone(), request, admission)
+                .await?
+        };
+
+        match fill {
+            Some(fill) => Ok(store_stream(stream, fill)),
+            None => Ok(stream),
+        }
+    }
+
+    // dispatch_events:
+    // streaming counterpart of dispatch, usage is settled when the stream
+    // reaches `Done`, fails or is dropped
+    async fn dispatch_events(
+        &self,
+        model: String,
+        request: GenerateRequest,
+        admission: Admission,
+    ) -> Result<EventStream> {
+        // the permit lives as long as the stream so in-flight limits cover it
+        let permit = self.throttle(&model, &request).await;
+        let hedge = self.hedging.lock().unwrap().clone();
+        let (stream, key) = self
+            .with_key_answered(|key| {
+                let (model, request, hedge) = (&model, &request, &hedge);
+                async move {
+                    match hedge {
+                        Some(hedge) => {
+                            let (stream, hedged) = hedged_events(
+                                &self.hedge_stats,
+                                hedge,
+                                self.provider.as_ref(),
+                                &key,
+                                model,
+                                request,
+                            )
+                            .await?;
+                            Ok((stream, !hedged || hedge.api_key.is_none()))
+                        }
+                        None => {
+                            let stream =
+                                self.provider.generate_events(&key, model, request).await?;
+                            Ok((stream, true))
+                        }
+                    }
+                }
+            })
+            .await?;
+
+        let mut charge = StreamCharge {
+            client: self.clone(),
+            key,
+            model,
+            request,
+            permit,
+            admission: Some(admission),
+        };
+        let recorded = stream.inspect(move |event| match event {
+            Ok(StreamEvent::Done {
+                usage, served_by, ..
+            }) => charge.settle(usage.as_ref(), served_by.as_ref()),
+            Err(_) => charge.settle(None, None),
+            Ok(_) => {}
+        });
+        Ok(Box::pin(recorded))
+    }
+
+    /// Renders prompt template `id` (`name@version`, or `name` for the latest
+    /// version) with `vars` and generates a reply to it, as
+    /// [`Generate`](Self::Generate) does. The templates come from
+    /// [`set_templates`](Self::set_templates).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use ey_ai::{models::model_client::ModelClient, prompt::registry::TemplateRegistry};
+    /// # use serde_json::json;
+    /// # async fn run(client: ModelClient) -> anyhow::Result<()> {
+    /// let client = client.set_templates(Some(Arc::new(TemplateRegistry::load("prompts")?)));
+    /// let reply = client
+    ///     .GenerateTemplate("support/answer@v3", json!({"customer": "Ada", "tickets": []}))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn GenerateTemplate(&self, id: &str, vars: Value) -> Result<Generation> {
+        let templates = self
+            .templates
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow!("No prompt templates configured"))?;
+        let request = templates.request(id, &vars)?;
+        self.Generate(request).await
+    }
+
+    /// Generates a reply to `request` with the client's tools (see
+    /// [`set_tools`](Self::set_tools)) declared, running the functions the
+    /// model calls until it answers, for at most [`MAX_TOOL_ROUNDS`] rounds.
+    ///
+    /// The returned generation is the final answer, with the usage of all
+    /// rounds. Failed tools are reported to the model as errors. For traces,
+    /// memory and limits use an [`Agent`](crate::agent::agent::Agent).
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ey_ai::{mcp::client::McpClient, model::generation::generation::GenerateRequest, models::model_client::ModelClient};
+    /// # async fn run(client: ModelClient) -> anyhow::Result<()> {
+    /// let files = McpClient::stdio("mcp-server-filesystem", &["/srv/docs"]).await?;
+    /// let client = client.set_tools(files.tool_set().await?);
+    /// let reply = client
+    ///     .GenerateWithTools(GenerateRequest::from_prompt("What is in /srv/docs/README.md?"))
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn GenerateWithTools(&self, mut request: GenerateRequest) -> Result<Generation> {
+        let tools = self.tools.lock().unwrap().clone();
+        for function in tools.declarations() {
+            if !request.functions.iter().any(|f| f.name == function.name) {
+                request = request.function(function);
+            }
+        }
+        let mut usage: Option<Usage> = None;
+        for _ in 0..=MAX_TOOL_ROUNDS {
+            let mut reply = self.Generate(request.clone()).await?;
+            if let Some(reply_usage) = &reply.usage {
+                usage.get_or_insert_default().add(reply_usage);
+            }
+            if reply.function_calls.is_empty() {
+                reply.usage = usage;
+                return Ok(reply);
+            }
+
+            let calls = reply.function_calls;
+            let results =
+                futures::future::join_all(calls.iter().map(|call| tools.call(call))).await;
+            let responses = calls
+                .iter()
+                .zip(results)
+                .map(|(call, result)| match result {
+                    Ok(result) => FunctionResponse::new(call, result),
+                    Err(e) => FunctionResponse::error(call, e.to_string()),
+                })
+                .collect();
+            request
+                .conversation
+                .turns
+                .push(Turn::function_calls(reply.text, calls));
+            request
+                .conversation
+                .turns
+                .push(Turn::function_responses(responses));
+        }
+        Err(anyhow!(
+            "Model kept calling functions after {} rounds",
+            MAX_TOOL_ROUNDS
+        ))
+    }
+
+    /// Answers the last user turn of `request` from retrieved context: the
+    /// `k` best chunks of `retriever` accepted by `filter` are added to the
+    /// prompt as numbered sources the model is asked to cite as `[n]`.
+    ///
+    /// The answer keeps the sources, see [`RagAnswer::cited_sources`]. When
+    /// nothing is retrieved the model is still asked, and told to say when
+    /// it cannot answer.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ey_ai::{model::generation::generation::GenerateRequest, models::model_client::ModelClient, rag::{document::Filter, retriever::VectorRetriever}};
+    /// # async fn run(client: ModelClient, docs: VectorRetriever) -> anyhow::Result<()> {
+    /// let answer = client
+    ///     .GenerateWithContext(
+    ///         GenerateRequest::from_prompt("How do I rotate API keys?"),
+    ///         &docs,
+    ///         4,
+    ///         &Filter::new().eq("product", "cli"),
+    ///     )
+    ///     .await?;
+    /// println!("{}", answer.text());
+    /// for source in answer.cited_sources() {
+    ///     println!("- {}", source.chunk.source());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn GenerateWithContext(
+        &self,
+        request: GenerateRequest,
+        retriever: &dyn Retriever,
+        k: usize,
+        filter: &Filter,
+    ) -> Result<RagAnswer> {
+        let query = question(&request)
+            .ok_or_else(|| anyhow!("The request has no question to retrieve context for"))?;
+        let sources = retriever.retrieve(query, k, filter).await?;
+        let generation = self.Generate(with_context(request, &sources)).await?;
+        Ok(RagAnswer {
+            generation,
+            sources,
+        })
+    }
+
+    /// Embeds `texts`, in order, with the embedding `model` (e.g.
+    /// `"gemini-embedding-001"`) through the key pool of the client.
+    ///
+    /// The tenant budget is checked and the usage ledger records the request
+    /// under `tag`; embedding endpoints report no usage, so the estimated size
+    /// of the texts is charged.
+    pub async fn Embed(
+        &self,
+        model: &str,
+        texts: &[String],
+        tag: Option<&str>,
+        tenant: Option<&str>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let conversation = Conversation {
+            system: None,
+            turns: texts.iter().map(Turn::user).collect(),
+        };
+        let tenant = tenant.unwrap_or(DEFAULT_TENANT);
+        let admission = self.admit(tenant, model, &conversation)?;
+        let (vectors, key) = self
+            .with_key(|key| async move { self.provider.embed(&key, model, texts).await })
+            .await?;
+
+        let tokens = conversation.estimate_tokens();
+        let usage = Usage {
+            prompt_tokens: tokens,
+            total_tokens: tokens,
+            ..Default::default()
+        };
+        self.settle(&key, model, tag, admission, Some(&usage), &conversation);
+        Ok(vectors)
+    }
+
+    // with_defaults:
+    // applies the client's safety settings under the request's own, its
+    // built-in tools and the functions of its tools
+    fn with_defaults(&self, mut request: GenerateRequest) -> GenerateRequest {
+        let defaults = self.safety_settings.lock().unwrap();
+        if !defaults.is_empty() {
+            request.safety_settings = merge_settings(&defaults, &request.safety_settings);
+        }
+        for tool in self.builtin_tools.lock().unwrap().iter() {
+            request = request.tool(*tool);
+        }
+        request
+    }
+
+    // context_cache:
+    // name of the provider cache holding `prefix`, created when missing,
+    // expiring within a minute or when `renew` is set; `None` when the prefix
+    // cannot be cached. Only callers of the same prefix wait for its creation
+    async fn context_cache(&self, prefix: &Conversation, renew: bool) -> Result<Option<String>> {
+        let model = self.model.lock().unwrap().clone();
+        let ttl = *self.context_cache_ttl.lock().unwrap();
+        let fingerprint = serde_json::json!([model, prefix]).to_string();
+
+        let slot = self
+            .context_caches
+            .lock()
+            .unwrap()
+            .entry(fingerprint)
+            .or_default()
+            .clone();
+        let mut slot = slot.lock().await;
+        match &*slot {
+            Some(ContextCache::Refused { until }) if Instant::now() < *until => return Ok(None),
+            Some(ContextCache::Cached(cached))
+                if !renew && cached.is_valid_for(Duration::from_secs(60)) =>
+            {
+                return Ok(Some(cached.name.clone()));
+            }
+            _ => {}
+        }
+
+        let cache = match self.CreateCachedContent(prefix, ttl).await {
+            Ok(created) => ContextCache::Cached(created),
+            // refused rather than failed: too small (400) or no context caching
+            Err(e) => match ProviderError::classify(&e) {
+                Some(error) if error.status() != Some(400) => return Err(e),
+                _ => ContextCache::Refused {
+                    until: Instant::now() + CONTEXT_CACHE_RETRY,
+                },
+            },
+        };
+        let name = match &cache {
+            ContextCache::Cached(cached) => Some(cached.name.clone()),
+            ContextCache::Refused { .. } => None,
+        };
+        *slot = Some(cache);
+        Ok(name)
+    }
+
+    // lookup_caches:
+    // checks the response cache then the semantic cache; on a miss returns
+    // what is needed to store the reply once it arrives
+    async fn lookup_caches(
+        &self,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> (Option<Generation>, Option<CacheFill>) {
+        let response = self.cache.lock().unwrap().clone();
+        let semantic = self.semantic_cache.lock().unwrap().clone();
+        if response.is_none() && semantic.is_none() {
+            return (None, None);
+        }
+
+        if let Some(cache) = &response
+            && let Some(generation) = cache.get(model, request)
+        {
+            return (Some(generation), None);
+        }
+        let semantic = match semantic {
+            Some(cache) => match cache.lookup(model, request).await {
+                SemanticLookup::Hit(found) => {
+                    // keep the exact cache warm for the next identical prompt
+                    if let Some(cache) = &response {
+                        cache.put(model, request, &found.generation);
+                    }
+                    return (Some(found.generation), None);
+                }
+                SemanticLookup::Miss(embedding) => Some((cache, embedding)),
+                SemanticLookup::Skipped => None,
+            },
+            None => None,
+        };
+
+        let fill = CacheFill {
+            response,
+            semantic,
+            model: model.to_string(),
+            request: request.clone(),
+        };
+        (None, Some(fill))
+    }
+
+    // throttle:
+    // waits for the rate limiter of `model`, if one is configured
+    async fn throttle(&self, model: &str, request: &GenerateRequest) -> Option<Permit> {
+        let limiter = self.limiters.get(model)?;
+        let tokens = request.conversation.estimate_tokens();
+        Some(limiter.acquire(request.priority, tokens).await)
+    }
+
+    // admit:
+    // checks the tenant budget with the estimated prompt size before going
+    // upstream, reserving it until the request is settled
+    fn admit(&self, tenant: &str, model: &str, conversation: &Conversation) -> Result<Admission> {
+        let tokens = conversation.estimate_tokens();
+        let prompt = Usage {
+            prompt_tokens: tokens,
+            ..Default::default()
+        };
+        let cost = self.usage.estimate_cost(model, &prompt);
+        let reservation = self.budgets.check(tenant, tokens, cost)?;
+        Ok(Admission {
+            budgets: self.budgets.clone(),
+            reservation: Some(reservation),
+        })
+    }
+
+    // settle:
+    // records a finished request in the usage ledger and the tenant budget,
+    // falling back to the prompt estimate when the provider reports no usage
+    fn settle(
+        &self,
+        key: &str,
+        model: &str,
+        tag: Option<&str>,
+        mut admission: Admission,
+        usage: Option<&Usage>,
+        conversation: &Conversation,
+    ) {
+        let cost = self.usage.record(key, model, tag, usage);
+        let tokens = usage
+            .map(|u| u.total_tokens)
+            .unwrap_or_else(|| conversation.estimate_tokens());
+        if let Some(reservation) = admission.reservation.take() {
+            self.budgets.settle(reservation, tokens, cost);
+        }
+    }
+
+    // prepare:
+    // applies the truncation policy before a request is sent; no key is
+    // taken from the pool when the conversation fits for sure
+    async fn prepare(&self, model: &str, mut request: GenerateRequest) -> Result<GenerateRequest> {
+        let policy = *self.truncation.lock().unwrap();
+        if let Some(limit) = self.input_token_limit()
+            && may_exceed(&request.conversation, limit, policy)
+        {
+            let conversation = &request.conversation;
+            let summarize = |summary: GenerateRequest| {
+                self.summarize(model, request.tenant.clone(), summary)
+            };
+            let (truncated, _) = self
+                .with_key(|key| async move {
+                    truncate(
+                        self.provider.as_ref(),
+                        &key,
+                        model,
+                        conversation.clone(),
+                        limit,
+                        policy,
+                        summarize,
+                    )
+                    .await
+                })
+                .await?;
+            request.conversation = truncated;
+        }
+        Ok(request)
+    }
+
+    // summarize:
+    // writes a truncation summary through the metered path, booked under
+    // TRUNCATION_TAG for the tenant of the truncated request
+    async fn summarize(
+        &self,
+        model: &str,
+        tenant: Option<String>,
+        mut request: GenerateRequest,
+    ) -> Result<String> {
+        request.tag = Some(TRUNCATION_TAG.to_string());
+        request.tenant = tenant;
+        let tenant = request.tenant.as_deref().unwrap_or(DEFAULT_TENANT);
+        let admission = self.admit(tenant, model, &request.conversation)?;
+        let generation = self.dispatch(model.to_string(), request, admission).await?;
+        Ok(generation.text)
+    }
+
+    // with_key:
+    // runs `call` with a key of the pool, moving on to the next key while
+    // failures take keys out of rotation; without pool the key given to init() is used
+    async fn with_key<T, F, Fut>(&self, call: F) -> Result<(T, String)>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.with_key_answered(|key| {
+            let call = call(key);
+            async move { call.await.map(|value| (value, true)) }
+        })
+        .await
+    }
+
+    // with_key_answered:
+    // with_key for calls telling whether the key they were given answered;
+    // a reply billed to another key (fallback target, hedge) leaves the pool
+    // key unreported
+    async fn with_key_answered<T, F, Fut>(&self, call: F) -> Result<(T, String)>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<(T, bool)>>,
+    {
+        if self.keys.is_empty() {
+            let key = self.key.lock().unwrap().clone();
+            return call(key.clone()).await.map(|(value, _)| (value, key));
+        }
+
+        loop {
+            // in flight until dropped, also when `call` is cancelled
+            let lease = self.keys.select()?;
+            let key = lease.key().to_string();
+            match call(key.clone()).await {
+                Ok((value, answered)) => {
+                    if answered {
+                        self.keys.report(&key, Ok(()));
+                    }
+                    return Ok((value, key));
+                }
+                Err(e) => {
+                    if !self.keys.report(&key, Err(&e)) || !self.keys.has_available() {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    // with_key_blocking:
+    // blocking counterpart of with_key
+    fn with_key_blocking<T>(&self, call: impl Fn(String) -> Result<T>) -> Result<(T, String)> {
+        if self.keys.is_empty() {
+            let key = self.key.lock().unwrap().clone();
+            return call(key.clone()).map(|value| (value, key));
+        }
+
+        loop {
+            let lease = self.keys.select()?;
+            let key = lease.key().to_string();
+            match call(key.clone()) {
+                Ok(value) => {
+                    self.keys.report(&key, Ok(()));
+                    return Ok((value, key));
+                }
+                Err(e) => {
+                    if !self.keys.report(&key, Err(&e)) || !self.keys.has_available() {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Admission:
+// budget estimate reserved for an admitted request, given back when it is
+// dropped before the request was settled
+struct Admission {
+    budgets: Arc<BudgetManager>,
+    reservation: Option<Reservation>,
+}
+
+impl Drop for Admission {
+    fn drop(&mut self) {
+        if let Some(reservation) = self.reservation.take() {
+            self.budgets.release(reservation);
+        }
+    }
+}
+
+// StreamCharge:
+// settles a stream once, with the usage of its `Done` event or, when the
+// stream fails or is dropped before, with the prompt estimate
+struct StreamCharge {
+    client: ModelClient,
+    key: String,
+    model: String,
+    request: GenerateRequest,
+    permit: Option<Permit>,
+    admission: Option<Admission>,
+}
+
+impl StreamCharge {
+    fn settle(&mut self, usage: Option<&Usage>, served_by: Option<&ServedBy>) {
+        let Some(admission) = self.admission.take() else {
+            return;
+        };
+        settle_permit(self.permit.as_mut(), usage);
+        // a fallback chain may have answered with another model and key
+        let model = served_by.map_or(self.model.as_str(), |s| s.model.as_str());
+        let key = served_by.and_then(|s| s.api_key.as_deref());
+        self.client.settle(
+            key.unwrap_or(&self.key),
+            model,
+            self.request.tag.as_deref(),
+            admission,
+            usage,
+            &self.request.conversation,
+        );
+    }
+}
+
+impl Drop for StreamCharge {
+    fn drop(&mut self) {
+        self.settle(None, None);
+    }
+}
+
+// settle_permit:
+// charges the actual token count of a finished request to the rate limiter
+fn settle_permit(permit: Option<&mut Permit>, usage: Option<&Usage>) {
+    if let (Some(permit), Some(usage)) = (permit, usage) {
+        permit.settle(usage.total_tokens);
+    }
+}
+
+// classify_status:
+// HTTP status of a provider error, if any
+fn classify_status(error: &anyhow::Error) -> Option<u16> {
+    ProviderError::classify(error).and_then(|e| e.status())
+}
+
+// with_prefix:
+// sends a prefix inline, ahead of the turns of `request`
+fn with_prefix(prefix: &Conversation, mut request: GenerateRequest) -> GenerateRequest {
+    let mut turns = prefix.turns.clone();
+    turns.append(&mut request.conversation.turns);
+    request.conversation.turns = turns;
+    if request.conversation.system.is_none() {
+        request.conversation.system = prefix.system.clone();
+    }
+    request
+}
+
+// replay:
+// turns a cached reply into a stream of its thoughts, one text chunk, its code
+// parts, function calls, grounding and `Done`
+fn replay(generation: Generation) -> EventStream {
+    let mut events: Vec<StreamEvent> = Vec::new();
+    if let Some(text) = generation.thoughts {
+        events.push(StreamEvent::Thought { text });
+    }
+    events.push(StreamEvent::Text {
+        text: generation.text,
+    });
+    events.extend(
+        generation
+            .code
+            .into_iter()
+            .map(|part| StreamEvent::Code { part }),
+    );
+    events.extend(
+        generation
+            .function_calls
+            .into_iter()
+            .map(|call| StreamEvent::FunctionCall { call }),
+    );
+    if let Some(grounding) = generation.grounding {
+        events.push(StreamEvent::Grounding { grounding });
+    }
+    events.push(StreamEvent::Done {
+        usage: generation.usage,
+        model_version: generation.model_version,
+        served_by: generation.served_by,
+    });
+    Box::pin(futures::stream::iter(events.into_iter().map(Ok)))
+}
+
+// store_stream:
+// collects the text of a stream and caches the reply once `Done` is reached;
+// a stream that failed on the way is never cached
+fn store_stream(stream: EventStream, fill: CacheFill) -> EventStream {
+    let mut failed = false;
+    let mut text = String::new();
+    let mut thoughts: Option<String> = None;
+    let mut code: Vec<CodePart> = Vec::new();
+    let mut grounding: Option<GroundingMetadata> = None;
+    let mut function_calls: Vec<FunctionCall> = Vec::new();
+    let stored = stream.inspect(move |event| match event {
+        Ok(StreamEvent::Text { text: chunk }) => text.push_str(chunk),
+        Ok(StreamEvent::Thought { text: chunk }) => {
+            thoughts.get_or_insert_default().push_str(chunk)
+        }
+        Ok(StreamEvent::Code { part }) => code.push(part.clone()),
+        Ok(StreamEvent::FunctionCall { call }) => function_calls.push(call.clone()),
+        Ok(StreamEvent::Grounding {
+            grounding: metadata,
+        }) => grounding = Some(metadata.clone()),
+        Ok(StreamEvent::Done { .. }) if failed => {}
+        Ok(StreamEvent::Done {
+            usage,
+            model_version,
+            served_by,
+        }) => {
+            let generation = Generation {
+                text: std::mem::take(&mut text),
+                thoughts: thoughts.take(),
+                code: std::mem::take(&mut code),
+                grounding: grounding.take(),
+                function_calls: std::mem::take(&mut function_calls),
+                usage: *usage,
+                model_version: model_version.clone(),
+                served_by: served_by.clone(),
+                ..Default::default()
+            };
+            fill.store(&generation);
+        }
+        Err(_) => failed = true,
+    });
+    Box::pin(stored)
+}
+
+// caches to fill with the reply of a request that missed them
+struct CacheFill {
+    response: Option<Arc<ResponseCache>>,
+    semantic: Option<(Arc<SemanticCache>, Vec<f32>)>,
+    model: String,
+    request: GenerateRequest,
+}
+
+impl CacheFill {
+    fn store(&self, generation: &Generation) {
+        if let Some(cache) = &self.response {
+            cache.put(&self.model, &self.request, generation);
+        }
+        if let Some((cache, embedding)) = &self.semantic {
+            cache.store(&self.model, &self.request, embedding.clone(), generation);
+        }
     }
 }
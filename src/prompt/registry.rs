@@ -0,0 +1,258 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+
+use crate::{
+    model::{conversation::conversation::Conversation, generation::generation::GenerateRequest},
+    prompt::template::{Template, compare_versions, split_id},
+};
+
+/// Extension of template files.
+pub const TEMPLATE_EXTENSION: &str = "prompt";
+
+// Templates:
+// every version of every template, keyed by name
+type Templates = HashMap<String, Vec<Arc<Template>>>;
+
+/// A set of named, versioned [`Template`]s.
+///
+/// Loaded from a directory where each `.prompt` file is a template named
+/// after its path: `support/answer@v3.prompt` is version `v3` of
+/// `support/answer`. A template is referenced as `name@version`, or by its
+/// bare name for the latest version (`v10` is newer than `v9`).
+///
+/// [`reload`](Self::reload) re-reads the directory and [`watch`](Self::watch)
+/// does so whenever a file changes; a directory with an invalid template is
+/// rejected as a whole and the previous templates stay in use.
+///
+/// # Example
+/// ```no_run
+/// # use std::{sync::Arc, time::Duration};
+/// # use ey_ai::prompt::registry::TemplateRegistry;
+/// # use serde_json::json;
+/// # async fn run() -> anyhow::Result<()> {
+/// let templates = Arc::new(TemplateRegistry::load("prompts")?);
+/// templates.watch(Duration::from_secs(2));
+///
+/// let conversation = templates.render(
+///     "support/answer@v3",
+///     &json!({"customer": "Ada", "tickets": [{"subject": "Refund"}]}),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct TemplateRegistry {
+    dir: Option<PathBuf>,
+    templates: Mutex<Templates>,
+    // modification times of the loaded files, compared by `watch`
+    stamps: Mutex<Vec<(PathBuf, Option<SystemTime>)>>,
+}
+
+impl TemplateRegistry {
+    /// Empty registry for templates added with [`add`](Self::add).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every template under `dir`.
+    pub fn load(dir: impl Into<PathBuf>) -> Result<Self> {
+        let registry = Self {
+            dir: Some(dir.into()),
+            ..Self::default()
+        };
+        registry.reload()?;
+        Ok(registry)
+    }
+
+    /// Parses `source` and adds it as template `id` (`name@version`),
+    /// replacing a template with the same id.
+    pub fn add(&self, id: &str, source: &str) -> Result<()> {
+        let template = Template::parse(id, source)?;
+        insert(&mut self.templates.lock().unwrap(), template);
+        Ok(())
+    }
+
+    /// Re-reads the template directory and returns the number of templates
+    /// loaded. Templates added with [`add`](Self::add) are dropped.
+    pub fn reload(&self) -> Result<usize> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| anyhow!("Template registry has no directory"))?;
+        let files = template_files(dir)?;
+
+        let mut templates = Templates::new();
+        let mut stamps = Vec::new();
+        for path in files {
+            let source = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("Failed to read template {}: {}", path.display(), e))?;
+            insert(
+                &mut templates,
+                Template::parse(&template_id(dir, &path), &source)?,
+            );
+            stamps.push((path.clone(), modified(&path)));
+        }
+
+        let count = templates.values().map(Vec::len).sum();
+        *self.templates.lock().unwrap() = templates;
+        *self.stamps.lock().unwrap() = stamps;
+        Ok(count)
+    }
+
+    /// Checks the directory every `interval` and reloads it when a template
+    /// was added, removed or modified. The task stops once the registry is
+    /// dropped; reload errors are reported on stderr.
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let registry: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(registry) = registry.upgrade() else {
+                    return;
+                };
+                if registry.changed()
+                    && let Err(e) = registry.reload()
+                {
+                    eprintln!("Failed to reload templates: {}", e);
+                    // don't retry until the files change again
+                    if let Some(dir) = &registry.dir
+                        && let Ok(files) = template_files(dir)
+                    {
+                        *registry.stamps.lock().unwrap() = files
+                            .into_iter()
+                            .map(|p| (p.clone(), modified(&p)))
+                            .collect();
+                    }
+                }
+            }
+        })
+    }
+
+    // changed:
+    // whether the files of the directory differ from the ones last loaded
+    fn changed(&self) -> bool {
+        let Some(dir) = &self.dir else {
+            return false;
+        };
+        let Ok(files) = template_files(dir) else {
+            return false;
+        };
+        let stamps = self.stamps.lock().unwrap();
+        files.len() != stamps.len()
+            || files
+                .iter()
+                .zip(stamps.iter())
+                .any(|(path, (seen, time))| path != seen || modified(path) != *time)
+    }
+
+    /// Template `id`: `name@version`, or `name` for its latest version.
+    pub fn get(&self, id: &str) -> Result<Arc<Template>> {
+        let (name, version) = split_id(id);
+        let templates = self.templates.lock().unwrap();
+        let versions = templates
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown template {}", name))?;
+        match version {
+            Some(version) => versions
+                .iter()
+                .find(|t| t.version.as_deref() == Some(version))
+                .cloned()
+                .ok_or_else(|| anyhow!("Unknown template version {}", id)),
+            None => versions
+                .last()
+                .cloned()
+                .ok_or_else(|| anyhow!("Unknown template {}", name)),
+        }
+    }
+
+    /// Names of the templates, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.templates.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Versions of template `name`, oldest first.
+    pub fn versions(&self, name: &str) -> Vec<String> {
+        self.templates
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|versions| versions.iter().filter_map(|t| t.version.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Renders template `id` with `vars` into a conversation. Partials are
+    /// resolved against this registry at render time.
+    pub fn render(&self, id: &str, vars: &Value) -> Result<Conversation> {
+        self.get(id)?.render(vars, &|name| self.get(name))
+    }
+
+    /// Renders template `id` with `vars` into plain text, for templates
+    /// without sections.
+    pub fn render_text(&self, id: &str, vars: &Value) -> Result<String> {
+        self.get(id)?.render_text(vars, &|name| self.get(name))
+    }
+
+    /// Renders template `id` into a request, ready for further options.
+    pub fn request(&self, id: &str, vars: &Value) -> Result<GenerateRequest> {
+        Ok(GenerateRequest::new(self.render(id, vars)?))
+    }
+}
+
+// insert:
+// adds a template, keeping the versions of a name sorted oldest first
+fn insert(templates: &mut Templates, template: Template) {
+    let versions = templates.entry(template.name.clone()).or_default();
+    versions.retain(|t| t.version != template.version);
+    versions.push(Arc::new(template));
+    versions.sort_by(|a, b| match (&a.version, &b.version) {
+        (Some(a), Some(b)) => compare_versions(a, b),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    });
+}
+
+// template_files:
+// `.prompt` files under `dir`, sorted by path
+fn template_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|e| anyhow!("Failed to read template directory {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|e| e == TEMPLATE_EXTENSION) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// template_id:
+// `support/answer@v3` for `<dir>/support/answer@v3.prompt`
+fn template_id(dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(dir).unwrap_or(path).with_extension("");
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
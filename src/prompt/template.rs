@@ -0,0 +1,654 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::model::conversation::conversation::{Conversation, Turn};
+
+/// Type of a template variable.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VarType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+    Any,
+}
+
+impl VarType {
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            VarType::String => value.is_string(),
+            VarType::Number => value.is_number(),
+            VarType::Integer => value.is_i64() || value.is_u64(),
+            VarType::Boolean => value.is_boolean(),
+            VarType::Array => value.is_array(),
+            VarType::Object => value.is_object(),
+            VarType::Any => true,
+        }
+    }
+}
+
+impl std::str::FromStr for VarType {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "string" => Ok(VarType::String),
+            "number" => Ok(VarType::Number),
+            "integer" => Ok(VarType::Integer),
+            "boolean" => Ok(VarType::Boolean),
+            "array" => Ok(VarType::Array),
+            "object" => Ok(VarType::Object),
+            "any" => Ok(VarType::Any),
+            _ => Err(anyhow!("Unknown variable type {}", name)),
+        }
+    }
+}
+
+/// A variable declared in the header of a template.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub var_type: VarType,
+    pub required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+}
+
+// Block:
+// kinds of `{{#...}}` blocks
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Block {
+    If,
+    Unless,
+    Each,
+    Section(SectionRole),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SectionRole {
+    System,
+    User,
+    Model,
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Text(String),
+    Var(String),
+    Open(Block, String),
+    Close(String),
+    Else,
+    Partial(String),
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Var(String),
+    If {
+        path: String,
+        negate: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Each {
+        path: String,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    Section(SectionRole, Vec<Node>),
+    Partial(String),
+}
+
+/// A parsed prompt template.
+///
+/// Templates start with an optional header declaring their variables, one
+/// `name: type` per line, `?` marking optional ones and `= value` giving a
+/// JSON default. The body uses a Handlebars-like syntax:
+///
+/// - `{{ customer.name }}` inserts a value (objects and arrays as JSON),
+/// - `{{#if vip}} ... {{else}} ... {{/if}}` and `{{#unless ...}}`,
+/// - `{{#each tickets}} {{@index}}: {{title}} {{/each}}`, with `this`,
+///   `@index`, `@first` and `@last` inside the loop,
+/// - `{{> shared/signature}}` includes another template of the registry,
+/// - `{{! comment }}`,
+/// - `{{#system}}`, `{{#user}}` and `{{#model}}` sections become the system
+///   prompt and the turns of the rendered [`Conversation`].
+///
+/// Without sections the whole text is a single user turn. Block tags alone
+/// on their line don't leave an empty line behind.
+///
+/// ```text
+/// ---
+/// customer: string
+/// tickets: array
+/// tone: string = "friendly"
+/// ---
+/// {{#system}}You answer support tickets in a {{tone}} tone.{{/system}}
+/// {{#user}}
+/// Customer: {{customer}}
+/// {{#each tickets}}
+/// - {{subject}}
+/// {{/each}}
+/// {{/user}}
+/// ```
+#[derive(Clone, Debug)]
+pub struct Template {
+    pub name: String,
+    pub version: Option<String>,
+    pub variables: Vec<Variable>,
+    nodes: Vec<Node>,
+}
+
+/// Resolves the partials included by a template.
+pub type PartialLoader<'a> = &'a dyn Fn(&str) -> Result<Arc<Template>>;
+
+// MAX_PARTIAL_DEPTH:
+// guards against partials including each other
+const MAX_PARTIAL_DEPTH: usize = 16;
+
+impl Template {
+    /// Parses `source`; `id` is the template name, optionally followed by
+    /// `@version`.
+    pub fn parse(id: &str, source: &str) -> Result<Self> {
+        let (name, version) = split_id(id);
+        let (variables, body) = parse_header(source).map_err(|e| anyhow!("{}: {}", id, e))?;
+        let mut tokens = tokenize(body)
+            .map_err(|e| anyhow!("{}: {}", id, e))?
+            .into_iter();
+        let (nodes, end) = parse_nodes(&mut tokens, false).map_err(|e| anyhow!("{}: {}", id, e))?;
+        if let Some(end) = end {
+            return Err(anyhow!("{}: unexpected {{{{{}}}}}", id, end));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            version: version.map(str::to_string),
+            variables,
+            nodes,
+        })
+    }
+
+    /// `name@version`, or the bare name of an unversioned template.
+    pub fn id(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+
+    /// Checks `vars` against the declared variables and fills in defaults.
+    ///
+    /// Variables that are not declared are passed through untouched.
+    pub fn bind(&self, vars: &Value) -> Result<Value> {
+        let mut bound = match vars {
+            Value::Object(object) => object.clone(),
+            Value::Null => Map::new(),
+            _ => return Err(anyhow!("{}: variables must be an object", self.id())),
+        };
+        for variable in &self.variables {
+            match bound.get(&variable.name) {
+                Some(value) if !variable.var_type.matches(value) => {
+                    return Err(anyhow!(
+                        "{}: variable {} must be {:?}, got {}",
+                        self.id(),
+                        variable.name,
+                        variable.var_type,
+                        value
+                    ));
+                }
+                Some(_) => {}
+                None => match &variable.default {
+                    Some(default) => {
+                        bound.insert(variable.name.clone(), default.clone());
+                    }
+                    None if variable.required => {
+                        return Err(anyhow!("{}: missing variable {}", self.id(), variable.name));
+                    }
+                    None => {}
+                },
+            }
+        }
+        Ok(Value::Object(bound))
+    }
+
+    /// Renders the template with `vars` into the text of its body, sections
+    /// included verbatim.
+    pub fn render_text(&self, vars: &Value, partials: PartialLoader) -> Result<String> {
+        let vars = self.bind(vars)?;
+        let mut out = Output::default();
+        self.render_into(&mut out, &mut vec![Frame::root(&vars)], partials, 0)?;
+        if out.has_sections {
+            return Err(anyhow!("{}: sections can't be rendered as text", self.id()));
+        }
+        Ok(out.loose)
+    }
+
+    /// Renders the template with `vars` into a conversation.
+    pub fn render(&self, vars: &Value, partials: PartialLoader) -> Result<Conversation> {
+        let vars = self.bind(vars)?;
+        let mut out = Output::default();
+        self.render_into(&mut out, &mut vec![Frame::root(&vars)], partials, 0)?;
+
+        if !out.has_sections {
+            return Ok(Conversation::from_prompt(out.loose.trim()));
+        }
+        if !out.loose.trim().is_empty() {
+            return Err(anyhow!(
+                "{}: text outside of a system, user or model section",
+                self.id()
+            ));
+        }
+        let mut conversation = Conversation::new();
+        if !out.system.is_empty() {
+            conversation.system = Some(out.system.join("\n\n"));
+        }
+        conversation.turns = out.turns;
+        if conversation.turns.is_empty() {
+            return Err(anyhow!("{}: rendered without any turn", self.id()));
+        }
+        Ok(conversation)
+    }
+
+    fn render_into(
+        &self,
+        out: &mut Output,
+        scope: &mut Vec<Frame>,
+        partials: PartialLoader,
+        depth: usize,
+    ) -> Result<()> {
+        render_nodes(&self.nodes, out, scope, partials, depth)
+            .map_err(|e| anyhow!("{}: {}", self.id(), e))
+    }
+}
+
+/// Splits `name@version` into its parts.
+pub fn split_id(id: &str) -> (&str, Option<&str>) {
+    match id.rsplit_once('@') {
+        Some((name, version)) if !version.is_empty() => (name, Some(version)),
+        _ => (id, None),
+    }
+}
+
+/// Orders version identifiers by their numbers, so `v10` comes after `v9`;
+/// identifiers without numbers compare as text.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let numbers = |version: &str| -> Vec<u64> {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|part| !part.is_empty())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    };
+    numbers(a).cmp(&numbers(b)).then_with(|| a.cmp(b))
+}
+
+// parse_header:
+// variable declarations between `---` lines at the top, and the body after them
+fn parse_header(source: &str) -> Result<(Vec<Variable>, &str)> {
+    let Some(rest) = source
+        .strip_prefix("---\n")
+        .or_else(|| source.strip_prefix("---\r\n"))
+    else {
+        return Ok((Vec::new(), source));
+    };
+    let (header, body) = rest
+        .split_once("\n---\n")
+        .or_else(|| rest.split_once("\r\n---\r\n"))
+        .or_else(|| rest.strip_suffix("\n---").map(|header| (header, "")))
+        .ok_or_else(|| anyhow!("unterminated header"))?;
+
+    let mut variables = Vec::new();
+    for line in header.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, declaration) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid variable declaration {:?}", line))?;
+        let (var_type, default) = match declaration.split_once('=') {
+            Some((var_type, default)) => {
+                let default: Value = serde_json::from_str(default.trim())
+                    .map_err(|e| anyhow!("invalid default of {}: {}", name.trim(), e))?;
+                (var_type.trim(), Some(default))
+            }
+            None => (declaration.trim(), None),
+        };
+        let (var_type, optional) = match var_type.strip_suffix('?') {
+            Some(var_type) => (var_type, true),
+            None => (var_type, false),
+        };
+        variables.push(Variable {
+            name: name.trim().to_string(),
+            var_type: var_type.trim().parse()?,
+            required: !optional && default.is_none(),
+            default,
+        });
+    }
+    Ok((variables, body))
+}
+
+// tokenize:
+// splits the body into text and tags, dropping block tags standing alone on
+// their line together with the line break
+fn tokenize(body: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut rest = body;
+    // whether `rest` starts at the beginning of a line
+    let mut line_start = true;
+
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .map(|end| start + end)
+            .ok_or_else(|| anyhow!("unclosed {{{{"))?;
+        let tag = rest[start + 2..end].trim();
+        let mut before = &rest[..start];
+        let mut after = &rest[end + 2..];
+
+        let indent = match before.rfind('\n') {
+            Some(i) => Some(&before[i + 1..]),
+            None => line_start.then_some(before),
+        };
+        let line_end = after.find('\n');
+        let standalone = (tag.starts_with(['#', '/', '>', '!']) || tag == "else")
+            && indent.is_some_and(|indent| indent.trim().is_empty())
+            && after[..line_end.unwrap_or(after.len())].trim().is_empty();
+        if standalone {
+            before = &before[..before.len() - indent.map_or(0, str::len)];
+            after = &after[line_end.map_or(after.len(), |i| i + 1)..];
+        }
+        text.push_str(before);
+        rest = after;
+        line_start = standalone;
+
+        let token = if tag.starts_with('!') {
+            continue;
+        } else if let Some(open) = tag.strip_prefix('#') {
+            let (kind, arg) = open.split_once(char::is_whitespace).unwrap_or((open, ""));
+            let block = match kind {
+                "if" => Block::If,
+                "unless" => Block::Unless,
+                "each" => Block::Each,
+                "system" => Block::Section(SectionRole::System),
+                "user" => Block::Section(SectionRole::User),
+                "model" => Block::Section(SectionRole::Model),
+                _ => return Err(anyhow!("unknown block {{{{#{}}}}}", kind)),
+            };
+            let arg = arg.trim();
+            let needs_arg = matches!(block, Block::If | Block::Unless | Block::Each);
+            if needs_arg == arg.is_empty() {
+                return Err(anyhow!("invalid tag {{{{{}}}}}", tag));
+            }
+            Token::Open(block, arg.to_string())
+        } else if let Some(close) = tag.strip_prefix('/') {
+            Token::Close(close.trim().to_string())
+        } else if let Some(partial) = tag.strip_prefix('>') {
+            Token::Partial(partial.trim().to_string())
+        } else if tag == "else" {
+            Token::Else
+        } else if tag.is_empty() {
+            return Err(anyhow!("empty tag"));
+        } else {
+            Token::Var(tag.to_string())
+        };
+        if !text.is_empty() {
+            tokens.push(Token::Text(std::mem::take(&mut text)));
+        }
+        tokens.push(token);
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        tokens.push(Token::Text(text));
+    }
+    Ok(tokens)
+}
+
+// parse_nodes:
+// nodes up to the next `{{else}}` or closing tag, which is returned
+fn parse_nodes(
+    tokens: &mut impl Iterator<Item = Token>,
+    in_section: bool,
+) -> Result<(Vec<Node>, Option<String>)> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        let node = match token {
+            Token::Text(text) => Node::Text(text),
+            Token::Var(path) => Node::Var(path),
+            Token::Partial(name) => Node::Partial(name),
+            Token::Else => return Ok((nodes, Some("else".to_string()))),
+            Token::Close(kind) => return Ok((nodes, Some(format!("/{}", kind)))),
+            Token::Open(Block::Section(role), _) => {
+                if in_section {
+                    return Err(anyhow!("sections can't be nested"));
+                }
+                let name = section_name(role);
+                let (body, end) = parse_nodes(tokens, true)?;
+                expect_close(end, name)?;
+                Node::Section(role, body)
+            }
+            Token::Open(block, path) => {
+                let name = match block {
+                    Block::If => "if",
+                    Block::Unless => "unless",
+                    _ => "each",
+                };
+                let (then, end) = parse_nodes(tokens, in_section)?;
+                let (otherwise, end) = match end.as_deref() {
+                    Some("else") => parse_nodes(tokens, in_section)?,
+                    _ => (Vec::new(), end),
+                };
+                expect_close(end, name)?;
+                match block {
+                    Block::Each => Node::Each {
+                        path,
+                        body: then,
+                        otherwise,
+                    },
+                    _ => Node::If {
+                        path,
+                        negate: block == Block::Unless,
+                        then,
+                        otherwise,
+                    },
+                }
+            }
+        };
+        nodes.push(node);
+    }
+    Ok((nodes, None))
+}
+
+fn expect_close(end: Option<String>, name: &str) -> Result<()> {
+    match end {
+        Some(end) if end == format!("/{}", name) => Ok(()),
+        Some(end) => Err(anyhow!("expected {{{{/{}}}}}, found {{{{{}}}}}", name, end)),
+        None => Err(anyhow!("unclosed {{{{#{}}}}}", name)),
+    }
+}
+
+fn section_name(role: SectionRole) -> &'static str {
+    match role {
+        SectionRole::System => "system",
+        SectionRole::User => "user",
+        SectionRole::Model => "model",
+    }
+}
+
+// Frame:
+// one level of variable lookup, the root variables or the item of a loop
+struct Frame {
+    value: Value,
+    index: Option<(usize, usize)>,
+}
+
+impl Frame {
+    fn root(vars: &Value) -> Self {
+        Self {
+            value: vars.clone(),
+            index: None,
+        }
+    }
+}
+
+// Output:
+// rendered text, split into the sections it was written in
+#[derive(Default)]
+struct Output {
+    loose: String,
+    system: Vec<String>,
+    turns: Vec<Turn>,
+    has_sections: bool,
+    section: Option<String>,
+}
+
+impl Output {
+    fn push(&mut self, text: &str) {
+        match &mut self.section {
+            Some(section) => section.push_str(text),
+            None => self.loose.push_str(text),
+        }
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    out: &mut Output,
+    scope: &mut Vec<Frame>,
+    partials: PartialLoader,
+    depth: usize,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push(text),
+            Node::Var(path) => {
+                let value =
+                    lookup(scope, path).ok_or_else(|| anyhow!("undefined variable {}", path))?;
+                out.push(&display(&value));
+            }
+            Node::If {
+                path,
+                negate,
+                then,
+                otherwise,
+            } => {
+                let truthy = lookup(scope, path).is_some_and(|value| truthy(&value));
+                let branch = if truthy != *negate { then } else { otherwise };
+                render_nodes(branch, out, scope, partials, depth)?;
+            }
+            Node::Each {
+                path,
+                body,
+                otherwise,
+            } => {
+                let items = match lookup(scope, path) {
+                    Some(Value::Array(items)) => items,
+                    Some(Value::Null) | None => Vec::new(),
+                    Some(other) => return Err(anyhow!("{} is not an array: {}", path, other)),
+                };
+                if items.is_empty() {
+                    render_nodes(otherwise, out, scope, partials, depth)?;
+                }
+                let count = items.len();
+                for (i, item) in items.into_iter().enumerate() {
+                    scope.push(Frame {
+                        value: item,
+                        index: Some((i, count)),
+                    });
+                    let rendered = render_nodes(body, out, scope, partials, depth);
+                    scope.pop();
+                    rendered?;
+                }
+            }
+            Node::Section(role, body) => {
+                out.has_sections = true;
+                out.section = Some(String::new());
+                render_nodes(body, out, scope, partials, depth)?;
+                let text = out.section.take().unwrap_or_default();
+                let text = text.trim();
+                // sections emptied by conditionals are dropped
+                if !text.is_empty() {
+                    match role {
+                        SectionRole::System => out.system.push(text.to_string()),
+                        SectionRole::User => out.turns.push(Turn::user(text)),
+                        SectionRole::Model => out.turns.push(Turn::model(text)),
+                    }
+                }
+            }
+            Node::Partial(name) => {
+                if depth >= MAX_PARTIAL_DEPTH {
+                    return Err(anyhow!("partials nested too deep at {}", name));
+                }
+                let partial = partials(name)?;
+                let in_section = out.section.is_some();
+                let opens_section = partial
+                    .nodes
+                    .iter()
+                    .any(|node| matches!(node, Node::Section(..)));
+                if in_section && opens_section {
+                    return Err(anyhow!("partial {} opens a section inside a section", name));
+                }
+                partial.render_into(out, scope, partials, depth + 1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// lookup:
+// resolves a dotted path from the innermost frame outwards
+fn lookup(scope: &[Frame], path: &str) -> Option<Value> {
+    let frame = scope.last()?;
+    match path {
+        "this" | "." => return Some(frame.value.clone()),
+        "@index" => return frame.index.map(|(i, _)| Value::from(i)),
+        "@first" => return frame.index.map(|(i, _)| Value::Bool(i == 0)),
+        "@last" => return frame.index.map(|(i, count)| Value::Bool(i + 1 == count)),
+        _ => {}
+    }
+
+    let (head, rest) = match path.strip_prefix("this.") {
+        Some(rest) => (&frame.value, rest),
+        None => {
+            let first = path.split('.').next().unwrap_or(path);
+            let frame = scope
+                .iter()
+                .rev()
+                .find(|frame| frame.value.get(first).is_some())?;
+            (&frame.value, path)
+        }
+    };
+    rest.split('.')
+        .try_fold(head, |value, key| match value {
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => value.get(key),
+        })
+        .cloned()
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(object) => !object.is_empty(),
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::rag::{
+    document::{Chunk, Filter},
+    retriever::{Retrieved, Retriever},
+};
+
+/// Splits `text` into lowercase search terms.
+///
+/// Identifiers joined by `-`, `_`, `.` or `/` (error codes, SKUs, paths) are
+/// kept whole as well as split into their parts, so `AX-4411` matches
+/// `ax-4411` exactly and `4411` alone.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split(|c: char| !(c.is_alphanumeric() || "-_./".contains(c))) {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if word.is_empty() {
+            continue;
+        }
+        let word = word.to_lowercase();
+        let parts: Vec<&str> = word
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|p| !p.is_empty())
+            .collect();
+        if parts.len() > 1 {
+            terms.push(word.clone());
+        }
+        terms.extend(parts.into_iter().map(str::to_string));
+    }
+    terms
+}
+
+// IndexedChunk:
+// a chunk with its length in terms
+struct IndexedChunk {
+    chunk: Chunk,
+    length: usize,
+}
+
+/// In-process BM25 inverted index over chunks, for exact keyword matches
+/// (error codes, SKUs, names) that embeddings tend to blur.
+///
+/// Scores are Okapi BM25 with `k1 = 1.2` and `b = 0.75` by default; they are
+/// not comparable with cosine similarities, fuse both with
+/// [`reciprocal_rank_fusion`](crate::rag::hybrid::reciprocal_rank_fusion).
+pub struct Bm25Index {
+    k1: f32,
+    b: f32,
+    chunks: HashMap<String, IndexedChunk>,
+    /// term -> chunk id -> term frequency
+    postings: HashMap<String, HashMap<String, u32>>,
+    total_length: usize,
+}
+
+impl Default for Bm25Index {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bm25Index {
+    pub fn new() -> Self {
+        Self {
+            k1: 1.2,
+            b: 0.75,
+            chunks: HashMap::new(),
+            postings: HashMap::new(),
+            total_length: 0,
+        }
+    }
+
+    /// Sets the term frequency saturation `k1` and the length normalization `b`.
+    pub fn params(mut self, k1: f32, b: f32) -> Self {
+        self.k1 = k1.max(0.0);
+        self.b = b.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn from_chunks(chunks: impl IntoIterator<Item = Chunk>) -> Self {
+        let mut index = Self::new();
+        for chunk in chunks {
+            index.add(chunk);
+        }
+        index
+    }
+
+    /// Indexes `chunk`, replacing a chunk with the same id.
+    pub fn add(&mut self, chunk: Chunk) {
+        self.remove(&chunk.id.clone());
+        let terms = tokenize(&chunk.text);
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for term in &terms {
+            *frequencies.entry(term.clone()).or_default() += 1;
+        }
+        for (term, frequency) in frequencies {
+            self.postings
+                .entry(term)
+                .or_default()
+                .insert(chunk.id.clone(), frequency);
+        }
+        self.total_length += terms.len();
+        self.chunks.insert(
+            chunk.id.clone(),
+            IndexedChunk {
+                chunk,
+                length: terms.len(),
+            },
+        );
+    }
+
+    /// Removes chunk `id`, returning whether it was indexed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let Some(removed) = self.chunks.remove(id) else {
+            return false;
+        };
+        self.total_length -= removed.length;
+        for term in tokenize(&removed.chunk.text) {
+            if let Some(posting) = self.postings.get_mut(&term) {
+                posting.remove(id);
+                if posting.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        true
+    }
+
+    /// Removes the chunks of document `document_id` and returns how many there were.
+    pub fn remove_document(&mut self, document_id: &str) -> usize {
+        let ids: Vec<String> = self
+            .chunks
+            .values()
+            .filter(|c| c.chunk.document_id == document_id)
+            .map(|c| c.chunk.id.clone())
+            .collect();
+        for id in &ids {
+            self.remove(id);
+        }
+        ids.len()
+    }
+
+    /// The `k` chunks scoring best for `query` among those `filter` accepts;
+    /// chunks sharing no term with the query are left out.
+    pub fn search(&self, query: &str, k: usize, filter: &Filter) -> Vec<Retrieved> {
+        if self.chunks.is_empty() {
+            return Vec::new();
+        }
+        let count = self.chunks.len() as f32;
+        let average = self.total_length as f32 / count;
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        let mut scores: HashMap<&str, f32> = HashMap::new();
+        for term in &terms {
+            let Some(posting) = self.postings.get(term) else {
+                continue;
+            };
+            let containing = posting.len() as f32;
+            let idf = ((count - containing + 0.5) / (containing + 0.5) + 1.0).ln();
+            for (id, frequency) in posting {
+                let length = self.chunks[id].length as f32;
+                let tf = *frequency as f32;
+                let norm = self.k1 * (1.0 - self.b + self.b * length / average.max(1.0));
+                *scores.entry(id.as_str()).or_default() += idf * tf * (self.k1 + 1.0) / (tf + norm);
+            }
+        }
+
+        let mut hits: Vec<Retrieved> = scores
+            .into_iter()
+            .map(|(id, score)| (&self.chunks[id].chunk, score))
+            .filter(|(chunk, _)| filter.matches(&chunk.metadata))
+            .map(|(chunk, score)| Retrieved {
+                chunk: chunk.clone(),
+                score,
+            })
+            .collect();
+        // ties broken by id so results are stable
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.chunk.id.cmp(&b.chunk.id))
+        });
+        hits.truncate(k);
+        hits
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values().map(|c| &c.chunk)
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.postings.clear();
+        self.total_length = 0;
+    }
+}
+
+#[async_trait]
+impl Retriever for Bm25Index {
+    async fn retrieve(&self, query: &str, k: usize, filter: &Filter) -> Result<Vec<Retrieved>> {
+        Ok(self.search(query, k, filter))
+    }
+}
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::rag::document::{Chunk, Document};
+
+/// How documents are cut into chunks. Sizes are in characters.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Chunker {
+    /// Windows of `size` characters overlapping by `overlap`, cut at a
+    /// whitespace when there is one near the end of the window.
+    Fixed { size: usize, overlap: usize },
+    /// One chunk per Markdown section, with its heading path as `heading`
+    /// metadata; longer sections are split into sentences.
+    Markdown { max_size: usize },
+    /// Whole sentences packed up to `max_size` characters, each chunk
+    /// repeating the last `overlap` sentences of the previous one.
+    Sentences { max_size: usize, overlap: usize },
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Chunker::Sentences {
+            max_size: 1000,
+            overlap: 1,
+        }
+    }
+}
+
+impl Chunker {
+    /// Cuts `document` into chunks carrying its metadata.
+    pub fn chunk(&self, document: &Document) -> Vec<Chunk> {
+        let pieces: Vec<(String, Option<String>)> = match *self {
+            Chunker::Fixed { size, overlap } => fixed(&document.text, size, overlap)
+                .into_iter()
+                .map(|text| (text, None))
+                .collect(),
+            Chunker::Markdown { max_size } => markdown(&document.text, max_size),
+            Chunker::Sentences { max_size, overlap } => {
+                pack(&sentences(&document.text), max_size, overlap)
+                    .into_iter()
+                    .map(|text| (text, None))
+                    .collect()
+            }
+        };
+
+        pieces
+            .into_iter()
+            .enumerate()
+            .map(|(index, (text, heading))| {
+                let mut metadata = document.metadata.clone();
+                if let Some(heading) = heading {
+                    metadata.insert("heading".to_string(), Value::String(heading));
+                }
+                Chunk {
+                    id: format!("{}#{}", document.id, index),
+                    document_id: document.id.clone(),
+                    index,
+                    text,
+                    metadata,
+                }
+            })
+            .collect()
+    }
+}
+
+// fixed:
+// overlapping character windows, preferring to end at a whitespace in the
+// last quarter of the window
+fn fixed(text: &str, size: usize, overlap: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let size = size.max(1);
+    let overlap = overlap.min(size - 1);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < chars.len() {
+        let mut end = (start + size).min(chars.len());
+        if end < chars.len()
+            && let Some(space) = chars[start + size * 3 / 4..end]
+                .iter()
+                .rposition(|c| c.is_whitespace())
+        {
+            end = start + size * 3 / 4 + space + 1;
+        }
+        let chunk: String = chars[start..end].iter().collect();
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        if end == chars.len() {
+            break;
+        }
+        start = end.saturating_sub(overlap).max(start + 1);
+    }
+    chunks
+}
+
+// sentences:
+// splits after `.`, `!` or `?` followed by a whitespace, and at blank lines
+fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let next = chars.peek().copied();
+        let end = match c {
+            '.' | '!' | '?' => next.is_none_or(char::is_whitespace),
+            '\n' => next == Some('\n'),
+            _ => false,
+        };
+        if end {
+            let sentence = current.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            current.clear();
+        }
+    }
+    let sentence = current.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+    sentences
+}
+
+// pack:
+// joins sentences into chunks of at most `max_size` characters; a sentence
+// longer than that is cut into fixed windows
+fn pack(sentences: &[String], max_size: usize, overlap: usize) -> Vec<String> {
+    let max_size = max_size.max(1);
+    let mut chunks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut length = 0;
+    // sentences of `current` carried over from the previous chunk
+    let mut carried = 0;
+
+    for sentence in sentences {
+        let size = sentence.chars().count();
+        if size > max_size {
+            if current.len() > carried {
+                chunks.push(current.join(" "));
+            }
+            chunks.extend(fixed(sentence, max_size, 0));
+            current.clear();
+            length = 0;
+            carried = 0;
+            continue;
+        }
+        if !current.is_empty() && length + 1 + size > max_size {
+            chunks.push(current.join(" "));
+            let keep = overlap.min(current.len());
+            current.drain(..current.len() - keep);
+            // drop carried sentences that would not leave room for this one
+            while !current.is_empty() && current_length(&current) + 1 + size > max_size {
+                current.remove(0);
+            }
+            carried = current.len();
+            length = current_length(&current);
+        }
+        length += if current.is_empty() { size } else { size + 1 };
+        current.push(sentence);
+    }
+    if current.len() > carried {
+        chunks.push(current.join(" "));
+    }
+    chunks
+}
+
+fn current_length(sentences: &[&str]) -> usize {
+    sentences.iter().map(|s| s.chars().count()).sum::<usize>() + sentences.len().saturating_sub(1)
+}
+
+// markdown:
+// sections under their heading path; fenced code blocks are never split at
+// the `#` lines they contain
+fn markdown(text: &str, max_size: usize) -> Vec<(String, Option<String>)> {
+    let mut pieces = Vec::new();
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut section = String::new();
+    let mut in_code = false;
+
+    let mut flush = |section: &mut String, headings: &[(usize, String)]| {
+        let path = (!headings.is_empty()).then(|| {
+            headings
+                .iter()
+                .map(|(_, title)| title.as_str())
+                .collect::<Vec<_>>()
+                .join(" > ")
+        });
+        let body = section.trim();
+        // a heading alone is not worth a chunk
+        let only_heading = body.lines().all(|line| line.starts_with('#'));
+        if !body.is_empty() && !only_heading {
+            if body.chars().count() <= max_size {
+                pieces.push((body.to_string(), path));
+            } else {
+                for text in pack(&sentences(body), max_size, 0) {
+                    pieces.push((text, path.clone()));
+                }
+            }
+        }
+        section.clear();
+    };
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        let heading = !in_code && (1..=6).contains(&level) && line[level..].starts_with(' ');
+        if heading {
+            flush(&mut section, &headings);
+            headings.retain(|(l, _)| *l < level);
+            headings.push((level, line[level..].trim().to_string()));
+        }
+        section.push_str(line);
+        section.push('\n');
+    }
+    flush(&mut section, &headings);
+    pieces
+}
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::{
+        conversation::conversation::TurnRole,
+        generation::generation::{GenerateRequest, Generation},
+    },
+    rag::retriever::Retrieved,
+};
+
+/// Instruction added to the system prompt of requests with retrieved context.
+pub const CONTEXT_INSTRUCTION: &str = "Answer using the numbered sources given with the \
+question. Cite the sources you use as [n] right after the statements they support. If the \
+sources do not contain the answer, say so instead of guessing.";
+
+/// The sources as a numbered list, `[n] <source>` followed by the chunk text.
+pub fn context_block(sources: &[Retrieved]) -> String {
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            format!(
+                "[{}] {}\n{}",
+                i + 1,
+                source.chunk.source(),
+                source.chunk.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The text of the question `request` asks: its last user turn that is not
+/// a function response.
+pub fn question(request: &GenerateRequest) -> Option<&str> {
+    request
+        .conversation
+        .turns
+        .iter()
+        .rev()
+        .find(|t| t.role == TurnRole::User && t.function_responses.is_empty())
+        .map(|t| t.text.as_str())
+}
+
+/// Adds `sources` to `request`: the citation instruction goes to the system
+/// prompt and the numbered sources in front of the question.
+pub fn with_context(mut request: GenerateRequest, sources: &[Retrieved]) -> GenerateRequest {
+    let conversation = &mut request.conversation;
+    conversation.system = Some(match conversation.system.take() {
+        Some(system) => format!("{}\n\n{}", system, CONTEXT_INSTRUCTION),
+        None => CONTEXT_INSTRUCTION.to_string(),
+    });
+    if let Some(turn) = conversation
+        .turns
+        .iter_mut()
+        .rev()
+        .find(|t| t.role == TurnRole::User && t.function_responses.is_empty())
+    {
+        turn.text = format!(
+            "Sources:\n\n{}\n\nQuestion: {}",
+            context_block(sources),
+            turn.text
+        );
+    }
+    request
+}
+
+/// A reply generated from retrieved context, with the sources it was given.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RagAnswer {
+    pub generation: Generation,
+    /// The sources in prompt order, `[1]` first.
+    pub sources: Vec<Retrieved>,
+}
+
+impl RagAnswer {
+    pub fn text(&self) -> &str {
+        &self.generation.text
+    }
+
+    /// Numbers of the sources cited in the answer, in order of first citation.
+    /// Markers out of range are ignored.
+    pub fn citations(&self) -> Vec<usize> {
+        let mut cited = Vec::new();
+        let mut rest = self.generation.text.as_str();
+        while let Some(open) = rest.find('[') {
+            rest = &rest[open + 1..];
+            let Some(close) = rest.find(']') else {
+                break;
+            };
+            // `[1, 3]` cites two sources
+            for number in rest[..close].split(',') {
+                if let Ok(n) = number.trim().parse::<usize>()
+                    && (1..=self.sources.len()).contains(&n)
+                    && !cited.contains(&n)
+                {
+                    cited.push(n);
+                }
+            }
+        }
+        cited
+    }
+
+    /// The sources cited in the answer, in order of first citation.
+    pub fn cited_sources(&self) -> Vec<&Retrieved> {
+        self.citations()
+            .into_iter()
+            .map(|n| &self.sources[n - 1])
+            .collect()
+    }
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Metadata of a document or chunk (`source`, `title`, `heading`, or any
+/// key set by the application), used by [`Filter`]s and citations.
+pub type Metadata = BTreeMap<String, Value>;
+
+/// A text to retrieve from, as produced by the loaders.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Document {
+    /// Unique id; chunks of a document indexed again replace the old ones.
+    pub id: String,
+    pub text: String,
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl Document {
+    pub fn new(id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            text: text.into(),
+            metadata: Metadata::new(),
+        }
+    }
+
+    pub fn meta(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.metadata.get("title").and_then(Value::as_str)
+    }
+}
+
+/// A piece of a document, the unit that is embedded and retrieved.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Chunk {
+    /// `<document id>#<index>`.
+    pub id: String,
+    pub document_id: String,
+    /// Position of the chunk in its document.
+    pub index: usize,
+    pub text: String,
+    /// The document's metadata, plus `heading` for Markdown sections.
+    #[serde(default)]
+    pub metadata: Metadata,
+}
+
+impl Chunk {
+    /// Where the chunk comes from, for citations: the document's `source`
+    /// (or id) followed by the section heading, when there is one.
+    pub fn source(&self) -> String {
+        let source = self
+            .metadata
+            .get("source")
+            .and_then(Value::as_str)
+            .unwrap_or(&self.document_id);
+        match self.metadata.get("heading").and_then(Value::as_str) {
+            Some(heading) => format!("{} > {}", source, heading),
+            None => source.to_string(),
+        }
+    }
+}
+
+/// One condition of a [`Filter`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Condition {
+    /// The value of `key` is `value`, or an array containing it.
+    Eq { key: String, value: Value },
+    /// The value of `key` is one of `values`.
+    OneOf { key: String, values: Vec<Value> },
+    /// `key` is set.
+    Exists { key: String },
+}
+
+impl Condition {
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        match self {
+            Condition::Eq { key, value } => metadata.get(key).is_some_and(|v| contains(v, value)),
+            Condition::OneOf { key, values } => metadata
+                .get(key)
+                .is_some_and(|v| values.iter().any(|value| contains(v, value))),
+            Condition::Exists { key } => metadata.contains_key(key),
+        }
+    }
+}
+
+// contains:
+// whether `field` is `value`, or an array holding it (e.g. tags)
+fn contains(field: &Value, value: &Value) -> bool {
+    match field {
+        Value::Array(items) => items.contains(value),
+        field => field == value,
+    }
+}
+
+/// Metadata conditions chunks must all meet to be retrieved; the default
+/// filter lets everything through.
+///
+/// # Example
+/// ```
+/// # use ey_ai::rag::document::Filter;
+/// let filter = Filter::new().eq("lang", "en").one_of("product", ["cli", "api"]);
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub conditions: Vec<Condition>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn eq(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.conditions.push(Condition::Eq {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn one_of<V: Into<Value>>(
+        mut self,
+        key: impl Into<String>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        self.conditions.push(Condition::OneOf {
+            key: key.into(),
+            values: values.into_iter().map(Into::into).collect(),
+        });
+        self
+    }
+
+    pub fn exists(mut self, key: impl Into<String>) -> Self {
+        self.conditions.push(Condition::Exists { key: key.into() });
+        self
+    }
+
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.conditions.iter().all(|c| c.matches(metadata))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conditions.is_empty()
+    }
+}
@@ -0,0 +1,190 @@
+use std::{collections::HashMap, path::Path, sync::Arc, sync::RwLock};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+use crate::{
+    embedding::embedder::Embedder,
+    rag::{
+        bm25::Bm25Index,
+        chunker::Chunker,
+        document::{Chunk, Document, Filter},
+        rerank::Reranker,
+        retriever::{Retrieved, Retriever, VectorRetriever},
+    },
+};
+
+/// Rank constant of [`reciprocal_rank_fusion`], 60 as in the original paper.
+pub const RRF_K: f32 = 60.0;
+
+/// Merges ranked lists: each chunk scores the sum of `1 / (k + rank)` over
+/// the lists it appears in (rank 1 first), so chunks ranked well by several
+/// retrievers come first whatever the scales of their scores.
+pub fn reciprocal_rank_fusion(lists: &[Vec<Retrieved>], k: f32) -> Vec<Retrieved> {
+    let mut fused: HashMap<&str, (f32, &Retrieved)> = HashMap::new();
+    for list in lists {
+        for (rank, hit) in list.iter().enumerate() {
+            let score = 1.0 / (k + rank as f32 + 1.0);
+            fused.entry(hit.chunk.id.as_str()).or_insert((0.0, hit)).0 += score;
+        }
+    }
+
+    let mut fused: Vec<Retrieved> = fused
+        .into_values()
+        .map(|(score, hit)| Retrieved {
+            chunk: hit.chunk.clone(),
+            score,
+        })
+        .collect();
+    fused.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.chunk.id.cmp(&b.chunk.id))
+    });
+    fused
+}
+
+/// Vector search and BM25 keyword search over the same chunks, fused with
+/// [`reciprocal_rank_fusion`] and optionally reranked by a model.
+///
+/// Each retriever contributes its best [`candidates`](Self::candidates)
+/// chunks, so exact identifiers found by keywords are not lost to the
+/// embeddings, nor paraphrases to the keywords.
+///
+/// # Example
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ey_ai::{embedding::embedder::ClientEmbedder, model::generation::generation::GenerateRequest, models::model_client::ModelClient, rag::{document::Filter, hybrid::HybridRetriever, loader::load_dir, rerank::Reranker}};
+/// # async fn run(client: ModelClient) -> anyhow::Result<()> {
+/// let embedder = Arc::new(ClientEmbedder::new(client.clone(), "gemini-embedding-001").tag("rag"));
+/// let docs = HybridRetriever::new(embedder).reranker(Reranker::new(client.clone()));
+/// docs.index(&load_dir("docs")?).await?;
+///
+/// let request = GenerateRequest::from_prompt("What does error E1234 mean?");
+/// let answer = client.GenerateWithContext(request, &docs, 4, &Filter::new()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct HybridRetriever {
+    vector: VectorRetriever,
+    keywords: RwLock<Bm25Index>,
+    chunker: Chunker,
+    candidates: usize,
+    rrf_k: f32,
+    reranker: Option<Reranker>,
+}
+
+impl HybridRetriever {
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        Self::from_vector(VectorRetriever::new(embedder))
+    }
+
+    /// Adds keyword search to `vector`, indexing the chunks it already holds.
+    pub fn from_vector(vector: VectorRetriever) -> Self {
+        let keywords = Bm25Index::from_chunks(vector.chunks());
+        Self {
+            vector,
+            keywords: RwLock::new(keywords),
+            chunker: Chunker::default(),
+            candidates: 20,
+            rrf_k: RRF_K,
+            reranker: None,
+        }
+    }
+
+    /// Reopens a vector store saved with [`save`](Self::save); the keyword
+    /// index is rebuilt from its chunks.
+    pub fn load(embedder: Arc<dyn Embedder>, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_vector(VectorRetriever::load(embedder, path)?))
+    }
+
+    pub fn chunker(mut self, chunker: Chunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Chunks each retriever contributes before fusion [default: 20].
+    pub fn candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates.max(1);
+        self
+    }
+
+    /// Rank constant of the fusion [default: [`RRF_K`]].
+    pub fn rrf_k(mut self, k: f32) -> Self {
+        self.rrf_k = k;
+        self
+    }
+
+    /// Reranks the fused candidates with a model before the best are kept.
+    pub fn reranker(mut self, reranker: Reranker) -> Self {
+        self.reranker = Some(reranker);
+        self
+    }
+
+    pub fn vector(&self) -> &VectorRetriever {
+        &self.vector
+    }
+
+    /// Chunks, embeds and indexes `documents` for both searches, replacing
+    /// documents indexed before under the same ids; returns the number of chunks.
+    pub async fn index(&self, documents: &[Document]) -> Result<usize> {
+        let chunks: Vec<Chunk> = documents
+            .iter()
+            .flat_map(|d| self.chunker.chunk(d))
+            .collect();
+        let count = self.vector.index_chunks(chunks.clone()).await?;
+
+        let mut keywords = self.keywords.write().unwrap();
+        for document in documents {
+            keywords.remove_document(&document.id);
+        }
+        for chunk in chunks {
+            keywords.add(chunk);
+        }
+        Ok(count)
+    }
+
+    pub fn remove_document(&self, document_id: &str) -> usize {
+        self.keywords.write().unwrap().remove_document(document_id);
+        self.vector.remove_document(document_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vector.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vector.is_empty()
+    }
+
+    /// Saves the vector store; the keyword index is rebuilt on load.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.vector.save(path)
+    }
+}
+
+#[async_trait]
+impl Retriever for HybridRetriever {
+    /// A failed rerank fails the query rather than quietly returning the
+    /// fused order.
+    async fn retrieve(&self, query: &str, k: usize, filter: &Filter) -> Result<Vec<Retrieved>> {
+        let candidates = self.candidates.max(k);
+        let keyword = self
+            .keywords
+            .read()
+            .unwrap()
+            .search(query, candidates, filter);
+        let dense = self.vector.retrieve(query, candidates, filter).await?;
+
+        let mut fused = reciprocal_rank_fusion(&[dense, keyword], self.rrf_k);
+        fused.truncate(candidates);
+        if let Some(reranker) = &self.reranker {
+            fused = reranker
+                .rerank(query, fused)
+                .await
+                .map_err(|e| anyhow!("Reranking failed: {}", e))?;
+        }
+        fused.truncate(k);
+        Ok(fused)
+    }
+}
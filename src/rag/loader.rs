@@ -0,0 +1,288 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::rag::document::Document;
+
+/// Formats the loaders read.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentFormat {
+    Text,
+    Markdown,
+    Html,
+    /// Text of a PDF, with the `pdf` feature.
+    Pdf,
+}
+
+impl DocumentFormat {
+    /// The format of files with extension `ext`, `None` for unknown ones.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "txt" | "text" => Some(DocumentFormat::Text),
+            "md" | "markdown" => Some(DocumentFormat::Markdown),
+            "html" | "htm" => Some(DocumentFormat::Html),
+            "pdf" => Some(DocumentFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DocumentFormat::Text => "text",
+            DocumentFormat::Markdown => "markdown",
+            DocumentFormat::Html => "html",
+            DocumentFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// Reads `content` as a document of `format`, with `format` and `title`
+/// metadata (Markdown `# ` heading, HTML `<title>` or `<h1>`).
+pub fn parse(id: &str, content: &[u8], format: DocumentFormat) -> Result<Document> {
+    let (title, text) = match format {
+        DocumentFormat::Text => (None, String::from_utf8_lossy(content).into_owned()),
+        DocumentFormat::Markdown => {
+            let text = String::from_utf8_lossy(content).into_owned();
+            let title = text
+                .lines()
+                .find_map(|line| line.strip_prefix("# "))
+                .map(|title| title.trim().to_string());
+            (title, text)
+        }
+        DocumentFormat::Html => html_to_text(&String::from_utf8_lossy(content)),
+        DocumentFormat::Pdf => (None, pdf_to_text(content)?),
+    };
+
+    let mut document = Document::new(id, text).meta("format", format.name());
+    if let Some(title) = title.filter(|t| !t.is_empty()) {
+        document = document.meta("title", title);
+    }
+    Ok(document)
+}
+
+/// Loads the file at `path`; its path is the document id and `source`.
+pub fn load_file(path: impl AsRef<Path>) -> Result<Document> {
+    let path = path.as_ref();
+    let format = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .and_then(DocumentFormat::from_extension)
+        .ok_or_else(|| anyhow!("Unsupported document {}", path.display()))?;
+    let content = std::fs::read(path)
+        .map_err(|e| anyhow!("Failed to read document {}: {}", path.display(), e))?;
+    let source = path.to_string_lossy().replace('\\', "/");
+    Ok(parse(&source, &content, format)
+        .map_err(|e| anyhow!("{}: {}", path.display(), e))?
+        .meta("source", source))
+}
+
+/// Loads the documents under `dir`, recursively and sorted by path; files of
+/// unknown formats are skipped.
+pub fn load_dir(dir: impl AsRef<Path>) -> Result<Vec<Document>> {
+    let mut files = Vec::new();
+    collect_files(dir.as_ref(), &mut files)?;
+    files.sort();
+    files.iter().map(load_file).collect()
+}
+
+// collect_files:
+// paths of the files of a known format under `dir`
+fn collect_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<()> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| anyhow!("Failed to read directory {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(DocumentFormat::from_extension)
+            .is_some()
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "pdf")]
+pub fn pdf_to_text(content: &[u8]) -> Result<String> {
+    pdf_extract::extract_text_from_mem(content).map_err(|e| anyhow!("Failed to read PDF: {}", e))
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn pdf_to_text(_content: &[u8]) -> Result<String> {
+    Err(anyhow!("PDF support needs the `pdf` feature"))
+}
+
+// elements whose content is not text
+const SKIPPED_ELEMENTS: [&str; 5] = ["script", "style", "noscript", "template", "svg"];
+
+// elements starting a new paragraph or line
+const BLOCK_ELEMENTS: [&str; 20] = [
+    "p",
+    "div",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "main",
+    "nav",
+    "aside",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "table",
+    "pre",
+    "blockquote",
+];
+const LINE_ELEMENTS: [&str; 4] = ["br", "tr", "dt", "dd"];
+
+/// The title and readable text of an HTML page: tags are dropped, scripts
+/// and styles skipped, blocks become paragraphs and entities are decoded.
+pub fn html_to_text(html: &str) -> (Option<String>, String) {
+    let mut out = String::new();
+    let mut title: Option<String> = None;
+    let mut h1: Option<String> = None;
+    // start of the text of the open <title> or <h1> in `out`
+    let mut capture: Option<(&str, usize)> = None;
+    let mut rest = html;
+
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(close) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..close];
+        rest = &rest[close + 1..];
+
+        let closing = tag.starts_with('/');
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+
+        if !closing && SKIPPED_ELEMENTS.contains(&name.as_str()) {
+            let end = format!("</{}", name);
+            rest = find_ignore_case(rest, &end)
+                .map_or("", |at| &rest[at..])
+                .split_once('>')
+                .map_or("", |(_, after)| after);
+            continue;
+        }
+        match (name.as_str(), closing) {
+            ("title", false) => capture = Some(("title", out.len())),
+            ("h1", false) if h1.is_none() => {
+                out.push_str("\n\n");
+                capture = Some(("h1", out.len()));
+            }
+            ("title", true) | ("h1", true) => {
+                if let Some((element, start)) = capture.take() {
+                    let text = decode_entities(out[start..].trim());
+                    if element == "title" {
+                        // the title is metadata, not page text
+                        out.truncate(start);
+                        title.get_or_insert(text);
+                    } else {
+                        h1.get_or_insert(text);
+                    }
+                }
+                out.push_str("\n\n");
+            }
+            (name, _) if BLOCK_ELEMENTS.contains(&name) => out.push_str("\n\n"),
+            ("li", false) => out.push_str("\n- "),
+            (name, false) if LINE_ELEMENTS.contains(&name) => out.push('\n'),
+            ("td" | "th", false) => out.push(' '),
+            _ => {}
+        }
+    }
+    out.push_str(rest);
+
+    (title.or(h1), tidy(&decode_entities(&out)))
+}
+
+// find_ignore_case:
+// byte offset of `needle` (ASCII) in `haystack`, ignoring case
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+// decode_entities:
+// the named entities common in pages and numeric ones
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => match entity.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16)
+                        .ok()
+                        .and_then(char::from_u32),
+                    Some(decimal) => decimal.parse().ok().and_then(char::from_u32),
+                    None => None,
+                },
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// tidy:
+// collapses spaces within lines and runs of blank lines
+fn tidy(text: &str) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() || line == "-" {
+            blank = !out.is_empty();
+            continue;
+        }
+        if !out.is_empty() {
+            out.push_str(if blank { "\n\n" } else { "\n" });
+        }
+        out.push_str(&line);
+        blank = false;
+    }
+    out
+}
@@ -0,0 +1,9 @@
+pub mod bm25;
+pub mod chunker;
+pub mod context;
+pub mod document;
+pub mod hybrid;
+pub mod loader;
+pub mod rerank;
+pub mod retriever;
+pub mod store;
@@ -0,0 +1,97 @@
+use anyhow::{Result, anyhow};
+
+use crate::{
+    model::{
+        conversation::conversation::Conversation,
+        generation::generation::{GenerateRequest, GenerationConfig},
+    },
+    models::model_client::ModelClient,
+    rag::retriever::Retrieved,
+};
+
+/// System prompt of rerank requests.
+pub const RERANK_PROMPT: &str = "You rank passages by how well they answer a question. Reply \
+with a JSON array of passage numbers, most relevant first, leaving out passages that do not \
+help. Reply with the array only.";
+
+/// Reorders retrieved candidates by asking a model which passages answer the
+/// query best; a second opinion on the ranking of cheaper retrievers.
+pub struct Reranker {
+    client: ModelClient,
+    max_chars: usize,
+}
+
+impl Reranker {
+    pub fn new(client: ModelClient) -> Self {
+        Self {
+            client,
+            max_chars: 800,
+        }
+    }
+
+    /// Characters of each passage shown to the model [default: 800].
+    pub fn max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars.max(1);
+        self
+    }
+
+    /// `candidates` in the model's order, scored `1 / (1 + position)`.
+    /// Candidates the model leaves out follow in their original order.
+    pub async fn rerank(&self, query: &str, candidates: Vec<Retrieved>) -> Result<Vec<Retrieved>> {
+        if candidates.len() < 2 {
+            return Ok(candidates);
+        }
+        let passages: Vec<String> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let text: String = c.chunk.text.chars().take(self.max_chars).collect();
+                format!("[{}] {}", i, text)
+            })
+            .collect();
+        let prompt = format!(
+            "Question: {}\n\nPassages:\n{}",
+            query,
+            passages.join("\n\n")
+        );
+        let request =
+            GenerateRequest::new(Conversation::from_prompt(prompt).with_system(RERANK_PROMPT))
+                .config(GenerationConfig {
+                    temperature: Some(0.0),
+                    ..Default::default()
+                })
+                .tag("rerank");
+        let reply = self.client.Generate(request).await?;
+        let order = parse_order(&reply.text)?;
+
+        let mut ranked: Vec<usize> = Vec::with_capacity(candidates.len());
+        for i in order.into_iter().chain(0..candidates.len()) {
+            if i < candidates.len() && !ranked.contains(&i) {
+                ranked.push(i);
+            }
+        }
+        let mut candidates: Vec<Option<Retrieved>> = candidates.into_iter().map(Some).collect();
+        Ok(ranked
+            .into_iter()
+            .enumerate()
+            .filter_map(|(position, i)| {
+                candidates[i].take().map(|mut c| {
+                    c.score = 1.0 / (1.0 + position as f32);
+                    c
+                })
+            })
+            .collect())
+    }
+}
+
+// parse_order:
+// the JSON array of passage numbers in a reply, code fences and prose around it allowed
+fn parse_order(reply: &str) -> Result<Vec<usize>> {
+    let start = reply.find('[');
+    let end = reply.rfind(']');
+    let array = match (start, end) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err(anyhow!("No ranking in rerank reply: {}", reply)),
+    };
+    serde_json::from_str(array).map_err(|e| anyhow!("Invalid ranking in rerank reply: {}", e))
+}
@@ -0,0 +1,169 @@
+use std::{path::Path, sync::Arc, sync::RwLock};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    embedding::embedder::Embedder,
+    rag::{
+        chunker::Chunker,
+        document::{Chunk, Document, Filter},
+        store::VectorStore,
+    },
+};
+
+/// Texts embedded per request; `batchEmbedContents` accepts up to 100.
+pub const EMBED_BATCH_SIZE: usize = 100;
+
+/// A chunk found for a query, with its relevance score (higher is better,
+/// the scale depends on the retriever).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Retrieved {
+    pub chunk: Chunk,
+    pub score: f32,
+}
+
+/// Finds the chunks relevant to a query, e.g. in front of
+/// [`GenerateWithContext`](crate::models::model_client::ModelClient::GenerateWithContext).
+#[async_trait]
+pub trait Retriever: Send + Sync {
+    /// The `k` best chunks for `query` among those `filter` accepts, best first.
+    async fn retrieve(&self, query: &str, k: usize, filter: &Filter) -> Result<Vec<Retrieved>>;
+}
+
+/// Embeds the text of `chunks` with `embedder`, [`EMBED_BATCH_SIZE`] at a time.
+pub async fn embed_chunks(embedder: &dyn Embedder, chunks: &[Chunk]) -> Result<Vec<Vec<f32>>> {
+    let mut vectors = Vec::with_capacity(chunks.len());
+    for batch in chunks.chunks(EMBED_BATCH_SIZE) {
+        let texts: Vec<String> = batch.iter().map(|c| c.text.clone()).collect();
+        let embedded = embedder.embed(&texts).await?;
+        if embedded.len() != texts.len() {
+            return Err(anyhow!(
+                "Embedder returned {} vectors for {} texts",
+                embedded.len(),
+                texts.len()
+            ));
+        }
+        vectors.extend(embedded);
+    }
+    Ok(vectors)
+}
+
+/// Dense retrieval: documents are chunked and embedded into a
+/// [`VectorStore`], queries are embedded and matched by cosine similarity.
+///
+/// # Example
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use ey_ai::{embedding::embedder::ClientEmbedder, models::model_client::ModelClient, rag::{chunker::Chunker, document::Filter, loader::load_dir, retriever::{Retriever, VectorRetriever}}};
+/// # async fn run(client: ModelClient) -> anyhow::Result<()> {
+/// let embedder = Arc::new(ClientEmbedder::new(client, "gemini-embedding-001").tag("rag"));
+/// let retriever = VectorRetriever::new(embedder).chunker(Chunker::Markdown { max_size: 1500 });
+/// retriever.index(&load_dir("docs")?).await?;
+/// retriever.save("docs.index.json")?;
+///
+/// for hit in retriever.retrieve("How do I rotate keys?", 4, &Filter::new()).await? {
+///     println!("{:.2} {}", hit.score, hit.chunk.source());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct VectorRetriever {
+    embedder: Arc<dyn Embedder>,
+    chunker: Chunker,
+    store: RwLock<VectorStore>,
+}
+
+impl VectorRetriever {
+    pub fn new(embedder: Arc<dyn Embedder>) -> Self {
+        let store = VectorStore::new(embedder.name());
+        Self {
+            embedder,
+            chunker: Chunker::default(),
+            store: RwLock::new(store),
+        }
+    }
+
+    /// Reopens a store saved with [`save`](Self::save), which must hold
+    /// vectors of `embedder`.
+    pub fn load(embedder: Arc<dyn Embedder>, path: impl AsRef<Path>) -> Result<Self> {
+        let store = VectorStore::load(path)?;
+        if store.embedder() != embedder.name() {
+            return Err(anyhow!(
+                "Vector store holds {} vectors, not {}",
+                store.embedder(),
+                embedder.name()
+            ));
+        }
+        Ok(Self {
+            embedder,
+            chunker: Chunker::default(),
+            store: RwLock::new(store),
+        })
+    }
+
+    pub fn chunker(mut self, chunker: Chunker) -> Self {
+        self.chunker = chunker;
+        self
+    }
+
+    /// Chunks and embeds `documents`, replacing the chunks of documents
+    /// indexed before under the same ids, and returns the number of chunks.
+    pub async fn index(&self, documents: &[Document]) -> Result<usize> {
+        let chunks: Vec<Chunk> = documents
+            .iter()
+            .flat_map(|d| self.chunker.chunk(d))
+            .collect();
+        self.index_chunks(chunks).await
+    }
+
+    /// Embeds chunks cut by the caller; documents are replaced as by
+    /// [`index`](Self::index).
+    pub async fn index_chunks(&self, chunks: Vec<Chunk>) -> Result<usize> {
+        // embed before locking, the store stays searchable meanwhile
+        let vectors = embed_chunks(self.embedder.as_ref(), &chunks).await?;
+
+        let mut store = self.store.write().unwrap();
+        let mut replaced: Vec<&str> = chunks.iter().map(|c| c.document_id.as_str()).collect();
+        replaced.dedup();
+        for document_id in replaced {
+            store.remove_document(document_id);
+        }
+        // the documents are gone, no chunk needs replacing
+        let count = chunks.len();
+        for (chunk, vector) in chunks.into_iter().zip(vectors) {
+            store.insert(chunk, vector);
+        }
+        Ok(count)
+    }
+
+    pub fn remove_document(&self, document_id: &str) -> usize {
+        self.store.write().unwrap().remove_document(document_id)
+    }
+
+    /// Copies of the indexed chunks.
+    pub fn chunks(&self) -> Vec<Chunk> {
+        self.store.read().unwrap().chunks().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.read().unwrap().is_empty()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        self.store.read().unwrap().save(path)
+    }
+}
+
+#[async_trait]
+impl Retriever for VectorRetriever {
+    async fn retrieve(&self, query: &str, k: usize, filter: &Filter) -> Result<Vec<Retrieved>> {
+        let vector = self.embedder.embed_one(query).await?;
+        Ok(self.store.read().unwrap().search(&vector, k, filter))
+    }
+}
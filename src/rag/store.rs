@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    embedding::index::VectorIndex,
+    rag::{
+        document::{Chunk, Filter},
+        retriever::Retrieved,
+    },
+};
+
+// StoreFile:
+// on-disk layout of a store
+#[derive(Serialize, Deserialize)]
+struct StoreFile {
+    embedder: String,
+    chunks: Vec<StoredChunk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredChunk {
+    #[serde(flatten)]
+    chunk: Chunk,
+    vector: Vec<f32>,
+}
+
+/// In-memory store of embedded chunks with exact cosine search, saved to and
+/// loaded from a JSON file.
+///
+/// A store belongs to one embedder: vectors of different embedders cannot be
+/// compared, so loading checks the name.
+pub struct VectorStore {
+    embedder: String,
+    index: VectorIndex<Chunk>,
+}
+
+impl VectorStore {
+    /// An empty store for the vectors of embedder `embedder` (see
+    /// [`Embedder::name`](crate::embedding::embedder::Embedder::name)).
+    pub fn new(embedder: impl Into<String>) -> Self {
+        Self {
+            embedder: embedder.into(),
+            index: VectorIndex::new(),
+        }
+    }
+
+    pub fn embedder(&self) -> &str {
+        &self.embedder
+    }
+
+    /// Adds `chunk` under `vector`, replacing a chunk with the same id.
+    pub fn add(&mut self, chunk: Chunk, vector: Vec<f32>) {
+        self.index.retain(|entry| entry.value.id != chunk.id);
+        self.index.insert(vector, chunk);
+    }
+
+    // insert:
+    // adds `chunk` without looking for one with the same id, for callers that
+    // removed its document first
+    pub(crate) fn insert(&mut self, chunk: Chunk, vector: Vec<f32>) {
+        self.index.insert(vector, chunk);
+    }
+
+    /// Removes the chunks of document `document_id` and returns how many there were.
+    pub fn remove_document(&mut self, document_id: &str) -> usize {
+        let before = self.index.len();
+        self.index
+            .retain(|entry| entry.value.document_id != document_id);
+        before - self.index.len()
+    }
+
+    /// The `k` chunks closest to `query` among those `filter` accepts, best first.
+    pub fn search(&self, query: &[f32], k: usize, filter: &Filter) -> Vec<Retrieved> {
+        self.index
+            .search(query, self.index.len())
+            .into_iter()
+            .filter(|(_, entry)| filter.matches(&entry.value.metadata))
+            .take(k)
+            .map(|(score, entry)| Retrieved {
+                chunk: entry.value.clone(),
+                score,
+            })
+            .collect()
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.index.iter().map(|entry| &entry.value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.index.clear();
+    }
+
+    /// Writes the store to `path`, replacing the file in one step.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let file = StoreFile {
+            embedder: self.embedder.clone(),
+            chunks: self
+                .index
+                .iter()
+                .map(|entry| StoredChunk {
+                    chunk: entry.value.clone(),
+                    vector: entry.vector.clone(),
+                })
+                .collect(),
+        };
+        let content = serde_json::to_vec(&file)?;
+        // write then rename so a crash never leaves a partial store
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, content)
+            .and_then(|_| std::fs::rename(&tmp, path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&tmp);
+                anyhow!("Failed to write vector store {}: {}", path.display(), e)
+            })
+    }
+
+    /// Reads a store written by [`save`](Self::save).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read(path)
+            .map_err(|e| anyhow!("Failed to read vector store {}: {}", path.display(), e))?;
+        let file: StoreFile = serde_json::from_slice(&content)
+            .map_err(|e| anyhow!("Invalid vector store {}: {}", path.display(), e))?;
+
+        let mut store = Self::new(file.embedder);
+        for stored in file.chunks {
+            store.index.insert(stored.vector, stored.chunk);
+        }
+        Ok(store)
+    }
+}
@@ -0,0 +1,415 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Result, anyhow};
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode},
+    response::Response,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::oneshot;
+
+use crate::models::gemini::{GEMINI_BASE_URL, GeminiProvider};
+
+// response headers kept in cassettes, others (dates, server ids) are noise
+const KEPT_HEADERS: [&str; 3] = ["content-type", "x-goog-upload-url", "x-goog-upload-status"];
+
+// placeholder of the server address in recorded header values
+const BASE_PLACEHOLDER: &str = "{base}";
+
+/// A request as stored in a cassette, without secrets.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path and query, the `key` query parameter removed.
+    pub path: String,
+    /// JSON body, or the raw text of other bodies. A request recorded without
+    /// body matches any body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Body as received, chunk by chunk, so streaming responses are replayed
+    /// with the same boundaries; a character split across two chunks is kept
+    /// whole in the second one.
+    #[serde(default)]
+    pub chunks: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Recorded HTTP interactions, stored as a JSON file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read cassette {}: {}", path.display(), e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse cassette {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Serves recorded responses, never touching the network.
+    Replay,
+    /// Forwards requests to `upstream` and writes every interaction to the
+    /// cassette, replacing its previous content.
+    Record { upstream: String },
+}
+
+struct ServerState {
+    mode: CassetteMode,
+    path: PathBuf,
+    base_url: String,
+    cassette: Mutex<Cassette>,
+    // replayed interactions, each is served once
+    used: Mutex<Vec<bool>>,
+    client: reqwest::Client,
+}
+
+/// Local HTTP server recording or replaying provider traffic, to test
+/// provider code offline.
+///
+/// Point a provider at [`url`](Self::url) (see [`gemini`](Self::gemini)).
+/// Recorded requests never hold the API key: the `key` query parameter is
+/// dropped and headers are not stored. In replay mode a request is answered
+/// by the first unused interaction with the same method, path and body;
+/// unmatched requests get a `501` error.
+///
+/// # Usage
+/// ```rust,no_run
+/// # use ey_ai::{testing::cassette::CassetteServer, traits::ModelProvider};
+/// # async fn run() -> anyhow::Result<()> {
+/// // EY_AI_RECORD=1 with a real key to (re)record, replayed from disk otherwise
+/// let server = CassetteServer::auto("tests/cassettes/hello.json").await?;
+/// let reply = server.gemini().generate_text("test-key", "gemini-2.5-flash", "Hello".into()).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct CassetteServer {
+    state: Arc<ServerState>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl CassetteServer {
+    pub async fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        let cassette = Cassette::load(&path)?;
+        Self::start(path.as_ref(), CassetteMode::Replay, cassette).await
+    }
+
+    pub async fn record(path: impl AsRef<Path>, upstream: impl Into<String>) -> Result<Self> {
+        let upstream = upstream.into().trim_end_matches('/').to_string();
+        let mode = CassetteMode::Record { upstream };
+        Self::start(path.as_ref(), mode, Cassette::default()).await
+    }
+
+    /// Records against the Gemini API when `EY_AI_RECORD` is set or the
+    /// cassette does not exist yet, replays it otherwise.
+    pub async fn auto(path: impl AsRef<Path>) -> Result<Self> {
+        if std::env::var_os("EY_AI_RECORD").is_some() || !path.as_ref().exists() {
+            Self::record(path, GEMINI_BASE_URL).await
+        } else {
+            Self::replay(path).await
+        }
+    }
+
+    async fn start(path: &Path, mode: CassetteMode, cassette: Cassette) -> Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(ServerState {
+            mode,
+            path: path.to_path_buf(),
+            base_url,
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            cassette: Mutex::new(cassette),
+            client: reqwest::Client::new(),
+        });
+
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+
+        Ok(Self {
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.state.base_url
+    }
+
+    pub fn mode(&self) -> &CassetteMode {
+        &self.state.mode
+    }
+
+    /// A Gemini provider sending its requests to this server.
+    pub fn gemini(&self) -> GeminiProvider {
+        GeminiProvider::with_base_url(self.url())
+    }
+
+    /// Interactions recorded so far, or loaded for replay.
+    pub fn cassette(&self) -> Cassette {
+        self.state.cassette.lock().unwrap().clone()
+    }
+
+    /// Replayed interactions no request asked for yet.
+    pub fn unused(&self) -> Vec<Interaction> {
+        let cassette = self.state.cassette.lock().unwrap();
+        let used = self.state.used.lock().unwrap();
+        cassette
+            .interactions
+            .iter()
+            .zip(used.iter())
+            .filter(|(_, used)| !**used)
+            .map(|(interaction, _)| interaction.clone())
+            .collect()
+    }
+}
+
+impl Drop for CassetteServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+// redact:
+// path and query without the `key` parameter
+fn redact(path_and_query: &str) -> String {
+    let Some((path, query)) = path_and_query.split_once('?') else {
+        return path_and_query.to_string();
+    };
+    let query: Vec<&str> = query
+        .split('&')
+        .filter(|param| !param.starts_with("key="))
+        .collect();
+    if query.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, query.join("&"))
+    }
+}
+
+// body_value:
+// JSON body as a value, other bodies as text
+fn body_value(bytes: &Bytes) -> Option<Value> {
+    if bytes.is_empty() {
+        return None;
+    }
+    Some(
+        serde_json::from_slice(bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned())),
+    )
+}
+
+async fn handle(State(state): State<Arc<ServerState>>, request: Request) -> Response {
+    let (parts, body) = request.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| parts.uri.path().to_string());
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+    };
+    let recorded = RecordedRequest {
+        method: parts.method.to_string(),
+        path: redact(&path_and_query),
+        body: body_value(&bytes),
+    };
+
+    let result = match &state.mode {
+        CassetteMode::Replay => replay(&state, &recorded),
+        CassetteMode::Record { upstream } => {
+            let url = format!("{}{}", upstream, path_and_query);
+            let forward = Forward {
+                method: parts.method,
+                headers: parts.headers,
+                url,
+                body: bytes,
+            };
+            record(&state, upstream, forward, recorded).await
+        }
+    };
+    match result {
+        Ok(response) => respond(&state, response),
+        Err(e) => error_response(StatusCode::NOT_IMPLEMENTED, &e.to_string()),
+    }
+}
+
+fn replay(state: &ServerState, request: &RecordedRequest) -> Result<RecordedResponse> {
+    let cassette = state.cassette.lock().unwrap();
+    let mut used = state.used.lock().unwrap();
+    let found = cassette
+        .interactions
+        .iter()
+        .enumerate()
+        .position(|(i, interaction)| {
+            let recorded = &interaction.request;
+            !used[i]
+                && recorded.method == request.method
+                && recorded.path == request.path
+                && (recorded.body.is_none() || recorded.body == request.body)
+        });
+    match found {
+        Some(i) => {
+            used[i] = true;
+            Ok(cassette.interactions[i].response.clone())
+        }
+        None => Err(anyhow!(
+            "Cassette {} has no interaction for {} {}",
+            state.path.display(),
+            request.method,
+            request.path
+        )),
+    }
+}
+
+// request forwarded upstream while recording
+struct Forward {
+    method: Method,
+    headers: HeaderMap,
+    url: String,
+    body: Bytes,
+}
+
+async fn record(
+    state: &ServerState,
+    base: &str,
+    forward: Forward,
+    request: RecordedRequest,
+) -> Result<RecordedResponse> {
+    let mut upstream = state
+        .client
+        .request(forward.method, forward.url)
+        .body(forward.body);
+    for (name, value) in &forward.headers {
+        let name = name.as_str();
+        if name == "content-type" || name.starts_with("x-goog-") {
+            upstream = upstream.header(name, value);
+        }
+    }
+    let res = upstream.send().await?;
+
+    let status = res.status().as_u16();
+    let headers: BTreeMap<String, String> = KEPT_HEADERS
+        .iter()
+        .filter_map(|name| {
+            let value = res.headers().get(*name)?.to_str().ok()?;
+            Some((name.to_string(), value.replace(base, BASE_PLACEHOLDER)))
+        })
+        .collect();
+    let mut chunks = Vec::new();
+    let mut pending = Vec::new();
+    let mut body = res.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        pending.extend_from_slice(&chunk?);
+        let text = split_utf8(&mut pending);
+        if !text.is_empty() {
+            chunks.push(text);
+        }
+    }
+    if !pending.is_empty() {
+        chunks.push(String::from_utf8_lossy(&pending).into_owned());
+    }
+
+    let response = RecordedResponse {
+        status,
+        headers,
+        chunks,
+    };
+    let mut cassette = state.cassette.lock().unwrap();
+    cassette.interactions.push(Interaction {
+        request,
+        response: response.clone(),
+    });
+    state.used.lock().unwrap().push(true);
+    cassette.save(&state.path)?;
+    Ok(response)
+}
+
+// split_utf8:
+// text of `pending` up to a character cut at the end of a chunk, whose bytes
+// stay in `pending` for the next chunk
+fn split_utf8(pending: &mut Vec<u8>) -> String {
+    let complete = match std::str::from_utf8(pending) {
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        _ => pending.len(),
+    };
+    let rest = pending.split_off(complete);
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
+}
+
+// respond:
+// sends a recorded response, chunk by chunk
+fn respond(state: &ServerState, recorded: RecordedResponse) -> Response {
+    let chunks = recorded
+        .chunks
+        .into_iter()
+        .map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk)));
+    let mut response = Response::new(Body::from_stream(futures::stream::iter(chunks)));
+    *response.status_mut() = StatusCode::from_u16(recorded.status).unwrap_or(StatusCode::OK);
+    for (name, value) in recorded.headers {
+        let value = value.replace(BASE_PLACEHOLDER, &state.base_url);
+        if let (Ok(name), Ok(value)) = (
+            axum::http::HeaderName::try_from(name),
+            HeaderValue::from_str(&value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    let body = json!({ "error": { "code": status.as_u16(), "message": message } });
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = status;
+    response
+        .headers_mut()
+        .insert("content-type", HeaderValue::from_static("application/json"));
+    response
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde_json::{Value, json};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::oneshot,
+};
+
+use crate::mcp::protocol::PROTOCOL_VERSION;
+
+/// Text of the fixture's only resource, `fixture://readme`.
+pub const README: &str = "The MCP fixture server has tools echo, add and fail.";
+
+/// Answers one JSON-RPC message like a small MCP server would, `None` for
+/// notifications.
+///
+/// Tools: `echo {text}` returns the text, `add {a, b}` returns
+/// `{"sum": a + b}` as structured content and `fail` always fails; they are
+/// listed over two pages. There is one resource, `fixture://readme`, and one
+/// prompt, `greet {name}`.
+pub fn handle(message: &Value) -> Option<Value> {
+    let id = message.get("id")?.clone();
+    let params = &message["params"];
+    let result = match message["method"].as_str().unwrap_or_default() {
+        "initialize" => Ok(json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": { "tools": {}, "resources": {}, "prompts": {} },
+            "serverInfo": { "name": "mcp-fixture", "version": env!("CARGO_PKG_VERSION") },
+            "instructions": "Test fixture, tools have no side effects."
+        })),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(match params["cursor"].as_str() {
+            None => json!({ "tools": [echo_tool(), add_tool()], "nextCursor": "page-2" }),
+            Some(_) => json!({ "tools": [{
+                "name": "fail",
+                "description": "Always fails",
+                "inputSchema": { "type": "object" }
+            }] }),
+        }),
+        "tools/call" => call_tool(params),
+        "resources/list" => Ok(json!({ "resources": [{
+            "uri": "fixture://readme",
+            "name": "readme",
+            "mimeType": "text/plain"
+        }] })),
+        "resources/read" if params["uri"] == "fixture://readme" => Ok(json!({ "contents": [{
+            "uri": "fixture://readme",
+            "mimeType": "text/plain",
+            "text": README
+        }] })),
+        "resources/read" => Err((-32002, format!("Resource not found: {}", params["uri"]))),
+        "prompts/list" => Ok(json!({ "prompts": [{
+            "name": "greet",
+            "description": "Greets someone",
+            "arguments": [{ "name": "name", "required": true }]
+        }] })),
+        "prompts/get" if params["name"] == "greet" => {
+            let name = params["arguments"]["name"].as_str().unwrap_or("world");
+            Ok(json!({ "messages": [
+                { "role": "user", "content": { "type": "text", "text": format!("Say hello to {}", name) } },
+                { "role": "assistant", "content": { "type": "text", "text": format!("Hello, {}!", name) } }
+            ] }))
+        }
+        "prompts/get" => Err((-32602, format!("Unknown prompt {}", params["name"]))),
+        method => Err((-32601, format!("Method not found: {}", method))),
+    };
+
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message }
+        }),
+    })
+}
+
+fn echo_tool() -> Value {
+    json!({
+        "name": "echo",
+        "description": "Returns the text it is given",
+        "inputSchema": {
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": { "text": { "type": "string" } },
+            "required": ["text"],
+            "additionalProperties": false
+        }
+    })
+}
+
+fn add_tool() -> Value {
+    json!({
+        "name": "add",
+        "title": "Add two numbers",
+        "inputSchema": {
+            "type": "object",
+            "properties": { "a": { "type": "number" }, "b": { "type": "number" } },
+            "required": ["a", "b"]
+        },
+        "outputSchema": {
+            "type": "object",
+            "properties": { "sum": { "type": "number" } }
+        }
+    })
+}
+
+// call_tool:
+// result of tools/call; unknown tools are protocol errors, `fail` is a tool error
+fn call_tool(params: &Value) -> Result<Value, (i64, String)> {
+    let args = &params["arguments"];
+    match params["name"].as_str().unwrap_or_default() {
+        "echo" => Ok(json!({
+            "content": [{ "type": "text", "text": args["text"].as_str().unwrap_or_default() }]
+        })),
+        "add" => {
+            let sum = args["a"].as_f64().unwrap_or(0.0) + args["b"].as_f64().unwrap_or(0.0);
+            Ok(json!({
+                "content": [{ "type": "text", "text": sum.to_string() }],
+                "structuredContent": { "sum": sum }
+            }))
+        }
+        "fail" => Ok(json!({
+            "content": [{ "type": "text", "text": "fixture failure" }],
+            "isError": true
+        })),
+        name => Err((-32602, format!("Unknown tool: {}", name))),
+    }
+}
+
+/// Serves [`handle`] over stdin and stdout, one message per line, until
+/// stdin is closed.
+pub async fn serve_stdio() -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        if let Some(response) = handle(&message) {
+            stdout
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Sessions {
+    open: Mutex<HashSet<String>>,
+}
+
+/// Serves [`handle`] as a streamable HTTP endpoint at `/mcp`.
+///
+/// `initialize` opens a session (`Mcp-Session-Id`) that later messages must
+/// carry; `tools/call` is answered as an event stream, everything else as JSON.
+pub fn router() -> Router {
+    Router::new()
+        .route("/mcp", post(post_message).delete(end_session))
+        .with_state(Arc::new(Sessions::default()))
+}
+
+async fn post_message(
+    State(sessions): State<Arc<Sessions>>,
+    headers: HeaderMap,
+    Json(message): Json<Value>,
+) -> Response {
+    let session = headers
+        .get("mcp-session-id")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let initialize = message["method"] == "initialize";
+    if !initialize
+        && !session
+            .as_ref()
+            .is_some_and(|s| sessions.open.lock().unwrap().contains(s))
+    {
+        return (StatusCode::NOT_FOUND, "Unknown session").into_response();
+    }
+
+    let Some(response) = handle(&message) else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+    if initialize {
+        let session = uuid::Uuid::new_v4().to_string();
+        sessions.open.lock().unwrap().insert(session.clone());
+        return ([("mcp-session-id", session)], Json(response)).into_response();
+    }
+    if message["method"] == "tools/call" {
+        let body = format!("event: message\ndata: {}\n\n", response);
+        return ([("content-type", "text/event-stream")], body).into_response();
+    }
+    Json(response).into_response()
+}
+
+async fn end_session(State(sessions): State<Arc<Sessions>>, headers: HeaderMap) -> StatusCode {
+    let session = headers.get("mcp-session-id").and_then(|v| v.to_str().ok());
+    match session.map(|s| sessions.open.lock().unwrap().remove(s)) {
+        Some(true) => StatusCode::OK,
+        _ => StatusCode::NOT_FOUND,
+    }
+}
+
+/// In-process [`router`] on a free local port.
+pub struct McpFixtureServer {
+    url: String,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl McpFixtureServer {
+    pub async fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/mcp", listener.local_addr()?);
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, router())
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+        Ok(Self {
+            url,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// URL of the MCP endpoint.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for McpFixtureServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::{Stream, StreamExt};
+use serde_json::{Value, json};
+
+use crate::{
+    embedding::embedder::{Embedder, HashEmbedder},
+    model::{
+        conversation::conversation::{Conversation, Turn},
+        function::function::FunctionCall,
+        generation::generation::{GenerateRequest, Generation, StreamEvent, Usage},
+        message::message::{Choice, Message, Role},
+    },
+    models::error::ProviderError,
+    traits::{EventStream, ModelProvider},
+};
+
+// ReplyKind:
+// what a scripted reply resolves to
+#[derive(Clone, Debug)]
+enum ReplyKind {
+    Generation(Box<Generation>),
+    Stream {
+        chunks: Vec<(Duration, String)>,
+        usage: Option<Usage>,
+        // error yielded after the chunks instead of `Done`
+        error: Option<ProviderError>,
+    },
+    Error(ProviderError),
+}
+
+/// The answer of a [`MockProvider`] to one call.
+///
+/// Any reply serves both kinds of calls: a streamed reply is joined into one
+/// [`Generation`] for `generate`, a generation is sent as a single chunk to a
+/// stream.
+#[derive(Clone, Debug)]
+pub struct MockReply {
+    kind: ReplyKind,
+    latency: Duration,
+}
+
+impl MockReply {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::generation(Generation::from_text(text))
+    }
+
+    pub fn generation(generation: Generation) -> Self {
+        Self {
+            kind: ReplyKind::Generation(Box::new(generation)),
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Asks the caller to run `name` with `args`.
+    pub fn function_call(name: impl Into<String>, args: Value) -> Self {
+        Self::function_calls(vec![FunctionCall::new(name, args)])
+    }
+
+    /// Asks the caller to run several functions in one turn.
+    pub fn function_calls(calls: Vec<FunctionCall>) -> Self {
+        Self::generation(Generation {
+            function_calls: calls,
+            ..Default::default()
+        })
+    }
+
+    /// Fails the call with `error`, as a provider would.
+    pub fn error(error: ProviderError) -> Self {
+        Self {
+            kind: ReplyKind::Error(error),
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Streams `chunks` without delay.
+    pub fn stream<S: Into<String>>(chunks: impl IntoIterator<Item = S>) -> Self {
+        Self::stream_with_delays(chunks.into_iter().map(|chunk| (Duration::ZERO, chunk)))
+    }
+
+    /// Streams every chunk after its delay.
+    pub fn stream_with_delays<S: Into<String>>(
+        chunks: impl IntoIterator<Item = (Duration, S)>,
+    ) -> Self {
+        Self {
+            kind: ReplyKind::Stream {
+                chunks: chunks
+                    .into_iter()
+                    .map(|(delay, chunk)| (delay, chunk.into()))
+                    .collect(),
+                usage: None,
+                error: None,
+            },
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Usage reported with the reply.
+    pub fn usage(mut self, usage: Usage) -> Self {
+        match &mut self.kind {
+            ReplyKind::Generation(generation) => generation.usage = Some(usage),
+            ReplyKind::Stream { usage: u, .. } => *u = Some(usage),
+            ReplyKind::Error(_) => {}
+        }
+        self
+    }
+
+    /// Ends a streamed reply with `error` after its chunks instead of `Done`.
+    pub fn then_fail(mut self, error: ProviderError) -> Self {
+        if let ReplyKind::Stream { error: e, .. } = &mut self.kind {
+            *e = Some(error);
+        }
+        self
+    }
+
+    /// Time the call takes before answering (or before the first chunk).
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    // into_generation:
+    // the whole reply at once
+    fn into_generation(self) -> Result<Generation> {
+        match self.kind {
+            ReplyKind::Generation(generation) => Ok(*generation),
+            ReplyKind::Stream { error: Some(e), .. } | ReplyKind::Error(e) => Err(e.into()),
+            ReplyKind::Stream { chunks, usage, .. } => Ok(Generation {
+                text: chunks.into_iter().map(|(_, chunk)| chunk).collect(),
+                usage,
+                ..Default::default()
+            }),
+        }
+    }
+
+    // into_events:
+    // the reply as a stream; an error reply fails opening the stream
+    fn into_events(self) -> Result<EventStream> {
+        let (chunks, calls, usage, model_version, error) = match self.kind {
+            ReplyKind::Error(e) => return Err(e.into()),
+            ReplyKind::Generation(generation) => (
+                vec![(Duration::ZERO, generation.text)],
+                generation.function_calls,
+                generation.usage,
+                generation.model_version,
+                None,
+            ),
+            ReplyKind::Stream {
+                chunks,
+                usage,
+                error,
+            } => (chunks, Vec::new(), usage, None, error),
+        };
+
+        let text = futures::stream::iter(chunks).then(|(delay, text)| async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            Ok(StreamEvent::Text { text })
+        });
+        let calls = futures::stream::iter(
+            calls
+                .into_iter()
+                .map(|call| Ok(StreamEvent::FunctionCall { call })),
+        );
+        let end = match error {
+            Some(e) => Err(e.into()),
+            None => Ok(StreamEvent::Done {
+                usage,
+                model_version,
+                served_by: None,
+            }),
+        };
+        Ok(Box::pin(
+            text.chain(calls)
+                .chain(futures::stream::once(async { end })),
+        ))
+    }
+}
+
+/// Which provider method a [`MockCall`] went through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockMethod {
+    /// `generate`, `generate_text` and `generate_without_async`.
+    Generate,
+    /// `generate_events` and `generate_stream`.
+    Stream,
+    CountTokens,
+    /// `embed`, with one user turn per text; vectors come from a
+    /// [`HashEmbedder`].
+    Embed,
+}
+
+/// A call received by a [`MockProvider`].
+#[derive(Clone, Debug)]
+pub struct MockCall {
+    pub method: MockMethod,
+    pub api_key: String,
+    pub model: String,
+    /// The request as it reached the provider, after the client's defaults
+    /// and truncation; prompt-only methods get a single turn request.
+    pub request: GenerateRequest,
+}
+
+type Handler = Arc<dyn Fn(&GenerateRequest) -> MockReply + Send + Sync>;
+
+#[derive(Default)]
+struct MockState {
+    script: VecDeque<MockReply>,
+    handler: Option<Handler>,
+    fallback: Option<MockReply>,
+    token_count: Option<u64>,
+    calls: Vec<MockCall>,
+}
+
+/// [`ModelProvider`] answering from a script, for testing code built on
+/// `ModelClient` without calling a real model.
+///
+/// Replies are taken from the script in order; once it is exhausted the
+/// handler set with [`respond_with`](Self::respond_with) answers, then the
+/// fallback reply, and without either the call fails. Every call is recorded
+/// for assertions. Clones share the script and the record, so keep one
+/// to inspect after handing the provider to a client.
+///
+/// # Usage
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use ey_ai::{model::generation::generation::GenerateRequest, model_llm::Models, models::{error::ProviderError, model_client::ModelClient}, testing::mock::{MockProvider, MockReply}, traits::ModelProvider};
+/// # async fn run() -> anyhow::Result<()> {
+/// let mock = MockProvider::new()
+///     .reply(MockReply::text("Paris"))
+///     .reply(MockReply::error(ProviderError::Timeout));
+/// let client = ModelClient::new(Arc::new(mock.clone()))
+///     .init("test-key".to_string(), Models::Gemini25Flash);
+///
+/// let reply = client.Generate(GenerateRequest::from_prompt("Capital of France?")).await?;
+/// assert_eq!(reply.text, "Paris");
+/// assert!(client.Generate(GenerateRequest::from_prompt("Again")).await.is_err());
+/// assert_eq!(mock.calls()[0].request.conversation.turns[0].text, "Capital of France?");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct MockProvider {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockProvider {
+    /// Appends `reply` to the script.
+    pub fn reply(&self, reply: MockReply) -> Self {
+        self.state.lock().unwrap().script.push_back(reply);
+        self.clone()
+    }
+
+    pub fn replies(&self, replies: impl IntoIterator<Item = MockReply>) -> Self {
+        self.state.lock().unwrap().script.extend(replies);
+        self.clone()
+    }
+
+    /// Computes the reply of calls past the script from their request.
+    pub fn respond_with(
+        &self,
+        handler: impl Fn(&GenerateRequest) -> MockReply + Send + Sync + 'static,
+    ) -> Self {
+        self.state.lock().unwrap().handler = Some(Arc::new(handler));
+        self.clone()
+    }
+
+    /// Reply of every call past the script when no handler is set.
+    pub fn fallback(&self, reply: MockReply) -> Self {
+        self.state.lock().unwrap().fallback = Some(reply);
+        self.clone()
+    }
+
+    /// Answer of `count_tokens`, the local estimate when unset.
+    pub fn token_count(&self, tokens: u64) -> Self {
+        self.state.lock().unwrap().token_count = Some(tokens);
+        self.clone()
+    }
+
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.state.lock().unwrap().calls.len()
+    }
+
+    pub fn last_call(&self) -> Option<MockCall> {
+        self.state.lock().unwrap().calls.last().cloned()
+    }
+
+    /// Scripted replies not consumed yet.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().script.len()
+    }
+
+    /// Forgets the script, the handler, the fallback and the recorded calls.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = MockState::default();
+    }
+
+    // next_reply:
+    // records the call and picks its reply
+    fn next_reply(
+        &self,
+        method: MockMethod,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<MockReply> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(MockCall {
+            method,
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            request: request.clone(),
+        });
+        if let Some(reply) = state.script.pop_front() {
+            return Ok(reply);
+        }
+        if let Some(handler) = state.handler.clone() {
+            drop(state);
+            return Ok(handler(request));
+        }
+        state.fallback.clone().ok_or_else(|| {
+            anyhow!(
+                "MockProvider has no reply left for call {}",
+                state.calls.len()
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl ModelProvider for MockProvider {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    async fn generate_text(&self, api_key: &str, model: &str, prompt: String) -> Result<String> {
+        let request = GenerateRequest::from_prompt(prompt);
+        Ok(self.generate(api_key, model, &request).await?.text)
+    }
+
+    fn generate_without_async(
+        &self,
+        api_key: String,
+        model: String,
+        prompt: String,
+    ) -> Result<Value> {
+        let request = GenerateRequest::from_prompt(prompt.clone());
+        let reply = self.next_reply(MockMethod::Generate, &api_key, &model, &request)?;
+        std::thread::sleep(reply.latency);
+        let generation = reply.into_generation()?;
+
+        let message = Message {
+            id: "mock".to_string(),
+            models: model,
+            question: prompt,
+            choice: Choice {
+                role: Role {
+                    role: "assistant".into(),
+                    content: generation.text,
+                },
+            },
+            timestamp: Utc::now().to_string(),
+            loading: true,
+            usage: generation.usage,
+        };
+        Ok(json!(message))
+    }
+
+    async fn generate_stream(
+        &self,
+        api_key: String,
+        model: String,
+        prompt: String,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<String, anyhow::Error>> + Send>>> {
+        let request = GenerateRequest::from_prompt(prompt);
+        let events = self.generate_events(&api_key, &model, &request).await?;
+        Ok(Box::pin(events.filter_map(|event| async move {
+            match event {
+                Ok(StreamEvent::Text { text }) => Some(Ok(text)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            }
+        })))
+    }
+
+    async fn embed(&self, api_key: &str, model: &str, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let conversation = Conversation {
+            system: None,
+            turns: texts.iter().map(Turn::user).collect(),
+        };
+        self.state.lock().unwrap().calls.push(MockCall {
+            method: MockMethod::Embed,
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            request: GenerateRequest::new(conversation),
+        });
+        HashEmbedder::default().embed(texts).await
+    }
+
+    async fn count_tokens(
+        &self,
+        api_key: &str,
+        model: &str,
+        conversation: &Conversation,
+    ) -> Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(MockCall {
+            method: MockMethod::CountTokens,
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            request: GenerateRequest::new(conversation.clone()),
+        });
+        Ok(state
+            .token_count
+            .unwrap_or_else(|| conversation.estimate_tokens()))
+    }
+
+    async fn generate(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<Generation> {
+        let reply = self.next_reply(MockMethod::Generate, api_key, model, request)?;
+        tokio::time::sleep(reply.latency).await;
+        reply.into_generation()
+    }
+
+    async fn generate_events(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<EventStream> {
+        let reply = self.next_reply(MockMethod::Stream, api_key, model, request)?;
+        tokio::time::sleep(reply.latency).await;
+        reply.into_events()
+    }
+}
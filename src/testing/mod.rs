@@ -0,0 +1,4 @@
+pub mod cassette;
+pub mod mcp;
+pub mod mock;
+pub mod standin;
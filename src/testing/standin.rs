@@ -0,0 +1,468 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    str::FromStr,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use axum::{
+    Json, Router,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use futures::StreamExt;
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::oneshot};
+
+use crate::{
+    embedding::embedder::HashEmbedder, model::conversation::conversation::estimate_text_tokens,
+    model_llm::Models, models::gemini::GeminiProvider,
+};
+
+// embedding model listed next to the generation models
+const EMBEDDING_MODEL: &str = "gemini-embedding-001";
+
+/// A failure the stand-in answers with instead of a reply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// `429 RESOURCE_EXHAUSTED`.
+    RateLimited,
+    /// `500 INTERNAL`.
+    ServerError,
+    /// A prompt blocked by the safety filters (`promptFeedback.blockReason`).
+    SafetyBlock,
+    /// A `200` whose body is cut in the middle of the JSON.
+    MalformedJson,
+}
+
+impl FromStr for Fault {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "429" | "rate-limit" => Ok(Fault::RateLimited),
+            "500" | "server-error" => Ok(Fault::ServerError),
+            "safety" => Ok(Fault::SafetyBlock),
+            "malformed" => Ok(Fault::MalformedJson),
+            _ => Err(anyhow!(
+                "Unknown fault {}, expected 429, 500, safety or malformed",
+                s
+            )),
+        }
+    }
+}
+
+impl Fault {
+    // marker:
+    // text that makes a prompt fail with this fault, whatever the configuration
+    pub fn marker(&self) -> &'static str {
+        match self {
+            Fault::RateLimited => "[standin:429]",
+            Fault::ServerError => "[standin:500]",
+            Fault::SafetyBlock => "[standin:safety]",
+            Fault::MalformedJson => "[standin:malformed]",
+        }
+    }
+}
+
+/// Behaviour of a [`StandInServer`].
+#[derive(Clone, Debug, Default)]
+pub struct StandInConfig {
+    /// Fixed reply text; without it the last user turn is echoed back.
+    pub reply: Option<String>,
+    /// Wait before answering a generation request.
+    pub latency: Duration,
+    /// Wait between the chunks of a streamed reply.
+    pub chunk_delay: Duration,
+    /// Fault injected every `fail_every` generation requests (1 for all).
+    pub fault: Option<(Fault, u64)>,
+}
+
+impl StandInConfig {
+    pub fn reply(mut self, reply: impl Into<String>) -> Self {
+        self.reply = Some(reply.into());
+        self
+    }
+
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn chunk_delay(mut self, delay: Duration) -> Self {
+        self.chunk_delay = delay;
+        self
+    }
+
+    /// Fails every `every`-th generation request with `fault`.
+    pub fn fail(mut self, fault: Fault, every: u64) -> Self {
+        self.fault = Some((fault, every.max(1)));
+        self
+    }
+}
+
+struct StandIn {
+    config: StandInConfig,
+    // generation requests received, numbers responses and paces injected faults
+    generations: AtomicU64,
+}
+
+/// Router emulating the part of the Gemini REST API the crate uses:
+/// `generateContent`, `streamGenerateContent`, `countTokens`, `embedContent`,
+/// `batchEmbedContents` and `models.list` / `models.get`.
+///
+/// Answers are deterministic: the reply is the configured text or an echo of
+/// the prompt, token counts use the local estimate and embeddings come from
+/// [`HashEmbedder`]. Any model name and any API key are accepted, the key must
+/// be present. A prompt containing a [`Fault::marker`] fails with that fault.
+pub fn router(config: StandInConfig) -> Router {
+    let state = Arc::new(StandIn {
+        config,
+        generations: AtomicU64::new(0),
+    });
+    Router::new()
+        .route("/v1beta/models", get(list_models))
+        .route("/v1beta/models/{target}", get(get_model).post(call_model))
+        .fallback(|| async { error(StatusCode::NOT_FOUND, "NOT_FOUND", "Unknown endpoint") })
+        .with_state(state)
+}
+
+/// In-process [`router`] on a free local port, for tests and demos.
+///
+/// # Usage
+/// ```rust,no_run
+/// # use ey_ai::{testing::standin::{StandInConfig, StandInServer}, traits::ModelProvider};
+/// # async fn run() -> anyhow::Result<()> {
+/// let server = StandInServer::start(StandInConfig::default().reply("Hi there")).await?;
+/// let reply = server.gemini().generate_text("any-key", "gemini-2.5-flash", "Hello".into()).await?;
+/// assert_eq!(reply, "Hi there");
+/// # Ok(())
+/// # }
+/// ```
+pub struct StandInServer {
+    url: String,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl StandInServer {
+    pub async fn start(config: StandInConfig) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let (shutdown, stopped) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, router(config))
+                .with_graceful_shutdown(async {
+                    let _ = stopped.await;
+                })
+                .await;
+        });
+        Ok(Self {
+            url,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A Gemini provider sending its requests to this server.
+    pub fn gemini(&self) -> GeminiProvider {
+        GeminiProvider::with_base_url(self.url())
+    }
+}
+
+impl Drop for StandInServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+fn error(status: StatusCode, code: &str, message: &str) -> Response {
+    let body = json!({
+        "error": { "code": status.as_u16(), "message": message, "status": code }
+    });
+    (status, Json(body)).into_response()
+}
+
+fn model_json(name: &str, input_token_limit: u64, methods: &[&str]) -> Value {
+    json!({
+        "name": format!("models/{}", name),
+        "version": "standin",
+        "displayName": name,
+        "inputTokenLimit": input_token_limit,
+        "outputTokenLimit": 65536,
+        "supportedGenerationMethods": methods,
+    })
+}
+
+fn models() -> Vec<Value> {
+    let generation = ["generateContent", "streamGenerateContent", "countTokens"];
+    let mut models: Vec<Value> = Models::ALL
+        .iter()
+        .map(|m| model_json(m.name(), m.input_token_limit(), &generation))
+        .collect();
+    models.push(model_json(
+        EMBEDDING_MODEL,
+        2048,
+        &["embedContent", "batchEmbedContents"],
+    ));
+    models
+}
+
+async fn list_models() -> Response {
+    Json(json!({ "models": models() })).into_response()
+}
+
+async fn get_model(Path(target): Path<String>) -> Response {
+    let name = format!("models/{}", target);
+    match models().into_iter().find(|m| m["name"] == name) {
+        Some(model) => Json(model).into_response(),
+        None => error(
+            StatusCode::NOT_FOUND,
+            "NOT_FOUND",
+            &format!("{} is not found", name),
+        ),
+    }
+}
+
+async fn call_model(
+    State(state): State<Arc<StandIn>>,
+    Path(target): Path<String>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let has_key = query.get("key").is_some_and(|key| !key.is_empty())
+        || headers.contains_key("x-goog-api-key");
+    if !has_key {
+        return error(
+            StatusCode::FORBIDDEN,
+            "PERMISSION_DENIED",
+            "Method doesn't allow unregistered callers. Please use an API key.",
+        );
+    }
+    let Some((model, method)) = target.split_once(':') else {
+        return error(StatusCode::NOT_FOUND, "NOT_FOUND", "Unknown endpoint");
+    };
+    let Ok(request) = serde_json::from_slice::<Value>(&body) else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "INVALID_ARGUMENT",
+            "Invalid JSON payload received.",
+        );
+    };
+
+    match method {
+        "generateContent" => state.generate(model, &request, None).await,
+        "streamGenerateContent" => {
+            let sse = query.get("alt").is_some_and(|alt| alt == "sse");
+            state.generate(model, &request, Some(sse)).await
+        }
+        "countTokens" => {
+            // either `contents` or a full `generateContentRequest`
+            let request = request.get("generateContentRequest").unwrap_or(&request);
+            Json(json!({ "totalTokens": prompt_tokens(request) })).into_response()
+        }
+        "embedContent" => Json(json!({ "embedding": embed(&request) })).into_response(),
+        "batchEmbedContents" => {
+            let embeddings: Vec<Value> = request["requests"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(embed)
+                .collect();
+            Json(json!({ "embeddings": embeddings })).into_response()
+        }
+        _ => error(
+            StatusCode::NOT_FOUND,
+            "NOT_FOUND",
+            &format!("Method {} is not supported by the stand-in", method),
+        ),
+    }
+}
+
+// texts:
+// text parts of a list of contents
+fn texts(contents: &Value) -> impl Iterator<Item = &str> {
+    contents
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|content| content["parts"].as_array().into_iter().flatten())
+        .filter_map(|part| part["text"].as_str())
+}
+
+fn prompt_tokens(request: &Value) -> u64 {
+    let system = texts(&json!([request["systemInstruction"]]))
+        .map(estimate_text_tokens)
+        .sum::<u64>();
+    system
+        + texts(&request["contents"])
+            .map(estimate_text_tokens)
+            .sum::<u64>()
+}
+
+// last_prompt:
+// text of the last user turn
+fn last_prompt(request: &Value) -> String {
+    let last = request["contents"]
+        .as_array()
+        .and_then(|contents| contents.last())
+        .cloned()
+        .unwrap_or_default();
+    texts(&json!([last])).collect()
+}
+
+fn embed(request: &Value) -> Value {
+    let text: String = texts(&json!([request["content"]])).collect();
+    let dimensions = request["outputDimensionality"].as_u64().unwrap_or(768) as usize;
+    json!({ "values": HashEmbedder::new(dimensions).embed_text(&text) })
+}
+
+impl StandIn {
+    // fault:
+    // fault of this request, from a prompt marker or the configured pace
+    fn fault(&self, prompt: &str, count: u64) -> Option<Fault> {
+        let marked = [
+            Fault::RateLimited,
+            Fault::ServerError,
+            Fault::SafetyBlock,
+            Fault::MalformedJson,
+        ]
+        .into_iter()
+        .find(|fault| prompt.contains(fault.marker()));
+        marked.or_else(|| {
+            let (fault, every) = self.config.fault?;
+            count.is_multiple_of(every).then_some(fault)
+        })
+    }
+
+    // generate:
+    // answers `generateContent`, or `streamGenerateContent` when `stream` is
+    // set (with whether SSE was asked for)
+    async fn generate(&self, model: &str, request: &Value, stream: Option<bool>) -> Response {
+        let count = self.generations.fetch_add(1, Ordering::Relaxed) + 1;
+        let prompt = last_prompt(request);
+        let prompt_tokens = prompt_tokens(request);
+        tokio::time::sleep(self.config.latency).await;
+
+        let chunks: Vec<Value> = match self.fault(&prompt, count) {
+            Some(Fault::RateLimited) => {
+                return error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    "RESOURCE_EXHAUSTED",
+                    "Resource has been exhausted (e.g. check quota).",
+                );
+            }
+            Some(Fault::ServerError) => {
+                return error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "INTERNAL",
+                    "An internal error has occurred. Please retry or report in https://developers.generativeai.google/guide/troubleshooting",
+                );
+            }
+            Some(Fault::MalformedJson) => {
+                let body = match stream {
+                    Some(true) => "data: {\"candidates\": [{\"content\": \r\n\r\n",
+                    _ => "{\"candidates\": [{\"content\": {\"parts\": [",
+                };
+                return text_response(body, stream == Some(true));
+            }
+            Some(Fault::SafetyBlock) => vec![json!({
+                "promptFeedback": {
+                    "blockReason": "SAFETY",
+                    "safetyRatings": [{
+                        "category": "HARM_CATEGORY_DANGEROUS_CONTENT",
+                        "probability": "HIGH",
+                        "blocked": true,
+                    }],
+                },
+                "usageMetadata": { "promptTokenCount": prompt_tokens, "totalTokenCount": prompt_tokens },
+                "modelVersion": model,
+            })],
+            None => {
+                let reply = match &self.config.reply {
+                    Some(reply) => reply.clone(),
+                    None => format!("Echo: {}", prompt),
+                };
+                let pieces: Vec<String> = match stream {
+                    Some(_) => reply.split_inclusive(' ').map(str::to_string).collect(),
+                    None => vec![reply.clone()],
+                };
+                let reply_tokens = estimate_text_tokens(&reply);
+                let last = pieces.len().saturating_sub(1);
+                pieces
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, text)| {
+                        let mut candidate = json!({
+                            "content": { "parts": [{ "text": text }], "role": "model" },
+                            "index": 0,
+                        });
+                        let mut usage = json!({
+                            "promptTokenCount": prompt_tokens,
+                            "totalTokenCount": prompt_tokens,
+                        });
+                        if i == last {
+                            candidate["finishReason"] = json!("STOP");
+                            usage["candidatesTokenCount"] = json!(reply_tokens);
+                            usage["totalTokenCount"] = json!(prompt_tokens + reply_tokens);
+                        }
+                        json!({
+                            "candidates": [candidate],
+                            "usageMetadata": usage,
+                            "modelVersion": model,
+                            "responseId": format!("standin-{}", count),
+                        })
+                    })
+                    .collect()
+            }
+        };
+
+        match stream {
+            None => Json(chunks[0].clone()).into_response(),
+            Some(false) => Json(Value::Array(chunks)).into_response(),
+            Some(true) => {
+                let delay = self.config.chunk_delay;
+                let events = futures::stream::iter(chunks.into_iter().enumerate()).then(
+                    move |(i, chunk)| async move {
+                        if i > 0 {
+                            tokio::time::sleep(delay).await;
+                        }
+                        Ok::<_, Infallible>(Bytes::from(format!("data: {}\r\n\r\n", chunk)))
+                    },
+                );
+                let mut response = Response::new(Body::from_stream(events));
+                response.headers_mut().insert(
+                    "content-type",
+                    HeaderValue::from_static("text/event-stream"),
+                );
+                response
+            }
+        }
+    }
+}
+
+fn text_response(body: &'static str, sse: bool) -> Response {
+    let content_type = if sse {
+        "text/event-stream"
+    } else {
+        "application/json; charset=UTF-8"
+    };
+    let mut response = Response::new(Body::from(body));
+    response
+        .headers_mut()
+        .insert("content-type", HeaderValue::from_static(content_type));
+    response
+}
@@ -1,10 +1,20 @@
-use std::pin::Pin;
+use std::{pin::Pin, time::Duration};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use async_trait::async_trait;
 use futures::Stream;
 use serde_json::Value;
 
+use crate::model::{
+    batch::batch::{BatchJob, BatchRequest, BatchResult},
+    cached_content::cached_content::CachedContent,
+    conversation::conversation::Conversation,
+    generation::generation::{GenerateRequest, Generation, StreamEvent},
+};
+
+/// Stream of [`StreamEvent`]s returned by [`ModelProvider::generate_events`].
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
 /// A trait that defines the contract for a Large Language Model (LLM) provider.
 ///
 /// This trait abstracts the common functionalities required to interact with
@@ -25,6 +35,12 @@ pub trait ModelProvider: Send + Sync {
     where
         Self: Sized;
 
+    /// Short identifier of the provider (e.g. `"gemini"`), used in reports
+    /// such as [`ServedBy`](crate::model::generation::generation::ServedBy).
+    fn name(&self) -> &str {
+        "provider"
+    }
+
     /// Asynchronously generates a text response from the LLM Providers.
     ///
     /// This is the primary method for non-streaming text generation and is designed
@@ -70,4 +86,156 @@ pub trait ModelProvider: Send + Sync {
         model: String,
         prompt: String,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<String, anyhow::Error>> + Send>>>;
+
+    /// Counts the tokens `conversation` occupies in the model's input window.
+    ///
+    /// The default implementation falls back to a local character based
+    /// estimate; providers with a token counting endpoint should override it.
+    async fn count_tokens(
+        &self,
+        _api_key: &str,
+        _model: &str,
+        conversation: &Conversation,
+    ) -> Result<u64> {
+        Ok(conversation.estimate_tokens())
+    }
+
+    /// Asynchronously generates a reply to a [`GenerateRequest`].
+    ///
+    /// Unlike [`generate_text`](Self::generate_text) the returned
+    /// [`Generation`] carries the token usage reported by the provider.
+    ///
+    /// The default implementation flattens the conversation into a single
+    /// prompt and delegates to `generate_text`, without usage.
+    async fn generate(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<Generation> {
+        let text = self
+            .generate_text(api_key, model, request.conversation.to_prompt())
+            .await?;
+        Ok(Generation::from_text(text))
+    }
+
+    /// Asynchronously generates a streaming reply to a [`GenerateRequest`].
+    ///
+    /// The stream yields text chunks and ends with a single
+    /// [`StreamEvent::Done`] carrying the usage of the whole request.
+    ///
+    /// The default implementation wraps [`generate_stream`](Self::generate_stream)
+    /// and reports no usage.
+    async fn generate_events(
+        &self,
+        api_key: &str,
+        model: &str,
+        request: &GenerateRequest,
+    ) -> Result<EventStream> {
+        use futures::StreamExt;
+
+        let stream = self
+            .generate_stream(
+                api_key.to_string(),
+                model.to_string(),
+                request.conversation.to_prompt(),
+            )
+            .await?;
+        let done = futures::stream::once(async {
+            Ok(StreamEvent::Done {
+                usage: None,
+                model_version: None,
+                served_by: None,
+            })
+        });
+        Ok(Box::pin(
+            stream
+                .map(|chunk| chunk.map(|text| StreamEvent::Text { text }))
+                .chain(done),
+        ))
+    }
+
+    /// Stores `prefix` on the provider for `ttl`, so that requests naming it in
+    /// [`GenerateRequest::cached_content`] don't resend it.
+    ///
+    /// The default implementation reports that context caching is not supported,
+    /// as do the other `*_cached_content` methods.
+    async fn create_cached_content(
+        &self,
+        _api_key: &str,
+        _model: &str,
+        _prefix: &Conversation,
+        _ttl: Duration,
+    ) -> Result<CachedContent> {
+        Err(anyhow!("{} does not support context caching", self.name()))
+    }
+
+    async fn list_cached_contents(&self, _api_key: &str) -> Result<Vec<CachedContent>> {
+        Err(anyhow!("{} does not support context caching", self.name()))
+    }
+
+    async fn get_cached_content(&self, _api_key: &str, _name: &str) -> Result<CachedContent> {
+        Err(anyhow!("{} does not support context caching", self.name()))
+    }
+
+    /// Moves the expiry of a cached content to `ttl` from now.
+    async fn update_cached_content_ttl(
+        &self,
+        _api_key: &str,
+        _name: &str,
+        _ttl: Duration,
+    ) -> Result<CachedContent> {
+        Err(anyhow!("{} does not support context caching", self.name()))
+    }
+
+    async fn delete_cached_content(&self, _api_key: &str, _name: &str) -> Result<()> {
+        Err(anyhow!("{} does not support context caching", self.name()))
+    }
+
+    /// Submits `requests` as one asynchronous batch job on `model`.
+    ///
+    /// The default implementation reports that batch mode is not supported, as
+    /// do the other `*_batch*` methods.
+    async fn create_batch(
+        &self,
+        _api_key: &str,
+        _model: &str,
+        _display_name: &str,
+        _requests: &[BatchRequest],
+    ) -> Result<BatchJob> {
+        Err(anyhow!("{} does not support batch mode", self.name()))
+    }
+
+    async fn get_batch(&self, _api_key: &str, _name: &str) -> Result<BatchJob> {
+        Err(anyhow!("{} does not support batch mode", self.name()))
+    }
+
+    async fn list_batches(&self, _api_key: &str) -> Result<Vec<BatchJob>> {
+        Err(anyhow!("{} does not support batch mode", self.name()))
+    }
+
+    async fn cancel_batch(&self, _api_key: &str, _name: &str) -> Result<()> {
+        Err(anyhow!("{} does not support batch mode", self.name()))
+    }
+
+    async fn delete_batch(&self, _api_key: &str, _name: &str) -> Result<()> {
+        Err(anyhow!("{} does not support batch mode", self.name()))
+    }
+
+    /// Results of a succeeded job, one per request, under the request ids.
+    async fn batch_results(&self, _api_key: &str, _name: &str) -> Result<Vec<BatchResult>> {
+        Err(anyhow!("{} does not support batch mode", self.name()))
+    }
+
+    /// Embeds every text of `texts`, in order, with the embedding `model`.
+    ///
+    /// The default implementation reports that embeddings are not supported.
+    async fn embed(
+        &self,
+        _api_key: &str,
+        _model: &str,
+        _texts: &[String],
+    ) -> Result<Vec<Vec<f32>>> {
+        Err(anyhow!("{} does not support embeddings", self.name()))
+    }
 }
@@ -0,0 +1,377 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+/// Tenant used for requests that don't name one.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Window the token and cost limits of a [`Budget`] apply to (UTC).
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BudgetPeriod {
+    #[default]
+    Day,
+    Month,
+}
+
+impl BudgetPeriod {
+    // current:
+    // identifier of the running period, counters reset when it changes
+    fn current(&self) -> String {
+        match self {
+            BudgetPeriod::Day => Utc::now().format("%Y-%m-%d").to_string(),
+            BudgetPeriod::Month => Utc::now().format("%Y-%m").to_string(),
+        }
+    }
+}
+
+/// What happens when a tenant goes over its budget.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Enforcement {
+    /// Reject the request before it reaches the provider.
+    #[default]
+    Hard,
+    /// Let the request through and emit a [`BudgetWarning`].
+    Soft,
+}
+
+/// Limits applied to a single tenant. Limits left at `None` are not enforced.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Budget {
+    #[serde(default)]
+    pub period: BudgetPeriod,
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    #[serde(default)]
+    pub max_requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub enforcement: Enforcement,
+}
+
+/// Typed error returned (wrapped in `anyhow::Error`) when a hard limit is hit.
+///
+/// ```rust,ignore
+/// if let Some(err) = error.downcast_ref::<BudgetError>() { ... }
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BudgetError {
+    TokensExceeded {
+        tenant: String,
+        used: u64,
+        limit: u64,
+    },
+    CostExceeded {
+        tenant: String,
+        used_usd: f64,
+        limit_usd: f64,
+    },
+    RequestRateExceeded {
+        tenant: String,
+        limit: u32,
+    },
+}
+
+impl fmt::Display for BudgetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BudgetError::TokensExceeded {
+                tenant,
+                used,
+                limit,
+            } => write!(
+                f,
+                "Token budget of tenant '{}' exceeded: {} of {} tokens used",
+                tenant, used, limit
+            ),
+            BudgetError::CostExceeded {
+                tenant,
+                used_usd,
+                limit_usd,
+            } => write!(
+                f,
+                "Cost budget of tenant '{}' exceeded: ${:.4} of ${:.4} used",
+                tenant, used_usd, limit_usd
+            ),
+            BudgetError::RequestRateExceeded { tenant, limit } => write!(
+                f,
+                "Request rate of tenant '{}' exceeded: limit is {} requests per minute",
+                tenant, limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BudgetError {}
+
+/// Event emitted when a soft limit is exceeded.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BudgetWarning {
+    pub tenant: String,
+    pub error: BudgetError,
+}
+
+/// Consumption of a tenant in its current period.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TenantUsage {
+    pub period: String,
+    pub tokens: u64,
+    pub cost_usd: f64,
+    /// Estimates held by requests in flight, see [`Reservation`].
+    pub reserved_tokens: u64,
+    pub reserved_cost_usd: f64,
+    pub requests_last_minute: u32,
+}
+
+/// Estimate of a request admitted by [`BudgetManager::check`], counted
+/// against the budget of its tenant until the request is
+/// [`settle`](BudgetManager::settle)d or [`release`](BudgetManager::release)d,
+/// so concurrent requests cannot overrun a limit together.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Reservation {
+    pub tenant: String,
+    pub tokens: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Default)]
+struct TenantState {
+    period: String,
+    tokens: u64,
+    cost_usd: f64,
+    reserved_tokens: u64,
+    reserved_cost_usd: f64,
+    recent: VecDeque<Instant>,
+}
+
+impl TenantState {
+    // release:
+    // gives back the estimate of a request that is no longer in flight
+    fn release(&mut self, reservation: &Reservation) {
+        self.reserved_tokens = self.reserved_tokens.saturating_sub(reservation.tokens);
+        self.reserved_cost_usd = (self.reserved_cost_usd - reservation.cost_usd).max(0.0);
+    }
+
+    // roll:
+    // resets counters when the budget period changed and forgets requests older
+    // than a minute; reservations stay, their requests are still in flight
+    fn roll(&mut self, period: &str) {
+        if self.period != period {
+            self.period = period.to_string();
+            self.tokens = 0;
+            self.cost_usd = 0.0;
+        }
+        while self
+            .recent
+            .front()
+            .is_some_and(|t| t.elapsed() >= Duration::from_secs(60))
+        {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// Per tenant token, cost and request rate limits enforced by `ModelClient`.
+///
+/// Budgets can be changed at any time; a tenant without its own budget falls
+/// back to the default budget, and is unlimited when there is none.
+pub struct BudgetManager {
+    budgets: Mutex<HashMap<String, Budget>>,
+    default_budget: Mutex<Option<Budget>>,
+    state: Mutex<HashMap<String, TenantState>>,
+    warnings: broadcast::Sender<BudgetWarning>,
+}
+
+impl Default for BudgetManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BudgetManager {
+    pub fn new() -> Self {
+        let (warnings, _) = broadcast::channel(64);
+        Self {
+            budgets: Mutex::new(HashMap::new()),
+            default_budget: Mutex::new(None),
+            state: Mutex::new(HashMap::new()),
+            warnings,
+        }
+    }
+
+    pub fn set_budget(&self, tenant: impl Into<String>, budget: Budget) {
+        self.budgets.lock().unwrap().insert(tenant.into(), budget);
+    }
+
+    pub fn remove_budget(&self, tenant: &str) -> Option<Budget> {
+        self.budgets.lock().unwrap().remove(tenant)
+    }
+
+    pub fn set_default_budget(&self, budget: Option<Budget>) {
+        *self.default_budget.lock().unwrap() = budget;
+    }
+
+    /// Budget applied to `tenant`, its own or the default one.
+    pub fn budget(&self, tenant: &str) -> Option<Budget> {
+        let own = self.budgets.lock().unwrap().get(tenant).cloned();
+        own.or_else(|| self.default_budget.lock().unwrap().clone())
+    }
+
+    pub fn budgets(&self) -> HashMap<String, Budget> {
+        self.budgets.lock().unwrap().clone()
+    }
+
+    /// Subscribes to warnings emitted for soft limits.
+    pub fn subscribe(&self) -> broadcast::Receiver<BudgetWarning> {
+        self.warnings.subscribe()
+    }
+
+    /// Checks a request of `tenant` against its budget before it is sent.
+    ///
+    /// `estimated_tokens` and `estimated_cost_usd` describe the prompt about to
+    /// be sent, so a request that would cross a limit is rejected up front.
+    /// An allowed request is counted towards the per minute rate and its
+    /// estimate is reserved until it is settled or released, so requests in
+    /// flight count against the limits as well.
+    ///
+    /// # Errors
+    /// Returns a [`BudgetError`] when a hard limit would be exceeded.
+    pub fn check(
+        &self,
+        tenant: &str,
+        estimated_tokens: u64,
+        estimated_cost_usd: f64,
+    ) -> Result<Reservation, BudgetError> {
+        let Some(budget) = self.budget(tenant) else {
+            return Ok(Reservation {
+                tenant: tenant.to_string(),
+                ..Default::default()
+            });
+        };
+
+        let mut states = self.state.lock().unwrap();
+        let state = states.entry(tenant.to_string()).or_default();
+        state.roll(&budget.period.current());
+        let used_tokens = state.tokens + state.reserved_tokens;
+        let used_cost = state.cost_usd + state.reserved_cost_usd;
+
+        let mut violation = None;
+        if let Some(limit) = budget.max_requests_per_minute
+            && state.recent.len() as u32 >= limit
+        {
+            violation = Some(BudgetError::RequestRateExceeded {
+                tenant: tenant.to_string(),
+                limit,
+            });
+        }
+        if let Some(limit) = budget.max_tokens
+            && used_tokens + estimated_tokens > limit
+        {
+            violation = Some(BudgetError::TokensExceeded {
+                tenant: tenant.to_string(),
+                used: used_tokens,
+                limit,
+            });
+        }
+        if let Some(limit) = budget.max_cost_usd
+            && used_cost + estimated_cost_usd > limit
+        {
+            violation = Some(BudgetError::CostExceeded {
+                tenant: tenant.to_string(),
+                used_usd: used_cost,
+                limit_usd: limit,
+            });
+        }
+
+        if let Some(error) = violation {
+            match budget.enforcement {
+                Enforcement::Hard => return Err(error),
+                Enforcement::Soft => {
+                    let _ = self.warnings.send(BudgetWarning {
+                        tenant: tenant.to_string(),
+                        error,
+                    });
+                }
+            }
+        }
+
+        state.recent.push_back(Instant::now());
+        state.reserved_tokens += estimated_tokens;
+        state.reserved_cost_usd += estimated_cost_usd;
+        Ok(Reservation {
+            tenant: tenant.to_string(),
+            tokens: estimated_tokens,
+            cost_usd: estimated_cost_usd,
+        })
+    }
+
+    /// Replaces the estimate reserved for a finished request with its actual
+    /// consumption.
+    pub fn settle(&self, reservation: Reservation, tokens: u64, cost_usd: f64) {
+        self.update(&reservation.tenant, |state| {
+            state.release(&reservation);
+            state.tokens += tokens;
+            state.cost_usd += cost_usd;
+        });
+    }
+
+    /// Gives back the estimate reserved for a request that was not sent or
+    /// failed without being billed.
+    pub fn release(&self, reservation: Reservation) {
+        self.update(&reservation.tenant, |state| state.release(&reservation));
+    }
+
+    /// Adds the consumption of a request that reserved nothing.
+    pub fn record(&self, tenant: &str, tokens: u64, cost_usd: f64) {
+        self.update(tenant, |state| {
+            state.tokens += tokens;
+            state.cost_usd += cost_usd;
+        });
+    }
+
+    // update:
+    // changes the counters of `tenant` in its current period
+    fn update(&self, tenant: &str, change: impl FnOnce(&mut TenantState)) {
+        let period = self
+            .budget(tenant)
+            .map(|b| b.period)
+            .unwrap_or_default()
+            .current();
+
+        let mut states = self.state.lock().unwrap();
+        let state = states.entry(tenant.to_string()).or_default();
+        state.roll(&period);
+        change(state);
+    }
+
+    /// Consumption of `tenant` in its current period.
+    pub fn tenant_usage(&self, tenant: &str) -> TenantUsage {
+        let period = self
+            .budget(tenant)
+            .map(|b| b.period)
+            .unwrap_or_default()
+            .current();
+
+        let mut states = self.state.lock().unwrap();
+        let state = states.entry(tenant.to_string()).or_default();
+        state.roll(&period);
+        TenantUsage {
+            period: state.period.clone(),
+            tokens: state.tokens,
+            cost_usd: state.cost_usd,
+            reserved_tokens: state.reserved_tokens,
+            reserved_cost_usd: state.reserved_cost_usd,
+            requests_last_minute: state.recent.len() as u32,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::Serialize;
+
+use crate::{
+    models::model_client::ModelClient,
+    usage::{
+        budget::{Budget, TenantUsage},
+        ledger::{UsageFilter, UsageReport},
+    },
+};
+
+/// Reports the tokens and estimated cost recorded by the client's usage ledger.
+///
+/// # Example Request
+///
+/// ```http
+/// GET /usage?from=2025-10-01&to=2025-10-31&group_by=tag
+/// ```
+///
+/// # Example Response
+///
+/// ```json
+/// {
+///   "records": [
+///     { "tag": "support", "requests": 120, "prompt_tokens": 51200, "candidates_tokens": 9800,
+///       "cached_tokens": 0, "thoughts_tokens": 0, "total_tokens": 61000, "cost_usd": 0.04 }
+///   ],
+///   "total": { "requests": 120, "prompt_tokens": 51200, "...": "..." }
+/// }
+/// ```
+///
+/// # Usage
+///
+/// ```rust,ignore
+/// let app = Router::new()
+///     .route("/usage", get(UsageResponse))
+///     .with_state(Arc::new(client));
+/// ```
+pub async fn UsageResponse(
+    State(client): State<Arc<ModelClient>>,
+    Query(filter): Query<UsageFilter>,
+) -> Json<UsageReport> {
+    Json(client.usage.query(&filter))
+}
+
+#[derive(Serialize)]
+pub struct TenantBudget {
+    pub budget: Budget,
+    pub usage: TenantUsage,
+}
+
+/// Lists the configured tenant budgets with their consumption in the current period.
+///
+/// # Example Request
+///
+/// ```http
+/// GET /budgets
+/// ```
+pub async fn BudgetsResponse(
+    State(client): State<Arc<ModelClient>>,
+) -> Json<HashMap<String, TenantBudget>> {
+    let budgets = client
+        .budgets
+        .budgets()
+        .into_iter()
+        .map(|(tenant, budget)| {
+            let usage = client.budgets.tenant_usage(&tenant);
+            (tenant, TenantBudget { budget, usage })
+        })
+        .collect();
+    Json(budgets)
+}
+
+/// Sets the budget of a tenant at runtime.
+///
+/// # Example Request
+///
+/// ```http
+/// PUT /budgets/batch-team
+/// Content-Type: application/json
+///
+/// { "period": "month", "max_cost_usd": 50.0, "max_requests_per_minute": 30, "enforcement": "hard" }
+/// ```
+pub async fn SetBudget(
+    State(client): State<Arc<ModelClient>>,
+    Path(tenant): Path<String>,
+    Json(budget): Json<Budget>,
+) -> Json<TenantBudget> {
+    client.budgets.set_budget(tenant.clone(), budget.clone());
+    let usage = client.budgets.tenant_usage(&tenant);
+    Json(TenantBudget { budget, usage })
+}
@@ -0,0 +1,253 @@
+use std::{collections::HashMap, fmt::Write, sync::Mutex};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{model::generation::generation::Usage, usage::pricing::PricingTable};
+
+/// Identifies an API key in reports without revealing it: `key-` and the
+/// first 12 hex digits of its SHA-256, so distinct keys stay apart.
+pub fn key_id(api_key: &str) -> String {
+    let digest = Sha256::digest(api_key.as_bytes());
+    digest
+        .iter()
+        .take(6)
+        .fold(String::from("key-"), |mut id, byte| {
+            let _ = write!(id, "{:02x}", byte);
+            id
+        })
+}
+
+// EntryKey:
+// one ledger bucket, usage is aggregated per key, model, tag and UTC day
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct EntryKey {
+    key_id: String,
+    model: String,
+    tag: Option<String>,
+    day: String,
+}
+
+/// Aggregated usage of a group of requests.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct UsageTotals {
+    pub requests: u64,
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, other: &UsageTotals) {
+        self.requests += other.requests;
+        self.usage.add(&other.usage);
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// One row of a [`UsageReport`]. Dimensions that were not grouped on are `None`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UsageRecord {
+    /// The [`key_id`] of the API key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub day: Option<String>,
+    #[serde(flatten)]
+    pub totals: UsageTotals,
+}
+
+/// Selects and groups ledger entries.
+///
+/// Every field is optional; `key_id` is a [`key_id`] as found in reports,
+/// never the key itself, `from` / `to` are inclusive `YYYY-MM-DD` UTC days
+/// and `group_by` is a comma separated list of `key`, `model`, `tag`, `day`
+/// (all four when omitted).
+///
+/// # Example
+/// ```text
+/// GET /usage?from=2025-10-01&to=2025-10-31&group_by=tag,model
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UsageFilter {
+    pub key_id: Option<String>,
+    pub model: Option<String>,
+    pub tag: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub group_by: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct UsageReport {
+    pub records: Vec<UsageRecord>,
+    pub total: UsageTotals,
+}
+
+/// In-memory record of the tokens and estimated cost spent through a `ModelClient`.
+///
+/// API keys are only stored as their [`key_id`], so reports are safe to expose.
+pub struct UsageLedger {
+    pricing: Mutex<PricingTable>,
+    entries: Mutex<HashMap<EntryKey, UsageTotals>>,
+}
+
+impl Default for UsageLedger {
+    fn default() -> Self {
+        Self::new(PricingTable::default())
+    }
+}
+
+impl UsageLedger {
+    pub fn new(pricing: PricingTable) -> Self {
+        Self {
+            pricing: Mutex::new(pricing),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn pricing(&self) -> PricingTable {
+        self.pricing.lock().unwrap().clone()
+    }
+
+    pub fn set_pricing(&self, pricing: PricingTable) {
+        *self.pricing.lock().unwrap() = pricing;
+    }
+
+    /// Estimated cost of `usage` on `model` with the current pricing.
+    pub fn estimate_cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.pricing.lock().unwrap().cost(model, usage)
+    }
+
+    /// Records one request and returns its estimated cost in USD.
+    ///
+    /// Requests whose provider reported no usage are still counted.
+    pub fn record(
+        &self,
+        api_key: &str,
+        model: &str,
+        tag: Option<&str>,
+        usage: Option<&Usage>,
+    ) -> f64 {
+        let usage = usage.copied().unwrap_or_default();
+        let cost = self.estimate_cost(model, &usage);
+        self.add(api_key, model, tag, usage, cost);
+        cost
+    }
+
+    /// Records one request of a batch job, billed at the batch price, and
+    /// returns its estimated cost in USD.
+    pub fn record_batch(
+        &self,
+        api_key: &str,
+        model: &str,
+        tag: Option<&str>,
+        usage: Option<&Usage>,
+    ) -> f64 {
+        let usage = usage.copied().unwrap_or_default();
+        let cost = self.pricing.lock().unwrap().batch_cost(model, &usage);
+        self.add(api_key, model, tag, usage, cost);
+        cost
+    }
+
+    // add:
+    // adds one request to the bucket of its key, model, tag and day
+    fn add(&self, api_key: &str, model: &str, tag: Option<&str>, usage: Usage, cost: f64) {
+        let key = EntryKey {
+            key_id: key_id(api_key),
+            model: model.to_string(),
+            tag: tag.map(str::to_string),
+            day: Utc::now().format("%Y-%m-%d").to_string(),
+        };
+        let entry = UsageTotals {
+            requests: 1,
+            usage,
+            cost_usd: cost,
+        };
+
+        self.entries
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .add(&entry);
+    }
+
+    pub fn query(&self, filter: &UsageFilter) -> UsageReport {
+        let groups: Vec<String> = match &filter.group_by {
+            Some(group_by) => group_by
+                .split(',')
+                .map(|g| g.trim().to_lowercase())
+                .collect(),
+            None => vec!["key".into(), "model".into(), "tag".into(), "day".into()],
+        };
+        let grouped = |name: &str| groups.iter().any(|g| g == name);
+
+        let mut rows: HashMap<EntryKey, UsageTotals> = HashMap::new();
+        let mut total = UsageTotals::default();
+
+        for (key, totals) in self.entries.lock().unwrap().iter() {
+            if filter.key_id.as_ref().is_some_and(|id| *id != key.key_id)
+                || filter.model.as_ref().is_some_and(|m| *m != key.model)
+                || filter
+                    .tag
+                    .as_ref()
+                    .is_some_and(|t| Some(t) != key.tag.as_ref())
+                || filter.from.as_ref().is_some_and(|from| key.day < *from)
+                || filter.to.as_ref().is_some_and(|to| key.day > *to)
+            {
+                continue;
+            }
+
+            let row = EntryKey {
+                key_id: if grouped("key") {
+                    key.key_id.clone()
+                } else {
+                    String::new()
+                },
+                model: if grouped("model") {
+                    key.model.clone()
+                } else {
+                    String::new()
+                },
+                tag: if grouped("tag") {
+                    key.tag.clone()
+                } else {
+                    None
+                },
+                day: if grouped("day") {
+                    key.day.clone()
+                } else {
+                    String::new()
+                },
+            };
+            rows.entry(row).or_default().add(totals);
+            total.add(totals);
+        }
+
+        let mut records: Vec<UsageRecord> = rows
+            .into_iter()
+            .map(|(key, totals)| UsageRecord {
+                key_id: grouped("key").then_some(key.key_id),
+                model: grouped("model").then_some(key.model),
+                tag: key.tag,
+                day: grouped("day").then_some(key.day),
+                totals,
+            })
+            .collect();
+        records.sort_by(|a, b| {
+            (&a.day, &a.key_id, &a.model, &a.tag).cmp(&(&b.day, &b.key_id, &b.model, &b.tag))
+        });
+
+        UsageReport { records, total }
+    }
+
+    pub fn reset(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,4 @@
+pub mod budget;
+pub mod endpoint;
+pub mod ledger;
+pub mod pricing;
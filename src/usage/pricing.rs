@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::generation::generation::Usage;
+
+/// Share of the interactive price charged for requests of a batch job.
+pub const BATCH_PRICE_FACTOR: f64 = 0.5;
+
+/// Price of a model in USD per one million tokens.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+    /// Price of prompt tokens served from a context cache.
+    pub cached_input: f64,
+}
+
+impl ModelPricing {
+    pub const fn new(input: f64, output: f64, cached_input: f64) -> Self {
+        Self {
+            input,
+            output,
+            cached_input,
+        }
+    }
+
+    /// Estimated cost of `usage` in USD.
+    ///
+    /// Cached tokens are part of the prompt count and billed at the cached
+    /// rate, thinking tokens are billed as output.
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let cached = usage.cached_tokens.min(usage.prompt_tokens);
+        let input =
+            (usage.prompt_tokens - cached) as f64 * self.input + cached as f64 * self.cached_input;
+        let output = (usage.candidates_tokens + usage.thoughts_tokens) as f64 * self.output;
+        (input + output) / 1_000_000.0
+    }
+}
+
+/// Per model pricing used to estimate the cost of requests.
+///
+/// `PricingTable::default()` holds the public Gemini list prices (standard
+/// tier, prompts up to 200k tokens). Prices change, so deployments that need
+/// exact numbers should override them with [`PricingTable::set`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PricingTable {
+    pub models: HashMap<String, ModelPricing>,
+}
+
+impl Default for PricingTable {
+    fn default() -> Self {
+        // ref: https://ai.google.dev/gemini-api/docs/pricing
+        let models = [
+            ("gemini-2.5-pro", ModelPricing::new(1.25, 10.0, 0.125)),
+            ("gemini-2.5-flash", ModelPricing::new(0.30, 2.50, 0.03)),
+            ("gemini-2.5-flash-lite", ModelPricing::new(0.10, 0.40, 0.01)),
+            ("gemini-3-pro-preview", ModelPricing::new(2.0, 12.0, 0.20)),
+        ]
+        .into_iter()
+        .map(|(name, price)| (name.to_string(), price))
+        .collect();
+
+        Self { models }
+    }
+}
+
+impl PricingTable {
+    pub fn empty() -> Self {
+        Self {
+            models: HashMap::new(),
+        }
+    }
+
+    pub fn set(&mut self, model: impl Into<String>, pricing: ModelPricing) {
+        self.models.insert(model.into(), pricing);
+    }
+
+    /// Looks up the pricing of `model`.
+    ///
+    /// Versioned names such as `gemini-2.5-flash-001` fall back to the
+    /// longest known prefix.
+    pub fn get(&self, model: &str) -> Option<&ModelPricing> {
+        self.models.get(model).or_else(|| {
+            self.models
+                .iter()
+                .filter(|(name, _)| model.starts_with(name.as_str()))
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, price)| price)
+        })
+    }
+
+    /// Estimated cost of `usage` on `model` in USD, zero for unknown models.
+    pub fn cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.get(model).map(|p| p.cost(usage)).unwrap_or(0.0)
+    }
+
+    /// Estimated cost of `usage` on `model` when run in a batch job.
+    pub fn batch_cost(&self, model: &str, usage: &Usage) -> f64 {
+        self.cost(model, usage) * BATCH_PRICE_FACTOR
+    }
+}
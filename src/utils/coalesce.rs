@@ -0,0 +1,227 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+
+use anyhow::{Result, anyhow};
+use futures::{
+    FutureExt, StreamExt,
+    future::{BoxFuture, Shared},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::watch;
+
+use crate::{
+    model::generation::generation::{GenerateRequest, Generation, StreamEvent},
+    models::error::ProviderError,
+    traits::EventStream,
+};
+
+type SharedResult<T> = Result<T, Arc<anyhow::Error>>;
+
+// a running stream, replayed from the start to every subscriber
+struct Broadcast {
+    events: Mutex<Vec<SharedResult<StreamEvent>>>,
+    // number of events pushed so far, `None` once the upstream stream ended
+    progress: watch::Sender<Option<usize>>,
+}
+
+/// Merges concurrent identical requests into a single upstream call.
+///
+/// Requests are identical when the provider, the model and the whole
+/// [`GenerateRequest`] match; the first one goes upstream and every request
+/// arriving while it runs waits for its result. Streams are fanned out, a
+/// late subscriber first receives the chunks it missed.
+///
+/// Only deterministic requests (temperature 0) are merged, see
+/// [`GenerationConfig::is_deterministic`](crate::model::generation::generation::GenerationConfig::is_deterministic).
+#[derive(Default)]
+pub struct Coalescer {
+    enabled: AtomicBool,
+    generations: Mutex<HashMap<String, Shared<BoxFuture<'static, SharedResult<Generation>>>>>,
+    streams: Mutex<HashMap<String, Arc<Broadcast>>>,
+    requests: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CoalesceSnapshot {
+    /// Coalescible requests seen while coalescing was enabled.
+    pub requests: u64,
+    /// Requests answered by a call already in flight.
+    pub coalesced: u64,
+    /// Upstream calls currently shared.
+    pub in_flight: u64,
+}
+
+// fingerprint:
+// canonical key of a request, the serialized provider, model and request
+pub fn fingerprint(provider: &str, model: &str, request: &GenerateRequest) -> String {
+    json!([provider, model, request]).to_string()
+}
+
+// shared_error:
+// rebuilds an error for a waiter, keeping the `ProviderError` so failover
+// and key rotation still recognise it
+fn shared_error(error: &anyhow::Error) -> anyhow::Error {
+    match ProviderError::classify(error) {
+        Some(provider) => provider.into(),
+        None => anyhow!("{:#}", error),
+    }
+}
+
+impl Coalescer {
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Whether `request` may be merged with identical ones.
+    pub fn accepts(&self, request: &GenerateRequest) -> bool {
+        self.is_enabled() && request.config.is_deterministic()
+    }
+
+    pub fn snapshot(&self) -> CoalesceSnapshot {
+        let in_flight = self.generations.lock().unwrap().len() + self.streams.lock().unwrap().len();
+        CoalesceSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+            in_flight: in_flight as u64,
+        }
+    }
+
+    /// Runs `call` for `key`, or waits for the call already running for it.
+    ///
+    /// The call runs on a background task, so it finishes and releases what
+    /// it holds even when every waiter goes away.
+    pub async fn generate<F>(self: &Arc<Self>, key: String, call: F) -> Result<Generation>
+    where
+        F: Future<Output = Result<Generation>> + Send + 'static,
+    {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let shared = {
+            let mut generations = self.generations.lock().unwrap();
+            match generations.get(&key) {
+                Some(shared) => {
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    shared.clone()
+                }
+                None => {
+                    let coalescer = self.clone();
+                    let entry = key.clone();
+                    let task = tokio::spawn(async move {
+                        let result = call.await.map_err(Arc::new);
+                        coalescer.generations.lock().unwrap().remove(&entry);
+                        result
+                    });
+                    let shared = async move {
+                        task.await.unwrap_or_else(|e| {
+                            Err(Arc::new(anyhow!("coalesced call failed: {}", e)))
+                        })
+                    }
+                    .boxed()
+                    .shared();
+                    generations.insert(key, shared.clone());
+                    shared
+                }
+            }
+        };
+        shared.await.map_err(|e| shared_error(&e))
+    }
+
+    /// Subscribes to the stream opened by `open` for `key`, or to the stream
+    /// already running for it.
+    ///
+    /// The upstream stream is driven by a background task, so it keeps going
+    /// when the first subscriber goes away and stops once nobody listens.
+    pub async fn events<F>(self: &Arc<Self>, key: String, open: F) -> Result<EventStream>
+    where
+        F: Future<Output = Result<EventStream>> + Send + 'static,
+    {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let (broadcast, receiver) = {
+            let mut streams = self.streams.lock().unwrap();
+            match streams.get(&key) {
+                Some(broadcast) => {
+                    self.coalesced.fetch_add(1, Ordering::Relaxed);
+                    (broadcast.clone(), broadcast.progress.subscribe())
+                }
+                None => {
+                    let (progress, receiver) = watch::channel(Some(0));
+                    let broadcast = Arc::new(Broadcast {
+                        events: Mutex::new(Vec::new()),
+                        progress,
+                    });
+                    streams.insert(key.clone(), broadcast.clone());
+                    tokio::spawn(self.clone().pump(key, broadcast.clone(), open));
+                    (broadcast, receiver)
+                }
+            }
+        };
+
+        let mut events = subscribe(broadcast, receiver);
+        // a failure to open the stream is reported as an error of the call
+        match events.next().await {
+            Some(Err(e)) => Err(e),
+            first => Ok(Box::pin(futures::stream::iter(first).chain(events))),
+        }
+    }
+
+    // pump:
+    // reads the upstream stream into `broadcast` until it ends or nobody listens
+    async fn pump<F>(self: Arc<Self>, key: String, broadcast: Arc<Broadcast>, open: F)
+    where
+        F: Future<Output = Result<EventStream>> + Send + 'static,
+    {
+        let push = |event: SharedResult<StreamEvent>| {
+            let mut events = broadcast.events.lock().unwrap();
+            events.push(event);
+            broadcast.progress.send_replace(Some(events.len()));
+        };
+
+        match open.await {
+            Ok(mut stream) => {
+                while let Some(event) = stream.next().await {
+                    push(event.map_err(Arc::new));
+                    if broadcast.progress.receiver_count() == 0 {
+                        break;
+                    }
+                }
+            }
+            Err(e) => push(Err(Arc::new(e))),
+        }
+
+        self.streams.lock().unwrap().remove(&key);
+        broadcast.progress.send_replace(None);
+    }
+}
+
+// subscribe:
+// replays the events of `broadcast` from the start, then follows it live
+fn subscribe(broadcast: Arc<Broadcast>, receiver: watch::Receiver<Option<usize>>) -> EventStream {
+    let events = futures::stream::unfold(
+        (broadcast, receiver, 0usize),
+        |(broadcast, mut receiver, index)| async move {
+            loop {
+                let ended = receiver.borrow_and_update().is_none();
+                let next = broadcast.events.lock().unwrap().get(index).cloned();
+                if let Some(event) = next {
+                    let event = event.map_err(|e| shared_error(&e));
+                    return Some((event, (broadcast, receiver, index + 1)));
+                }
+                if ended || receiver.changed().await.is_err() {
+                    return None;
+                }
+            }
+        },
+    );
+    Box::pin(events)
+}
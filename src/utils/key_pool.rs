@@ -0,0 +1,316 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::{models::error::ProviderError, usage::ledger::key_id};
+
+/// How [`KeyPool::select`] picks the next key among the available ones.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySelection {
+    #[default]
+    RoundRobin,
+    /// Key with the fewest requests in flight, then the fewest requests overall.
+    LeastUsed,
+    /// Smooth weighted round-robin over the key weights.
+    Weighted,
+}
+
+/// Health of a pooled key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum KeyHealth {
+    Active,
+    /// Hit its quota (429); used again once the cooldown is over.
+    CoolingDown {
+        remaining_secs: u64,
+    },
+    /// Rejected by the provider (401 / 403); never used again until re-added.
+    Disabled {
+        reason: String,
+    },
+}
+
+/// Usage and health of one key, with the key itself masked.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KeyStats {
+    pub key: String,
+    pub weight: u32,
+    pub health: KeyHealth,
+    pub requests: u64,
+    pub failures: u64,
+    pub in_flight: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+struct PooledKey {
+    key: String,
+    weight: u32,
+    // running weight of the smooth weighted round-robin
+    current: i64,
+    requests: u64,
+    failures: u64,
+    in_flight: u64,
+    rate_limited: u32,
+    cooldown_until: Option<Instant>,
+    disabled: Option<String>,
+    last_error: Option<String>,
+}
+
+impl PooledKey {
+    fn available(&self, now: Instant) -> bool {
+        self.disabled.is_none() && self.cooldown_until.is_none_or(|until| until <= now)
+    }
+}
+
+struct PoolState {
+    keys: Vec<PooledKey>,
+    cursor: usize,
+}
+
+/// Set of API keys a `ModelClient` spreads its requests over.
+///
+/// Keys answering 429 cool down for a while (doubling on repeated hits, up to
+/// 15 minutes) and keys answering 401 / 403 are disabled.
+pub struct KeyPool {
+    selection: Mutex<KeySelection>,
+    cooldown: Mutex<Duration>,
+    state: Mutex<PoolState>,
+}
+
+impl Default for KeyPool {
+    fn default() -> Self {
+        Self::new(KeySelection::default())
+    }
+}
+
+const MAX_COOLDOWN: Duration = Duration::from_secs(15 * 60);
+
+impl KeyPool {
+    pub fn new(selection: KeySelection) -> Self {
+        Self {
+            selection: Mutex::new(selection),
+            cooldown: Mutex::new(Duration::from_secs(60)),
+            state: Mutex::new(PoolState {
+                keys: Vec::new(),
+                cursor: 0,
+            }),
+        }
+    }
+
+    pub fn set_selection(&self, selection: KeySelection) {
+        *self.selection.lock().unwrap() = selection;
+    }
+
+    /// Base cooldown of a rate limited key (60 seconds by default).
+    pub fn set_cooldown(&self, cooldown: Duration) {
+        *self.cooldown.lock().unwrap() = cooldown;
+    }
+
+    /// Adds a key, or re-enables it with the new weight when already pooled.
+    pub fn add(&self, key: impl Into<String>, weight: u32) {
+        let key = key.into();
+        let mut state = self.state.lock().unwrap();
+        if let Some(existing) = state.keys.iter_mut().find(|k| k.key == key) {
+            existing.weight = weight.max(1);
+            existing.disabled = None;
+            existing.cooldown_until = None;
+            existing.rate_limited = 0;
+            return;
+        }
+        state.keys.push(PooledKey {
+            key,
+            weight: weight.max(1),
+            current: 0,
+            requests: 0,
+            failures: 0,
+            in_flight: 0,
+            rate_limited: 0,
+            cooldown_until: None,
+            disabled: None,
+            last_error: None,
+        });
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.state.lock().unwrap().keys.retain(|k| k.key != key);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().keys.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().keys.len()
+    }
+
+    /// Whether at least one key is neither cooling down nor disabled.
+    pub fn has_available(&self) -> bool {
+        let now = Instant::now();
+        self.state
+            .lock()
+            .unwrap()
+            .keys
+            .iter()
+            .any(|k| k.available(now))
+    }
+
+    /// Picks the next key, counted as in flight until the returned lease is
+    /// dropped, whether or not an outcome was [`report`](Self::report)ed
+    /// (e.g. a request cancelled by a timeout or a winning hedge).
+    ///
+    /// # Errors
+    /// Fails when every key is cooling down or disabled.
+    pub fn select(&self) -> Result<KeyLease<'_>> {
+        let selection = *self.selection.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let available: Vec<usize> = (0..state.keys.len())
+            .filter(|i| state.keys[*i].available(now))
+            .collect();
+        if available.is_empty() {
+            return Err(anyhow!(
+                "No API key available: all {} keys are cooling down or disabled",
+                state.keys.len()
+            ));
+        }
+
+        let index = match selection {
+            KeySelection::RoundRobin => {
+                let start = state.cursor;
+                let len = state.keys.len();
+                let index = (0..len)
+                    .map(|offset| (start + offset) % len)
+                    .find(|i| available.contains(i))
+                    .unwrap_or(available[0]);
+                state.cursor = index + 1;
+                index
+            }
+            KeySelection::LeastUsed => *available
+                .iter()
+                .min_by_key(|i| (state.keys[**i].in_flight, state.keys[**i].requests))
+                .unwrap_or(&available[0]),
+            KeySelection::Weighted => {
+                let total: i64 = available.iter().map(|i| state.keys[*i].weight as i64).sum();
+                for i in &available {
+                    let key = &mut state.keys[*i];
+                    key.current += key.weight as i64;
+                }
+                let index = *available
+                    .iter()
+                    .max_by_key(|i| state.keys[**i].current)
+                    .unwrap_or(&available[0]);
+                state.keys[index].current -= total;
+                index
+            }
+        };
+
+        let key = &mut state.keys[index];
+        key.requests += 1;
+        key.in_flight += 1;
+        Ok(KeyLease {
+            pool: self,
+            key: key.key.clone(),
+        })
+    }
+
+    /// Reports the outcome of a request made with `key`.
+    ///
+    /// Returns `true` when the failure put the key out of rotation, in which
+    /// case the request is worth retrying with another key.
+    pub fn report(&self, key: &str, outcome: Result<(), &anyhow::Error>) -> bool {
+        let base = *self.cooldown.lock().unwrap();
+        let mut state = self.state.lock().unwrap();
+        let Some(pooled) = state.keys.iter_mut().find(|k| k.key == key) else {
+            return false;
+        };
+
+        let error = match outcome {
+            Ok(()) => {
+                pooled.rate_limited = 0;
+                return false;
+            }
+            Err(error) => error,
+        };
+        pooled.failures += 1;
+        // provider errors are kept as classified, never with a request URL
+        let provider = ProviderError::classify(error);
+        let message = match &provider {
+            Some(provider) => provider.to_string(),
+            None => error.to_string(),
+        };
+        pooled.last_error = Some(message.clone());
+
+        match provider.and_then(|e| e.status()) {
+            Some(429) => {
+                pooled.rate_limited += 1;
+                let factor = 1u32 << (pooled.rate_limited - 1).min(10);
+                let cooldown = base.saturating_mul(factor).min(MAX_COOLDOWN);
+                pooled.cooldown_until = Some(Instant::now() + cooldown);
+                true
+            }
+            Some(status @ (401 | 403)) => {
+                pooled.disabled = Some(format!("HTTP {}: {}", status, message));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn stats(&self) -> Vec<KeyStats> {
+        let now = Instant::now();
+        self.state
+            .lock()
+            .unwrap()
+            .keys
+            .iter()
+            .map(|k| {
+                let health = match (&k.disabled, k.cooldown_until) {
+                    (Some(reason), _) => KeyHealth::Disabled {
+                        reason: reason.clone(),
+                    },
+                    (None, Some(until)) if until > now => KeyHealth::CoolingDown {
+                        remaining_secs: (until - now).as_secs(),
+                    },
+                    _ => KeyHealth::Active,
+                };
+                KeyStats {
+                    key: key_id(&k.key),
+                    weight: k.weight,
+                    health,
+                    requests: k.requests,
+                    failures: k.failures,
+                    in_flight: k.in_flight,
+                    last_error: k.last_error.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A key picked by [`KeyPool::select`], in flight until dropped.
+pub struct KeyLease<'a> {
+    pool: &'a KeyPool,
+    key: String,
+}
+
+impl KeyLease<'_> {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl Drop for KeyLease<'_> {
+    fn drop(&mut self) {
+        let mut state = self.pool.state.lock().unwrap();
+        if let Some(pooled) = state.keys.iter_mut().find(|k| k.key == self.key) {
+            pooled.in_flight = pooled.in_flight.saturating_sub(1);
+        }
+    }
+}
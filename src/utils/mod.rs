@@ -1,3 +1,7 @@
+pub mod coalesce;
+pub mod key_pool;
+pub mod rate_limit;
 pub mod select_model;
 pub mod stream;
+pub mod truncate;
 pub mod wrapper;
@@ -0,0 +1,368 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+/// Limits enforced locally before requests reach the provider.
+///
+/// Mirrors the RPM / TPM quotas of the provider so requests wait in line
+/// instead of being answered with 429. Limits left at `None` are not enforced.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    #[serde(default)]
+    pub requests_per_minute: Option<u32>,
+    #[serde(default)]
+    pub tokens_per_minute: Option<u64>,
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+}
+
+/// Position of a request in the limiter queue. Higher priorities are served
+/// first, requests of the same priority in arrival order.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+// Ticket:
+// a queued request, ordered by priority then by arrival
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Ticket {
+    priority: Priority,
+    seq: Reverse<u64>,
+}
+
+impl Ord for Ticket {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.priority, self.seq).cmp(&(other.priority, other.seq))
+    }
+}
+
+impl PartialOrd for Ticket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Bucket:
+// token bucket refilled continuously up to one minute worth of capacity
+struct Bucket {
+    capacity: f64,
+    available: f64,
+}
+
+impl Bucket {
+    fn new(per_minute: f64) -> Self {
+        Self {
+            capacity: per_minute,
+            available: per_minute,
+        }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        let added = elapsed.as_secs_f64() * self.capacity / 60.0;
+        self.available = (self.available + added).min(self.capacity);
+    }
+
+    // a request larger than the whole bucket is let through once the bucket is full
+    fn wanted(&self, amount: f64) -> f64 {
+        amount.min(self.capacity)
+    }
+
+    fn wait_for(&self, amount: f64) -> Duration {
+        let missing = self.wanted(amount) - self.available;
+        if missing <= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(missing * 60.0 / self.capacity)
+    }
+}
+
+struct State {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    last_refill: Instant,
+    in_flight: usize,
+    queue: BinaryHeap<Ticket>,
+    next_seq: u64,
+}
+
+impl State {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last_refill;
+        self.last_refill = now;
+        if let Some(bucket) = &mut self.requests {
+            bucket.refill(elapsed);
+        }
+        if let Some(bucket) = &mut self.tokens {
+            bucket.refill(elapsed);
+        }
+    }
+}
+
+// Wait:
+// outcome of a single attempt to take a permit
+enum Wait {
+    Granted,
+    // head of the queue, waiting for buckets to refill
+    For(Duration),
+    // behind other requests or over the in-flight limit
+    Turn,
+}
+
+/// Token bucket rate limiter combined with a max-in-flight semaphore.
+///
+/// Requests queue fairly: a request is only admitted when it is at the head
+/// of the queue, so a large request isn't starved by a stream of small ones.
+/// Both async ([`acquire`](Self::acquire)) and blocking
+/// ([`acquire_blocking`](Self::acquire_blocking)) callers share the same queue.
+pub struct RateLimiter {
+    limits: RateLimits,
+    state: Mutex<State>,
+    condvar: Condvar,
+    notify: Notify,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::new(State {
+                requests: limits.requests_per_minute.map(|r| Bucket::new(r as f64)),
+                tokens: limits.tokens_per_minute.map(|t| Bucket::new(t as f64)),
+                last_refill: Instant::now(),
+                in_flight: 0,
+                queue: BinaryHeap::new(),
+                next_seq: 0,
+            }),
+            condvar: Condvar::new(),
+            notify: Notify::new(),
+        }
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    /// Number of requests currently holding a permit.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    /// Number of requests waiting for a permit.
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().queue.len()
+    }
+
+    /// Waits until a request of `tokens` estimated tokens may be sent.
+    ///
+    /// Dropping the returned future while it waits removes the request from
+    /// the queue.
+    pub async fn acquire(self: &Arc<Self>, priority: Priority, tokens: u64) -> Permit {
+        let ticket = self.enqueue(priority);
+        let mut queued = Queued {
+            limiter: self,
+            ticket,
+            granted: false,
+        };
+
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                self.try_take(&mut state, ticket, tokens)
+            };
+            match wait {
+                Wait::Granted => {
+                    queued.granted = true;
+                    self.wake();
+                    return self.permit(tokens);
+                }
+                Wait::For(duration) => {
+                    tokio::select! {
+                        _ = notified => {}
+                        _ = tokio::time::sleep(duration) => {}
+                    }
+                }
+                Wait::Turn => notified.await,
+            }
+        }
+    }
+
+    /// Blocking counterpart of [`acquire`](Self::acquire).
+    pub fn acquire_blocking(self: &Arc<Self>, priority: Priority, tokens: u64) -> Permit {
+        let ticket = self.enqueue(priority);
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            match self.try_take(&mut state, ticket, tokens) {
+                Wait::Granted => {
+                    drop(state);
+                    self.wake();
+                    return self.permit(tokens);
+                }
+                Wait::For(duration) => {
+                    state = self.condvar.wait_timeout(state, duration).unwrap().0;
+                }
+                Wait::Turn => state = self.condvar.wait(state).unwrap(),
+            }
+        }
+    }
+
+    fn enqueue(&self, priority: Priority) -> Ticket {
+        let mut state = self.state.lock().unwrap();
+        let ticket = Ticket {
+            priority,
+            seq: Reverse(state.next_seq),
+        };
+        state.next_seq += 1;
+        state.queue.push(ticket);
+        ticket
+    }
+
+    fn try_take(&self, state: &mut MutexGuard<'_, State>, ticket: Ticket, tokens: u64) -> Wait {
+        state.refill();
+
+        if state.queue.peek() != Some(&ticket) {
+            return Wait::Turn;
+        }
+        if let Some(max) = self.limits.max_in_flight
+            && state.in_flight >= max
+        {
+            return Wait::Turn;
+        }
+
+        let tokens = tokens as f64;
+        let wait = [
+            state.requests.as_ref().map(|b| b.wait_for(1.0)),
+            state.tokens.as_ref().map(|b| b.wait_for(tokens)),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap_or(Duration::ZERO);
+        if !wait.is_zero() {
+            return Wait::For(wait);
+        }
+
+        if let Some(bucket) = &mut state.requests {
+            bucket.available -= 1.0;
+        }
+        if let Some(bucket) = &mut state.tokens {
+            bucket.available -= bucket.wanted(tokens);
+        }
+        state.queue.pop();
+        state.in_flight += 1;
+        Wait::Granted
+    }
+
+    fn permit(self: &Arc<Self>, tokens: u64) -> Permit {
+        Permit {
+            limiter: self.clone(),
+            reserved: tokens,
+        }
+    }
+
+    fn wake(&self) {
+        self.notify.notify_waiters();
+        self.condvar.notify_all();
+    }
+}
+
+// Queued:
+// removes an abandoned ticket from the queue when an async waiter is dropped
+struct Queued<'a> {
+    limiter: &'a RateLimiter,
+    ticket: Ticket,
+    granted: bool,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        if !self.granted {
+            let ticket = self.ticket;
+            self.limiter
+                .state
+                .lock()
+                .unwrap()
+                .queue
+                .retain(|t| *t != ticket);
+            self.limiter.wake();
+        }
+    }
+}
+
+/// Slot of an admitted request, released when dropped.
+pub struct Permit {
+    limiter: Arc<RateLimiter>,
+    reserved: u64,
+}
+
+impl Permit {
+    /// Charges the difference between the estimated and the actual token count
+    /// (prompt plus output) once the response is known.
+    pub fn settle(&mut self, actual_tokens: u64) {
+        let mut state = self.limiter.state.lock().unwrap();
+        if let Some(bucket) = &mut state.tokens {
+            let extra = actual_tokens as f64 - self.reserved as f64;
+            bucket.available = (bucket.available - extra).min(bucket.capacity);
+        }
+        self.reserved = actual_tokens;
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().in_flight -= 1;
+        self.limiter.wake();
+    }
+}
+
+/// Rate limiters of a `ModelClient`: one for the whole client plus optional
+/// overrides per model.
+#[derive(Default)]
+pub struct RateLimiterSet {
+    default: Mutex<Option<Arc<RateLimiter>>>,
+    models: Mutex<HashMap<String, Arc<RateLimiter>>>,
+}
+
+impl RateLimiterSet {
+    /// Sets the limits of `model`, or of the whole client when `model` is `None`.
+    /// Passing `None` as limits removes the limiter.
+    ///
+    /// Requests already holding a permit keep it; new requests use the new limiter.
+    pub fn set(&self, model: Option<&str>, limits: Option<RateLimits>) {
+        let limiter = limits.map(|l| Arc::new(RateLimiter::new(l)));
+        match model {
+            Some(model) => {
+                let mut models = self.models.lock().unwrap();
+                match limiter {
+                    Some(limiter) => models.insert(model.to_string(), limiter),
+                    None => models.remove(model),
+                };
+            }
+            None => *self.default.lock().unwrap() = limiter,
+        }
+    }
+
+    /// Limiter that applies to `model`.
+    pub fn get(&self, model: &str) -> Option<Arc<RateLimiter>> {
+        let own = self.models.lock().unwrap().get(model).cloned();
+        own.or_else(|| self.default.lock().unwrap().clone())
+    }
+}
@@ -1,14 +1,48 @@
 use std::sync::Arc;
 
 use crate::{
-    model_llm::ModelLLM,
-    models::{gemini::GeminiProvider, model_client::ModelClient},
+    model_llm::{ModelLLM, Models},
+    models::{
+        fallback::{FailoverConditions, FallbackProvider, FallbackTarget},
+        gemini::GeminiProvider,
+        model_client::ModelClient,
+    },
+    traits::ModelProvider,
 };
 
 // selector:
 // this function is for matching enum model & return into string
 pub fn selector(model: ModelLLM) -> ModelClient {
+    ModelClient::new(provider(model))
+}
+
+// provider:
+// matching enum model & return the bare provider
+pub fn provider(model: ModelLLM) -> Arc<dyn ModelProvider> {
     match model {
-        ModelLLM::Gemini => ModelClient::new(Arc::new(GeminiProvider::new())),
+        ModelLLM::Gemini => Arc::new(GeminiProvider::new()),
     }
 }
+
+// fallback_selector:
+// same as selector() but backed by an ordered fallback chain of
+// (provider, model, api key) targets, a `None` key uses the key given to init()
+pub fn fallback_selector(
+    targets: Vec<(ModelLLM, Models, Option<String>)>,
+    conditions: FailoverConditions,
+) -> ModelClient {
+    let targets = targets
+        .into_iter()
+        .map(|(llm, model, api_key)| {
+            let target = FallbackTarget::new(provider(llm)).model(model);
+            match api_key {
+                Some(api_key) => target.api_key(api_key),
+                None => target,
+            }
+        })
+        .collect();
+
+    ModelClient::new(Arc::new(
+        FallbackProvider::chain(targets).conditions(conditions),
+    ))
+}
@@ -5,10 +5,13 @@ use axum::{
     extract::State,
     response::{Sse, sse::Event},
 };
-use futures::Stream;
+use futures::{Stream, StreamExt, future::Either};
+use serde_json::json;
 
+use crate::model::generation::generation::{GenerateRequest, StreamEvent};
 use crate::models::gemini::PromptInput;
 use crate::models::model_client::ModelClient;
+use crate::usage::budget::BudgetError;
 
 /// Handles streaming AI model responses
 ///
@@ -32,13 +35,15 @@ use crate::models::model_client::ModelClient;
 /// # Returns
 ///
 /// Returns an SSE stream where each event contains a text chunk from the AI response.
-/// The stream never returns errors to the client (uses `Infallible` type).
+/// The stream never returns errors to the client (uses `Infallible` type): a
+/// request refused before any chunk (budget, rate limit, no API key) is sent
+/// as a single `error` event, a [`BudgetError`] in its serialized form.
 ///
 /// # Behavior
 ///
-/// 1. Extracts API key and model name from shared state
-/// 2. Initiates streaming generation from the provider
-/// 3. Transforms provider chunks into SSE events
+/// 1. Initiates streaming generation through the model client
+/// 2. Transforms provider chunks into SSE events
+/// 3. Sends the request usage as a final `usage` event
 /// 4. Converts any chunk errors into error messages sent to the client
 ///
 /// # Example Request
@@ -60,27 +65,58 @@ use crate::models::model_client::ModelClient;
 /// data: a system that ensures
 ///
 /// data: memory safety...
+///
+/// event: usage
+/// data: {"type":"done","usage":{"prompt_tokens":4,"candidates_tokens":120,...}}
 /// ```
 pub async fn GenerateStreamResponse(
     State(client): State<Arc<ModelClient>>,
     Json(input): Json<PromptInput>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    let api_key = client.key.lock().unwrap().clone();
-    let model = client.model.lock().unwrap().clone();
-
-    // Initiate streaming generation from the AI provider
-    let base_stream = client
-        .provider
-        .generate_stream(api_key, model, input.prompt)
+    // Initiate streaming generation through the client so usage is recorded
+    let base_stream = match client
+        .GenerateStream(GenerateRequest::from_prompt(input.prompt))
         .await
-        .unwrap();
+    {
+        Ok(stream) => stream,
+        Err(e) => {
+            let event = Ok::<Event, Infallible>(error_event(&e));
+            return Sse::new(Either::Right(futures::stream::iter([event])));
+        }
+    };
 
     // Transform provider stream into SSE events
     // Errors in individual chunks are converted to error messages
-    let sse_stream = futures::stream::StreamExt::map(base_stream, |chunk| {
-        let text = chunk.unwrap_or_else(|e| format!("error: {e}"));
-        Ok::<Event, Infallible>(Event::default().data(text))
+    let sse_stream = base_stream.map(|event| {
+        let event = match event {
+            Ok(StreamEvent::Text { text }) => Event::default().data(text),
+            Ok(StreamEvent::Thought { text }) => Event::default().event("thought").data(text),
+            Ok(code @ StreamEvent::Code { .. }) => Event::default()
+                .event("code")
+                .data(serde_json::to_string(&code).unwrap_or_default()),
+            Ok(call @ StreamEvent::FunctionCall { .. }) => Event::default()
+                .event("function_call")
+                .data(serde_json::to_string(&call).unwrap_or_default()),
+            Ok(grounding @ StreamEvent::Grounding { .. }) => Event::default()
+                .event("grounding")
+                .data(serde_json::to_string(&grounding).unwrap_or_default()),
+            Ok(done @ StreamEvent::Done { .. }) => Event::default()
+                .event("usage")
+                .data(serde_json::to_string(&done).unwrap_or_default()),
+            Err(e) => Event::default().data(format!("error: {e}")),
+        };
+        Ok::<Event, Infallible>(event)
     });
 
-    Sse::new(sse_stream)
+    Sse::new(Either::Left(sse_stream))
+}
+
+// error_event:
+// a request that failed before streaming, budget rejections keep their typed form
+fn error_event(error: &anyhow::Error) -> Event {
+    let data = match error.downcast_ref::<BudgetError>() {
+        Some(budget) => json!({ "error": budget, "message": budget.to_string() }),
+        None => json!({ "message": error.to_string() }),
+    };
+    Event::default().event("error").data(data.to_string())
 }
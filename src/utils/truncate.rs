@@ -0,0 +1,190 @@
+use std::future::Future;
+
+use anyhow::{Result, anyhow};
+
+use crate::{
+    model::{
+        conversation::conversation::{Conversation, TurnRole, estimate_text_tokens},
+        generation::generation::GenerateRequest,
+    },
+    traits::ModelProvider,
+};
+
+/// Usage tag of the summaries written for [`TruncationPolicy::Summarize`].
+pub const TRUNCATION_TAG: &str = "truncation";
+
+/// Strategy applied by `ModelClient` when a conversation no longer fits into
+/// the model's input window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TruncationPolicy {
+    /// Never touch the conversation, let the provider reject it.
+    Disabled,
+    /// Drop the oldest content first, the system prompt included.
+    DropOldest,
+    /// Drop the oldest turns but always keep the system prompt.
+    #[default]
+    KeepSystemPrompt,
+    /// Replace the oldest turns with a model-written summary that is appended
+    /// to the system prompt.
+    Summarize,
+}
+
+/// Whether `conversation` may not fit into `limit` tokens, so [`truncate`]
+/// has to ask the provider for its size.
+///
+/// The local estimate can undercount up to ~4x (e.g. CJK text); below that
+/// margin the conversation fits for sure.
+pub fn may_exceed(conversation: &Conversation, limit: u64, policy: TruncationPolicy) -> bool {
+    policy != TruncationPolicy::Disabled && conversation.estimate_tokens() * 4 > limit
+}
+
+/// Shrinks `conversation` until it fits into `limit` tokens according to `policy`.
+///
+/// Conversations far below the limit are returned untouched without asking
+/// the provider. Otherwise token counts come from `count_tokens`; to avoid a round trip
+/// per dropped turn, the local estimate is scaled by the ratio between the
+/// provider count and the estimate to pick a cut point, which is then
+/// confirmed with the provider.
+///
+/// With [`TruncationPolicy::Summarize`] the dropped turns are summarized by
+/// `summarize`, in chunks that fit into the window, each one together with
+/// the summary of the chunks before it. `ModelClient` sends these requests
+/// through its metered path under [`TRUNCATION_TAG`].
+///
+/// # Errors
+/// Returns an error if counting or summarizing fails, or if the conversation
+/// cannot be made to fit (e.g. the latest user turn alone is over the limit).
+pub async fn truncate<S, Fut>(
+    provider: &dyn ModelProvider,
+    api_key: &str,
+    model: &str,
+    conversation: Conversation,
+    limit: u64,
+    policy: TruncationPolicy,
+    summarize: S,
+) -> Result<Conversation>
+where
+    S: Fn(GenerateRequest) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    if !may_exceed(&conversation, limit, policy) {
+        return Ok(conversation);
+    }
+
+    let mut total = provider.count_tokens(api_key, model, &conversation).await?;
+    if total <= limit {
+        return Ok(conversation);
+    }
+
+    let original = conversation.clone();
+    let mut current = conversation;
+    loop {
+        let scale = total as f64 / current.estimate_tokens().max(1) as f64;
+        let mut dropped = 0usize;
+        let mut dropped_system = false;
+
+        // drop until the scaled estimate fits, always dropping at least one
+        // turn so every provider round trip makes progress
+        loop {
+            let fits = (current.estimate_tokens() as f64 * scale) as u64 <= limit;
+            if fits && (dropped > 0 || dropped_system) {
+                break;
+            }
+            if policy == TruncationPolicy::DropOldest && current.system.is_some() {
+                current.system = None;
+                dropped_system = true;
+                continue;
+            }
+            if current.turns.len() <= 1 {
+                return Err(anyhow!(
+                    "Conversation does not fit into {} tokens even after truncation",
+                    limit
+                ));
+            }
+            current.turns.remove(0);
+            dropped += 1;
+            // keep the history starting with a user turn
+            while current.turns.len() > 1 && current.turns[0].role == TurnRole::Model {
+                current.turns.remove(0);
+                dropped += 1;
+            }
+        }
+
+        if policy == TruncationPolicy::Summarize {
+            current = summarize_dropped(&summarize, &original, current, limit, scale).await?;
+        }
+
+        total = provider.count_tokens(api_key, model, &current).await?;
+        if total <= limit {
+            return Ok(current);
+        }
+    }
+}
+
+// summarize_dropped:
+// folds every turn of `original` missing from `kept` into the system prompt;
+// the dropped turns are summarized chunk by chunk, each request holding at
+// most half of the window (turns longer than that are cut)
+async fn summarize_dropped<S, Fut>(
+    summarize: &S,
+    original: &Conversation,
+    kept: Conversation,
+    limit: u64,
+    scale: f64,
+) -> Result<Conversation>
+where
+    S: Fn(GenerateRequest) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let cut = original.turns.len() - kept.turns.len();
+    let budget = ((limit / 2) as f64 / scale.max(f64::MIN_POSITIVE)).max(1.0) as u64;
+
+    let mut chunks: Vec<String> = Vec::new();
+    for turn in &original.turns[..cut] {
+        let single = Conversation {
+            system: None,
+            turns: vec![turn.clone()],
+        };
+        let line: String = single
+            .to_prompt()
+            .chars()
+            .take(budget as usize * 4)
+            .collect();
+        match chunks.last_mut() {
+            Some(chunk) if estimate_text_tokens(chunk) + estimate_text_tokens(&line) <= budget => {
+                chunk.push_str(&line)
+            }
+            _ => chunks.push(line),
+        }
+    }
+
+    let mut summary: Option<String> = None;
+    for chunk in chunks {
+        let earlier = summary
+            .map(|summary| format!("Summary of the conversation so far:\n{}\n\n", summary))
+            .unwrap_or_default();
+        let request = GenerateRequest::from_prompt(format!(
+            "Summarize the following conversation in a few sentences. \
+             Keep names, facts and decisions, omit small talk.\n\n{}{}",
+            earlier, chunk
+        ));
+        let text = summarize(request)
+            .await
+            .map_err(|e| anyhow!("Failed to summarize conversation: {}", e))?;
+        summary = Some(text);
+    }
+    let summary = summary.unwrap_or_default();
+
+    let system = match &original.system {
+        Some(system) => format!(
+            "{}\n\nSummary of the earlier conversation:\n{}",
+            system, summary
+        ),
+        None => format!("Summary of the earlier conversation:\n{}", summary),
+    };
+
+    Ok(Conversation {
+        system: Some(system),
+        turns: kept.turns,
+    })
+}
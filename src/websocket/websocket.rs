@@ -1,4 +1,5 @@
 use crate::{
+    model::generation::generation::GenerateRequest,
     model::message::message::{Choice, Message, Role},
     models::model_client::ModelClient,
 };
@@ -58,14 +59,18 @@ pub async fn WebSocketController(mut socket: WebSocket, client: ModelClient) {
                     },
                     timestamp: "".into(),
                     loading: true,
+                    usage: None,
                 };
 
                 let _ = socket
                     .send(WsMessage::Text(to_string(&loading_msg).unwrap().into()))
                     .await;
 
-                match client.GenerateContent(text.to_string()).await {
-                    Ok(reply) => {
+                match client
+                    .Generate(GenerateRequest::from_prompt(text.to_string()))
+                    .await
+                {
+                    Ok(generation) => {
                         let response_msg = Message {
                             id: "response".into(),
                             models: "".into(),
@@ -73,11 +78,12 @@ pub async fn WebSocketController(mut socket: WebSocket, client: ModelClient) {
                             choice: Choice {
                                 role: Role {
                                     role: "assistant".into(),
-                                    content: reply,
+                                    content: generation.text,
                                 },
                             },
                             timestamp: "".into(),
                             loading: false,
+                            usage: generation.usage,
                         };
 
                         let _ = socket
@@ -97,6 +103,7 @@ pub async fn WebSocketController(mut socket: WebSocket, client: ModelClient) {
                             },
                             timestamp: "".into(),
                             loading: false,
+                            usage: None,
                         };
 
                         let _ = socket
@@ -0,0 +1,278 @@
+//! The agent loop, driven by scripted models and by a recorded Gemini
+//! function calling exchange.
+
+mod common;
+
+use std::time::Duration;
+
+use common::client;
+use ey_ai::{
+    agent::{
+        agent::Agent,
+        memory::Memory,
+        tool::tool_fn,
+        trace::{AgentEvent, StopReason},
+    },
+    model::{
+        conversation::conversation::{Turn, TurnRole},
+        function::function::{
+            FunctionCall, FunctionCallingMode, FunctionDeclaration, FunctionResponse,
+        },
+        generation::generation::Usage,
+    },
+    testing::{
+        cassette::CassetteServer,
+        mock::{MockProvider, MockReply},
+    },
+    traits::ModelProvider,
+};
+use futures::StreamExt;
+use serde_json::{Value, json};
+
+fn weather() -> FunctionDeclaration {
+    FunctionDeclaration::new("get_weather", "Current weather of a city").parameters(json!({
+        "type": "object",
+        "properties": { "city": { "type": "string" } },
+        "required": ["city"]
+    }))
+}
+
+fn agent(mock: &MockProvider) -> Agent {
+    Agent::new(client(mock.clone()))
+        .system("Use the tools to answer.")
+        .tool(tool_fn(weather(), |args: Value| async move {
+            Ok(json!({ "city": args["city"], "celsius": 18, "sky": "rain" }))
+        }))
+        .tool(tool_fn(
+            FunctionDeclaration::new("get_time", "Local time of a city"),
+            |_| async { anyhow::bail!("clock unavailable") },
+        ))
+}
+
+fn kinds(trace: &[AgentEvent]) -> Vec<&'static str> {
+    trace.iter().map(AgentEvent::kind).collect()
+}
+
+#[tokio::test]
+async fn runs_tools_until_the_model_answers() {
+    let usage = Usage {
+        total_tokens: 10,
+        ..Usage::default()
+    };
+    let mock = MockProvider::new().replies([
+        MockReply::function_calls(vec![
+            FunctionCall::new("get_weather", json!({"city": "Paris"})),
+            FunctionCall::new("get_time", json!({"city": "Paris"})),
+        ])
+        .usage(usage),
+        MockReply::text("Take an umbrella.").usage(usage),
+    ]);
+
+    let run = agent(&mock).run("Umbrella in Paris?").await.unwrap();
+
+    assert_eq!(run.output, "Take an umbrella.");
+    assert_eq!((run.stop, run.steps), (StopReason::Answered, 2));
+    assert_eq!(run.usage.total_tokens, 20);
+    assert_eq!(
+        kinds(&run.trace),
+        vec![
+            "model_output",
+            "tool_call",
+            "tool_call",
+            "tool_result",
+            "tool_error",
+            "model_output",
+            "finished"
+        ]
+    );
+
+    let calls = mock.calls();
+    let first = &calls[0].request;
+    assert_eq!(first.functions.len(), 2);
+    assert_eq!(
+        first.conversation.system.as_deref(),
+        Some("Use the tools to answer.")
+    );
+    let results = &calls[1].request.conversation.turns[2];
+    assert_eq!(results.role, TurnRole::User);
+    assert_eq!(results.function_responses[0].response["sky"], "rain");
+    assert_eq!(
+        results.function_responses[1].response,
+        json!({"error": "clock unavailable"})
+    );
+}
+
+#[tokio::test]
+async fn stops_at_the_step_and_token_limits() {
+    let looping = MockProvider::new().fallback(
+        MockReply::function_call("get_weather", json!({"city": "Oslo"})).usage(Usage {
+            total_tokens: 100,
+            ..Usage::default()
+        }),
+    );
+
+    let run = agent(&looping).max_steps(3).run("Weather?").await.unwrap();
+    assert_eq!((run.stop, run.steps), (StopReason::MaxSteps, 3));
+
+    let run = agent(&looping)
+        .max_tokens(150)
+        .run("Weather?")
+        .await
+        .unwrap();
+    assert_eq!((run.stop, run.steps), (StopReason::MaxTokens, 2));
+}
+
+#[tokio::test]
+async fn times_out_slow_tools_and_keeps_the_history_valid() {
+    let mock = MockProvider::new().reply(MockReply::function_call("slow", json!({})));
+    let memory = Memory::new();
+    let agent = Agent::new(client(mock.clone()))
+        .tool(tool_fn(
+            FunctionDeclaration::new("slow", "Never returns in time"),
+            |_| async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(json!({}))
+            },
+        ))
+        .memory(memory.clone())
+        .timeout(Duration::from_millis(50));
+
+    let run = agent.run("Go").await.unwrap();
+
+    assert_eq!(run.stop, StopReason::Timeout);
+    assert!(matches!(&run.trace[2], AgentEvent::ToolError { error, .. } if error == "Timed out"));
+    let turns = memory.turns();
+    assert_eq!(turns.len(), 3);
+    assert_eq!(
+        turns[1].function_calls.len(),
+        turns[2].function_responses.len()
+    );
+}
+
+#[tokio::test]
+async fn plans_first_and_streams_the_trace_with_memory() {
+    let mock = MockProvider::new().replies([
+        MockReply::text("1. Check the weather\n2. Answer"),
+        MockReply::function_call("get_weather", json!({"city": "Rome"})),
+        MockReply::text("Sunny enough."),
+        MockReply::text("You asked about Rome."),
+    ]);
+    let memory = Memory::new();
+    let agent = agent(&mock).plan(true).memory(memory.clone());
+
+    let events: Vec<AgentEvent> = agent.run_stream("Umbrella in Rome?").collect().await;
+
+    assert_eq!(
+        kinds(&events),
+        vec![
+            "plan",
+            "model_output",
+            "tool_call",
+            "tool_result",
+            "model_output",
+            "finished"
+        ]
+    );
+    assert!(matches!(
+        events.last(),
+        Some(AgentEvent::Finished { output, stop: StopReason::Answered, steps: 2, .. })
+            if output == "Sunny enough."
+    ));
+    let planning = &mock.calls()[0].request;
+    assert_eq!(planning.function_calling, Some(FunctionCallingMode::None));
+    assert!(mock.calls()[1].request.function_calling.is_none());
+    assert_eq!(memory.len(), 5);
+
+    // the next run continues the conversation
+    agent.plan(false).run("What did I ask?").await.unwrap();
+    let turns = &mock.last_call().unwrap().request.conversation.turns;
+    assert_eq!(turns.len(), 6);
+    assert_eq!(turns[0].text, "Umbrella in Rome?");
+}
+
+#[tokio::test]
+async fn model_failures_end_the_stream_with_an_error() {
+    let mock = MockProvider::new();
+
+    let events: Vec<AgentEvent> = agent(&mock).run_stream("Hi").collect().await;
+
+    assert_eq!(kinds(&events), vec!["error", "finished"]);
+    assert!(agent(&mock).run("Hi").await.is_err());
+}
+
+#[tokio::test]
+async fn dropping_the_stream_stops_the_run() {
+    let mock = MockProvider::new().fallback(
+        MockReply::function_call("get_weather", json!({"city": "Oslo"}))
+            .latency(Duration::from_millis(20)),
+    );
+    let mut events = agent(&mock).max_steps(50).run_stream("Weather in Oslo?");
+
+    assert_eq!(events.next().await.unwrap().kind(), "model_output");
+    drop(events);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(mock.call_count() <= 2, "{} calls", mock.call_count());
+}
+
+#[test]
+fn a_memory_window_keeps_the_last_exchange_whole() {
+    let weather = FunctionCall::new("get_weather", json!({"city": "Oslo"}));
+    let call = || Turn::function_calls("", vec![weather.clone()]);
+    let responses = || {
+        Turn::function_responses(vec![FunctionResponse::new(
+            &weather,
+            json!({"celsius": 18}),
+        )])
+    };
+    let memory = Memory::window(3);
+
+    memory.extend([
+        Turn::user("Weather?"),
+        call(),
+        responses(),
+        Turn::model("Rain."),
+    ]);
+    assert_eq!(memory.len(), 4);
+
+    memory.extend([Turn::user("And now?"), Turn::model("Sun.")]);
+    let texts: Vec<_> = memory.turns().into_iter().map(|t| t.text).collect();
+    assert_eq!(texts, ["And now?", "Sun."]);
+
+    memory.extend([
+        Turn::user("Tomorrow?"),
+        call(),
+        responses(),
+        Turn::model("Snow."),
+    ]);
+    assert_eq!(memory.turns()[0].text, "Tomorrow?");
+    assert_eq!(memory.len(), 4);
+}
+
+#[tokio::test]
+async fn gemini_function_calls_round_trip_with_thought_signatures() {
+    let cassette = format!(
+        "{}/tests/cassettes/function_calling.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let agent = Agent::new(client(server.gemini()))
+        .system("Use the tools to answer.")
+        .tool(tool_fn(weather(), |args: Value| async move {
+            Ok(json!({ "city": args["city"], "celsius": 18, "sky": "rain" }))
+        }));
+
+    let run = agent.run("Do I need an umbrella in Paris?").await.unwrap();
+
+    assert_eq!(
+        run.output,
+        "Yes, take an umbrella: it is 18°C and raining in Paris."
+    );
+    assert_eq!(run.usage.total_tokens, 135);
+    let AgentEvent::ToolCall { call, .. } = &run.trace[1] else {
+        panic!("expected a tool call, got {:?}", run.trace[1]);
+    };
+    assert_eq!(call.args, json!({"city": "Paris"}));
+    assert_eq!(call.thought_signature.as_deref(), Some("c2lnbmF0dXJlLTE="));
+    assert!(server.unused().is_empty());
+}
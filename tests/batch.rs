@@ -0,0 +1,149 @@
+//! Batch jobs against a recorded Gemini exchange: submission, waiting with a
+//! deadline, results correlated by request id and their usage booked under
+//! the key that submitted the job.
+
+mod common;
+
+use std::time::Duration;
+
+use common::client;
+use ey_ai::{
+    model::{
+        batch::batch::{BatchRequest, BatchState},
+        generation::generation::GenerateRequest,
+    },
+    testing::cassette::CassetteServer,
+    usage::{
+        budget::{Budget, DEFAULT_TENANT},
+        ledger::UsageFilter,
+    },
+};
+
+const POLL: Duration = Duration::from_millis(10);
+
+#[tokio::test]
+async fn results_are_correlated_and_booked_under_the_submitting_key() {
+    let cassette = format!("{}/tests/cassettes/batch.json", env!("CARGO_MANIFEST_DIR"));
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini())
+        .add_key("key-a", 1)
+        .add_key("key-b", 1);
+    let request = |prompt: &str, tag: &str| GenerateRequest::from_prompt(prompt).tag(tag);
+
+    let job = client
+        .SubmitBatch(
+            "ticket-summaries",
+            vec![
+                BatchRequest::new("q1", request("Summarize ticket 1182", "refunds")),
+                BatchRequest::new("q2", request("Summarize ticket 1190", "it")),
+                BatchRequest::new("q3", request("Summarize ticket 1191", "it")),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(job.name, "batches/tickets");
+    let submitter = client
+        .keys
+        .stats()
+        .into_iter()
+        .find(|k| k.requests == 1)
+        .unwrap()
+        .key;
+
+    let error = client
+        .WaitBatch(&job.name, POLL, Duration::ZERO)
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("Running"), "{}", error);
+    let done = client
+        .WaitBatch(&job.name, POLL, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(done.state, BatchState::Succeeded);
+    assert_eq!(done.stats.failed_request_count, 1);
+
+    // answered out of order, matched by id
+    let mut results = client.BatchResults(&job.name).await.unwrap();
+    results.sort_by(|a, b| a.id.cmp(&b.id));
+    let texts: Vec<_> = results
+        .iter()
+        .map(|r| r.generation.as_ref().map(|g| g.text.as_str()))
+        .collect();
+    assert_eq!(
+        texts,
+        [
+            Some("Customer asks for a refund of order 1182."),
+            Some("The printer on floor 3 jams on duplex jobs."),
+            None,
+        ]
+    );
+    assert_eq!(
+        results[2].error.as_deref(),
+        Some("Request contains an invalid argument.")
+    );
+    assert!(server.unused().is_empty(), "{:?}", server.unused());
+
+    // polling and reading went to the submitting key, not through the pool
+    let requests: u64 = client.keys.stats().iter().map(|k| k.requests).sum();
+    assert_eq!(requests, 1);
+    let report = client.usage.query(&UsageFilter {
+        group_by: Some("key,tag".into()),
+        ..Default::default()
+    });
+    let mut booked: Vec<_> = report
+        .records
+        .iter()
+        .map(|r| {
+            (
+                r.key_id.clone().unwrap(),
+                r.tag.clone().unwrap(),
+                r.totals.usage.total_tokens,
+            )
+        })
+        .collect();
+    booked.sort();
+    assert_eq!(
+        booked,
+        [
+            (submitter.clone(), "it".to_string(), 21),
+            (submitter.clone(), "refunds".to_string(), 19),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_job_cancelled_elsewhere_gives_back_its_reservations() {
+    let cassette = format!(
+        "{}/tests/cassettes/batch_cancelled.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini());
+    client.budgets.set_budget(
+        DEFAULT_TENANT,
+        Budget {
+            max_tokens: Some(10_000),
+            ..Default::default()
+        },
+    );
+    let reserved = || client.budgets.tenant_usage(DEFAULT_TENANT).reserved_tokens;
+
+    let job = client
+        .SubmitBatch(
+            "ticket-summaries",
+            vec![BatchRequest::new(
+                "q1",
+                GenerateRequest::from_prompt("Summarize ticket 1182"),
+            )],
+        )
+        .await
+        .unwrap();
+    assert!(reserved() > 0);
+
+    // cancelled from the console, not through this client
+    let job = client.GetBatch(&job.name).await.unwrap();
+    assert_eq!(job.state, BatchState::Cancelled);
+    assert_eq!(reserved(), 0);
+    assert!(server.unused().is_empty(), "{:?}", server.unused());
+}
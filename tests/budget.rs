@@ -0,0 +1,198 @@
+//! Tenant budgets: hard and soft limits, reservations of requests in flight
+//! and settling of streams that never reach `Done`.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{Router, routing::post};
+use common::client;
+use ey_ai::{
+    model::{
+        conversation::conversation::Conversation,
+        generation::generation::{GenerateRequest, Usage},
+    },
+    models::error::ProviderError,
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+    usage::budget::{Budget, BudgetError, DEFAULT_TENANT, Enforcement},
+    utils::stream::GenerateStreamResponse,
+};
+use futures::{StreamExt, future::join_all};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+const PROMPT: &str = "Summarize the release notes of the last three versions.";
+
+fn estimate() -> u64 {
+    Conversation::from_prompt(PROMPT.to_string()).estimate_tokens()
+}
+
+fn request() -> GenerateRequest {
+    GenerateRequest::from_prompt(PROMPT).tenant("acme")
+}
+
+fn tokens(total: u64) -> Usage {
+    Usage {
+        total_tokens: total,
+        ..Default::default()
+    }
+}
+
+fn max_tokens(limit: u64, enforcement: Enforcement) -> Budget {
+    Budget {
+        max_tokens: Some(limit),
+        enforcement,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn rejects_requests_over_a_hard_limit() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok").usage(tokens(100)));
+    let client = client(mock.clone());
+    client
+        .budgets
+        .set_budget("acme", max_tokens(150, Enforcement::Hard));
+
+    client.Generate(request()).await.unwrap();
+    client.Generate(request()).await.unwrap();
+    let error = client.Generate(request()).await.err().unwrap();
+
+    assert_eq!(
+        error.downcast_ref::<BudgetError>(),
+        Some(&BudgetError::TokensExceeded {
+            tenant: "acme".into(),
+            used: 200,
+            limit: 150,
+        })
+    );
+    assert_eq!(mock.call_count(), 2);
+    // other tenants are not limited
+    client
+        .Generate(GenerateRequest::from_prompt(PROMPT))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn warns_over_a_soft_limit() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok").usage(tokens(100)));
+    let client = client(mock.clone());
+    client
+        .budgets
+        .set_budget("acme", max_tokens(50, Enforcement::Soft));
+    let mut warnings = client.budgets.subscribe();
+
+    client.Generate(request()).await.unwrap();
+    client.Generate(request()).await.unwrap();
+
+    let warning = warnings.try_recv().unwrap();
+    assert_eq!(warning.tenant, "acme");
+    assert!(matches!(
+        warning.error,
+        BudgetError::TokensExceeded { used: 100, .. }
+    ));
+    assert_eq!(mock.call_count(), 2);
+}
+
+#[tokio::test]
+async fn requests_in_flight_count_against_the_limit() {
+    let mock = MockProvider::new().fallback(
+        MockReply::text("ok")
+            .usage(tokens(estimate()))
+            .latency(Duration::from_millis(100)),
+    );
+    let client = client(mock.clone());
+    client
+        .budgets
+        .set_budget("acme", max_tokens(estimate() * 5 / 2, Enforcement::Hard));
+
+    let results = join_all((0..3).map(|_| client.Generate(request()))).await;
+
+    assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 2);
+    assert_eq!(mock.call_count(), 2);
+    let usage = client.budgets.tenant_usage("acme");
+    assert_eq!(usage.tokens, estimate() * 2);
+    assert_eq!(usage.reserved_tokens, 0);
+}
+
+#[tokio::test]
+async fn failed_requests_give_back_their_reservation() {
+    let mock = MockProvider::new().reply(MockReply::error(ProviderError::Status {
+        status: 500,
+        message: "internal".into(),
+    }));
+    let client = client(mock);
+    client
+        .budgets
+        .set_budget("acme", max_tokens(1_000, Enforcement::Hard));
+
+    assert!(client.Generate(request()).await.is_err());
+
+    let usage = client.budgets.tenant_usage("acme");
+    assert_eq!((usage.tokens, usage.reserved_tokens), (0, 0));
+}
+
+#[tokio::test]
+async fn streams_ending_early_are_charged_the_prompt() {
+    let mock = MockProvider::new().replies([
+        MockReply::stream(["one", "two", "three"]).usage(tokens(500)),
+        MockReply::stream(["one"]).then_fail(ProviderError::Status {
+            status: 500,
+            message: "internal".into(),
+        }),
+    ]);
+    let client = client(mock);
+    client
+        .budgets
+        .set_budget("acme", max_tokens(10_000, Enforcement::Hard));
+
+    // dropped after the first chunk
+    let mut stream = client.GenerateStream(request()).await.unwrap();
+    stream.next().await.unwrap().unwrap();
+    drop(stream);
+    let usage = client.budgets.tenant_usage("acme");
+    assert_eq!((usage.tokens, usage.reserved_tokens), (estimate(), 0));
+
+    // ended by an error
+    let events: Vec<_> = client
+        .GenerateStream(request())
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert!(events.last().unwrap().is_err());
+    let usage = client.budgets.tenant_usage("acme");
+    assert_eq!((usage.tokens, usage.reserved_tokens), (estimate() * 2, 0));
+    assert_eq!(client.usage.query(&Default::default()).total.requests, 2);
+}
+
+#[tokio::test]
+async fn the_stream_endpoint_reports_a_rejection_as_an_error_event() {
+    let mock = MockProvider::new().fallback(MockReply::stream(["ok"]));
+    let client = client(mock.clone());
+    client
+        .budgets
+        .set_budget(DEFAULT_TENANT, max_tokens(1, Enforcement::Hard));
+
+    let app = Router::new()
+        .route("/generate-stream", post(GenerateStreamResponse))
+        .with_state(Arc::new(client));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/generate-stream", addr))
+        .json(&json!({ "prompt": PROMPT }))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let body = response.text().await.unwrap();
+
+    assert!(body.starts_with("event: error\n"), "{}", body);
+    assert!(body.contains(r#""kind":"tokens_exceeded""#), "{}", body);
+    assert_eq!(mock.call_count(), 0);
+}
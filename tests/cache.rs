@@ -0,0 +1,169 @@
+//! The response cache: hits and misses, per request cache control, cached
+//! streams, the `/cache` endpoint and the files of the disk backend.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::{Router, routing::get};
+use common::client;
+use ey_ai::{
+    cache::{
+        backend::{CacheBackend, CacheEntry, DiskCache, MemoryCache},
+        endpoint::CacheStatsResponse,
+        response::{CacheControl, CacheSnapshot, ResponseCache},
+    },
+    model::generation::generation::{GenerateRequest, Generation, StreamEvent},
+    testing::{
+        cassette::CassetteServer,
+        mock::{MockProvider, MockReply},
+    },
+    traits::ModelProvider,
+};
+use futures::StreamExt;
+use tokio::net::TcpListener;
+
+fn cache() -> Option<ResponseCache> {
+    Some(ResponseCache::new(MemoryCache::new(16)))
+}
+
+#[tokio::test]
+async fn answers_repeated_requests_from_the_cache() {
+    let mock = MockProvider::new().replies([MockReply::text("first"), MockReply::text("second")]);
+    let client = client(mock.clone()).set_cache(cache());
+
+    let miss = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    let hit = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    let other = client
+        .Generate(GenerateRequest::from_prompt("hello"))
+        .await
+        .unwrap();
+
+    assert_eq!((miss.text.as_str(), hit.text.as_str()), ("first", "first"));
+    assert_eq!(other.text, "second");
+    assert_eq!(mock.call_count(), 2);
+    let stats = client.cache.lock().unwrap().clone().unwrap().snapshot();
+    assert_eq!((stats.hits, stats.misses, stats.stores), (1, 2, 2));
+    // only upstream calls are charged
+    assert_eq!(client.usage.query(&Default::default()).total.requests, 2);
+}
+
+#[tokio::test]
+async fn bypass_and_refresh_skip_the_cached_reply() {
+    let mock = MockProvider::new().replies([
+        MockReply::text("first"),
+        MockReply::text("bypassed"),
+        MockReply::text("refreshed"),
+    ]);
+    let client = client(mock.clone()).set_cache(cache());
+    let request = |control| GenerateRequest::from_prompt("hi").cache(control);
+
+    client
+        .Generate(request(CacheControl::Default))
+        .await
+        .unwrap();
+    let bypassed = client
+        .Generate(request(CacheControl::Bypass))
+        .await
+        .unwrap();
+    let refreshed = client
+        .Generate(request(CacheControl::Refresh))
+        .await
+        .unwrap();
+    let cached = client
+        .Generate(request(CacheControl::Default))
+        .await
+        .unwrap();
+
+    assert_eq!(bypassed.text, "bypassed");
+    assert_eq!(refreshed.text, "refreshed");
+    assert_eq!(cached.text, "refreshed");
+    assert_eq!(mock.call_count(), 3);
+}
+
+#[tokio::test]
+async fn caches_streams_only_when_complete() {
+    // the first stream has a malformed chunk between two good ones
+    let cassette = format!(
+        "{}/tests/cassettes/broken_stream.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini()).set_cache(cache());
+    let stream = || client.GenerateStream(GenerateRequest::from_prompt("Name three colors"));
+
+    let broken: Vec<_> = stream().await.unwrap().collect().await;
+    assert!(broken.iter().any(|event| event.is_err()));
+    assert!(matches!(broken.last(), Some(Ok(StreamEvent::Done { .. }))));
+
+    let complete: Vec<_> = stream().await.unwrap().collect().await;
+    assert!(complete.iter().all(|event| event.is_ok()));
+    assert!(server.unused().is_empty());
+
+    // replayed from the cache as a single chunk
+    let replayed: Vec<_> = stream().await.unwrap().collect().await;
+    assert!(matches!(
+        &replayed[0],
+        Ok(StreamEvent::Text { text }) if text == "Red, green, blue."
+    ));
+}
+
+#[tokio::test]
+async fn serves_the_cache_stats() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = client(mock).set_cache(cache());
+    for _ in 0..3 {
+        client
+            .Generate(GenerateRequest::from_prompt("hi"))
+            .await
+            .unwrap();
+    }
+
+    let app = Router::new()
+        .route("/cache", get(CacheStatsResponse))
+        .with_state(Arc::new(client));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let url = format!("http://{}/cache", addr);
+    let stats: Option<CacheSnapshot> = reqwest::get(url).await.unwrap().json().await.unwrap();
+    let stats = stats.unwrap();
+    assert_eq!((stats.hits, stats.misses, stats.entries), (2, 1, 1));
+}
+
+#[test]
+fn the_disk_cache_only_touches_its_own_files() {
+    let dir = std::env::temp_dir().join(format!("ey-ai-disk-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let key = |prompt: &str| ResponseCache::key("model", &GenerateRequest::from_prompt(prompt));
+    let entry = |text: &str| CacheEntry {
+        generation: Generation {
+            text: text.into(),
+            ..Default::default()
+        },
+        stored_at: 0,
+        expires_at: None,
+    };
+    // room for a single entry
+    let size = serde_json::to_vec(&entry("second")).unwrap().len() as u64;
+    let disk = DiskCache::new(&dir, size).unwrap();
+    std::fs::write(dir.join("settings.json"), "x".repeat(1000)).unwrap();
+
+    // the foreign file neither counts towards the limit nor gets evicted
+    assert_eq!(disk.put(&key("a"), entry("first")), 0);
+    assert_eq!(disk.put(&key("b"), entry("second")), 1);
+    assert_eq!(disk.len(), 1);
+    assert_eq!(disk.get(&key("b")).unwrap().generation.text, "second");
+
+    disk.clear();
+    assert_eq!(disk.len(), 0);
+    assert!(dir.join("settings.json").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,178 @@
+//! Coalescing of identical in-flight requests: one upstream call shared by
+//! every caller, for generations, errors and streams joined late.
+
+mod common;
+
+use std::time::Duration;
+
+use common::client;
+use ey_ai::{
+    model::generation::generation::{GenerateRequest, StreamEvent, Usage},
+    models::{error::ProviderError, model_client::ModelClient},
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+    usage::budget::Budget,
+};
+use futures::StreamExt;
+
+const LATENCY: Duration = Duration::from_millis(100);
+
+fn deterministic(prompt: &str) -> GenerateRequest {
+    GenerateRequest::from_prompt(prompt).temperature(0.0)
+}
+
+fn coalescing(mock: &MockProvider) -> ModelClient {
+    client(mock.clone()).set_coalescing(true)
+}
+
+fn text(event: &StreamEvent) -> &str {
+    match event {
+        StreamEvent::Text { text } => text,
+        _ => "",
+    }
+}
+
+#[tokio::test]
+async fn identical_requests_share_one_upstream_call() {
+    let usage = Usage {
+        total_tokens: 10,
+        ..Default::default()
+    };
+    let mock =
+        MockProvider::new().fallback(MockReply::text("Paris.").usage(usage).latency(LATENCY));
+    let client = coalescing(&mock);
+
+    let (a, b, c) = tokio::join!(
+        client.Generate(deterministic("Capital of France?")),
+        client.Generate(deterministic("Capital of France?")),
+        client.Generate(deterministic("Capital of France?"))
+    );
+
+    for generation in [a, b, c] {
+        assert_eq!(generation.unwrap().text, "Paris.");
+    }
+    assert_eq!(mock.call_count(), 1);
+    let stats = client.coalescer.snapshot();
+    assert_eq!(
+        (stats.requests, stats.coalesced, stats.in_flight),
+        (3, 2, 0)
+    );
+    // charged once
+    let total = client.usage.query(&Default::default()).total;
+    assert_eq!((total.requests, total.usage.total_tokens), (1, 10));
+
+    // a later request goes upstream again
+    client
+        .Generate(deterministic("Capital of France?"))
+        .await
+        .unwrap();
+    assert_eq!(mock.call_count(), 2);
+}
+
+#[tokio::test]
+async fn only_identical_deterministic_requests_are_merged() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok").latency(LATENCY));
+    let client = coalescing(&mock);
+    let (a, b, c, d) = tokio::join!(
+        client.Generate(deterministic("hi")),
+        client.Generate(deterministic("hello")),
+        client.Generate(GenerateRequest::from_prompt("hi")),
+        client.Generate(GenerateRequest::from_prompt("hi").temperature(0.7))
+    );
+    for generation in [a, b, c, d] {
+        generation.unwrap();
+    }
+    assert_eq!(mock.call_count(), 4);
+
+    let client = client.set_coalescing(false);
+    let (a, b) = tokio::join!(
+        client.Generate(deterministic("hi")),
+        client.Generate(deterministic("hi"))
+    );
+    a.unwrap();
+    b.unwrap();
+    assert_eq!(mock.call_count(), 6);
+}
+
+#[tokio::test]
+async fn every_caller_gets_the_provider_error() {
+    let mock = MockProvider::new().fallback(
+        MockReply::error(ProviderError::Status {
+            status: 500,
+            message: "internal".into(),
+        })
+        .latency(LATENCY),
+    );
+    let client = coalescing(&mock);
+
+    let (a, b) = tokio::join!(
+        client.Generate(deterministic("hi")),
+        client.Generate(deterministic("hi"))
+    );
+
+    for result in [a, b] {
+        let error = result.err().unwrap();
+        assert!(matches!(
+            ProviderError::classify(&error),
+            Some(ProviderError::Status { status: 500, .. })
+        ));
+    }
+    assert_eq!(mock.call_count(), 1);
+}
+
+#[tokio::test]
+async fn a_late_subscriber_receives_the_chunks_it_missed() {
+    let mock = MockProvider::new().fallback(MockReply::stream_with_delays([
+        (Duration::ZERO, "Red, "),
+        (LATENCY, "green."),
+    ]));
+    let client = coalescing(&mock);
+
+    let mut first = client
+        .GenerateStream(deterministic("Name two colors"))
+        .await
+        .unwrap();
+    let red = first.next().await.unwrap().unwrap();
+    assert_eq!(text(&red), "Red, ");
+
+    let late = client
+        .GenerateStream(deterministic("Name two colors"))
+        .await
+        .unwrap();
+    let (first, late): (Vec<_>, Vec<_>) = tokio::join!(first.collect(), late.collect());
+
+    let late: Vec<_> = late.into_iter().map(Result::unwrap).collect();
+    let texts: Vec<_> = late.iter().map(text).filter(|t| !t.is_empty()).collect();
+    assert_eq!(texts, ["Red, ", "green."]);
+    assert!(matches!(late.last(), Some(StreamEvent::Done { .. })));
+    assert!(matches!(first.last(), Some(Ok(StreamEvent::Done { .. }))));
+    assert_eq!(mock.call_count(), 1);
+    assert_eq!(client.coalescer.snapshot().coalesced, 1);
+}
+
+#[tokio::test]
+async fn a_call_whose_waiters_are_gone_still_finishes() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok").latency(LATENCY));
+    let client = coalescing(&mock).add_key("pool-key", 1);
+    client.budgets.set_budget(
+        "acme",
+        Budget {
+            max_tokens: Some(1000),
+            ..Default::default()
+        },
+    );
+
+    let cancelled = tokio::time::timeout(
+        LATENCY / 4,
+        client.Generate(deterministic("hi").tenant("acme")),
+    )
+    .await;
+    assert!(cancelled.is_err());
+    assert!(client.budgets.tenant_usage("acme").reserved_tokens > 0);
+
+    tokio::time::sleep(LATENCY * 2).await;
+    assert_eq!(client.budgets.tenant_usage("acme").reserved_tokens, 0);
+    assert_eq!(client.keys.stats()[0].in_flight, 0);
+    assert_eq!(client.coalescer.snapshot().in_flight, 0);
+    assert_eq!(mock.call_count(), 1);
+}
@@ -0,0 +1,10 @@
+//! Fixtures shared by the integration tests.
+
+use std::sync::Arc;
+
+use ey_ai::{model_llm::Models, models::model_client::ModelClient, traits::ModelProvider};
+
+/// A client on `provider` with a test key and Gemini 2.5 Flash.
+pub fn client(provider: impl ModelProvider + 'static) -> ModelClient {
+    ModelClient::new(Arc::new(provider)).init("test-key".into(), Models::Gemini25Flash)
+}
@@ -0,0 +1,87 @@
+//! Context caching of shared prefixes with `GenerateWithPrefix`, against
+//! recorded Gemini `cachedContents` exchanges.
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::client;
+use ey_ai::{
+    model::{conversation::conversation::Conversation, generation::generation::GenerateRequest},
+    models::model_client::{ContextCache, ModelClient},
+    testing::{
+        cassette::CassetteServer,
+        mock::{MockProvider, MockReply},
+    },
+    traits::ModelProvider,
+};
+
+fn handbook() -> Conversation {
+    Conversation::from_prompt("Refunds are accepted within 30 days, approved by team leads.")
+}
+
+fn ask(client: &ModelClient, question: &str) -> impl Future<Output = String> {
+    let (client, question) = (client.clone(), question.to_string());
+    async move {
+        client
+            .GenerateWithPrefix(&handbook(), GenerateRequest::from_prompt(question))
+            .await
+            .unwrap()
+            .text
+    }
+}
+
+#[tokio::test]
+async fn sends_refused_prefixes_inline_until_they_are_offered_again() {
+    let cassette = format!(
+        "{}/tests/cassettes/context_cache.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini());
+
+    // refused as too small, then not offered again right away
+    assert_eq!(ask(&client, "Hi").await, "Sent inline.");
+    assert_eq!(ask(&client, "Hi again").await, "Still inline.");
+
+    // once the refusal expired the prefix is cached, once for both callers
+    for slot in client.context_caches.lock().unwrap().values() {
+        *slot.try_lock().unwrap() = Some(ContextCache::Refused {
+            until: Instant::now(),
+        });
+    }
+    let (refunds, approvals) = tokio::join!(
+        ask(&client, "What is the refund window?"),
+        ask(&client, "Who approves refunds?")
+    );
+
+    assert_eq!(
+        (refunds.as_str(), approvals.as_str()),
+        ("30 days.", "Team leads.")
+    );
+    assert!(server.unused().is_empty(), "{:?}", server.unused());
+}
+
+#[tokio::test]
+async fn other_prefixes_do_not_wait_for_a_cache_being_created() {
+    let client = client(MockProvider::new().fallback(MockReply::text("ok")));
+    assert_eq!(ask(&client, "Hi").await, "ok");
+
+    // a slot being filled only holds callers of its own prefix
+    let busy = client
+        .context_caches
+        .lock()
+        .unwrap()
+        .values()
+        .next()
+        .unwrap()
+        .clone();
+    let _creating = busy.lock().await;
+    let other = Conversation::from_prompt("Another prefix");
+    let answer = tokio::time::timeout(
+        Duration::from_secs(5),
+        client.GenerateWithPrefix(&other, GenerateRequest::from_prompt("Hi")),
+    )
+    .await;
+    assert_eq!(answer.unwrap().unwrap().text, "ok");
+}
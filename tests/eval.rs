@@ -0,0 +1,224 @@
+//! The eval harness, run against scripted models.
+
+mod common;
+
+use common::client;
+use ey_ai::{
+    eval::{
+        assertion::Assertion,
+        dataset::{Dataset, EvalCase},
+        report::EvalReport,
+        runner::{EvalTarget, Evaluator},
+        schema,
+    },
+    model::generation::generation::Usage,
+    models::error::ProviderError,
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+};
+use serde_json::json;
+
+const DATASET: &str = r#"
+name: smoke
+cases:
+  - id: capital
+    prompt: Capital of France?
+    assert:
+      - type: contains
+        value: paris
+        ignore_case: true
+      - type: regex
+        pattern: "^\\w+\\.?$"
+  - id: sum
+    system: Answer with a number only.
+    prompt: 2 + 2
+    assert:
+      - type: exact
+        value: "4"
+  - id: person
+    prompt: A person as JSON
+    assert:
+      - type: json_schema
+        schema:
+          type: object
+          required: [name, age]
+          properties:
+            name: { type: string }
+            age: { type: integer, minimum: 0 }
+      - type: predicate
+        name: short
+"#;
+
+// answers every case of DATASET, `sum` wrongly when `wrong_sum` is set
+fn model(wrong_sum: bool) -> MockProvider {
+    MockProvider::new().respond_with(move |request| {
+        let usage = Usage {
+            prompt_tokens: 10,
+            candidates_tokens: 5,
+            total_tokens: 15,
+            ..Usage::default()
+        };
+        let text = match request.conversation.turns[0].text.as_str() {
+            "Capital of France?" => "Paris.",
+            "2 + 2" if wrong_sum => "5",
+            "2 + 2" => "4",
+            _ => "```json\n{\"name\": \"Ada\", \"age\": 36}\n```",
+        };
+        MockReply::text(text).usage(usage)
+    })
+}
+
+fn evaluator(mock: &MockProvider) -> Evaluator {
+    Evaluator::new()
+        .target(EvalTarget::new("flash", client(mock.clone())))
+        .predicate("short", |_, output| {
+            anyhow::ensure!(output.len() < 100, "answer too long");
+            Ok(())
+        })
+}
+
+#[tokio::test]
+async fn grades_every_case_and_summarizes_usage() {
+    let mock = model(false);
+    let dataset = Dataset::from_yaml(DATASET).unwrap();
+
+    let report = evaluator(&mock).run(&dataset).await.unwrap();
+
+    let target = report.target("flash").unwrap();
+    assert_eq!(target.model, "gemini-2.5-flash");
+    assert!(report.passed(), "{}", report.to_markdown());
+    assert_eq!(target.summary.cases, 3);
+    assert_eq!(target.summary.pass_rate, 1.0);
+    assert_eq!(target.summary.total_tokens, 45);
+    assert!(target.summary.cost_usd > 0.0);
+
+    let sum = mock
+        .calls()
+        .into_iter()
+        .find(|call| call.request.conversation.turns[0].text == "2 + 2")
+        .unwrap();
+    assert_eq!(
+        sum.request.conversation.system.as_deref(),
+        Some("Answer with a number only.")
+    );
+    assert_eq!(sum.request.tag.as_deref(), Some("eval"));
+}
+
+#[tokio::test]
+async fn failed_requests_and_assertions_fail_the_case() {
+    let mock = MockProvider::new()
+        .reply(MockReply::text("{\"name\": 7}"))
+        .reply(MockReply::error(ProviderError::Timeout))
+        .reply(MockReply::text("Anything goes"));
+    let dataset = Dataset::new(
+        "failures",
+        vec![
+            EvalCase::new("schema", "A person").assert(Assertion::JsonSchema {
+                schema: json!({"type": "object", "required": ["age"], "properties": {"name": {"type": "string"}}}),
+            }),
+            EvalCase::new("timeout", "Anything"),
+            EvalCase::new("unchecked", "Anything"),
+        ],
+    );
+
+    let report = evaluator(&mock).concurrency(1).run(&dataset).await.unwrap();
+
+    let cases = &report.targets[0].cases;
+    assert!(!cases[0].passed);
+    let reason = cases[0].assertions[0].reason.as_deref().unwrap();
+    assert!(reason.contains("missing property age"), "{}", reason);
+    assert!(reason.contains("$.name: expected string"), "{}", reason);
+    assert!(!cases[1].passed);
+    assert!(cases[1].error.is_some());
+    assert_eq!(report.targets[0].summary.errors, 1);
+    assert!(report.to_markdown().contains("| timeout | request |"));
+    // nothing checked is not a pass
+    assert!(!cases[2].passed);
+    assert!(report.to_markdown().contains("the case has no assertions"));
+}
+
+#[tokio::test]
+async fn judge_verdict_decides_judge_assertions() {
+    let mock = MockProvider::new().fallback(MockReply::text("Bonjour !"));
+    let judge = MockProvider::new().reply(MockReply::text(
+        "{\"pass\": false, \"reason\": \"not English\"}",
+    ));
+    let dataset = Dataset::new(
+        "judged",
+        vec![
+            EvalCase::new("greet", "Say hello").assert(Assertion::Judge {
+                rubric: "The greeting is in English.".into(),
+            }),
+        ],
+    );
+
+    let report = Evaluator::new()
+        .target(EvalTarget::new("flash", client(mock.clone())))
+        .judge(client(judge.clone()))
+        .run(&dataset)
+        .await
+        .unwrap();
+
+    let assertion = &report.targets[0].cases[0].assertions[0];
+    assert!(!assertion.passed);
+    assert_eq!(assertion.reason.as_deref(), Some("not English"));
+    let prompt = &judge.calls()[0].request.conversation.turns[0].text;
+    assert!(prompt.contains("The greeting is in English.") && prompt.contains("Bonjour !"));
+}
+
+#[tokio::test]
+async fn diff_reports_regressions_against_a_baseline() {
+    let dataset = Dataset::from_yaml(DATASET).unwrap();
+    let baseline = evaluator(&model(false)).run(&dataset).await.unwrap();
+    let baseline = EvalReport::from_json(&baseline.to_json().unwrap()).unwrap();
+
+    let current = evaluator(&model(true)).run(&dataset).await.unwrap();
+    let diff = current.diff(&baseline);
+
+    assert!(diff.has_regressions());
+    assert_eq!(diff.targets[0].regressions, vec!["sum".to_string()]);
+    assert!(diff.targets[0].fixes.is_empty());
+    assert!((diff.targets[0].pass_rate_delta + 1.0 / 3.0).abs() < 1e-9);
+    assert!(diff.to_markdown().contains("| flash | -33.3 pts |"));
+    assert!(baseline.diff(&current).targets[0].fixes == vec!["sum".to_string()]);
+}
+
+#[test]
+fn datasets_reject_duplicate_ids() {
+    let jsonl = "{\"id\": \"a\", \"prompt\": \"x\"}\n\n{\"id\": \"a\", \"prompt\": \"y\"}\n";
+    let path = std::env::temp_dir().join(format!("ey-eval-{}.jsonl", std::process::id()));
+    std::fs::write(&path, jsonl).unwrap();
+
+    let error = Dataset::load(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    assert!(error.to_string().contains("Duplicate case id a"));
+}
+
+#[test]
+fn schema_accepts_type_lists_and_enums() {
+    let valid = json!({"type": ["string", "null"], "enum": ["a", null]});
+    assert!(schema::validate(&valid, &json!(null)).is_empty());
+    assert_eq!(schema::validate(&valid, &json!("b")).len(), 1);
+}
+
+#[test]
+fn schema_rejects_keywords_it_does_not_enforce() {
+    let schema = json!({
+        "type": "object",
+        "title": "Person",
+        "properties": {
+            "email": { "type": "string", "format": "email" },
+            "pet": { "oneOf": [{ "type": "string" }, { "type": "null" }] }
+        }
+    });
+
+    // also when the value never reaches the unsupported keywords
+    let errors = schema::validate(&schema, &json!({}));
+    assert_eq!(
+        errors,
+        [
+            "schema.properties.email: unsupported keyword format",
+            "schema.properties.pet: unsupported keyword oneOf",
+        ]
+    );
+}
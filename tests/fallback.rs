@@ -0,0 +1,168 @@
+//! Fallback chains: failover on server errors, rate limits and timeouts,
+//! accounting under the target that answered and forwarding of cached
+//! contents and batch jobs.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use common::client;
+use ey_ai::{
+    model::{
+        batch::batch::BatchState,
+        generation::generation::{GenerateRequest, StreamEvent, Usage},
+    },
+    models::{
+        error::ProviderError,
+        fallback::{FallbackProvider, FallbackTarget},
+    },
+    testing::{
+        cassette::CassetteServer,
+        mock::{MockProvider, MockReply},
+    },
+    traits::ModelProvider,
+    usage::ledger::{UsageFilter, key_id},
+};
+use futures::StreamExt;
+
+fn status(status: u16) -> MockReply {
+    MockReply::error(ProviderError::Status {
+        status,
+        message: "unavailable".into(),
+    })
+}
+
+fn chain(primary: &MockProvider, secondary: &MockProvider) -> FallbackProvider {
+    FallbackProvider::chain(vec![
+        FallbackTarget::new(Arc::new(primary.clone())).model_name("gemini-2.5-pro"),
+        FallbackTarget::new(Arc::new(secondary.clone()))
+            .model_name("gemini-2.5-flash")
+            .api_key("secondary-key"),
+    ])
+}
+
+#[tokio::test]
+async fn fails_over_on_server_errors() {
+    let primary = MockProvider::new().reply(status(503));
+    let secondary = MockProvider::new().reply(MockReply::text("from flash"));
+    let client = client(chain(&primary, &secondary));
+
+    let generation = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+
+    assert_eq!(generation.text, "from flash");
+    let served_by = generation.served_by.unwrap();
+    assert_eq!(
+        (served_by.provider.as_str(), served_by.model.as_str()),
+        ("mock", "gemini-2.5-flash")
+    );
+    assert_eq!(primary.last_call().unwrap().model, "gemini-2.5-pro");
+    assert_eq!(primary.last_call().unwrap().api_key, "test-key");
+    assert_eq!(secondary.last_call().unwrap().api_key, "secondary-key");
+}
+
+#[tokio::test]
+async fn returns_other_errors_right_away() {
+    let primary = MockProvider::new().reply(status(400));
+    let secondary = MockProvider::new().reply(MockReply::text("unused"));
+    let client = client(chain(&primary, &secondary));
+
+    let error = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .err()
+        .unwrap();
+
+    assert_eq!(ProviderError::classify(&error).unwrap().status(), Some(400));
+    assert_eq!(secondary.call_count(), 0);
+}
+
+#[tokio::test]
+async fn fails_over_slow_targets() {
+    let primary =
+        MockProvider::new().reply(MockReply::text("late").latency(Duration::from_secs(5)));
+    let secondary = MockProvider::new().reply(MockReply::text("in time"));
+    let chain = FallbackProvider::chain(vec![
+        FallbackTarget::new(Arc::new(primary)).timeout(Duration::from_millis(50)),
+        FallbackTarget::new(Arc::new(secondary)),
+    ]);
+
+    let generation = client(chain)
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    assert_eq!(generation.text, "in time");
+}
+
+#[tokio::test]
+async fn books_usage_under_the_target_that_answered() {
+    let usage = Usage {
+        prompt_tokens: 10,
+        total_tokens: 10,
+        ..Default::default()
+    };
+    let primary = MockProvider::new().replies([status(429), status(429)]);
+    let secondary = MockProvider::new().replies([
+        MockReply::text("ok").usage(usage),
+        MockReply::stream(["o", "k"]).usage(usage),
+    ]);
+    let client = client(chain(&primary, &secondary));
+
+    let generation = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    // the key of the target is kept for accounting only
+    assert!(
+        !serde_json::to_string(&generation)
+            .unwrap()
+            .contains("secondary-key")
+    );
+    assert!(!format!("{:?}", generation).contains("secondary-key"));
+    let events: Vec<_> = client
+        .GenerateStream(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert!(matches!(
+        events.last(),
+        Some(Ok(StreamEvent::Done { served_by: Some(s), .. })) if s.model == "gemini-2.5-flash"
+    ));
+
+    let report = client.usage.query(&UsageFilter {
+        group_by: Some("key,model".into()),
+        ..Default::default()
+    });
+    assert_eq!(report.records.len(), 1);
+    let record = &report.records[0];
+    assert_eq!(record.key_id, Some(key_id("secondary-key")));
+    assert_eq!(record.model.as_deref(), Some("gemini-2.5-flash"));
+    assert_eq!(record.totals.requests, 2);
+}
+
+#[tokio::test]
+async fn forwards_cached_contents_and_batches_to_the_first_target() {
+    let cassette = format!(
+        "{}/tests/cassettes/fallback_forwarding.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let secondary = MockProvider::new();
+    let chain = FallbackProvider::chain(vec![
+        FallbackTarget::new(Arc::new(server.gemini())).model_name("gemini-2.5-pro"),
+        FallbackTarget::new(Arc::new(secondary.clone())),
+    ]);
+    let client = client(chain);
+
+    let contents = client.ListCachedContents().await.unwrap();
+    assert_eq!(contents[0].name, "cachedContents/handbook");
+    let jobs = client.ListBatches().await.unwrap();
+    assert_eq!(jobs[0].name, "batches/nightly");
+    assert_eq!(jobs[0].state, BatchState::Running);
+
+    assert!(server.unused().is_empty());
+    assert_eq!(secondary.call_count(), 0);
+}
@@ -0,0 +1,232 @@
+//! Gemini response parsing, replayed from the cassettes of `tests/cassettes`.
+
+use axum::{Router, body::Body};
+use ey_ai::{
+    model::{
+        generation::generation::{GenerateRequest, StreamEvent},
+        message::message::Message,
+        safety::safety::{HarmCategory, HarmProbability},
+    },
+    models::error::ProviderError,
+    testing::cassette::{CassetteMode, CassetteServer},
+    traits::ModelProvider,
+};
+use futures::StreamExt;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+const KEY: &str = "test-key";
+const MODEL: &str = "gemini-2.5-flash";
+
+fn cassette(name: &str) -> String {
+    format!("{}/tests/cassettes/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[tokio::test]
+async fn generate_text_returns_the_reply() {
+    let server = CassetteServer::replay(cassette("generate_text.json"))
+        .await
+        .unwrap();
+
+    let reply = server
+        .gemini()
+        .generate_text(KEY, MODEL, "What is Rust in one sentence?".into())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        reply,
+        "Rust is a systems programming language focused on safety, speed and concurrency."
+    );
+    assert!(server.unused().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn generate_without_async_returns_a_message_with_usage() {
+    let server = CassetteServer::replay(cassette("generate_without_async.json"))
+        .await
+        .unwrap();
+    let provider = server.gemini();
+
+    let value = tokio::task::spawn_blocking(move || {
+        provider.generate_without_async(KEY.into(), MODEL.into(), "Say hello".into())
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    let message: Message = serde_json::from_value(value).unwrap();
+
+    assert_eq!(message.choice.role.content, "Hello!");
+    assert_eq!(message.models, MODEL);
+    assert_eq!(message.question, "Say hello");
+    let usage = message.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.candidates_tokens), (2, 2));
+}
+
+#[tokio::test]
+async fn generate_stream_joins_events_split_across_chunks() {
+    let server = CassetteServer::replay(cassette("generate_stream.json"))
+        .await
+        .unwrap();
+
+    let chunks: Vec<String> = server
+        .gemini()
+        .generate_stream(KEY.into(), MODEL.into(), "Count to three".into())
+        .await
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    // the thought summary is not part of the text
+    assert_eq!(chunks, ["One, ", "two, three."]);
+}
+
+#[tokio::test]
+async fn generate_events_separates_thoughts_and_reports_usage() {
+    let server = CassetteServer::replay(cassette("generate_stream.json"))
+        .await
+        .unwrap();
+
+    let events: Vec<StreamEvent> = server
+        .gemini()
+        .generate_events(KEY, MODEL, &GenerateRequest::from_prompt("Count to three"))
+        .await
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(
+        events[0],
+        StreamEvent::Thought {
+            text: "Counting upward from one.".into()
+        }
+    );
+    let StreamEvent::Done {
+        usage,
+        model_version,
+        ..
+    } = events.last().unwrap()
+    else {
+        panic!("stream did not end with Done: {:?}", events);
+    };
+    let usage = usage.unwrap();
+    assert_eq!((usage.thoughts_tokens, usage.total_tokens), (5, 15));
+    assert_eq!(model_version.as_deref(), Some(MODEL));
+}
+
+#[tokio::test]
+async fn blocked_prompts_and_error_statuses_are_typed() {
+    let server = CassetteServer::replay(cassette("errors.json"))
+        .await
+        .unwrap();
+    let provider = server.gemini();
+
+    let blocked = provider
+        .generate_text(KEY, MODEL, "How do I pick a lock?".into())
+        .await
+        .unwrap_err();
+    let Some(ProviderError::SafetyBlock { reason, ratings }) = ProviderError::classify(&blocked)
+    else {
+        panic!("not a safety block: {}", blocked);
+    };
+    assert_eq!(reason, "SAFETY");
+    assert_eq!(ratings[0].category, HarmCategory::DangerousContent);
+    assert_eq!(ratings[0].probability, HarmProbability::High);
+
+    let limited = provider
+        .generate_text(KEY, MODEL, "Too many requests".into())
+        .await
+        .unwrap_err();
+    assert_eq!(
+        ProviderError::classify(&limited).and_then(|e| e.status()),
+        Some(429)
+    );
+}
+
+#[tokio::test]
+async fn unrecorded_requests_fail_without_network() {
+    let server = CassetteServer::replay(cassette("generate_text.json"))
+        .await
+        .unwrap();
+
+    let error = server
+        .gemini()
+        .generate_text(KEY, MODEL, "Something else".into())
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        ProviderError::classify(&error).and_then(|e| e.status()),
+        Some(501)
+    );
+}
+
+#[tokio::test]
+async fn recording_redacts_the_api_key() {
+    // a replaying server stands in for the real API
+    let upstream = CassetteServer::replay(cassette("generate_text.json"))
+        .await
+        .unwrap();
+    let path = std::env::temp_dir().join(format!("ey-ai-{}.json", uuid::Uuid::new_v4()));
+    let recorder = CassetteServer::record(&path, upstream.url()).await.unwrap();
+    assert!(matches!(recorder.mode(), CassetteMode::Record { .. }));
+
+    let reply = recorder
+        .gemini()
+        .generate_text(
+            "secret-key-123",
+            MODEL,
+            "What is Rust in one sentence?".into(),
+        )
+        .await
+        .unwrap();
+    assert!(reply.starts_with("Rust is"));
+
+    let saved = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!saved.contains("secret-key-123"));
+    assert_eq!(
+        recorder.cassette().interactions[0].request.path,
+        "/v1beta/models/gemini-2.5-flash:generateContent"
+    );
+}
+
+#[tokio::test]
+async fn recording_keeps_characters_split_across_chunks() {
+    // "é" is cut in the middle of its two bytes by the upstream chunking
+    let event = |text: &str| {
+        let candidate = json!({ "candidates": [{ "content": { "parts": [{ "text": text }] } }] });
+        format!("data: {}\r\n\r\n", candidate).into_bytes()
+    };
+    let body = [event("Café "), event("crème.")].concat();
+    let cut = body.iter().position(|b| *b == 0xc3).unwrap() + 1;
+    let chunks = [body[..cut].to_vec(), body[cut..].to_vec()];
+    let app = Router::new().fallback(move || {
+        let chunks = chunks.clone().map(Ok::<_, std::io::Error>);
+        async move { Body::from_stream(futures::stream::iter(chunks)) }
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let path = std::env::temp_dir().join(format!("ey-ai-{}.json", uuid::Uuid::new_v4()));
+    let recorder = CassetteServer::record(&path, upstream).await.unwrap();
+    async fn stream(server: &CassetteServer) -> Vec<String> {
+        let provider = server.gemini();
+        let chunks = provider
+            .generate_stream(KEY.into(), MODEL.into(), "Order".into())
+            .await
+            .unwrap();
+        chunks.map(|chunk| chunk.unwrap()).collect().await
+    }
+    let recorded = stream(&recorder).await;
+    drop(recorder);
+
+    let replayer = CassetteServer::replay(&path).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let replayed = stream(&replayer).await;
+    assert_eq!(recorded, ["Café ", "crème."]);
+    assert_eq!(replayed, recorded);
+}
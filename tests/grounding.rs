@@ -0,0 +1,102 @@
+//! Built-in tools: the tools sent to Gemini, the grounding metadata and
+//! citations of recorded search answers and the parts of code execution.
+
+mod common;
+
+use common::client;
+use ey_ai::{
+    model::{
+        generation::generation::{GenerateRequest, StreamEvent},
+        grounding::grounding::{BuiltinTool, CodePart},
+    },
+    testing::cassette::CassetteServer,
+};
+use futures::StreamExt;
+
+const QUESTION: &str = "Wann wurde der Eiffelturm eröffnet und wie hoch ist er?";
+const CITED: &str = "Der Eiffelturm wurde 1889 eröffnet.[1][2] Er ist 330 m hoch.[2]";
+
+fn search(prompt: &str) -> GenerateRequest {
+    GenerateRequest::from_prompt(prompt).tool(BuiltinTool::GoogleSearch)
+}
+
+#[tokio::test]
+async fn cites_the_sources_of_a_grounded_answer() {
+    let cassette = format!(
+        "{}/tests/cassettes/grounding.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini());
+
+    let answer = client.Generate(search(QUESTION)).await.unwrap();
+    let grounding = answer.grounding.unwrap();
+    assert_eq!(grounding.web_search_queries, ["Eiffelturm Eröffnung Höhe"]);
+    let sources: Vec<_> = grounding.sources().iter().map(|s| s.uri.as_str()).collect();
+    assert_eq!(
+        sources,
+        [
+            "https://www.toureiffel.paris/de",
+            "https://de.wikipedia.org/wiki/Eiffelturm"
+        ]
+    );
+
+    // spans are byte offsets, "ö" takes two
+    let citations = grounding.citations();
+    assert_eq!(citations.len(), 3);
+    for citation in &citations {
+        assert_eq!(
+            &answer.text[citation.start_index..citation.end_index],
+            citation.text
+        );
+    }
+    assert_eq!(grounding.cite(&answer.text), CITED);
+
+    // streamed, the sources arrive once right before `Done`
+    let events: Vec<_> = client
+        .GenerateStream(search(QUESTION))
+        .await
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+        .await;
+    let text: String = events
+        .iter()
+        .filter_map(|event| match event {
+            StreamEvent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let [
+        ..,
+        StreamEvent::Grounding { grounding },
+        StreamEvent::Done { .. },
+    ] = events.as_slice()
+    else {
+        panic!("expected grounding before done, got {:?}", events);
+    };
+    assert_eq!(grounding.cite(&text), CITED);
+
+    let code = client
+        .Generate(
+            GenerateRequest::from_prompt("Was ist die Summe von 1 bis 100?")
+                .tool(BuiltinTool::CodeExecution),
+        )
+        .await
+        .unwrap();
+    assert_eq!(code.text, "Ich rechne das nach.Die Summe ist 5050.");
+    assert_eq!(
+        code.code,
+        [
+            CodePart::Executable {
+                language: "PYTHON".into(),
+                code: "print(sum(range(1, 101)))".into()
+            },
+            CodePart::Result {
+                outcome: "OUTCOME_OK".into(),
+                output: "5050\n".into()
+            },
+        ]
+    );
+    assert!(server.unused().is_empty(), "{:?}", server.unused());
+}
@@ -0,0 +1,131 @@
+//! Hedged requests: the hedge fires after its delay, the first success wins
+//! and usage is booked under the key that answered.
+
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use common::client;
+use ey_ai::{
+    model::generation::generation::{GenerateRequest, StreamEvent, Usage},
+    models::{error::ProviderError, hedge::HedgeConfig, model_client::ModelClient},
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+    usage::ledger::{UsageFilter, key_id},
+};
+use futures::StreamExt;
+
+const SLOW: Duration = Duration::from_millis(500);
+
+fn usage() -> Usage {
+    Usage {
+        total_tokens: 10,
+        ..Default::default()
+    }
+}
+
+fn hedge(provider: &MockProvider) -> HedgeConfig {
+    HedgeConfig::new(Arc::new(provider.clone()), Duration::from_millis(50))
+        .model("gemini-2.5-flash-lite")
+        .api_key("hedge-key")
+}
+
+fn booked_keys(client: &ModelClient) -> Vec<Option<String>> {
+    client
+        .usage
+        .query(&UsageFilter {
+            group_by: Some("key".into()),
+            ..Default::default()
+        })
+        .records
+        .into_iter()
+        .map(|r| r.key_id)
+        .collect()
+}
+
+#[tokio::test]
+async fn the_hedge_answers_for_a_slow_primary() {
+    let primary =
+        MockProvider::new().fallback(MockReply::text("primary").usage(usage()).latency(SLOW));
+    let secondary = MockProvider::new().fallback(MockReply::text("hedge").usage(usage()));
+    let client = client(primary.clone()).set_hedging(Some(hedge(&secondary)));
+
+    let generation = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+
+    assert_eq!(generation.text, "hedge");
+    let served_by = generation.served_by.unwrap();
+    assert_eq!(served_by.model, "gemini-2.5-flash-lite");
+    assert_eq!(secondary.last_call().unwrap().api_key, "hedge-key");
+    assert_eq!(booked_keys(&client), [Some(key_id("hedge-key"))]);
+
+    let stats = client.hedge_stats.snapshot();
+    assert_eq!((stats.requests, stats.hedged), (1, 1));
+    assert_eq!((stats.primary_wins, stats.hedge_wins), (0, 1));
+}
+
+#[tokio::test]
+async fn a_fast_primary_never_fires_the_hedge() {
+    let primary = MockProvider::new().fallback(MockReply::text("primary").usage(usage()));
+    let secondary = MockProvider::new().fallback(MockReply::text("hedge"));
+    let client = client(primary).set_hedging(Some(hedge(&secondary)));
+
+    let generation = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+
+    assert_eq!(generation.text, "primary");
+    assert!(generation.served_by.is_none());
+    assert_eq!(secondary.call_count(), 0);
+    assert_eq!(booked_keys(&client), [Some(key_id("test-key"))]);
+    assert_eq!(client.hedge_stats.snapshot().hedged, 0);
+}
+
+#[tokio::test]
+async fn the_primary_answers_when_the_hedge_fails() {
+    let primary = MockProvider::new()
+        .fallback(MockReply::text("primary").latency(Duration::from_millis(150)));
+    let secondary = MockProvider::new().fallback(MockReply::error(ProviderError::Status {
+        status: 503,
+        message: "unavailable".into(),
+    }));
+    let client = client(primary).set_hedging(Some(hedge(&secondary)));
+
+    let generation = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+
+    assert_eq!(generation.text, "primary");
+    assert_eq!(secondary.call_count(), 1);
+    assert_eq!(client.hedge_stats.snapshot().primary_wins, 1);
+}
+
+#[tokio::test]
+async fn streams_of_the_hedge_are_booked_under_its_key() {
+    let primary =
+        MockProvider::new().fallback(MockReply::stream(["slow"]).usage(usage()).latency(SLOW));
+    let secondary = MockProvider::new().fallback(MockReply::stream(["fa", "st"]).usage(usage()));
+    let client = client(primary)
+        .add_key("pool-key", 1)
+        .set_hedging(Some(hedge(&secondary)));
+
+    let events: Vec<_> = client
+        .GenerateStream(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    assert!(matches!(
+        events.last(),
+        Some(Ok(StreamEvent::Done { served_by: Some(s), .. })) if s.model == "gemini-2.5-flash-lite"
+    ));
+    assert_eq!(booked_keys(&client), [Some(key_id("hedge-key"))]);
+    // the overtaken request leaves the pool
+    let stats = client.keys.stats();
+    assert_eq!((stats[0].requests, stats[0].in_flight), (1, 0));
+}
@@ -0,0 +1,232 @@
+//! Hybrid retrieval: the BM25 keyword index, reciprocal rank fusion with
+//! vector search and model reranking, offline with the hash embedder.
+
+mod common;
+
+use std::sync::Arc;
+
+use common::client;
+use ey_ai::{
+    embedding::embedder::HashEmbedder,
+    model::generation::generation::GenerateRequest,
+    rag::{
+        bm25::{Bm25Index, tokenize},
+        chunker::Chunker,
+        document::{Chunk, Document, Filter},
+        hybrid::{HybridRetriever, reciprocal_rank_fusion},
+        rerank::Reranker,
+        retriever::{Retrieved, Retriever, VectorRetriever},
+    },
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+};
+
+fn chunk(id: &str, text: &str) -> Chunk {
+    Chunker::default().chunk(&Document::new(id, text)).remove(0)
+}
+
+// error pages differing only by their code, the case embeddings blur
+fn docs() -> Vec<Document> {
+    vec![
+        Document::new(
+            "e1243",
+            "Error E1243 means the project quota of the project is exhausted, raise the project quota.",
+        )
+        .meta("product", "api"),
+        Document::new(
+            "e1234",
+            "Error E1234 means the billing account was closed.",
+        )
+        .meta("product", "billing"),
+        Document::new(
+            "e1324",
+            "Error E1324 means the project was deleted with its quota.",
+        )
+        .meta("product", "api"),
+        Document::new("keys", "Rotate API keys every 90 days from the console.")
+            .meta("product", "console"),
+    ]
+}
+
+fn hit(id: &str) -> Retrieved {
+    Retrieved {
+        chunk: chunk(id, id),
+        score: 0.0,
+    }
+}
+
+#[test]
+fn bm25_matches_exact_identifiers() {
+    assert_eq!(
+        tokenize("See SKU AX-4411, or src/main.rs."),
+        vec![
+            "see",
+            "sku",
+            "ax-4411",
+            "ax",
+            "4411",
+            "or",
+            "src/main.rs",
+            "src",
+            "main",
+            "rs"
+        ]
+    );
+
+    let mut index = Bm25Index::from_chunks(docs().iter().map(|d| chunk(&d.id, &d.text)));
+    assert_eq!(index.len(), 4);
+
+    let hits = index.search("e1234", 3, &Filter::new());
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].chunk.document_id, "e1234");
+
+    // repeated terms in a short chunk score higher
+    let hits = index.search("project quota", 3, &Filter::new());
+    assert_eq!(hits[0].chunk.document_id, "e1243");
+    assert_eq!(hits[1].chunk.document_id, "e1324");
+    assert!(hits[0].score > hits[1].score);
+    assert!(
+        index
+            .search("nothing matches", 3, &Filter::new())
+            .is_empty()
+    );
+
+    assert_eq!(index.remove_document("e1243"), 1);
+    let hits = index.search("quota", 3, &Filter::new());
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].chunk.document_id, "e1324");
+
+    // adding a chunk again replaces it
+    index.add(chunk("e1324", "Error E1324 is gone."));
+    assert_eq!(index.len(), 3);
+    assert!(index.search("quota", 3, &Filter::new()).is_empty());
+}
+
+#[test]
+fn fuses_rankings_by_reciprocal_rank() {
+    let dense = vec![hit("a"), hit("b"), hit("c")];
+    let keyword = vec![hit("c"), hit("d")];
+
+    let fused = reciprocal_rank_fusion(&[dense, keyword], 60.0);
+    let ids: Vec<&str> = fused.iter().map(|h| h.chunk.document_id.as_str()).collect();
+    // c is third and first: 1/63 + 1/61 beats a at 1/61 alone
+    // b and d are both second, ties are broken by chunk id
+    assert_eq!(ids, vec!["c", "a", "b", "d"]);
+    assert!((fused[0].score - (1.0 / 63.0 + 1.0 / 61.0)).abs() < 1e-6);
+    assert!((fused[1].score - 1.0 / 61.0).abs() < 1e-6);
+    assert_eq!(fused[2].score, fused[3].score);
+
+    assert!(reciprocal_rank_fusion(&[], 60.0).is_empty());
+}
+
+#[tokio::test]
+async fn hybrid_finds_identifiers_embeddings_blur() {
+    let query = "E1234 projects quotas";
+    let vector = VectorRetriever::new(Arc::new(HashEmbedder::new(256)));
+    vector.index(&docs()).await.unwrap();
+    let dense = vector.retrieve(query, 1, &Filter::new()).await.unwrap();
+    assert_ne!(dense[0].chunk.document_id, "e1234");
+
+    let hybrid = HybridRetriever::new(Arc::new(HashEmbedder::new(256)));
+    assert_eq!(hybrid.index(&docs()).await.unwrap(), 4);
+    let hits = hybrid.retrieve(query, 2, &Filter::new()).await.unwrap();
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].chunk.document_id, "e1234");
+
+    // filters apply to both searches
+    let api = Filter::new().eq("product", "api");
+    let hits = hybrid.retrieve(query, 4, &api).await.unwrap();
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().all(|h| h.chunk.metadata["product"] == "api"));
+
+    assert_eq!(hybrid.remove_document("e1234"), 1);
+    let hits = hybrid.retrieve("E1234", 4, &Filter::new()).await.unwrap();
+    assert!(hits.iter().all(|h| h.chunk.document_id != "e1234"));
+}
+
+#[tokio::test]
+async fn reranks_candidates_with_the_model() {
+    let mock = MockProvider::new()
+        .reply(MockReply::text("```json\n[3, 0, 9, 3]\n```"))
+        .reply(MockReply::text("I cannot rank these."));
+    let reranker = Reranker::new(client(mock.clone())).max_chars(20);
+    let candidates = vec![hit("a"), hit("b"), hit("c"), hit("d")];
+
+    let ranked = reranker.rerank("query", candidates.clone()).await.unwrap();
+    let ids: Vec<&str> = ranked
+        .iter()
+        .map(|h| h.chunk.document_id.as_str())
+        .collect();
+    // listed first, unknown and repeated numbers ignored, the rest in order
+    assert_eq!(ids, vec!["d", "a", "b", "c"]);
+    assert_eq!(ranked[0].score, 1.0);
+    assert_eq!(ranked[1].score, 0.5);
+
+    let sent = mock.last_call().unwrap().request;
+    assert!(sent.conversation.system.unwrap().contains("JSON array"));
+    assert_eq!(sent.config.temperature, Some(0.0));
+    let prompt = &sent.conversation.turns[0].text;
+    assert!(
+        prompt.starts_with("Question: query\n\nPassages:\n[0] a"),
+        "{}",
+        prompt
+    );
+
+    let error = reranker.rerank("query", candidates).await.err().unwrap();
+    assert!(error.to_string().contains("No ranking"), "{}", error);
+}
+
+#[tokio::test]
+async fn hybrid_reranks_the_fused_order_and_reports_failed_reranks() {
+    let mock = MockProvider::new()
+        .reply(MockReply::text("[1]"))
+        .reply(MockReply::text("not json"));
+    let fused = HybridRetriever::new(Arc::new(HashEmbedder::new(256))).candidates(3);
+    fused.index(&docs()).await.unwrap();
+    let hybrid = HybridRetriever::new(Arc::new(HashEmbedder::new(256)))
+        .candidates(3)
+        .reranker(Reranker::new(client(mock.clone())));
+    hybrid.index(&docs()).await.unwrap();
+
+    let query = "E1234 projects quotas";
+    let fused = fused.retrieve(query, 2, &Filter::new()).await.unwrap();
+    let reranked = hybrid.retrieve(query, 2, &Filter::new()).await.unwrap();
+    assert_eq!(fused[0].chunk.document_id, "e1234");
+    assert_eq!(reranked[0].chunk.id, fused[1].chunk.id);
+    assert_eq!(reranked[1].chunk.id, fused[0].chunk.id);
+
+    let error = hybrid
+        .retrieve(query, 2, &Filter::new())
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("Reranking failed"), "{}", error);
+    assert_eq!(mock.call_count(), 2);
+}
+
+#[tokio::test]
+async fn reloads_and_generates_with_hybrid_context() {
+    let path = std::env::temp_dir().join(format!("ey-hybrid-{}.json", std::process::id()));
+    let hybrid = HybridRetriever::new(Arc::new(HashEmbedder::new(256)));
+    hybrid.index(&docs()).await.unwrap();
+    hybrid.save(&path).unwrap();
+
+    // the keyword index is rebuilt from the saved chunks
+    let reopened = HybridRetriever::load(Arc::new(HashEmbedder::new(256)), &path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(reopened.len(), 4);
+
+    let mock = MockProvider::new().reply(MockReply::text("The billing account was closed [1]."));
+    let request = GenerateRequest::from_prompt("What does error E1234 mean?");
+    let answer = client(mock.clone())
+        .GenerateWithContext(request, &reopened, 2, &Filter::new())
+        .await
+        .unwrap();
+    assert_eq!(answer.cited_sources()[0].chunk.document_id, "e1234");
+    let prompt = &mock.last_call().unwrap().request.conversation.turns[0].text;
+    assert!(
+        prompt.starts_with("Sources:\n\n[1] e1234\nError E1234"),
+        "{}",
+        prompt
+    );
+}
@@ -0,0 +1,189 @@
+//! The API key pool: rotation away from rate limited and rejected keys,
+//! key statistics and in-flight accounting.
+
+mod common;
+
+use std::time::Duration;
+
+use common::client;
+use ey_ai::{
+    model::generation::generation::GenerateRequest,
+    models::{error::ProviderError, gemini::GeminiProvider},
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+    usage::ledger::key_id,
+    utils::key_pool::{KeyHealth, KeySelection},
+};
+
+fn status(status: u16) -> MockReply {
+    MockReply::error(ProviderError::Status {
+        status,
+        message: "refused".into(),
+    })
+}
+
+#[tokio::test]
+async fn rotates_away_from_rate_limited_and_rejected_keys() {
+    let mock = MockProvider::new()
+        .reply(status(429))
+        .reply(status(401))
+        .reply(MockReply::text("ok"));
+    let client = client(mock.clone())
+        .add_key("project-key-one", 1)
+        .add_key("project-key-two", 1)
+        .add_key("project-key-three", 1);
+
+    let reply = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    assert_eq!(reply.text, "ok");
+
+    let keys: Vec<String> = mock.calls().into_iter().map(|c| c.api_key).collect();
+    assert_eq!(keys.len(), 3);
+    assert!(keys[0] != keys[1] && keys[1] != keys[2] && keys[0] != keys[2]);
+
+    let stats = client.keys.stats();
+    let health = |key: &str| {
+        let stats = stats.iter().find(|s| s.key == key_id(key)).unwrap();
+        (stats.health.clone(), stats.failures)
+    };
+    assert!(matches!(
+        health(&keys[0]),
+        (KeyHealth::CoolingDown { .. }, 1)
+    ));
+    assert!(matches!(health(&keys[1]), (KeyHealth::Disabled { .. }, 1)));
+    assert_eq!(health(&keys[2]), (KeyHealth::Active, 0));
+
+    // only the healthy key is left for the next request
+    mock.reply(MockReply::text("again"));
+    client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    assert_eq!(mock.last_call().unwrap().api_key, keys[2]);
+}
+
+#[tokio::test]
+async fn fails_once_every_key_is_out_of_rotation() {
+    let mock = MockProvider::new().reply(status(429)).reply(status(429));
+    let client = client(mock.clone())
+        .add_key("project-key-one", 1)
+        .add_key("project-key-two", 1);
+
+    let error = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(ProviderError::classify(&error).unwrap().status(), Some(429));
+    assert_eq!(mock.call_count(), 2);
+
+    let error = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .err()
+        .unwrap();
+    assert!(
+        error.to_string().contains("No API key available"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn stats_never_show_the_key() {
+    // nothing listens there, the transport error names the request URL
+    let provider = GeminiProvider::with_base_url("http://127.0.0.1:1");
+    let secret = "AIzaSecretKeyValue0123456789";
+    let client = client(provider).add_key(secret, 1);
+
+    let error = client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .err()
+        .unwrap();
+    assert!(!format!("{:#}", error).contains(secret), "{:#}", error);
+
+    let stats = client.keys.stats();
+    assert_eq!(stats[0].key, key_id(secret));
+    let last_error = stats[0].last_error.as_deref().unwrap();
+    assert!(last_error.contains("127.0.0.1"), "{}", last_error);
+    assert!(!last_error.contains(secret), "{}", last_error);
+    assert!(!serde_json::to_string(&stats).unwrap().contains(secret));
+}
+
+#[test]
+fn a_disabled_key_keeps_only_the_classified_reason() {
+    let secret = "AIzaSecretKeyValue0123456789";
+    let client = client(MockProvider::new()).add_key(secret, 1);
+    let error = anyhow::Error::from(ProviderError::Status {
+        status: 403,
+        message: "API key not valid".into(),
+    })
+    .context(format!(
+        "POST https://generativelanguage.googleapis.com/v1beta/models/gemini-2.5-flash:generateContent?key={}",
+        secret
+    ));
+
+    assert!(client.keys.report(secret, Err(&error)));
+    let stats = client.keys.stats();
+    let KeyHealth::Disabled { reason } = &stats[0].health else {
+        panic!("expected a disabled key, got {:?}", stats[0].health);
+    };
+    assert!(reason.starts_with("HTTP 403: "), "{}", reason);
+    assert!(reason.contains("API key not valid"), "{}", reason);
+    assert!(!reason.contains(secret), "{}", reason);
+}
+
+#[tokio::test]
+async fn cancelled_requests_leave_the_pool() {
+    let mock = MockProvider::new()
+        .reply(MockReply::text("slow").latency(Duration::from_secs(5)))
+        .fallback(MockReply::text("fast"));
+    let client = client(mock.clone())
+        .add_key("project-key-one", 1)
+        .add_key("project-key-two", 1)
+        .set_key_selection(KeySelection::LeastUsed);
+
+    let slow = client.Generate(GenerateRequest::from_prompt("hi"));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), slow)
+            .await
+            .is_err()
+    );
+    assert!(client.keys.stats().iter().all(|k| k.in_flight == 0));
+
+    // least used goes by requests once nothing is in flight
+    client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    assert_eq!(mock.last_call().unwrap().api_key, "project-key-two");
+}
+
+#[tokio::test]
+async fn spreads_requests_round_robin() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = client(mock.clone())
+        .add_key("project-key-one", 1)
+        .add_key("project-key-two", 1);
+
+    for _ in 0..4 {
+        client
+            .Generate(GenerateRequest::from_prompt("hi"))
+            .await
+            .unwrap();
+    }
+    let keys: Vec<String> = mock.calls().into_iter().map(|c| c.api_key).collect();
+    assert_eq!(
+        keys,
+        [
+            "project-key-one",
+            "project-key-two",
+            "project-key-one",
+            "project-key-two"
+        ]
+    );
+    assert!(client.keys.stats().iter().all(|k| k.requests == 2));
+}
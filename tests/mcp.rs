@@ -0,0 +1,263 @@
+//! The MCP client against the fixture server, over stdio and streamable
+//! HTTP, and its tools in the function calling path.
+
+mod common;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use axum::{Json, Router, body::Body, extract::State, response::Response, routing::post};
+use common::client;
+use ey_ai::{
+    agent::agent::Agent,
+    mcp::{
+        client::McpClient,
+        transport::{HttpTransport, McpTransport, StdioTransport},
+    },
+    model::{
+        conversation::conversation::TurnRole, function::function::FunctionCall,
+        generation::generation::GenerateRequest,
+    },
+    testing::{
+        mcp::{McpFixtureServer, README},
+        mock::{MockProvider, MockReply},
+    },
+    traits::ModelProvider,
+};
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, process::Command};
+
+async fn stdio_fixture() -> McpClient {
+    McpClient::stdio(env!("CARGO_BIN_EXE_mcp-fixture"), &[])
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn lists_and_calls_tools_over_stdio() {
+    let mcp = stdio_fixture().await;
+    assert_eq!(mcp.server_info().server_info.name, "mcp-fixture");
+    assert!(mcp.server_info().supports("tools"));
+    mcp.ping().await.unwrap();
+
+    // the fixture lists its tools over two pages
+    let names: Vec<String> = mcp
+        .list_tools()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["echo", "add", "fail"]);
+
+    let echo = mcp.call_tool("echo", json!({"text": "hi"})).await.unwrap();
+    assert_eq!(echo.text(), "hi");
+    let sum = mcp.call_tool("add", json!({"a": 2, "b": 3})).await.unwrap();
+    assert_eq!(sum.into_result().unwrap(), json!({"sum": 5.0}));
+
+    let failed = mcp.call_tool("fail", json!({})).await.unwrap();
+    assert!(failed.is_error);
+    assert_eq!(
+        failed.into_result().unwrap_err().to_string(),
+        "fixture failure"
+    );
+    let unknown = mcp.call_tool("nope", json!({})).await.unwrap_err();
+    assert!(unknown.to_string().contains("Unknown tool"), "{}", unknown);
+
+    mcp.close().await.unwrap();
+    assert!(mcp.ping().await.is_err());
+}
+
+#[tokio::test]
+async fn reads_resources_and_prompts_over_stdio() {
+    let mcp = stdio_fixture().await;
+
+    let resources = mcp.list_resources().await.unwrap();
+    assert_eq!(resources[0].uri, "fixture://readme");
+    let contents = mcp.read_resource("fixture://readme").await.unwrap();
+    assert_eq!(contents[0].text.as_deref(), Some(README));
+    assert!(mcp.read_resource("fixture://missing").await.is_err());
+
+    assert_eq!(mcp.list_prompts().await.unwrap()[0].name, "greet");
+    let arguments = HashMap::from([("name".to_string(), "Ada".to_string())]);
+    let conversation = mcp.get_prompt("greet", arguments).await.unwrap();
+    let turns: Vec<(TurnRole, String)> = conversation
+        .turns
+        .iter()
+        .map(|t| (t.role, t.text.clone()))
+        .collect();
+    assert_eq!(
+        turns,
+        vec![
+            (TurnRole::User, "Say hello to Ada".to_string()),
+            (TurnRole::Model, "Hello, Ada!".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn talks_streamable_http_with_a_session() {
+    let server = McpFixtureServer::start().await.unwrap();
+    let mcp = McpClient::http(server.url()).await.unwrap();
+
+    assert_eq!(mcp.list_tools().await.unwrap().len(), 3);
+    // tools/call is answered as an event stream
+    let echo = mcp
+        .call_tool("echo", json!({"text": "over http"}))
+        .await
+        .unwrap();
+    assert_eq!(echo.text(), "over http");
+    assert_eq!(
+        mcp.read_resource("fixture://readme").await.unwrap()[0]
+            .text
+            .as_deref(),
+        Some(README)
+    );
+
+    // the session is gone once closed
+    mcp.close().await.unwrap();
+    assert!(mcp.list_tools().await.is_err());
+
+    // requests without a session are refused
+    let raw = HttpTransport::new(server.url());
+    let refused = raw.request("tools/list", json!({})).await.unwrap_err();
+    assert!(refused.to_string().contains("404"), "{}", refused);
+}
+
+#[tokio::test]
+async fn tool_sets_declare_json_schemas_under_prefixed_names() {
+    let mcp = McpClient::connect(
+        StdioTransport::spawn(Command::new(env!("CARGO_BIN_EXE_mcp-fixture"))).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let tools = mcp.tool_set_prefixed("fixture/").await.unwrap();
+    let declarations = tools.declarations();
+    let echo = &declarations[0];
+    assert_eq!(echo.name, "fixture_echo");
+    assert_eq!(echo.description, "Returns the text it is given");
+    assert_eq!(echo.parameters, None);
+    assert_eq!(
+        echo.parameters_json_schema.as_ref().unwrap()["additionalProperties"],
+        json!(false)
+    );
+    // the title stands in for a missing description
+    assert_eq!(declarations[1].description, "Add two numbers");
+
+    let call = FunctionCall::new("fixture_add", json!({"a": 1, "b": 1}));
+    assert_eq!(tools.call(&call).await.unwrap(), json!({"sum": 2.0}));
+}
+
+#[tokio::test]
+async fn model_client_declares_and_runs_mcp_tools() {
+    let mcp = stdio_fixture().await;
+    let mock = MockProvider::new().replies([
+        MockReply::function_calls(vec![
+            FunctionCall::new("add", json!({"a": 20, "b": 22})),
+            FunctionCall::new("fail", json!({})),
+        ]),
+        MockReply::text("The sum is 42."),
+    ]);
+    let client = client(mock.clone()).set_tools(mcp.tool_set().await.unwrap());
+
+    let reply = client
+        .GenerateWithTools(GenerateRequest::from_prompt("What is 20 + 22?"))
+        .await
+        .unwrap();
+    assert_eq!(reply.text, "The sum is 42.");
+
+    let calls = mock.calls();
+    let declared: Vec<&str> = calls[0]
+        .request
+        .functions
+        .iter()
+        .map(|f| f.name.as_str())
+        .collect();
+    assert_eq!(declared, vec!["echo", "add", "fail"]);
+
+    let responses = &calls[1].request.conversation.turns[2].function_responses;
+    assert_eq!(responses[0].response, json!({"sum": 42.0}));
+    assert_eq!(responses[1].response, json!({"error": "fixture failure"}));
+}
+
+#[tokio::test]
+async fn plain_generations_do_not_declare_the_client_tools() {
+    let mcp = stdio_fixture().await;
+    let mock = MockProvider::new().fallback(MockReply::text("Hello."));
+    let client = client(mock.clone()).set_tools(mcp.tool_set().await.unwrap());
+
+    client
+        .Generate(GenerateRequest::from_prompt("Say hello"))
+        .await
+        .unwrap();
+    assert!(mock.last_call().unwrap().request.functions.is_empty());
+}
+
+#[tokio::test]
+async fn agents_use_the_client_tools() {
+    let mcp = stdio_fixture().await;
+    let mock = MockProvider::new().replies([
+        MockReply::function_call("echo", json!({"text": "from the agent"})),
+        MockReply::text("Echoed."),
+    ]);
+    let client = client(mock.clone()).set_tools(mcp.tool_set().await.unwrap());
+
+    let run = Agent::new(client).run("Echo something").await.unwrap();
+    assert_eq!(run.output, "Echoed.");
+
+    let responses = &mock.calls()[1].request.conversation.turns[2].function_responses;
+    assert_eq!(responses[0].response, json!({"result": "from the agent"}));
+}
+
+#[tokio::test]
+async fn answers_server_requests_and_reads_split_events_over_http() {
+    // a server pinging the client before answering, with the response spread
+    // over two `data` lines and "é" cut between two chunks
+    let received: Arc<Mutex<Vec<Value>>> = Arc::default();
+    async fn handle(
+        State(received): State<Arc<Mutex<Vec<Value>>>>,
+        Json(message): Json<Value>,
+    ) -> Response {
+        received.lock().unwrap().push(message.clone());
+        if message.get("method").is_none() {
+            return Response::new(Body::empty());
+        }
+        let response = json!({"jsonrpc": "2.0", "id": message["id"], "result": {"text": "Café"}});
+        let response = response.to_string();
+        let (head, tail) = response.split_at(response.find("\"result\"").unwrap());
+        let body = format!(
+            "data: {}\n\ndata: {}\r\ndata: {}\n\n",
+            json!({"jsonrpc": "2.0", "id": "srv-1", "method": "ping"}),
+            head,
+            tail
+        )
+        .into_bytes();
+        let cut = body.iter().position(|b| *b == 0xc3).unwrap() + 1;
+        let chunks = [body[..cut].to_vec(), body[cut..].to_vec()].map(Ok::<_, std::io::Error>);
+        Response::builder()
+            .header("content-type", "text/event-stream")
+            .body(Body::from_stream(futures::stream::iter(chunks)))
+            .unwrap()
+    }
+    let app = Router::new()
+        .route("/mcp", post(handle))
+        .with_state(received.clone());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let result = HttpTransport::new(url)
+        .request("tools/list", json!({}))
+        .await
+        .unwrap();
+
+    assert_eq!(result, json!({"text": "Café"}));
+    let received = received.lock().unwrap();
+    assert_eq!(
+        received[1],
+        json!({"jsonrpc": "2.0", "id": "srv-1", "result": {}})
+    );
+}
@@ -0,0 +1,284 @@
+//! The MCP server, driven by the crate's own MCP client over streamable HTTP
+//! and by the `ey-mcp` binary over stdio.
+
+mod common;
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use common::client;
+use ey_ai::{
+    mcp::{
+        client::McpClient,
+        server::McpServer,
+        transport::{HttpTransport, StdioTransport},
+    },
+    model::conversation::conversation::TurnRole,
+    models::error::ProviderError,
+    prompt::registry::TemplateRegistry,
+    testing::{
+        mock::{MockMethod, MockProvider, MockReply},
+        standin::{StandInConfig, StandInServer},
+    },
+    traits::ModelProvider,
+    usage::ledger::{UsageFilter, key_id},
+};
+use serde_json::json;
+use tokio::{net::TcpListener, process::Command};
+
+const SUMMARY: &str = r#"---
+topic: string
+points: integer = 3
+---
+{{#system}}You write summaries.{{/system}}
+{{#user}}Summarize {{topic}} in {{points}} points.{{/user}}
+"#;
+
+// serve:
+// runs `server` on a free port and returns the URL of its endpoint
+async fn serve(server: McpServer) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/mcp", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, server.router()).await });
+    url
+}
+
+#[tokio::test]
+async fn serves_generation_through_the_client() {
+    let mock = MockProvider::new().reply(MockReply::text("Delegated answer"));
+    let server = McpServer::new(client(mock.clone()))
+        .tag("mcp")
+        .tenant("agents");
+    let mcp = McpClient::http(&serve(server).await).await.unwrap();
+
+    let names: Vec<String> = mcp
+        .list_tools()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["generate"]);
+
+    let output = mcp
+        .call_tool(
+            "generate",
+            json!({"prompt": "Hi", "system": "Be brief.", "temperature": 0.5}),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.text(), "Delegated answer");
+    assert_eq!(
+        output.structured_content.unwrap()["text"],
+        "Delegated answer"
+    );
+
+    let request = mock.last_call().unwrap().request;
+    assert_eq!(request.conversation.system.as_deref(), Some("Be brief."));
+    assert_eq!(request.config.temperature, Some(0.5));
+    assert_eq!(request.tag.as_deref(), Some("mcp"));
+    assert_eq!(request.tenant.as_deref(), Some("agents"));
+}
+
+#[tokio::test]
+async fn failures_are_tool_errors() {
+    let mock = MockProvider::new().reply(MockReply::error(ProviderError::Status {
+        status: 400,
+        message: "bad request".into(),
+    }));
+    let mcp = McpClient::http(&serve(McpServer::new(client(mock.clone()))).await)
+        .await
+        .unwrap();
+
+    let failed = mcp
+        .call_tool("generate", json!({"prompt": "Hi"}))
+        .await
+        .unwrap();
+    assert!(failed.is_error);
+    assert!(failed.text().contains("400"), "{}", failed.text());
+
+    let missing = mcp.call_tool("generate", json!({})).await.unwrap();
+    assert_eq!(
+        (missing.is_error, missing.text().as_str()),
+        (true, "prompt is required")
+    );
+
+    // tools that are not configured are unknown
+    assert!(mcp.call_tool("embed", json!({"texts": []})).await.is_err());
+}
+
+#[tokio::test]
+async fn serves_templates_as_prompts_and_tools() {
+    let templates = TemplateRegistry::new();
+    templates.add("summary@v1", SUMMARY).unwrap();
+    templates.add("summary@v2", SUMMARY).unwrap();
+    let mock = MockProvider::new().reply(MockReply::text("1. a 2. b"));
+    let server = McpServer::new(client(mock.clone()).set_templates(Some(Arc::new(templates))));
+    let mcp = McpClient::http(&serve(server).await).await.unwrap();
+
+    let prompts = mcp.list_prompts().await.unwrap();
+    assert_eq!(prompts[0].name, "summary");
+    assert_eq!(prompts[0].description.as_deref(), Some("Versions: v1, v2"));
+    let required: Vec<(&str, bool)> = prompts[0]
+        .arguments
+        .iter()
+        .map(|a| (a.name.as_str(), a.required))
+        .collect();
+    assert_eq!(required, vec![("topic", true), ("points", false)]);
+
+    // typed arguments are read from their string form
+    let arguments = HashMap::from([
+        ("topic".to_string(), "MCP".to_string()),
+        ("points".to_string(), "2".to_string()),
+    ]);
+    let conversation = mcp.get_prompt("summary@v1", arguments).await.unwrap();
+    let turns: Vec<(TurnRole, &str)> = conversation
+        .turns
+        .iter()
+        .map(|t| (t.role, t.text.as_str()))
+        .collect();
+    assert_eq!(
+        turns,
+        vec![
+            (TurnRole::User, "You write summaries."),
+            (TurnRole::User, "Summarize MCP in 2 points."),
+        ]
+    );
+    assert!(mcp.get_prompt("summary", HashMap::new()).await.is_err());
+
+    let output = mcp
+        .call_tool(
+            "generate_template",
+            json!({"id": "summary", "vars": {"topic": "RAG"}}),
+        )
+        .await
+        .unwrap();
+    assert_eq!(output.text(), "1. a 2. b");
+    let request = mock.last_call().unwrap().request;
+    assert_eq!(
+        request.conversation.turns[0].text,
+        "Summarize RAG in 3 points."
+    );
+}
+
+#[tokio::test]
+async fn embeds_through_the_client_and_requires_the_bearer_token() {
+    let mock = MockProvider::new();
+    let client = client(mock.clone()).add_key("pool-key", 1);
+    let server = McpServer::new(client.clone())
+        .embedding_model("gemini-embedding-001")
+        .tag("mcp")
+        .bearer_token("s3cret");
+    let url = serve(server).await;
+
+    let refused = McpClient::http(&url).await.err().unwrap();
+    assert!(refused.to_string().contains("401"), "{}", refused);
+
+    let mcp = McpClient::connect(HttpTransport::new(&url).header("Authorization", "Bearer s3cret"))
+        .await
+        .unwrap();
+    let output = mcp
+        .call_tool("embed", json!({"texts": ["error E1234", "sku 42"]}))
+        .await
+        .unwrap();
+    let embeddings = output.into_result().unwrap()["embeddings"].clone();
+    assert_eq!(embeddings.as_array().unwrap().len(), 2);
+
+    // with a key of the pool, booked in the ledger under the server's tag
+    let call = mock.last_call().unwrap();
+    assert_eq!(call.method, MockMethod::Embed);
+    assert_eq!(
+        (call.api_key.as_str(), call.model.as_str()),
+        ("pool-key", "gemini-embedding-001")
+    );
+    let report = client.usage.query(&UsageFilter {
+        group_by: Some("key,model,tag".into()),
+        ..Default::default()
+    });
+    let record = &report.records[0];
+    assert_eq!(record.key_id, Some(key_id("pool-key")));
+    assert_eq!(record.model.as_deref(), Some("gemini-embedding-001"));
+    assert_eq!(record.tag.as_deref(), Some("mcp"));
+    assert!(record.totals.usage.total_tokens > 0);
+}
+
+#[tokio::test]
+async fn forgets_idle_sessions() {
+    let server =
+        McpServer::new(client(MockProvider::new())).session_timeout(Duration::from_millis(100));
+    let url = serve(server).await;
+    let mcp = McpClient::http(&url).await.unwrap();
+    mcp.ping().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let expired = mcp.ping().await.unwrap_err();
+    assert!(expired.to_string().contains("404"), "{}", expired);
+}
+
+#[tokio::test]
+async fn refuses_foreign_origins_and_open_binds_without_a_token() {
+    let server =
+        McpServer::new(client(MockProvider::new())).allowed_origin("https://tools.example.com");
+    let url = serve(server.clone()).await;
+    let initialize = |origin: &str| {
+        reqwest::Client::new()
+            .post(&url)
+            .header("Origin", origin)
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}))
+            .send()
+    };
+
+    assert_eq!(
+        initialize("https://evil.example").await.unwrap().status(),
+        403
+    );
+    for origin in [
+        "http://localhost:6274",
+        "http://[::1]:80",
+        "https://tools.example.com",
+    ] {
+        assert_eq!(
+            initialize(origin).await.unwrap().status(),
+            200,
+            "{}",
+            origin
+        );
+    }
+
+    let open = TcpListener::bind("0.0.0.0:0").await.unwrap();
+    let refused = server.serve_http(open).await.unwrap_err();
+    assert!(
+        refused.to_string().contains("without a bearer token"),
+        "{}",
+        refused
+    );
+}
+
+#[tokio::test]
+async fn binary_serves_stdio_against_the_standin() {
+    let standin = StandInServer::start(StandInConfig::default().reply("Stand-in reply"))
+        .await
+        .unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_ey-mcp"));
+    command
+        .env("GEMINI_API_KEY", "standin-key")
+        .env_remove("GEMINI_API_KEYS")
+        .env_remove("EY_MCP_TOKEN")
+        .env("GEMINI_BASE_URL", standin.url());
+    let mcp = McpClient::connect(StdioTransport::spawn(command).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(mcp.server_info().server_info.name, "ey-ai");
+    let reply = mcp
+        .call_tool("generate", json!({"prompt": "Hello"}))
+        .await
+        .unwrap();
+    assert_eq!(reply.text(), "Stand-in reply");
+    let embedded = mcp
+        .call_tool("embed", json!({"texts": ["one"]}))
+        .await
+        .unwrap();
+    assert!(!embedded.is_error, "{}", embedded.text());
+
+    mcp.close().await.unwrap();
+}
@@ -0,0 +1,140 @@
+//! The scripted `MockProvider`: the order replies are picked in, the record
+//! of calls and replies converted between generations and streams.
+
+mod common;
+
+use std::time::Duration;
+
+use common::client;
+use ey_ai::{
+    model::generation::generation::{GenerateRequest, StreamEvent, Usage},
+    models::error::ProviderError,
+    testing::mock::{MockMethod, MockProvider, MockReply},
+    traits::ModelProvider,
+};
+use futures::StreamExt;
+
+#[tokio::test]
+async fn answers_from_the_script_then_the_handler_then_the_fallback() {
+    let mock = MockProvider::new()
+        .reply(MockReply::text("first"))
+        .reply(MockReply::text("second"));
+    let client = client(mock.clone());
+    let ask = |prompt: &str| client.Generate(GenerateRequest::from_prompt(prompt));
+
+    assert_eq!(ask("a").await.unwrap().text, "first");
+    assert_eq!(mock.remaining(), 1);
+    assert_eq!(ask("b").await.unwrap().text, "second");
+    let error = ask("c").await.err().unwrap();
+    assert!(
+        error.to_string().contains("no reply left for call 3"),
+        "{}",
+        error
+    );
+
+    mock.fallback(MockReply::text("fallback"));
+    assert_eq!(ask("d").await.unwrap().text, "fallback");
+    mock.respond_with(|request| MockReply::text(request.conversation.turns[0].text.to_uppercase()));
+    assert_eq!(ask("echo").await.unwrap().text, "ECHO");
+
+    let prompts: Vec<_> = mock
+        .calls()
+        .into_iter()
+        .map(|call| call.request.conversation.turns[0].text.clone())
+        .collect();
+    assert_eq!(prompts, ["a", "b", "c", "d", "echo"]);
+
+    mock.reset();
+    assert_eq!((mock.call_count(), mock.remaining()), (0, 0));
+    assert!(ask("e").await.is_err());
+}
+
+#[tokio::test]
+async fn records_the_method_key_and_model_of_every_call() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = client(mock.clone());
+
+    client
+        .Generate(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    let _ = client
+        .GenerateStream(GenerateRequest::from_prompt("hi"))
+        .await
+        .unwrap();
+    client
+        .CountTokens(&GenerateRequest::from_prompt("hi").conversation)
+        .await
+        .unwrap();
+
+    let calls = mock.calls();
+    let methods: Vec<_> = calls.iter().map(|call| call.method).collect();
+    assert_eq!(
+        methods,
+        [
+            MockMethod::Generate,
+            MockMethod::Stream,
+            MockMethod::CountTokens
+        ]
+    );
+    assert!(
+        calls
+            .iter()
+            .all(|call| call.api_key == "test-key" && call.model == "gemini-2.5-flash")
+    );
+}
+
+#[tokio::test]
+async fn converts_replies_between_generations_and_streams() {
+    let usage = Usage {
+        total_tokens: 7,
+        ..Default::default()
+    };
+    let mock = MockProvider::new().replies([
+        MockReply::stream(["Red, ", "green."]).usage(usage),
+        MockReply::text("Blue.").usage(usage),
+        MockReply::stream(["Yel"]).then_fail(ProviderError::Timeout),
+    ]);
+    let client = client(mock.clone());
+    let stream = || client.GenerateStream(GenerateRequest::from_prompt("Colors"));
+
+    // a streamed reply joined for a generation
+    let joined = client
+        .Generate(GenerateRequest::from_prompt("Colors"))
+        .await
+        .unwrap();
+    assert_eq!(joined.text, "Red, green.");
+    assert_eq!(joined.usage.unwrap().total_tokens, 7);
+
+    // a generation sent as a single chunk
+    let events: Vec<_> = stream().await.unwrap().map(Result::unwrap).collect().await;
+    assert!(matches!(&events[0], StreamEvent::Text { text } if text == "Blue."));
+    assert!(matches!(
+        &events[1],
+        StreamEvent::Done { usage: Some(u), .. } if u.total_tokens == 7
+    ));
+
+    // the chunks, then the error instead of `Done`
+    let events: Vec<_> = stream().await.unwrap().collect().await;
+    assert!(matches!(&events[0], Ok(StreamEvent::Text { text }) if text == "Yel"));
+    let error = events[1].as_ref().err().unwrap();
+    assert!(matches!(
+        ProviderError::classify(error),
+        Some(ProviderError::Timeout)
+    ));
+    assert_eq!(events.len(), 2);
+}
+
+#[test]
+fn serves_the_blocking_path_with_latency() {
+    let mock =
+        MockProvider::new().reply(MockReply::text("slow").latency(Duration::from_millis(50)));
+    let client = client(mock.clone());
+
+    let started = std::time::Instant::now();
+    let message = client.GenerateSyncContent("hi".into()).unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(message["choice"]["role"]["content"], "slow");
+    assert_eq!(mock.last_call().unwrap().method, MockMethod::Generate);
+}
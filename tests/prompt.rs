@@ -0,0 +1,222 @@
+//! Prompt templates: rendering, the versioned registry and hot reload.
+
+mod common;
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use common::client;
+use ey_ai::{
+    model::conversation::conversation::{Turn, TurnRole},
+    prompt::{registry::TemplateRegistry, template::Template},
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+};
+use serde_json::json;
+
+const ANSWER: &str = r#"---
+# ticket answer
+customer: string
+tickets: array
+vip: boolean?
+tone: string = "friendly"
+---
+{{#system}}
+You answer support tickets in a {{tone}} tone.
+{{#if vip}}
+The customer is a VIP.
+{{/if}}
+{{/system}}
+{{#each examples}}
+{{#user}}{{question}}{{/user}}
+{{#model}}{{answer}}{{/model}}
+{{/each}}
+{{#user}}
+Customer: {{customer}}
+{{#each tickets}}
+{{@index}}. {{subject}}{{#if @last}} (latest){{/if}}
+{{else}}
+No open tickets.
+{{/each}}
+{{> shared/signature}}
+{{/user}}
+"#;
+
+fn no_partials(name: &str) -> anyhow::Result<Arc<Template>> {
+    Err(anyhow::anyhow!("no partial {}", name))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ey-prompt-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("support")).unwrap();
+    std::fs::create_dir_all(dir.join("shared")).unwrap();
+    dir
+}
+
+#[test]
+fn renders_sections_loops_and_conditionals() {
+    let registry = TemplateRegistry::new();
+    registry.add("support/answer@v1", ANSWER).unwrap();
+    registry
+        .add("shared/signature", "Sign as {{agent.name}}.")
+        .unwrap();
+
+    let conversation = registry
+        .render(
+            "support/answer",
+            &json!({
+                "customer": "Ada",
+                "vip": true,
+                "agent": {"name": "Sam"},
+                "tickets": [{"subject": "Refund"}, {"subject": "Login"}],
+                "examples": [{"question": "Hi", "answer": "Hello!"}],
+            }),
+        )
+        .unwrap();
+
+    assert_eq!(
+        conversation.system.as_deref(),
+        Some("You answer support tickets in a friendly tone.\nThe customer is a VIP.")
+    );
+    assert_eq!(
+        conversation.turns,
+        vec![
+            Turn::user("Hi"),
+            Turn::model("Hello!"),
+            Turn::user("Customer: Ada\n0. Refund\n1. Login (latest)\nSign as Sam."),
+        ]
+    );
+
+    let empty = registry
+        .render(
+            "support/answer@v1",
+            &json!({"customer": "Bob", "tickets": [], "agent": {"name": "Sam"}}),
+        )
+        .unwrap();
+    assert_eq!(
+        empty.system.as_deref(),
+        Some("You answer support tickets in a friendly tone.")
+    );
+    assert_eq!(empty.turns.len(), 1);
+    assert_eq!(empty.turns[0].role, TurnRole::User);
+    assert!(
+        empty.turns[0]
+            .text
+            .starts_with("Customer: Bob\nNo open tickets.\n")
+    );
+}
+
+#[test]
+fn checks_variables_against_their_declarations() {
+    let template = Template::parse("answer@v1", ANSWER).unwrap();
+    assert_eq!(template.id(), "answer@v1");
+    assert_eq!(template.variables.len(), 4);
+
+    let missing = template
+        .render(&json!({"tickets": []}), &no_partials)
+        .unwrap_err();
+    assert!(missing.to_string().contains("missing variable customer"));
+
+    let mistyped = template
+        .render(&json!({"customer": "Ada", "tickets": "none"}), &no_partials)
+        .unwrap_err();
+    assert!(
+        mistyped
+            .to_string()
+            .contains("variable tickets must be Array")
+    );
+
+    let plain = Template::parse("hello", "Hello {{name}}!").unwrap();
+    assert_eq!(
+        plain
+            .render_text(&json!({"name": "Ada"}), &no_partials)
+            .unwrap(),
+        "Hello Ada!"
+    );
+    let undefined = plain.render_text(&json!({}), &no_partials).unwrap_err();
+    assert!(undefined.to_string().contains("undefined variable name"));
+
+    assert!(Template::parse("broken", "{{#if a}}open").is_err());
+    assert!(Template::parse("broken", "{{#user}}{{#system}}x{{/system}}{{/user}}").is_err());
+}
+
+#[test]
+fn loads_versions_from_a_directory() {
+    let dir = temp_dir("versions");
+    std::fs::write(dir.join("support/answer@v2.prompt"), "v2 for {{name}}").unwrap();
+    std::fs::write(dir.join("support/answer@v10.prompt"), "v10 for {{name}}").unwrap();
+    std::fs::write(dir.join("shared/notes.txt"), "ignored").unwrap();
+
+    let registry = TemplateRegistry::load(&dir).unwrap();
+
+    assert_eq!(registry.names(), vec!["support/answer".to_string()]);
+    assert_eq!(registry.versions("support/answer"), vec!["v2", "v10"]);
+    let vars = json!({"name": "Ada"});
+    assert_eq!(
+        registry.render_text("support/answer", &vars).unwrap(),
+        "v10 for Ada"
+    );
+    assert_eq!(
+        registry.render_text("support/answer@v2", &vars).unwrap(),
+        "v2 for Ada"
+    );
+    assert!(registry.get("support/answer@v3").is_err());
+
+    // an invalid directory is rejected as a whole
+    std::fs::write(dir.join("support/answer@v11.prompt"), "{{#each}}").unwrap();
+    assert!(registry.reload().is_err());
+    assert_eq!(registry.versions("support/answer"), vec!["v2", "v10"]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn watch_picks_up_new_versions() {
+    let dir = temp_dir("watch");
+    std::fs::write(dir.join("greet@v1.prompt"), "Hello v1").unwrap();
+    let registry = Arc::new(TemplateRegistry::load(&dir).unwrap());
+    let watcher = registry.watch(Duration::from_millis(20));
+
+    std::fs::write(dir.join("greet@v2.prompt"), "Hello v2").unwrap();
+    let mut latest = String::new();
+    for _ in 0..100 {
+        latest = registry.render_text("greet", &json!({})).unwrap();
+        if latest == "Hello v2" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    assert_eq!(latest, "Hello v2");
+    watcher.abort();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn client_generates_from_a_template() {
+    let mock = MockProvider::new().reply(MockReply::text("Sorry about that."));
+    let registry = TemplateRegistry::new();
+    registry
+        .add(
+            "support/answer@v3",
+            "{{#system}}Be brief.{{/system}}\n{{#user}}{{question}}{{/user}}",
+        )
+        .unwrap();
+    let client = client(mock.clone()).set_templates(Some(Arc::new(registry)));
+
+    let reply = client
+        .GenerateTemplate(
+            "support/answer@v3",
+            json!({"question": "Where is my order?"}),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(reply.text, "Sorry about that.");
+    let request = mock.last_call().unwrap().request;
+    assert_eq!(request.conversation.system.as_deref(), Some("Be brief."));
+    assert_eq!(
+        request.conversation.turns,
+        vec![Turn::user("Where is my order?")]
+    );
+}
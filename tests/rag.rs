@@ -0,0 +1,314 @@
+//! The RAG pipeline: loaders, chunkers, the vector store and retrieval in
+//! front of generation, offline with the hash embedder and the stand-in.
+
+mod common;
+
+use std::{path::PathBuf, sync::Arc};
+
+use common::client;
+use ey_ai::{
+    embedding::embedder::{ClientEmbedder, GeminiEmbedder, HashEmbedder},
+    model::generation::generation::GenerateRequest,
+    rag::{
+        chunker::Chunker,
+        document::{Document, Filter},
+        loader::{html_to_text, load_dir},
+        retriever::{Retriever, VectorRetriever},
+    },
+    testing::{
+        mock::{MockMethod, MockProvider, MockReply},
+        standin::{StandInConfig, StandInServer},
+    },
+    traits::ModelProvider,
+    usage::ledger::UsageFilter,
+};
+use serde_json::json;
+
+const GUIDE: &str = "# Operations guide
+
+Intro paragraph.
+
+## Keys
+
+Rotate API keys every 90 days. Old keys stay valid for one hour.
+
+```sh
+# not a heading
+ey keys rotate
+```
+
+## Budgets
+
+### Alerts
+
+Budget alerts fire at 80% of the monthly limit.
+";
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ey-rag-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    dir
+}
+
+fn docs() -> Vec<Document> {
+    vec![
+        Document::new("keys", "Rotate API keys every 90 days from the console.")
+            .meta("product", "console")
+            .meta("tags", json!(["security", "keys"])),
+        Document::new(
+            "budgets",
+            "Budget alerts fire at 80 percent of the monthly limit.",
+        )
+        .meta("product", "billing"),
+        Document::new(
+            "errors",
+            "Error E1234 means the quota of the project is exhausted.",
+        )
+        .meta("product", "api"),
+    ]
+}
+
+fn retriever() -> VectorRetriever {
+    VectorRetriever::new(Arc::new(HashEmbedder::new(256)))
+}
+
+#[test]
+fn loads_text_markdown_and_html() {
+    let (title, text) = html_to_text(
+        "<html><head><title>Help &amp; FAQ</title><style>p { color: red }</style></head>\
+         <body><h1>Getting started</h1><p>Install  the <b>CLI</b>.</p>\
+         <script>track()</script><ul><li>Linux</li><li>macOS &#8211; 13+</li></ul>\
+         <!-- hidden --><p>Done&nbsp;!</p></body></html>",
+    );
+    assert_eq!(title.as_deref(), Some("Help & FAQ"));
+    assert_eq!(
+        text,
+        "Getting started\n\nInstall the CLI.\n\n- Linux\n- macOS \u{2013} 13+\n\nDone !"
+    );
+
+    let dir = temp_dir("load");
+    std::fs::write(dir.join("guide.md"), GUIDE).unwrap();
+    std::fs::write(dir.join("nested/notes.txt"), "Plain notes.").unwrap();
+    std::fs::write(dir.join("nested/page.html"), "<h1>Page</h1><p>Body</p>").unwrap();
+    std::fs::write(dir.join("image.png"), [0u8, 1, 2]).unwrap();
+
+    let documents = load_dir(&dir).unwrap();
+    let loaded: Vec<(&str, Option<&str>)> = documents
+        .iter()
+        .map(|d| (d.metadata["format"].as_str().unwrap(), d.title()))
+        .collect();
+    assert_eq!(
+        loaded,
+        vec![
+            ("markdown", Some("Operations guide")),
+            ("text", None),
+            ("html", Some("Page")),
+        ]
+    );
+    assert!(documents[1].id.ends_with("nested/notes.txt"));
+    assert_eq!(documents[1].metadata["source"], json!(documents[1].id));
+    assert_eq!(documents[2].text, "Page\n\nBody");
+}
+
+#[test]
+fn chunks_by_size_sentences_and_headings() {
+    let text = "aaaa bbbb cccc dddd eeee ffff";
+    let fixed = Chunker::Fixed {
+        size: 10,
+        overlap: 5,
+    }
+    .chunk(&Document::new("f", text));
+    let fixed: Vec<&str> = fixed.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(
+        fixed,
+        vec![
+            "aaaa bbbb",
+            "bbbb cccc",
+            "cccc dddd",
+            "dddd eeee",
+            "eeee ffff"
+        ]
+    );
+
+    let sentences = Chunker::Sentences {
+        max_size: 40,
+        overlap: 1,
+    }
+    .chunk(&Document::new(
+        "s",
+        "First one here. Second one here! Third one here? Fourth.",
+    ));
+    let sentences: Vec<&str> = sentences.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(
+        sentences,
+        vec![
+            "First one here. Second one here!",
+            "Second one here! Third one here? Fourth.",
+        ]
+    );
+
+    let guide = Document::new("guide", GUIDE).meta("source", "docs/guide.md");
+    let sections = Chunker::Markdown { max_size: 500 }.chunk(&guide);
+    let headings: Vec<String> = sections.iter().map(|c| c.source()).collect();
+    assert_eq!(
+        headings,
+        vec![
+            "docs/guide.md > Operations guide",
+            "docs/guide.md > Operations guide > Keys",
+            "docs/guide.md > Operations guide > Budgets > Alerts",
+        ]
+    );
+    assert!(sections[1].text.contains("# not a heading"));
+    assert_eq!((sections[2].id.as_str(), sections[2].index), ("guide#2", 2));
+}
+
+#[tokio::test]
+async fn retrieves_with_metadata_filters() {
+    let retriever = retriever();
+    assert_eq!(retriever.index(&docs()).await.unwrap(), 3);
+
+    let hits = retriever
+        .retrieve("how often should I rotate keys", 2, &Filter::new())
+        .await
+        .unwrap();
+    assert_eq!(hits[0].chunk.document_id, "keys");
+    assert!(hits[0].score > hits[1].score);
+
+    let api = Filter::new().one_of("product", ["api", "billing"]);
+    let hits = retriever.retrieve("rotate keys", 3, &api).await.unwrap();
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().all(|h| h.chunk.document_id != "keys"));
+
+    // array metadata matches any of its items
+    let tagged = Filter::new().eq("tags", "security");
+    let hits = retriever.retrieve("anything", 3, &tagged).await.unwrap();
+    assert_eq!(hits.len(), 1);
+
+    // indexing a document again replaces its chunks
+    let updated = Document::new("keys", "Keys never expire.");
+    retriever.index(&[updated]).await.unwrap();
+    assert_eq!(retriever.len(), 3);
+    let hits = retriever
+        .retrieve("keys expire", 1, &Filter::new())
+        .await
+        .unwrap();
+    assert_eq!(hits[0].chunk.text, "Keys never expire.");
+}
+
+#[tokio::test]
+async fn persists_the_store_to_disk() {
+    let path = temp_dir("store").join("index.json");
+    let retriever = retriever();
+    retriever.index(&docs()).await.unwrap();
+    retriever.save(&path).unwrap();
+
+    let reopened = VectorRetriever::load(Arc::new(HashEmbedder::new(256)), &path).unwrap();
+    assert_eq!(reopened.len(), 3);
+    let hits = reopened
+        .retrieve("error E1234", 1, &Filter::new())
+        .await
+        .unwrap();
+    assert_eq!(hits[0].chunk.document_id, "errors");
+    assert_eq!(hits[0].chunk.metadata["product"], "api");
+
+    let other = GeminiEmbedder::new("key", "gemini-embedding-001");
+    let mismatch = VectorRetriever::load(Arc::new(other), &path).err().unwrap();
+    assert!(mismatch.to_string().contains("hash"), "{}", mismatch);
+}
+
+#[tokio::test]
+async fn embeds_through_the_provider_api_in_batches() {
+    let standin = StandInServer::start(StandInConfig::default())
+        .await
+        .unwrap();
+    let embedder =
+        GeminiEmbedder::new("standin-key", "gemini-embedding-001").base_url(standin.url());
+    let retriever = VectorRetriever::new(Arc::new(embedder));
+
+    // more chunks than one batchEmbedContents request takes
+    let mut documents: Vec<Document> = (0..120)
+        .map(|i| {
+            Document::new(
+                format!("filler-{}", i),
+                format!("Filler page number {}.", i),
+            )
+        })
+        .collect();
+    documents.push(Document::new("sku", "SKU AX-4411 ships in blue and red."));
+    assert_eq!(retriever.index(&documents).await.unwrap(), 121);
+
+    let hits = retriever
+        .retrieve("SKU AX-4411 colours", 1, &Filter::new())
+        .await
+        .unwrap();
+    assert_eq!(hits[0].chunk.document_id, "sku");
+}
+
+#[tokio::test]
+async fn embeds_through_a_model_client() {
+    let mock = MockProvider::new();
+    let client = client(mock.clone()).add_key("pool-key", 1);
+    let embedder = ClientEmbedder::new(client.clone(), "gemini-embedding-001")
+        .tag("rag")
+        .tenant("acme");
+    let retriever = VectorRetriever::new(Arc::new(embedder));
+
+    retriever.index(&docs()).await.unwrap();
+    let hits = retriever
+        .retrieve("error E1234", 1, &Filter::new())
+        .await
+        .unwrap();
+    assert_eq!(hits[0].chunk.document_id, "errors");
+
+    // one call for the documents, one for the query
+    assert_eq!(mock.call_count(), 2);
+    assert!(
+        mock.calls()
+            .iter()
+            .all(|call| call.method == MockMethod::Embed
+                && call.api_key == "pool-key"
+                && call.model == "gemini-embedding-001")
+    );
+    let report = client.usage.query(&UsageFilter {
+        tag: Some("rag".into()),
+        ..Default::default()
+    });
+    assert_eq!(report.total.requests, 2);
+    assert!(client.budgets.tenant_usage("acme").tokens > 0);
+}
+
+#[tokio::test]
+async fn generates_with_cited_context() {
+    let retriever = retriever();
+    retriever.index(&docs()).await.unwrap();
+    let mock = MockProvider::new().reply(MockReply::text(
+        "Rotate them every 90 days [1]. See also [7] and [1, 2].",
+    ));
+    let client = client(mock.clone());
+
+    let request = GenerateRequest::from_prompt("How often do I rotate keys?");
+    let answer = client
+        .GenerateWithContext(request, &retriever, 2, &Filter::new())
+        .await
+        .unwrap();
+
+    assert_eq!(answer.sources.len(), 2);
+    assert_eq!(answer.citations(), vec![1, 2]);
+    assert_eq!(answer.cited_sources()[0].chunk.document_id, "keys");
+
+    let sent = mock.last_call().unwrap().request;
+    assert!(
+        sent.conversation
+            .system
+            .unwrap()
+            .contains("Cite the sources")
+    );
+    let prompt = &sent.conversation.turns[0].text;
+    assert!(
+        prompt.starts_with("Sources:\n\n[1] keys\nRotate API keys"),
+        "{}",
+        prompt
+    );
+    assert!(prompt.ends_with("Question: How often do I rotate keys?"));
+}
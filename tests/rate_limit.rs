@@ -0,0 +1,143 @@
+//! The local rate limiter: in-flight slots, priorities, request and token
+//! buckets settled with the actual usage, and the limits of a client.
+
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use common::client;
+use ey_ai::{
+    model::generation::generation::{GenerateRequest, Usage},
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+    utils::rate_limit::{Priority, RateLimiter, RateLimits},
+};
+
+const SHORT: Duration = Duration::from_millis(50);
+
+fn limiter(limits: RateLimits) -> Arc<RateLimiter> {
+    Arc::new(RateLimiter::new(limits))
+}
+
+#[tokio::test]
+async fn serves_higher_priorities_first_once_a_slot_frees() {
+    let limiter = limiter(RateLimits {
+        max_in_flight: Some(1),
+        ..Default::default()
+    });
+    let held = limiter.acquire(Priority::Normal, 0).await;
+
+    let order = Arc::new(Mutex::new(Vec::new()));
+    let mut waiters = Vec::new();
+    for priority in [Priority::Low, Priority::Normal, Priority::High] {
+        let (waiting, order) = (limiter.clone(), order.clone());
+        waiters.push(tokio::spawn(async move {
+            let _permit = waiting.acquire(priority, 0).await;
+            order.lock().unwrap().push(priority);
+        }));
+        // queued in this order
+        while limiter.queued() < waiters.len() {
+            tokio::task::yield_now().await;
+        }
+    }
+    assert_eq!(limiter.in_flight(), 1);
+
+    drop(held);
+    for waiter in waiters {
+        waiter.await.unwrap();
+    }
+    assert_eq!(
+        *order.lock().unwrap(),
+        [Priority::High, Priority::Normal, Priority::Low]
+    );
+    assert_eq!((limiter.in_flight(), limiter.queued()), (0, 0));
+}
+
+#[tokio::test]
+async fn an_empty_request_bucket_holds_the_next_request() {
+    let limiter = limiter(RateLimits {
+        requests_per_minute: Some(1),
+        ..Default::default()
+    });
+    drop(limiter.acquire(Priority::Normal, 0).await);
+
+    let next = tokio::time::timeout(SHORT, limiter.acquire(Priority::High, 0)).await;
+    assert!(next.is_err());
+    // a request given up on leaves the queue
+    assert_eq!(limiter.queued(), 0);
+}
+
+#[tokio::test]
+async fn settled_usage_is_charged_to_the_token_bucket() {
+    let limiter = limiter(RateLimits {
+        tokens_per_minute: Some(1000),
+        ..Default::default()
+    });
+    let mut permit = limiter.acquire(Priority::Normal, 10).await;
+    permit.settle(100);
+    drop(permit);
+
+    // 890 tokens are left
+    drop(limiter.acquire(Priority::Normal, 800).await);
+    let over = tokio::time::timeout(SHORT, limiter.acquire(Priority::Normal, 800)).await;
+    assert!(over.is_err());
+}
+
+#[tokio::test]
+async fn blocking_callers_share_the_queue() {
+    let limiter = limiter(RateLimits {
+        max_in_flight: Some(1),
+        ..Default::default()
+    });
+    let held = limiter.acquire(Priority::Normal, 0).await;
+
+    let blocked = {
+        let limiter = limiter.clone();
+        std::thread::spawn(move || {
+            let _permit = limiter.acquire_blocking(Priority::Normal, 0);
+        })
+    };
+    while limiter.queued() == 0 {
+        tokio::task::yield_now().await;
+    }
+    drop(held);
+    blocked.join().unwrap();
+    assert_eq!(limiter.in_flight(), 0);
+}
+
+#[tokio::test]
+async fn the_client_waits_for_the_limits_of_its_model() {
+    let reply = MockReply::text("ok")
+        .usage(Usage {
+            total_tokens: 10,
+            ..Default::default()
+        })
+        .latency(Duration::from_millis(100));
+    let mock = MockProvider::new().fallback(reply);
+    let in_flight = |max| {
+        Some(RateLimits {
+            max_in_flight: Some(max),
+            ..Default::default()
+        })
+    };
+    let client = client(mock.clone()).set_rate_limits(None, in_flight(1));
+    let twice = || async {
+        let started = Instant::now();
+        let (a, b) = tokio::join!(
+            client.Generate(GenerateRequest::from_prompt("hi")),
+            client.Generate(GenerateRequest::from_prompt("hi"))
+        );
+        a.unwrap();
+        b.unwrap();
+        started.elapsed()
+    };
+
+    assert!(twice().await >= Duration::from_millis(200));
+    // the limits of the model itself take precedence
+    client.set_rate_limits(Some("gemini-2.5-flash"), in_flight(2));
+    assert!(twice().await < Duration::from_millis(200));
+    assert_eq!(mock.call_count(), 4);
+}
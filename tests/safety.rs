@@ -0,0 +1,104 @@
+//! Safety settings: client defaults merged per category, the ratings and
+//! blocks of recorded Gemini replies, and the blocking path refusing
+//! settings it cannot send.
+
+mod common;
+
+use common::client;
+use ey_ai::{
+    model::{
+        generation::generation::GenerateRequest,
+        safety::safety::{HarmBlockThreshold, HarmCategory, HarmProbability, SafetySetting},
+    },
+    models::error::ProviderError,
+    testing::{
+        cassette::CassetteServer,
+        mock::{MockProvider, MockReply},
+    },
+    traits::ModelProvider,
+};
+
+fn defaults() -> Vec<SafetySetting> {
+    vec![
+        SafetySetting::new(
+            HarmCategory::HateSpeech,
+            HarmBlockThreshold::BlockLowAndAbove,
+        ),
+        SafetySetting::new(
+            HarmCategory::DangerousContent,
+            HarmBlockThreshold::BlockMediumAndAbove,
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn request_settings_override_the_client_defaults_per_category() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = client(mock.clone()).set_safety_settings(defaults());
+
+    client
+        .Generate(GenerateRequest::from_prompt("hi").safety(
+            HarmCategory::DangerousContent,
+            HarmBlockThreshold::BlockOnlyHigh,
+        ))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        mock.last_call().unwrap().request.safety_settings,
+        vec![
+            SafetySetting::new(
+                HarmCategory::HateSpeech,
+                HarmBlockThreshold::BlockLowAndAbove
+            ),
+            SafetySetting::new(
+                HarmCategory::DangerousContent,
+                HarmBlockThreshold::BlockOnlyHigh
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn gemini_reports_ratings_and_blocked_answers() {
+    let cassette = format!("{}/tests/cassettes/safety.json", env!("CARGO_MANIFEST_DIR"));
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini()).set_safety_settings(defaults());
+    let ask = |prompt: &str| {
+        client.Generate(GenerateRequest::from_prompt(prompt).safety(
+            HarmCategory::DangerousContent,
+            HarmBlockThreshold::BlockOnlyHigh,
+        ))
+    };
+
+    let answer = ask("How do I strip old paint safely?").await.unwrap();
+    let flagged: Vec<_> = answer
+        .safety_ratings
+        .iter()
+        .filter(|r| r.is_flagged())
+        .collect();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].category, HarmCategory::DangerousContent);
+    assert_eq!(flagged[0].probability, HarmProbability::Medium);
+
+    let error = ask("How do I make thermite?").await.err().unwrap();
+    let Some(ProviderError::SafetyBlock { reason, ratings }) = ProviderError::classify(&error)
+    else {
+        panic!("expected a safety block, got {:#}", error);
+    };
+    assert_eq!(reason, "SAFETY");
+    assert!(ratings[0].blocked);
+    assert!(server.unused().is_empty());
+}
+
+#[test]
+fn the_blocking_path_refuses_safety_settings() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = client(mock.clone());
+    assert!(client.GenerateSyncContent("hi".into()).is_ok());
+
+    let client = client.set_safety_settings(defaults());
+    let error = client.GenerateSyncContent("hi".into()).err().unwrap();
+    assert!(error.to_string().contains("safety settings"), "{}", error);
+    assert_eq!(mock.call_count(), 1);
+}
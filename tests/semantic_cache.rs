@@ -0,0 +1,148 @@
+//! The semantic cache: prompts meaning the same answered from the cache,
+//! scopes, cache control and embedder failures, with the local hash embedder.
+
+mod common;
+
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use common::client;
+use ey_ai::{
+    cache::{response::CacheControl, semantic::SemanticCache},
+    embedding::embedder::{Embedder, HashEmbedder},
+    model::{
+        conversation::conversation::Conversation,
+        generation::generation::{GenerateRequest, GenerationConfig},
+    },
+    models::model_client::ModelClient,
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+};
+
+struct BrokenEmbedder;
+
+#[async_trait]
+impl Embedder for BrokenEmbedder {
+    fn name(&self) -> &str {
+        "broken"
+    }
+
+    async fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Err(anyhow!("embedding service unavailable"))
+    }
+}
+
+fn semantic(mock: &MockProvider, embedder: Arc<dyn Embedder>) -> ModelClient {
+    client(mock.clone()).set_semantic_cache(Some(SemanticCache::new(embedder)))
+}
+
+async fn ask(client: &ModelClient, request: GenerateRequest) -> String {
+    client.Generate(request).await.unwrap().text
+}
+
+#[tokio::test]
+async fn answers_prompts_that_mean_the_same_from_the_cache() {
+    let mock = MockProvider::new().replies([MockReply::text("Paris."), MockReply::text("Berlin.")]);
+    let client = semantic(&mock, Arc::new(HashEmbedder::default()));
+    let prompt = |text: &str| GenerateRequest::from_prompt(text);
+
+    assert_eq!(
+        ask(&client, prompt("What is the capital of France?")).await,
+        "Paris."
+    );
+    assert_eq!(
+        ask(&client, prompt("what is the capital of france")).await,
+        "Paris."
+    );
+    assert_eq!(
+        ask(&client, prompt("Which city hosts the Bundestag?")).await,
+        "Berlin."
+    );
+
+    assert_eq!(mock.call_count(), 2);
+    let stats = client
+        .semantic_cache
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap()
+        .snapshot();
+    assert_eq!(
+        (stats.hits, stats.misses, stats.stores, stats.entries),
+        (1, 2, 2, 2)
+    );
+}
+
+#[tokio::test]
+async fn compares_prompts_only_within_their_scope() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = semantic(&mock, Arc::new(HashEmbedder::default()));
+    let with_system = |system: &str| {
+        GenerateRequest::new(Conversation::from_prompt("Summarize the ticket").with_system(system))
+    };
+
+    ask(&client, with_system("Answer in English.")).await;
+    ask(&client, with_system("Answer in French.")).await;
+    ask(&client, with_system("Answer in English.")).await;
+    assert_eq!(mock.call_count(), 2);
+
+    // a different generation config is another scope
+    let short = GenerationConfig {
+        max_output_tokens: Some(20),
+        ..Default::default()
+    };
+    ask(
+        &client,
+        with_system("Answer in English.").config(short.clone()),
+    )
+    .await;
+    ask(&client, with_system("Answer in English.").config(short)).await;
+    assert_eq!(mock.call_count(), 3);
+}
+
+#[tokio::test]
+async fn cache_control_skips_reading_or_writing() {
+    let mock = MockProvider::new().replies([
+        MockReply::text("first"),
+        MockReply::text("bypassed"),
+        MockReply::text("refreshed"),
+    ]);
+    let client = semantic(&mock, Arc::new(HashEmbedder::default()));
+    let request = |control| GenerateRequest::from_prompt("Name a color").cache(control);
+
+    ask(&client, request(CacheControl::Default)).await;
+    assert_eq!(
+        ask(&client, request(CacheControl::Bypass)).await,
+        "bypassed"
+    );
+    assert_eq!(
+        ask(&client, request(CacheControl::Refresh)).await,
+        "refreshed"
+    );
+    assert_eq!(
+        ask(&client, request(CacheControl::Default)).await,
+        "refreshed"
+    );
+    assert_eq!(mock.call_count(), 3);
+}
+
+#[tokio::test]
+async fn a_failing_embedder_never_fails_the_request() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let client = semantic(&mock, Arc::new(BrokenEmbedder));
+
+    for _ in 0..2 {
+        assert_eq!(ask(&client, GenerateRequest::from_prompt("hi")).await, "ok");
+    }
+
+    assert_eq!(mock.call_count(), 2);
+    let stats = client
+        .semantic_cache
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap()
+        .snapshot();
+    assert_eq!((stats.errors, stats.hits, stats.stores), (2, 0, 0));
+}
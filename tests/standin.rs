@@ -0,0 +1,149 @@
+//! The Gemini stand-in, driven through the real Gemini provider.
+
+use std::sync::Arc;
+
+use ey_ai::{
+    embedding::embedder::{Embedder, GeminiEmbedder},
+    model::generation::generation::{GenerateRequest, StreamEvent},
+    model_llm::Models,
+    models::{error::ProviderError, model_client::ModelClient},
+    testing::standin::{Fault, StandInConfig, StandInServer},
+    traits::ModelProvider,
+};
+use futures::StreamExt;
+
+const KEY: &str = "test-key";
+const MODEL: &str = "gemini-2.5-flash";
+
+#[tokio::test]
+async fn echoes_the_prompt_with_usage() {
+    let server = StandInServer::start(StandInConfig::default())
+        .await
+        .unwrap();
+    let client =
+        ModelClient::new(Arc::new(server.gemini())).init(KEY.into(), Models::Gemini25Flash);
+
+    let reply = client
+        .Generate(GenerateRequest::from_prompt("Hello there"))
+        .await
+        .unwrap();
+
+    assert_eq!(reply.text, "Echo: Hello there");
+    let usage = reply.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.candidates_tokens), (3, 5));
+    assert_eq!(reply.model_version.as_deref(), Some(MODEL));
+}
+
+#[tokio::test]
+async fn streams_the_canned_reply_word_by_word() {
+    let config = StandInConfig::default().reply("one two three");
+    let server = StandInServer::start(config).await.unwrap();
+
+    let events: Vec<StreamEvent> = server
+        .gemini()
+        .generate_events(KEY, MODEL, &GenerateRequest::from_prompt("Count"))
+        .await
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    let text: Vec<&str> = events
+        .iter()
+        .filter_map(|event| match event {
+            StreamEvent::Text { text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text, ["one ", "two ", "three"]);
+    assert!(matches!(
+        events.last(),
+        Some(StreamEvent::Done { usage: Some(usage), .. }) if usage.candidates_tokens == 4
+    ));
+}
+
+#[tokio::test]
+async fn counts_tokens_and_embeds_deterministically() {
+    let server = StandInServer::start(StandInConfig::default())
+        .await
+        .unwrap();
+    let conversation = GenerateRequest::from_prompt("Twelve chars").conversation;
+
+    let tokens = server
+        .gemini()
+        .count_tokens(KEY, MODEL, &conversation)
+        .await
+        .unwrap();
+    assert_eq!(tokens, 3);
+
+    let embedder = GeminiEmbedder::new(KEY, "gemini-embedding-001").base_url(server.url());
+    let texts = vec![
+        "a cat".to_string(),
+        "a cat".to_string(),
+        "tax law".to_string(),
+    ];
+    let vectors = embedder.embed(&texts).await.unwrap();
+    assert_eq!(vectors.len(), 3);
+    assert_eq!(vectors[0].len(), 768);
+    assert_eq!(vectors[0], vectors[1]);
+    assert_ne!(vectors[0], vectors[2]);
+}
+
+#[tokio::test]
+async fn injects_configured_and_marked_faults() {
+    let config = StandInConfig::default().fail(Fault::RateLimited, 2);
+    let server = StandInServer::start(config).await.unwrap();
+    let provider = server.gemini();
+    let status = |error: &anyhow::Error| ProviderError::classify(error).and_then(|e| e.status());
+
+    assert!(
+        provider
+            .generate_text(KEY, MODEL, "first".into())
+            .await
+            .is_ok()
+    );
+    let limited = provider
+        .generate_text(KEY, MODEL, "second".into())
+        .await
+        .unwrap_err();
+    assert_eq!(status(&limited), Some(429));
+
+    let failed = provider
+        .generate_text(KEY, MODEL, "[standin:500]".into())
+        .await
+        .unwrap_err();
+    assert_eq!(status(&failed), Some(500));
+
+    let blocked = provider
+        .generate_text(KEY, MODEL, "[standin:safety]".into())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        ProviderError::classify(&blocked),
+        Some(ProviderError::SafetyBlock { .. })
+    ));
+
+    let malformed = provider
+        .generate_text(KEY, MODEL, "[standin:malformed]".into())
+        .await
+        .unwrap_err();
+    assert!(malformed.to_string().contains("Failed to parse response"));
+}
+
+#[tokio::test]
+async fn rejects_requests_without_key() {
+    let server = StandInServer::start(StandInConfig::default())
+        .await
+        .unwrap();
+
+    let error = server
+        .gemini()
+        .generate_text("", MODEL, "Hello".into())
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        ProviderError::classify(&error).and_then(|e| e.status()),
+        Some(403)
+    );
+}
@@ -0,0 +1,46 @@
+//! Thinking models: the `thinkingConfig` sent to Gemini and the thought
+//! summaries and thought tokens of recorded replies.
+
+mod common;
+
+use common::client;
+use ey_ai::{
+    model::generation::generation::{GenerateRequest, ThinkingConfig},
+    testing::cassette::CassetteServer,
+};
+
+#[tokio::test]
+async fn separates_thought_summaries_from_the_answer() {
+    let cassette = format!(
+        "{}/tests/cassettes/thinking.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let server = CassetteServer::replay(cassette).await.unwrap();
+    let client = client(server.gemini());
+
+    let thinking = client
+        .Generate(
+            GenerateRequest::from_prompt("Is 391 prime?")
+                .thinking(ThinkingConfig::budget(1024).include_thoughts()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(thinking.text, "No, 391 = 17 × 23.");
+    assert!(
+        thinking
+            .thoughts
+            .unwrap()
+            .starts_with("**Checking small factors**")
+    );
+    assert_eq!(thinking.usage.unwrap().thoughts_tokens, 143);
+
+    let direct = client
+        .Generate(GenerateRequest::from_prompt("Is 391 prime?").thinking(ThinkingConfig::budget(0)))
+        .await
+        .unwrap();
+    assert_eq!(direct.text, "No, 391 = 17 × 23.");
+    assert!(direct.thoughts.is_none());
+    assert_eq!(direct.usage.unwrap().thoughts_tokens, 0);
+
+    assert!(server.unused().is_empty());
+}
@@ -0,0 +1,271 @@
+//! Context window truncation: each policy against a provider counting with
+//! the local estimate, and the shortcut for conversations that fit for sure.
+
+mod common;
+
+use common::client;
+use ey_ai::{
+    model::{
+        conversation::conversation::{Conversation, Turn},
+        generation::generation::{GenerateRequest, Usage},
+    },
+    testing::mock::{MockMethod, MockProvider, MockReply},
+    traits::ModelProvider,
+    usage::ledger::UsageFilter,
+    utils::truncate::{TRUNCATION_TAG, TruncationPolicy, may_exceed, truncate},
+};
+
+// ten estimated tokens per turn and for the system prompt
+fn text(label: &str) -> String {
+    format!("{:<40}", label)
+}
+
+fn conversation() -> Conversation {
+    let mut conversation = Conversation::new().with_system(text("system"));
+    for turn in [
+        Turn::user(text("u1")),
+        Turn::model(text("m1")),
+        Turn::user(text("u2")),
+        Turn::model(text("m2")),
+        Turn::user(text("u3")),
+    ] {
+        conversation.push(turn);
+    }
+    conversation
+}
+
+fn labels(conversation: &Conversation) -> Vec<&str> {
+    conversation.turns.iter().map(|t| t.text.trim()).collect()
+}
+
+fn count_calls(mock: &MockProvider) -> usize {
+    mock.calls()
+        .iter()
+        .filter(|c| c.method == MockMethod::CountTokens)
+        .count()
+}
+
+async fn shrink(
+    mock: &MockProvider,
+    conversation: Conversation,
+    limit: u64,
+    policy: TruncationPolicy,
+) -> Conversation {
+    let summarize = |request: GenerateRequest| async move {
+        Ok(mock.generate("test-key", "model", &request).await?.text)
+    };
+    truncate(
+        mock,
+        "test-key",
+        "model",
+        conversation,
+        limit,
+        policy,
+        summarize,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn keep_system_prompt_drops_whole_exchanges_from_the_start() {
+    let mock = MockProvider::new();
+    let truncated = shrink(
+        &mock,
+        conversation(),
+        30,
+        TruncationPolicy::KeepSystemPrompt,
+    )
+    .await;
+
+    assert_eq!(truncated.system, Some(text("system")));
+    assert_eq!(labels(&truncated), ["u3"]);
+    assert_eq!(truncated.estimate_tokens(), 20);
+}
+
+#[tokio::test]
+async fn drop_oldest_gives_up_the_system_prompt_first() {
+    let mock = MockProvider::new();
+    let truncated = shrink(&mock, conversation(), 30, TruncationPolicy::DropOldest).await;
+
+    assert_eq!(truncated.system, None);
+    assert_eq!(labels(&truncated), ["u2", "m2", "u3"]);
+}
+
+#[tokio::test]
+async fn summarize_folds_the_dropped_turns_into_the_system_prompt() {
+    let mock = MockProvider::new().replies([
+        MockReply::text("About u1."),
+        MockReply::text("About u1 and m1."),
+    ]);
+    let mut conversation = conversation();
+    conversation.system = None;
+    let truncated = shrink(&mock, conversation, 45, TruncationPolicy::Summarize).await;
+
+    assert_eq!(
+        truncated.system.as_deref(),
+        Some("Summary of the earlier conversation:\nAbout u1 and m1.")
+    );
+    assert_eq!(labels(&truncated), ["u2", "m2", "u3"]);
+
+    // one chunk per request, within half of the window
+    let summaries: Vec<_> = mock
+        .calls()
+        .into_iter()
+        .filter(|c| c.method == MockMethod::Generate)
+        .map(|c| c.request.conversation.to_prompt())
+        .collect();
+    assert_eq!(summaries.len(), 2);
+    assert!(summaries[0].contains("User: u1") && !summaries[0].contains("m1"));
+    assert!(summaries[1].contains("Summary of the conversation so far:\nAbout u1."));
+    assert!(summaries[1].contains("Model: m1") && !summaries[1].contains("User: u1"));
+    assert!(!summaries.iter().any(|s| s.contains("u2")));
+}
+
+#[tokio::test]
+async fn disabled_leaves_the_conversation_to_the_provider() {
+    let mock = MockProvider::new();
+    let truncated = shrink(&mock, conversation(), 30, TruncationPolicy::Disabled).await;
+
+    assert_eq!(truncated, conversation());
+    assert_eq!(mock.call_count(), 0);
+}
+
+#[tokio::test]
+async fn fails_when_the_latest_turn_alone_is_over_the_limit() {
+    let mock = MockProvider::new();
+    let error = truncate(
+        &mock,
+        "test-key",
+        "model",
+        conversation(),
+        5,
+        TruncationPolicy::KeepSystemPrompt,
+        |_| async { Ok(String::new()) },
+    )
+    .await
+    .err()
+    .unwrap();
+
+    assert!(
+        error.to_string().contains("does not fit into 5 tokens"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn conversations_that_fit_for_sure_are_not_counted() {
+    // the estimate is 60 tokens, only a limit of 240 or more rules out
+    // an undercount
+    assert!(may_exceed(
+        &conversation(),
+        239,
+        TruncationPolicy::KeepSystemPrompt
+    ));
+    assert!(!may_exceed(
+        &conversation(),
+        240,
+        TruncationPolicy::KeepSystemPrompt
+    ));
+
+    let mock = MockProvider::new();
+    let kept = shrink(
+        &mock,
+        conversation(),
+        240,
+        TruncationPolicy::KeepSystemPrompt,
+    )
+    .await;
+    assert_eq!(kept, conversation());
+    assert_eq!(mock.call_count(), 0);
+
+    // counted, but fitting once the provider has its say
+    let kept = shrink(
+        &mock,
+        conversation(),
+        60,
+        TruncationPolicy::KeepSystemPrompt,
+    )
+    .await;
+    assert_eq!(kept, conversation());
+    assert_eq!(count_calls(&mock), 1);
+}
+
+#[tokio::test]
+async fn the_client_truncates_with_its_policy_only_when_needed() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok"));
+    let fitting = client(mock.clone());
+    fitting
+        .Generate(GenerateRequest::new(conversation()))
+        .await
+        .unwrap();
+    assert_eq!(count_calls(&mock), 0);
+
+    // a provider count far over the window of Gemini 2.5 Flash
+    let mock = MockProvider::new()
+        .fallback(MockReply::text("ok"))
+        .token_count(u64::MAX);
+    let client = client(mock.clone());
+    let mut huge = conversation();
+    huge.push(Turn::user("x".repeat(1_100_000)));
+    let error = client
+        .Generate(GenerateRequest::new(huge.clone()))
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("does not fit"), "{}", error);
+    assert_eq!(mock.call_count(), count_calls(&mock));
+
+    let client = client.set_truncation(TruncationPolicy::Disabled);
+    client.Generate(GenerateRequest::new(huge)).await.unwrap();
+    assert_eq!(
+        mock.last_call().unwrap().request.conversation.turns.len(),
+        6
+    );
+}
+
+#[tokio::test]
+async fn summaries_are_metered_under_the_truncation_tag() {
+    let usage = Usage {
+        total_tokens: 30,
+        ..Default::default()
+    };
+    let mock = MockProvider::new().fallback(MockReply::text("ok").usage(usage));
+    let client = client(mock.clone())
+        .add_key("pool-key", 1)
+        .set_token_limit(Some(45))
+        .set_truncation(TruncationPolicy::Summarize);
+    let mut conversation = conversation();
+    conversation.system = None;
+
+    client
+        .Generate(
+            GenerateRequest::new(conversation)
+                .tenant("acme")
+                .tag("chat"),
+        )
+        .await
+        .unwrap();
+
+    let report = client.usage.query(&UsageFilter {
+        group_by: Some("tag".into()),
+        ..Default::default()
+    });
+    let mut tags: Vec<_> = report
+        .records
+        .iter()
+        .map(|r| (r.tag.clone().unwrap(), r.totals.requests))
+        .collect();
+    tags.sort();
+    assert_eq!(
+        tags,
+        [("chat".to_string(), 1), (TRUNCATION_TAG.to_string(), 2)]
+    );
+    let tenant = client.budgets.tenant_usage("acme");
+    assert_eq!((tenant.tokens, tenant.reserved_tokens), (90, 0));
+    // counting, two summaries and the request itself, all with the pool key
+    let stats = client.keys.stats();
+    assert_eq!((stats[0].requests, stats[0].in_flight), (4, 0));
+    assert!(mock.calls().iter().all(|c| c.api_key == "pool-key"));
+}
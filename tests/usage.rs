@@ -0,0 +1,127 @@
+//! Usage accounting: the ledger per key, model, tag and day, the pricing
+//! table and the `/usage` endpoint.
+
+mod common;
+
+use std::sync::Arc;
+
+use axum::{Router, routing::get};
+use common::client;
+use ey_ai::{
+    model::generation::generation::{GenerateRequest, Usage},
+    testing::mock::{MockProvider, MockReply},
+    traits::ModelProvider,
+    usage::{
+        endpoint::UsageResponse,
+        ledger::{UsageFilter, UsageReport, key_id},
+        pricing::{ModelPricing, PricingTable},
+    },
+};
+use tokio::net::TcpListener;
+
+fn usage(prompt: u64, candidates: u64) -> Usage {
+    Usage {
+        prompt_tokens: prompt,
+        candidates_tokens: candidates,
+        total_tokens: prompt + candidates,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn prices_prompt_cached_and_output_tokens() {
+    let mut pricing = PricingTable::empty();
+    pricing.set("gemini-2.5-flash", ModelPricing::new(1.0, 10.0, 0.1));
+
+    let usage = Usage {
+        prompt_tokens: 1_000_000,
+        cached_tokens: 400_000,
+        candidates_tokens: 100_000,
+        thoughts_tokens: 100_000,
+        total_tokens: 1_200_000,
+    };
+    // 600k fresh input, 400k cached, 200k output with the thoughts
+    let cost = pricing.cost("gemini-2.5-flash", &usage);
+    assert!((cost - (0.6 + 0.04 + 2.0)).abs() < 1e-9, "{}", cost);
+    // versioned names use the longest known prefix
+    assert_eq!(pricing.cost("gemini-2.5-flash-001", &usage), cost);
+    assert_eq!(pricing.cost("unknown-model", &usage), 0.0);
+}
+
+#[test]
+fn key_ids_tell_keys_apart_without_revealing_them() {
+    assert_ne!(key_id("k1"), key_id("k2"));
+    assert_eq!(key_id("k1"), key_id("k1"));
+    assert!(key_id("AIzaSecretKeyValue").starts_with("key-"));
+    assert!(!key_id("AIzaSecretKeyValue").contains("Value"));
+}
+
+#[tokio::test]
+async fn records_usage_per_key_model_and_tag() {
+    let mock = MockProvider::new().fallback(MockReply::text("ok").usage(usage(100, 20)));
+    let client = client(mock.clone()).add_key("k1", 1).add_key("k2", 1);
+    let mut pricing = PricingTable::empty();
+    pricing.set("gemini-2.5-flash", ModelPricing::new(1.0, 10.0, 0.0));
+    client.usage.set_pricing(pricing);
+
+    for tag in ["support", "support", "search"] {
+        client
+            .Generate(GenerateRequest::from_prompt("hi").tag(tag))
+            .await
+            .unwrap();
+    }
+
+    let by_tag = client.usage.query(&UsageFilter {
+        group_by: Some("tag".into()),
+        ..Default::default()
+    });
+    let support = by_tag
+        .records
+        .iter()
+        .find(|r| r.tag.as_deref() == Some("support"))
+        .unwrap();
+    assert_eq!(support.totals.requests, 2);
+    assert_eq!(support.totals.usage.prompt_tokens, 200);
+    assert!((support.totals.cost_usd - 2.0 * 0.0003).abs() < 1e-12);
+    assert_eq!(by_tag.total.requests, 3);
+    assert_eq!(by_tag.total.usage.total_tokens, 360);
+
+    // every request is booked under the key it was sent with
+    let by_key = client.usage.query(&UsageFilter {
+        group_by: Some("key".into()),
+        ..Default::default()
+    });
+    for record in &by_key.records {
+        let sent = mock
+            .calls()
+            .iter()
+            .filter(|c| Some(key_id(&c.api_key)) == record.key_id)
+            .count();
+        assert_eq!(record.totals.requests as usize, sent);
+    }
+}
+
+#[tokio::test]
+async fn serves_the_ledger_filtered_by_key_id() {
+    // short keys are still counted apart
+    let client = client(MockProvider::new());
+    client
+        .usage
+        .record("k1", "gemini-2.5-flash", Some("a"), Some(&usage(10, 1)));
+    client
+        .usage
+        .record("k2", "gemini-2.5-flash", Some("b"), Some(&usage(20, 2)));
+
+    let app = Router::new()
+        .route("/usage", get(UsageResponse))
+        .with_state(Arc::new(client));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let url = format!("http://{}/usage?key_id={}&group_by=tag", addr, key_id("k2"));
+    let report: UsageReport = reqwest::get(url).await.unwrap().json().await.unwrap();
+    assert_eq!(report.records.len(), 1);
+    assert_eq!(report.records[0].tag.as_deref(), Some("b"));
+    assert_eq!(report.total.usage.prompt_tokens, 20);
+}